@@ -0,0 +1,48 @@
+//! Lexer throughput on a large generated Caddyfile.
+//!
+//! Run with `cargo bench --bench lexer`.
+
+use std::fmt::Write;
+use std::hint::black_box;
+use std::time::Instant;
+
+const ITERATIONS: u32 = 20;
+
+/// Roughly 1 MB of sites with words, placeholders, quoted strings with
+/// escapes and comments.
+fn input() -> String {
+    let mut out = String::new();
+    for i in 0..4000 {
+        let _ = write!(
+            out,
+            "# site {i}\n\
+             app{i}.example.com, www.app{i}.example.com {{\n\
+             \treverse_proxy /api/* backend-{i}:8080 backend-{i}:8081\n\
+             \theader X-Request-Id {{http.request.uuid}}\n\
+             \trespond \"hello \\\"world\\\" from {i}\\n\" 200\n\
+             \tlog {{\n\
+             \t\toutput file /var/log/caddy/app{i}.log\n\
+             \t}}\n\
+             }}\n\n"
+        );
+    }
+    out
+}
+
+fn main() {
+    let input = input();
+    caddyfile_rs::tokenize(&input).expect("generated input should lex");
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        black_box(caddyfile_rs::tokenize(black_box(&input)).unwrap());
+    }
+    let elapsed = start.elapsed() / ITERATIONS;
+
+    #[allow(clippy::cast_precision_loss)]
+    let mb = input.len() as f64 / 1_000_000.0;
+    println!(
+        "tokenize: {mb:.2} MB in {elapsed:?} ({:.1} MB/s)",
+        mb / elapsed.as_secs_f64()
+    );
+}
@@ -1,11 +1,12 @@
 //! Build a Caddyfile programmatically using the builder API.
 
-use caddyfile_rs::{Caddyfile, Directive, GlobalOptions, Matcher, SiteBlock, Snippet};
+use caddyfile_rs::{Caddyfile, Comments, Directive, GlobalOptions, Matcher, SiteBlock, Snippet};
 
 fn main() {
     let cf = Caddyfile::new()
         .global(GlobalOptions {
             directives: vec![Directive::new("email").arg("admin@example.com")],
+            comments: Comments::default(),
         })
         .snippet(Snippet {
             name: "security".to_string(),
@@ -13,6 +14,7 @@ fn main() {
                 Directive::new("X-Content-Type-Options").quoted_arg("nosniff"),
                 Directive::new("X-Frame-Options").quoted_arg("DENY"),
             ])],
+            comments: Comments::default(),
         })
         .site(
             SiteBlock::new("example.com")
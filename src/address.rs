@@ -0,0 +1,518 @@
+//! Strict parsing of site and upstream addresses.
+//!
+//! [`crate::parse_address`] never fails: anything it cannot make sense
+//! of ends up in the host. [`Address::parse_strict`] reports such input
+//! instead. [`parse_upstream_address`] handles the richer syntax of
+//! `reverse_proxy` upstreams, and [`Address::normalize`] resolves the
+//! scheme and port Caddy would serve an address on.
+
+use std::fmt;
+
+use crate::ast::{Address, Scheme};
+
+/// Error produced by [`Address::parse_strict`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum AddressError {
+    /// The address is empty.
+    #[error("empty address")]
+    Empty,
+    /// The scheme is neither `http` nor `https`.
+    #[error("unsupported scheme '{0}'")]
+    BadScheme(String),
+    /// Neither a host, a port nor a scheme is given.
+    #[error("address has no host")]
+    EmptyHost,
+    /// The port is not a number in `1..=65535`.
+    #[error("invalid port '{0}'")]
+    InvalidPort(String),
+    /// A character that cannot appear at this point of an address.
+    #[error("unexpected '{ch}' at offset {offset}")]
+    InvalidCharacter { ch: char, offset: usize },
+    /// An upstream address has a path, which Caddy does not allow.
+    #[error("upstream address cannot have a path: '{0}'")]
+    UnexpectedPath(String),
+}
+
+impl Address {
+    /// Parse a site address, rejecting invalid ports, empty hosts,
+    /// unknown schemes and stray characters.
+    ///
+    /// Valid input yields the same components as
+    /// [`crate::parse_address`]. Placeholders such as `{$DOMAIN}` are
+    /// accepted anywhere in the host.
+    pub fn parse_strict(text: &str) -> Result<Self, AddressError> {
+        if text.is_empty() {
+            return Err(AddressError::Empty);
+        }
+        let (scheme, rest, offset) = match text.find("://") {
+            Some(pos) => {
+                let scheme = match &text[..pos] {
+                    "http" => Scheme::Http,
+                    "https" => Scheme::Https,
+                    other => return Err(AddressError::BadScheme(other.to_string())),
+                };
+                (Some(scheme), &text[pos + 3..], pos + 3)
+            }
+            None => (None, text, 0),
+        };
+
+        let (host_port, path) = rest
+            .find('/')
+            .map_or((rest, None), |pos| (&rest[..pos], Some(&rest[pos..])));
+        if let Some(path) = path {
+            let start = offset + host_port.len();
+            if let Some((i, ch)) = path.char_indices().find(|(_, c)| c.is_whitespace()) {
+                return Err(AddressError::InvalidCharacter {
+                    ch,
+                    offset: start + i,
+                });
+            }
+        }
+
+        let (host, port) = split_port(host_port);
+        let port = port.map(parse_port).transpose()?;
+        check_host(host, offset)?;
+        if host.is_empty() && port.is_none() && scheme.is_none() {
+            return Err(AddressError::EmptyHost);
+        }
+
+        Ok(Self {
+            scheme,
+            host: host.to_string(),
+            port,
+            path: path.map(str::to_string),
+            raw: None,
+        })
+    }
+
+    /// The address with scheme and port filled in the way Caddy
+    /// serves it, and the host lowercased.
+    ///
+    /// Without a scheme, port 80 and host-less addresses such as `:8080`
+    /// are served over HTTP and everything else over HTTPS. Without a
+    /// port, HTTP uses 80 and HTTPS 443. Raw addresses are parsed first.
+    #[must_use]
+    pub fn normalize(&self) -> Self {
+        let addr = self.parsed();
+        let scheme = addr.scheme.clone().unwrap_or_else(|| {
+            if addr.port == Some(80) || (addr.host.is_empty() && addr.port.is_some()) {
+                Scheme::Http
+            } else {
+                Scheme::Https
+            }
+        });
+        let port = addr.port.unwrap_or(match scheme {
+            Scheme::Http => 80,
+            Scheme::Https => 443,
+        });
+        Self {
+            scheme: Some(scheme),
+            host: lowercase_host(&addr.host),
+            port: Some(port),
+            path: addr.path.clone().filter(|p| !p.is_empty()),
+            raw: None,
+        }
+    }
+
+    /// Whether both addresses serve the same sites, so that
+    /// `https://example.com`, `example.com:443` and `example.com` are
+    /// all equivalent.
+    #[must_use]
+    pub fn is_equivalent(&self, other: &Self) -> bool {
+        self.normalize() == other.normalize()
+    }
+}
+
+/// Lowercase `host`, leaving placeholders such as `{$DOMAIN}` alone.
+fn lowercase_host(host: &str) -> String {
+    let mut depth = 0usize;
+    host.chars()
+        .map(|ch| {
+            match ch {
+                '{' => depth += 1,
+                '}' => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+            if depth == 0 {
+                ch.to_ascii_lowercase()
+            } else {
+                ch
+            }
+        })
+        .collect()
+}
+
+/// Split `host:port` at the last colon outside `[...]` and `{...}`.
+///
+/// A placeholder port, such as `{$PORT}`, stays part of the host just
+/// like `parse_address` keeps it.
+fn split_port(host_port: &str) -> (&str, Option<&str>) {
+    let mut depth = 0usize;
+    let mut colon = None;
+    for (i, ch) in host_port.char_indices() {
+        match ch {
+            '[' | '{' => depth += 1,
+            ']' | '}' => depth = depth.saturating_sub(1),
+            ':' if depth == 0 => colon = Some(i),
+            _ => {}
+        }
+    }
+    match colon {
+        Some(pos) if !host_port[pos + 1..].starts_with('{') => {
+            (&host_port[..pos], Some(&host_port[pos + 1..]))
+        }
+        _ => (host_port, None),
+    }
+}
+
+/// Parse a port number in `1..=65535`.
+fn parse_port(text: &str) -> Result<u16, AddressError> {
+    match text.parse::<u16>() {
+        Ok(port) if port != 0 && text.bytes().all(|b| b.is_ascii_digit()) => Ok(port),
+        _ => Err(AddressError::InvalidPort(text.to_string())),
+    }
+}
+
+/// Check the characters of `host`, which starts at `offset` in the
+/// original text.
+fn check_host(host: &str, offset: usize) -> Result<(), AddressError> {
+    let invalid = |i: usize, ch: char| AddressError::InvalidCharacter {
+        ch,
+        offset: offset + i,
+    };
+
+    if let Some(inner) = host.strip_prefix('[') {
+        let Some(ip) = inner.strip_suffix(']') else {
+            return Err(invalid(0, '['));
+        };
+        return match ip
+            .char_indices()
+            .find(|(_, c)| !(c.is_ascii_hexdigit() || matches!(c, ':' | '.' | '%')))
+        {
+            Some((i, ch)) => Err(invalid(i + 1, ch)),
+            None => Ok(()),
+        };
+    }
+
+    let mut open = None;
+    for (i, ch) in host.char_indices() {
+        match ch {
+            '{' if open.is_none() => open = Some(i),
+            '}' if open.is_some() => open = None,
+            _ if ch.is_whitespace() => return Err(invalid(i, ch)),
+            _ if open.is_some() => {}
+            '-' | '.' | '_' | '*' => {}
+            ':' if host[i + 1..].starts_with('{') => {}
+            _ if ch.is_alphanumeric() => {}
+            _ => return Err(invalid(i, ch)),
+        }
+    }
+    open.map_or(Ok(()), |i| Err(invalid(i, '{')))
+}
+
+/// Transport scheme of an upstream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpstreamScheme {
+    Http,
+    Https,
+    /// HTTP/2 over cleartext.
+    H2c,
+    /// Upstreams looked up through DNS SRV records (`srv+http://`).
+    SrvHttp,
+    SrvHttps,
+}
+
+impl UpstreamScheme {
+    /// Scheme as written before `://`.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Http => "http",
+            Self::Https => "https",
+            Self::H2c => "h2c",
+            Self::SrvHttp => "srv+http",
+            Self::SrvHttps => "srv+https",
+        }
+    }
+}
+
+/// A `reverse_proxy` upstream such as `h2c://app:8080`,
+/// `unix//run/app.sock` or `app:8080-8090`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpstreamAddress {
+    pub scheme: Option<UpstreamScheme>,
+    /// Network prefix such as `unix` or `tcp6`.
+    pub network: Option<String>,
+    /// Host name, IP or placeholder; the socket path for unix networks.
+    pub host: String,
+    pub port: Option<u16>,
+    /// Last port of a `port-port` range.
+    pub port_end: Option<u16>,
+}
+
+impl UpstreamAddress {
+    /// Whether this is a unix socket.
+    #[must_use]
+    pub fn is_unix(&self) -> bool {
+        self.network
+            .as_deref()
+            .is_some_and(|n| n.starts_with("unix"))
+    }
+
+    /// Whether the host is a placeholder resolved per request.
+    #[must_use]
+    pub fn is_placeholder(&self) -> bool {
+        self.host.starts_with('{')
+    }
+
+    /// Number of upstreams this address expands to.
+    #[must_use]
+    pub fn port_count(&self) -> usize {
+        match (self.port, self.port_end) {
+            (Some(start), Some(end)) => usize::from(end - start) + 1,
+            _ => 1,
+        }
+    }
+}
+
+impl fmt::Display for UpstreamAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.network, self.scheme) {
+            (Some(network), Some(UpstreamScheme::H2c)) if self.is_unix() => {
+                write!(f, "{network}+h2c/")?;
+            }
+            (Some(network), _) => write!(f, "{network}/")?,
+            (None, Some(scheme)) => write!(f, "{}://", scheme.as_str())?,
+            (None, None) => {}
+        }
+        f.write_str(&self.host)?;
+        if let Some(port) = self.port {
+            write!(f, ":{port}")?;
+        }
+        if let Some(end) = self.port_end {
+            write!(f, "-{end}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Network prefixes accepted before a `/` in an upstream address.
+const NETWORKS: &[&str] = &[
+    "tcp",
+    "tcp4",
+    "tcp6",
+    "udp",
+    "udp4",
+    "udp6",
+    "ip",
+    "ip4",
+    "ip6",
+    "unix",
+    "unixgram",
+    "unixpacket",
+];
+
+/// Parse a `reverse_proxy` upstream address.
+pub fn parse_upstream_address(text: &str) -> Result<UpstreamAddress, AddressError> {
+    if text.is_empty() {
+        return Err(AddressError::Empty);
+    }
+    let mut upstream = UpstreamAddress {
+        scheme: None,
+        network: None,
+        host: String::new(),
+        port: None,
+        port_end: None,
+    };
+    let mut rest = text;
+
+    if let Some(pos) = text.find("://") {
+        upstream.scheme = Some(match &text[..pos] {
+            "http" => UpstreamScheme::Http,
+            "https" => UpstreamScheme::Https,
+            "h2c" => UpstreamScheme::H2c,
+            "srv+http" => UpstreamScheme::SrvHttp,
+            "srv+https" => UpstreamScheme::SrvHttps,
+            other => return Err(AddressError::BadScheme(other.to_string())),
+        });
+        rest = &text[pos + 3..];
+    } else if let Some((prefix, after)) = text.split_once('/') {
+        let network = prefix.strip_suffix("+h2c").unwrap_or(prefix);
+        if NETWORKS.contains(&network) {
+            if network != prefix {
+                upstream.scheme = Some(UpstreamScheme::H2c);
+            }
+            upstream.network = Some(network.to_string());
+            rest = after;
+        }
+    }
+
+    if upstream.is_unix() {
+        if rest.is_empty() {
+            return Err(AddressError::EmptyHost);
+        }
+        upstream.host = rest.to_string();
+        return Ok(upstream);
+    }
+    if rest.contains('/') {
+        return Err(AddressError::UnexpectedPath(text.to_string()));
+    }
+
+    let (host, port) = split_port(rest);
+    check_host(host, text.len() - rest.len())?;
+    if host.is_empty() && port.is_none() {
+        return Err(AddressError::EmptyHost);
+    }
+    if let Some(port) = port {
+        let (start, end) = match port.split_once('-') {
+            Some((start, end)) => (parse_port(start)?, Some(parse_port(end)?)),
+            None => (parse_port(port)?, None),
+        };
+        if end.is_some_and(|end| end < start) {
+            return Err(AddressError::InvalidPort(port.to_string()));
+        }
+        upstream.port = Some(start);
+        upstream.port_end = end;
+    }
+    upstream.host = host.to_string();
+    Ok(upstream)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_address;
+
+    #[test]
+    fn valid_addresses_match_lenient_parse() {
+        for text in [
+            "example.com",
+            "https://example.com:8443/api/*",
+            "http://",
+            ":8080",
+            "*.example.com",
+            "[::1]:2019",
+            "{$DOMAIN}",
+            "localhost:{$PORT}",
+            "bücher.example",
+        ] {
+            assert_eq!(
+                Address::parse_strict(text),
+                Ok(parse_address(text)),
+                "{text}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_addresses() {
+        let cases = [
+            ("", AddressError::Empty),
+            ("ftp://example.com", AddressError::BadScheme("ftp".into())),
+            ("/api", AddressError::EmptyHost),
+            ("example.com:", AddressError::InvalidPort(String::new())),
+            ("example.com:0", AddressError::InvalidPort("0".into())),
+            (
+                "example.com:99999",
+                AddressError::InvalidPort("99999".into()),
+            ),
+            ("example.com:+80", AddressError::InvalidPort("+80".into())),
+            (
+                "exa!mple.com",
+                AddressError::InvalidCharacter { ch: '!', offset: 3 },
+            ),
+            (
+                "http://a:b:80",
+                AddressError::InvalidCharacter { ch: ':', offset: 8 },
+            ),
+            (
+                "[::1",
+                AddressError::InvalidCharacter { ch: '[', offset: 0 },
+            ),
+            (
+                "{$DOMAIN",
+                AddressError::InvalidCharacter { ch: '{', offset: 0 },
+            ),
+        ];
+        for (text, err) in cases {
+            assert_eq!(Address::parse_strict(text), Err(err), "{text}");
+        }
+    }
+
+    #[test]
+    fn upstream_addresses() {
+        let up = parse_upstream_address("unix//run/app.sock").unwrap();
+        assert!(up.is_unix());
+        assert_eq!(up.host, "/run/app.sock");
+
+        let up = parse_upstream_address("unix+h2c//run/app.sock").unwrap();
+        assert_eq!(up.scheme, Some(UpstreamScheme::H2c));
+        assert_eq!(up.network.as_deref(), Some("unix"));
+
+        let up = parse_upstream_address("app:8080-8090").unwrap();
+        assert_eq!((up.port, up.port_end), (Some(8080), Some(8090)));
+        assert_eq!(up.port_count(), 11);
+
+        let up = parse_upstream_address("srv+https://_api._tcp.example.com").unwrap();
+        assert_eq!(up.scheme, Some(UpstreamScheme::SrvHttps));
+        assert_eq!(up.host, "_api._tcp.example.com");
+
+        assert!(
+            parse_upstream_address("{http.request.header.X-Backend}")
+                .unwrap()
+                .is_placeholder()
+        );
+
+        for text in [
+            "unix//run/app.sock",
+            "unix+h2c//run/app.sock",
+            "h2c://app:8080",
+            "tcp6/[::1]:9000",
+            "app:8080-8090",
+            "srv+http://api.internal",
+        ] {
+            assert_eq!(
+                parse_upstream_address(text).unwrap().to_string(),
+                text,
+                "{text}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_upstreams() {
+        let cases = [
+            ("ws://app", AddressError::BadScheme("ws".into())),
+            (
+                "app:9000-8000",
+                AddressError::InvalidPort("9000-8000".into()),
+            ),
+            ("app:80-x", AddressError::InvalidPort("x".into())),
+            (
+                "app:80/api",
+                AddressError::UnexpectedPath("app:80/api".into()),
+            ),
+            ("unix/", AddressError::EmptyHost),
+            ("h2c://", AddressError::EmptyHost),
+        ];
+        for (text, err) in cases {
+            assert_eq!(parse_upstream_address(text), Err(err), "{text}");
+        }
+    }
+
+    #[test]
+    fn normalizes_scheme_and_port() {
+        let equivalent = |a: &str, b: &str| parse_address(a).is_equivalent(&parse_address(b));
+        assert!(equivalent("https://example.com", "example.com:443"));
+        assert!(equivalent("example.com", "https://Example.COM:443"));
+        assert!(equivalent("http://example.com", "example.com:80"));
+        assert!(equivalent(":8080", "http://:8080"));
+        assert!(equivalent("localhost:8443", "https://localhost:8443"));
+        assert!(!equivalent("example.com", "http://example.com"));
+        assert!(!equivalent("example.com", "example.com/api"));
+        assert!(!equivalent("{$SITE}", "{$site}"));
+
+        let normalized = Address::raw("Example.com").normalize();
+        assert_eq!(normalized.to_string(), "https://example.com:443");
+    }
+}
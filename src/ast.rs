@@ -2,8 +2,11 @@
 //!
 //! Includes site blocks, directives, matchers, snippets, and global options.
 
+use std::borrow::Cow;
 use std::fmt;
 
+use crate::env::{self, EnvRef, Segment};
+
 /// Complete Caddyfile document.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Caddyfile {
@@ -11,12 +14,15 @@ pub struct Caddyfile {
     pub snippets: Vec<Snippet>,
     pub named_routes: Vec<NamedRoute>,
     pub sites: Vec<SiteBlock>,
+    /// Comment lines after the last block.
+    pub trailing_comments: Vec<String>,
 }
 
 /// Global options block (first block, no keys).
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct GlobalOptions {
     pub directives: Vec<Directive>,
+    pub comments: Comments,
 }
 
 /// Reusable snippet: `(name) { ... }`.
@@ -24,6 +30,7 @@ pub struct GlobalOptions {
 pub struct Snippet {
     pub name: String,
     pub directives: Vec<Directive>,
+    pub comments: Comments,
 }
 
 /// Named route: `&(name) { ... }`.
@@ -31,6 +38,7 @@ pub struct Snippet {
 pub struct NamedRoute {
     pub name: String,
     pub directives: Vec<Directive>,
+    pub comments: Comments,
 }
 
 /// Site block: one or more addresses + directives.
@@ -38,15 +46,46 @@ pub struct NamedRoute {
 pub struct SiteBlock {
     pub addresses: Vec<Address>,
     pub directives: Vec<Directive>,
+    pub comments: Comments,
+    /// Written without braces, in the single-site shorthand.
+    pub braceless: bool,
 }
 
 /// Site address with parsed components.
+///
+/// When `raw` is set (see `ParseOptions::raw_addresses`), the address is
+/// printed verbatim and the component fields are left empty; use
+/// [`Address::parsed`] to get the components.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Address {
     pub scheme: Option<Scheme>,
     pub host: String,
     pub port: Option<u16>,
     pub path: Option<String>,
+    /// Original source text, kept byte-exact.
+    pub raw: Option<String>,
+}
+
+impl Address {
+    /// Create an address that keeps `text` verbatim.
+    #[must_use]
+    pub fn raw(text: impl Into<String>) -> Self {
+        Self {
+            scheme: None,
+            host: String::new(),
+            port: None,
+            path: None,
+            raw: Some(text.into()),
+        }
+    }
+
+    /// Parsed components, computed from the raw text if there is one.
+    #[must_use]
+    pub fn parsed(&self) -> Cow<'_, Self> {
+        self.raw
+            .as_deref()
+            .map_or(Cow::Borrowed(self), |raw| Cow::Owned(parse_address(raw)))
+    }
 }
 
 /// URL scheme.
@@ -63,6 +102,34 @@ pub struct Directive {
     pub matcher: Option<Matcher>,
     pub arguments: Vec<Argument>,
     pub block: Option<Vec<Self>>,
+    pub comments: Comments,
+    /// Indices of arguments that start a new line after a `\` line
+    /// continuation.
+    pub continuations: Vec<usize>,
+    /// Blank lines above the directive and its leading comments, recorded
+    /// when parsing with `ParseOptions::blank_lines`.
+    pub blank_lines_before: usize,
+}
+
+/// Comments attached to a site block or directive, kept verbatim
+/// including the leading `#`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Comments {
+    /// Comment lines directly above the node.
+    pub leading: Vec<String>,
+    /// Comment at the end of the node's first line.
+    pub trailing: Option<String>,
+    /// Comment lines after the last directive of the node's block,
+    /// before its closing brace.
+    pub closing: Vec<String>,
+}
+
+impl Comments {
+    /// Whether there are no comments.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.leading.is_empty() && self.trailing.is_none() && self.closing.is_empty()
+    }
 }
 
 /// Matcher token after directive name.
@@ -72,8 +139,14 @@ pub enum Matcher {
     All,
     /// Path matcher `/path`.
     Path(String),
+    /// Several path patterns before a block, any of which may match:
+    /// `handle /a/* /b/* {`.
+    Paths(Vec<String>),
     /// Named matcher `@name`.
     Named(String),
+    /// Negated condition of a single-line matcher definition,
+    /// `@name not path /x/*`.
+    Not { module: String, args: Vec<Argument> },
 }
 
 /// Argument value preserving its quoting style.
@@ -87,15 +160,209 @@ pub enum Argument {
     Backtick(String),
     /// Heredoc value (`<<MARKER ... MARKER`).
     Heredoc { marker: String, content: String },
+    /// Environment reference `{$NAME}` or `{$NAME:default}`, resolved
+    /// when Caddy loads the config.
+    EnvVar {
+        name: String,
+        default: Option<String>,
+    },
 }
 
 impl Argument {
     /// Return the inner value regardless of quoting style.
+    ///
+    /// Environment references are returned in their `{$NAME}` form.
+    #[must_use]
+    pub fn value(&self) -> Cow<'_, str> {
+        match self {
+            Self::Unquoted(s) | Self::Quoted(s) | Self::Backtick(s) => Cow::Borrowed(s),
+            Self::Heredoc { content, .. } => Cow::Borrowed(content),
+            Self::EnvVar { .. } => Cow::Owned(self.to_string()),
+        }
+    }
+
+    /// The value split into literal text and environment references,
+    /// including references embedded in quoted strings.
+    #[must_use]
+    pub fn segments(&self) -> Vec<Segment<'_>> {
+        match self {
+            Self::Unquoted(s) | Self::Quoted(s) | Self::Backtick(s) => env::segments(s),
+            Self::Heredoc { content, .. } => env::segments(content),
+            Self::EnvVar { name, default } => vec![Segment::Env(EnvRef {
+                name: name.clone(),
+                default: default.clone(),
+            })],
+        }
+    }
+}
+
+/// CEL expression from an `expression` matcher, parsed with
+/// [`crate::expression::parse_expression`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expression {
+    /// Literal value.
+    Literal(Literal),
+    /// Caddy placeholder such as `{http.request.host}`, without braces.
+    Placeholder(String),
+    /// Bare identifier such as `request`.
+    Ident(String),
+    /// Field access `target.field`.
+    Member { target: Box<Self>, field: String },
+    /// Index access `target[index]`.
+    Index { target: Box<Self>, index: Box<Self> },
+    /// Function call, or method call when `target` is set.
+    Call {
+        target: Option<Box<Self>>,
+        name: String,
+        args: Vec<Self>,
+    },
+    /// List literal `[a, b]`.
+    List(Vec<Self>),
+    /// Unary operation.
+    Unary { op: UnaryOp, operand: Box<Self> },
+    /// Binary operation.
+    Binary {
+        op: BinaryOp,
+        left: Box<Self>,
+        right: Box<Self>,
+    },
+    /// Conditional `condition ? then : otherwise`.
+    Conditional {
+        condition: Box<Self>,
+        then: Box<Self>,
+        otherwise: Box<Self>,
+    },
+}
+
+/// Literal value in an expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Literal {
+    /// Number as written, e.g. `404` or `1.5`.
+    Number(String),
+    /// String literal, unescaped.
+    String(String),
+    Bool(bool),
+    Null,
+}
+
+/// Unary operator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    /// `!`
+    Not,
+    /// `-`
+    Neg,
+}
+
+/// Binary operator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    In,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+impl BinaryOp {
+    /// Operator as written in CEL.
     #[must_use]
-    pub fn value(&self) -> &str {
+    pub const fn as_str(self) -> &'static str {
         match self {
-            Self::Unquoted(s) | Self::Quoted(s) | Self::Backtick(s) => s,
-            Self::Heredoc { content, .. } => content,
+            Self::Or => "||",
+            Self::And => "&&",
+            Self::Eq => "==",
+            Self::Ne => "!=",
+            Self::Lt => "<",
+            Self::Le => "<=",
+            Self::Gt => ">",
+            Self::Ge => ">=",
+            Self::In => "in",
+            Self::Add => "+",
+            Self::Sub => "-",
+            Self::Mul => "*",
+            Self::Div => "/",
+            Self::Rem => "%",
+        }
+    }
+
+    /// Binding strength; higher binds tighter.
+    #[must_use]
+    pub const fn precedence(self) -> u8 {
+        match self {
+            Self::Or => 1,
+            Self::And => 2,
+            Self::Eq | Self::Ne | Self::Lt | Self::Le | Self::Gt | Self::Ge | Self::In => 3,
+            Self::Add | Self::Sub => 4,
+            Self::Mul | Self::Div | Self::Rem => 5,
+        }
+    }
+}
+
+impl Expression {
+    /// Names of all placeholders referenced, in order.
+    #[must_use]
+    pub fn placeholders(&self) -> Vec<&str> {
+        let mut out = Vec::new();
+        self.collect_placeholders(&mut out);
+        out
+    }
+
+    fn collect_placeholders<'a>(&'a self, out: &mut Vec<&'a str>) {
+        match self {
+            Self::Placeholder(name) => out.push(name),
+            Self::Literal(_) | Self::Ident(_) => {}
+            Self::Member { target, .. } => target.collect_placeholders(out),
+            Self::Index { target, index } => {
+                target.collect_placeholders(out);
+                index.collect_placeholders(out);
+            }
+            Self::Call { target, args, .. } => {
+                if let Some(target) = target {
+                    target.collect_placeholders(out);
+                }
+                for arg in args {
+                    arg.collect_placeholders(out);
+                }
+            }
+            Self::List(items) => {
+                for item in items {
+                    item.collect_placeholders(out);
+                }
+            }
+            Self::Unary { operand, .. } => operand.collect_placeholders(out),
+            Self::Binary { left, right, .. } => {
+                left.collect_placeholders(out);
+                right.collect_placeholders(out);
+            }
+            Self::Conditional {
+                condition,
+                then,
+                otherwise,
+            } => {
+                condition.collect_placeholders(out);
+                then.collect_placeholders(out);
+                otherwise.collect_placeholders(out);
+            }
+        }
+    }
+
+    /// Binding strength of the outermost node, for parenthesizing.
+    const fn precedence(&self) -> u8 {
+        match self {
+            Self::Conditional { .. } => 0,
+            Self::Binary { op, .. } => op.precedence(),
+            Self::Unary { .. } => 6,
+            _ => 7,
         }
     }
 }
@@ -111,6 +378,9 @@ impl fmt::Display for Scheme {
 
 impl fmt::Display for Address {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(raw) = &self.raw {
+            return f.write_str(raw);
+        }
         if let Some(scheme) = &self.scheme {
             write!(f, "{scheme}://")?;
         }
@@ -130,7 +400,15 @@ impl fmt::Display for Matcher {
         match self {
             Self::All => f.write_str("*"),
             Self::Path(p) => f.write_str(p),
+            Self::Paths(paths) => f.write_str(&paths.join(" ")),
             Self::Named(n) => write!(f, "@{n}"),
+            Self::Not { module, args } => {
+                write!(f, "not {module}")?;
+                for arg in args {
+                    write!(f, " {arg}")?;
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -157,6 +435,106 @@ impl fmt::Display for Argument {
             Self::Heredoc { marker, content } => {
                 write!(f, "<<{marker}\n{content}\n{marker}")
             }
+            Self::EnvVar { name, default } => match default {
+                Some(default) => write!(f, "{{${name}:{default}}}"),
+                None => write!(f, "{{${name}}}"),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Number(n) => f.write_str(n),
+            Self::String(s) => {
+                f.write_str("'")?;
+                for ch in s.chars() {
+                    match ch {
+                        '\'' => f.write_str("\\'")?,
+                        '\\' => f.write_str("\\\\")?,
+                        '\n' => f.write_str("\\n")?,
+                        _ => write!(f, "{ch}")?,
+                    }
+                }
+                f.write_str("'")
+            }
+            Self::Bool(b) => write!(f, "{b}"),
+            Self::Null => f.write_str("null"),
+        }
+    }
+}
+
+/// Write `expr`, parenthesized if it binds looser than `min`.
+fn write_operand(f: &mut fmt::Formatter<'_>, expr: &Expression, min: u8) -> fmt::Result {
+    if expr.precedence() < min {
+        write!(f, "({expr})")
+    } else {
+        write!(f, "{expr}")
+    }
+}
+
+fn write_list(f: &mut fmt::Formatter<'_>, items: &[Expression]) -> fmt::Result {
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            f.write_str(", ")?;
+        }
+        write!(f, "{item}")?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Literal(lit) => write!(f, "{lit}"),
+            Self::Placeholder(name) => write!(f, "{{{name}}}"),
+            Self::Ident(name) => f.write_str(name),
+            Self::Member { target, field } => {
+                write_operand(f, target, 7)?;
+                write!(f, ".{field}")
+            }
+            Self::Index { target, index } => {
+                write_operand(f, target, 7)?;
+                write!(f, "[{index}]")
+            }
+            Self::Call { target, name, args } => {
+                if let Some(target) = target {
+                    write_operand(f, target, 7)?;
+                    f.write_str(".")?;
+                }
+                write!(f, "{name}(")?;
+                write_list(f, args)?;
+                f.write_str(")")
+            }
+            Self::List(items) => {
+                f.write_str("[")?;
+                write_list(f, items)?;
+                f.write_str("]")
+            }
+            Self::Unary { op, operand } => {
+                f.write_str(match op {
+                    UnaryOp::Not => "!",
+                    UnaryOp::Neg => "-",
+                })?;
+                write_operand(f, operand, 6)
+            }
+            Self::Binary { op, left, right } => {
+                write_operand(f, left, op.precedence())?;
+                write!(f, " {} ", op.as_str())?;
+                write_operand(f, right, op.precedence() + 1)
+            }
+            Self::Conditional {
+                condition,
+                then,
+                otherwise,
+            } => {
+                write_operand(f, condition, 1)?;
+                f.write_str(" ? ")?;
+                write_operand(f, then, 1)?;
+                f.write_str(" : ")?;
+                write!(f, "{otherwise}")
+            }
         }
     }
 }
@@ -195,5 +573,6 @@ pub fn parse_address(addr: &str) -> Address {
         host,
         port,
         path,
+        raw: None,
     }
 }
@@ -0,0 +1,362 @@
+//! Command-line specification and parser.
+//!
+//! Every command, flag and positional argument is declared once in
+//! `COMMANDS`; parsing, usage text and shell completions are all
+//! derived from that table.
+
+use std::fmt;
+
+/// A command-line flag.
+pub struct Flag {
+    pub long: &'static str,
+    /// Name of the flag's value, if it takes one.
+    pub value: Option<&'static str>,
+    pub about: &'static str,
+}
+
+/// Positional arguments of a command.
+pub enum Positionals {
+    /// One or more files.
+    Files,
+    /// A fixed list of named arguments.
+    Named(&'static [Arg]),
+}
+
+/// A named positional argument.
+pub struct Arg {
+    pub name: &'static str,
+    pub kind: ArgKind,
+}
+
+/// What a positional argument holds, for completion.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ArgKind {
+    File,
+    Text,
+    Choice(&'static [&'static str]),
+}
+
+/// A subcommand.
+pub struct Command {
+    pub name: &'static str,
+    pub about: &'static str,
+    pub flags: &'static [Flag],
+    pub positionals: Positionals,
+}
+
+const WRITE: Flag = Flag {
+    long: "write",
+    value: None,
+    about: "Update files in place",
+};
+
+const TAB_WIDTH: Flag = Flag {
+    long: "tab-width",
+    value: Some("N"),
+    about: "Tab width for visual error columns (default 4)",
+};
+
+const CADDY: Flag = Flag {
+    long: "caddy",
+    value: None,
+    about: "Format exactly like `caddy fmt`",
+};
+
+/// Shells supported by `completions`.
+pub const SHELLS: &[&str] = &["bash", "zsh", "fish"];
+
+/// All subcommands.
+pub const COMMANDS: &[Command] = &[
+    Command {
+        name: "validate",
+        about: "Check if Caddyfile(s) are valid",
+        flags: &[TAB_WIDTH],
+        positionals: Positionals::Files,
+    },
+    Command {
+        name: "fmt",
+        about: "Format Caddyfile(s) and print to stdout",
+        flags: &[WRITE, CADDY, TAB_WIDTH],
+        positionals: Positionals::Files,
+    },
+    Command {
+        name: "check",
+        about: "Check if Caddyfile(s) are formatted",
+        flags: &[CADDY, TAB_WIDTH],
+        positionals: Positionals::Files,
+    },
+    Command {
+        name: "placeholders",
+        about: "List runtime placeholders by namespace",
+        flags: &[],
+        positionals: Positionals::Files,
+    },
+    Command {
+        name: "sort",
+        about: "Print Caddyfile(s) in canonical order",
+        flags: &[WRITE, TAB_WIDTH],
+        positionals: Positionals::Files,
+    },
+    Command {
+        name: "query",
+        about: "Print nodes matching a selector",
+        flags: &[Flag {
+            long: "json",
+            value: None,
+            about: "Print matches as JSON",
+        }],
+        positionals: Positionals::Named(&[
+            Arg {
+                name: "file",
+                kind: ArgKind::File,
+            },
+            Arg {
+                name: "selector",
+                kind: ArgKind::Text,
+            },
+        ]),
+    },
+    Command {
+        name: "merge-driver",
+        about: "Git merge driver (merge-driver %O %A %B)",
+        flags: &[],
+        positionals: Positionals::Named(&[
+            Arg {
+                name: "base",
+                kind: ArgKind::File,
+            },
+            Arg {
+                name: "ours",
+                kind: ArgKind::File,
+            },
+            Arg {
+                name: "theirs",
+                kind: ArgKind::File,
+            },
+        ]),
+    },
+    Command {
+        name: "completions",
+        about: "Print shell completions (bash, zsh, fish)",
+        flags: &[],
+        positionals: Positionals::Named(&[Arg {
+            name: "shell",
+            kind: ArgKind::Choice(SHELLS),
+        }]),
+    },
+];
+
+/// Error produced while parsing the command line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArgError {
+    MissingCommand,
+    UnknownCommand(String),
+    UnknownFlag {
+        command: &'static str,
+        flag: String,
+    },
+    MissingValue(&'static str),
+    UnexpectedValue(&'static str),
+    InvalidValue {
+        flag: &'static str,
+        value: String,
+    },
+    MissingArgument {
+        command: &'static str,
+        name: &'static str,
+    },
+    TooManyArguments(&'static str),
+    NoFiles,
+}
+
+impl fmt::Display for ArgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingCommand => f.write_str("no command specified"),
+            Self::UnknownCommand(c) => write!(f, "unknown command: {c}"),
+            Self::UnknownFlag { command, flag } => {
+                write!(f, "unknown flag for '{command}': {flag}")
+            }
+            Self::MissingValue(flag) => write!(f, "--{flag} requires a value"),
+            Self::UnexpectedValue(flag) => write!(f, "--{flag} does not take a value"),
+            Self::InvalidValue { flag, value } => {
+                write!(f, "invalid value for --{flag}: {value}")
+            }
+            Self::MissingArgument { command, name } => {
+                write!(f, "'{command}' requires <{name}>")
+            }
+            Self::TooManyArguments(command) => write!(f, "too many arguments for '{command}'"),
+            Self::NoFiles => f.write_str("no files specified"),
+        }
+    }
+}
+
+/// A parsed command line.
+pub struct Parsed {
+    pub command: &'static Command,
+    flags: Vec<(&'static str, Option<String>)>,
+    pub positionals: Vec<String>,
+}
+
+impl Parsed {
+    /// Whether a flag was given.
+    pub fn has(&self, long: &str) -> bool {
+        self.flags.iter().any(|(f, _)| *f == long)
+    }
+
+    /// Parse a flag's value, if given.
+    pub fn value<T: std::str::FromStr>(&self, long: &'static str) -> Result<Option<T>, ArgError> {
+        self.flags
+            .iter()
+            .rev()
+            .find(|(f, _)| *f == long)
+            .and_then(|(_, v)| v.as_deref())
+            .map(|v| {
+                v.parse().map_err(|_| ArgError::InvalidValue {
+                    flag: long,
+                    value: v.to_string(),
+                })
+            })
+            .transpose()
+    }
+}
+
+/// Parse arguments after the program name.
+pub fn parse(args: &[String]) -> Result<Parsed, ArgError> {
+    let (name, rest) = args.split_first().ok_or(ArgError::MissingCommand)?;
+    let command = COMMANDS
+        .iter()
+        .find(|c| c.name == name)
+        .ok_or_else(|| ArgError::UnknownCommand(name.clone()))?;
+
+    let mut flags = Vec::new();
+    let mut positionals = Vec::new();
+    let mut rest = rest.iter();
+    while let Some(arg) = rest.next() {
+        let Some(long) = arg.strip_prefix("--") else {
+            positionals.push(arg.clone());
+            continue;
+        };
+        let (long, inline) = long
+            .split_once('=')
+            .map_or((long, None), |(l, v)| (l, Some(v.to_string())));
+        let flag = command
+            .flags
+            .iter()
+            .find(|f| f.long == long)
+            .ok_or_else(|| ArgError::UnknownFlag {
+                command: command.name,
+                flag: arg.clone(),
+            })?;
+        let value = match (flag.value, inline) {
+            (Some(_), Some(v)) => Some(v),
+            (Some(_), None) => Some(
+                rest.next()
+                    .cloned()
+                    .ok_or(ArgError::MissingValue(flag.long))?,
+            ),
+            (None, Some(_)) => return Err(ArgError::UnexpectedValue(flag.long)),
+            (None, None) => None,
+        };
+        flags.push((flag.long, value));
+    }
+
+    match &command.positionals {
+        Positionals::Files if positionals.is_empty() => return Err(ArgError::NoFiles),
+        Positionals::Files => {}
+        Positionals::Named(names) => {
+            if let Some(arg) = names.get(positionals.len()) {
+                return Err(ArgError::MissingArgument {
+                    command: command.name,
+                    name: arg.name,
+                });
+            }
+            if positionals.len() > names.len() {
+                return Err(ArgError::TooManyArguments(command.name));
+            }
+        }
+    }
+
+    Ok(Parsed {
+        command,
+        flags,
+        positionals,
+    })
+}
+
+/// Print usage, generated from `COMMANDS`.
+pub fn print_usage() {
+    eprintln!("Usage: caddyfile <command> [flags] [args...]");
+    eprintln!();
+    eprintln!("Commands:");
+    for c in COMMANDS {
+        eprintln!("  {:<14}{}", c.name, c.about);
+        let args = match &c.positionals {
+            Positionals::Files => "<files...>".to_string(),
+            Positionals::Named(names) => names
+                .iter()
+                .map(|a| format!("<{}>", a.name))
+                .collect::<Vec<_>>()
+                .join(" "),
+        };
+        let flags: String = c
+            .flags
+            .iter()
+            .map(|f| {
+                f.value.map_or_else(
+                    || format!(" [--{}]", f.long),
+                    |v| format!(" [--{}={v}]", f.long),
+                )
+            })
+            .collect();
+        eprintln!("  {:<14}  caddyfile {}{flags} {args}", "", c.name);
+    }
+    eprintln!();
+    eprintln!("Markdown files (.md) are processed through their ```caddy code blocks.");
+    eprintln!();
+    eprintln!("Examples:");
+    eprintln!("  caddyfile validate Caddyfile");
+    eprintln!("  caddyfile fmt --write Caddyfile README.md");
+    eprintln!("  caddyfile query Caddyfile 'site[host=*.example.com] reverse_proxy'");
+    eprintln!("  caddyfile completions bash > /etc/bash_completion.d/caddyfile");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn flags_and_files() {
+        let p = parse(&args(&["fmt", "--write", "a", "--tab-width", "8", "b"])).unwrap();
+        assert_eq!(p.command.name, "fmt");
+        assert!(p.has("write"));
+        assert_eq!(p.value::<usize>("tab-width"), Ok(Some(8)));
+        assert_eq!(p.positionals, ["a", "b"]);
+
+        let p = parse(&args(&["check", "--tab-width=2", "a"])).unwrap();
+        assert_eq!(p.value::<usize>("tab-width"), Ok(Some(2)));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(parse(&[]).err(), Some(ArgError::MissingCommand));
+        assert!(matches!(
+            parse(&args(&["check", "--write", "a"])),
+            Err(ArgError::UnknownFlag { .. })
+        ));
+        assert!(matches!(parse(&args(&["fmt"])), Err(ArgError::NoFiles)));
+        assert!(matches!(
+            parse(&args(&["query", "Caddyfile"])),
+            Err(ArgError::MissingArgument {
+                name: "selector",
+                ..
+            })
+        ));
+        let p = parse(&args(&["fmt", "--tab-width=x", "a"])).unwrap();
+        assert!(p.value::<usize>("tab-width").is_err());
+    }
+}
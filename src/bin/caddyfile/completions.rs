@@ -0,0 +1,184 @@
+//! Shell completion scripts generated from the command table.
+
+use std::fmt::Write;
+
+use crate::args::{ArgKind, COMMANDS, Positionals};
+
+/// Completion script for `shell`, or `None` if it is not supported.
+pub fn script(shell: &str) -> Option<String> {
+    match shell {
+        "bash" => Some(bash()),
+        "zsh" => Some(zsh()),
+        "fish" => Some(fish()),
+        _ => None,
+    }
+}
+
+fn names() -> String {
+    COMMANDS
+        .iter()
+        .map(|c| c.name)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Choices for a command's positional arguments, if it takes a fixed set.
+fn choices(positionals: &Positionals) -> Option<&'static [&'static str]> {
+    match positionals {
+        Positionals::Named(args) => args.iter().find_map(|a| match a.kind {
+            ArgKind::Choice(values) => Some(values),
+            ArgKind::File | ArgKind::Text => None,
+        }),
+        Positionals::Files => None,
+    }
+}
+
+fn bash() -> String {
+    let mut out = String::new();
+    out.push_str("_caddyfile() {\n");
+    out.push_str("    local cur=\"${COMP_WORDS[COMP_CWORD]}\"\n");
+    out.push_str("    if [ \"$COMP_CWORD\" -eq 1 ]; then\n");
+    let _ = writeln!(
+        out,
+        "        COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))",
+        names()
+    );
+    out.push_str("        return\n    fi\n");
+    out.push_str("    local flags=\"\"\n");
+    out.push_str("    case \"${COMP_WORDS[1]}\" in\n");
+    for c in COMMANDS {
+        if let Some(values) = choices(&c.positionals) {
+            let _ = writeln!(
+                out,
+                "        {})\n            COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))\n            return\n            ;;",
+                c.name,
+                values.join(" ")
+            );
+        } else if !c.flags.is_empty() {
+            let flags: Vec<String> = c
+                .flags
+                .iter()
+                .map(|f| {
+                    let eq = if f.value.is_some() { "=" } else { "" };
+                    format!("--{}{eq}", f.long)
+                })
+                .collect();
+            let _ = writeln!(out, "        {}) flags=\"{}\" ;;", c.name, flags.join(" "));
+        }
+    }
+    out.push_str("    esac\n");
+    out.push_str("    if [[ \"$cur\" == -* ]]; then\n");
+    out.push_str("        COMPREPLY=($(compgen -W \"$flags\" -- \"$cur\"))\n");
+    out.push_str("    else\n");
+    out.push_str("        COMPREPLY=($(compgen -f -- \"$cur\"))\n");
+    out.push_str("    fi\n");
+    out.push_str("}\n");
+    out.push_str("complete -o filenames -o nospace -F _caddyfile caddyfile\n");
+    out
+}
+
+fn zsh() -> String {
+    let mut out = String::new();
+    out.push_str("#compdef caddyfile\n\n");
+    out.push_str("_caddyfile() {\n");
+    out.push_str("    local -a commands\n    commands=(\n");
+    for c in COMMANDS {
+        let _ = writeln!(
+            out,
+            "        '{}:{}'",
+            c.name,
+            c.about.replace('\'', "'\\''")
+        );
+    }
+    out.push_str("    )\n");
+    out.push_str("    if (( CURRENT == 2 )); then\n");
+    out.push_str("        _describe 'command' commands\n        return\n    fi\n");
+    out.push_str("    local cmd=$words[2]\n");
+    out.push_str("    shift words\n    (( CURRENT-- ))\n");
+    out.push_str("    case $cmd in\n");
+    for c in COMMANDS {
+        let mut specs: Vec<String> = c
+            .flags
+            .iter()
+            .map(|f| {
+                f.value.map_or_else(
+                    || format!("'--{}[{}]'", f.long, f.about),
+                    |v| format!("'--{}=[{}]:{}:'", f.long, f.about, v.to_lowercase()),
+                )
+            })
+            .collect();
+        match &c.positionals {
+            Positionals::Files => specs.push("'*:file:_files'".to_string()),
+            Positionals::Named(args) => {
+                for (i, a) in args.iter().enumerate() {
+                    let action = match a.kind {
+                        ArgKind::File => "_files".to_string(),
+                        ArgKind::Text => String::new(),
+                        ArgKind::Choice(values) => format!("({})", values.join(" ")),
+                    };
+                    specs.push(format!("'{}:{}:{action}'", i + 1, a.name));
+                }
+            }
+        }
+        let _ = writeln!(out, "        {}) _arguments {} ;;", c.name, specs.join(" "));
+    }
+    out.push_str("    esac\n}\n\n");
+    out.push_str("_caddyfile \"$@\"\n");
+    out
+}
+
+fn fish() -> String {
+    let mut out = String::new();
+    out.push_str("complete -c caddyfile -f\n");
+    for c in COMMANDS {
+        let _ = writeln!(
+            out,
+            "complete -c caddyfile -n __fish_use_subcommand -a {} -d '{}'",
+            c.name, c.about
+        );
+    }
+    for c in COMMANDS {
+        let cond = format!("'__fish_seen_subcommand_from {}'", c.name);
+        for f in c.flags {
+            let value = if f.value.is_some() { " -r" } else { "" };
+            let _ = writeln!(
+                out,
+                "complete -c caddyfile -n {cond} -l {}{value} -d '{}'",
+                f.long, f.about
+            );
+        }
+        if let Some(values) = choices(&c.positionals) {
+            let _ = writeln!(
+                out,
+                "complete -c caddyfile -n {cond} -a '{}'",
+                values.join(" ")
+            );
+        } else {
+            let _ = writeln!(out, "complete -c caddyfile -n {cond} -F");
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scripts_cover_every_command() {
+        for shell in crate::args::SHELLS {
+            let script = script(shell).expect("supported shell");
+            for c in COMMANDS {
+                assert!(script.contains(c.name), "{shell} is missing {}", c.name);
+            }
+        }
+        assert!(script("powershell").is_none());
+    }
+
+    #[test]
+    fn bash_flags() {
+        let script = bash();
+        assert!(script.contains("fmt) flags=\"--write --caddy --tab-width=\" ;;"));
+        assert!(script.contains("compgen -W \"bash zsh fish\""));
+    }
+}
@@ -0,0 +1,81 @@
+//! Minimal JSON encoding of AST nodes for `query --json`.
+
+use std::fmt::Write;
+
+use caddyfile_rs::selector::Node;
+use caddyfile_rs::{Argument, Directive};
+
+pub fn node(node: Node<'_>) -> String {
+    match node {
+        Node::Global(g) => format!(
+            "{{\"type\":\"global\",\"directives\":{}}}",
+            directives(&g.directives)
+        ),
+        Node::Snippet(s) => format!(
+            "{{\"type\":\"snippet\",\"name\":{},\"directives\":{}}}",
+            string(&s.name),
+            directives(&s.directives)
+        ),
+        Node::NamedRoute(r) => format!(
+            "{{\"type\":\"named_route\",\"name\":{},\"directives\":{}}}",
+            string(&r.name),
+            directives(&r.directives)
+        ),
+        Node::Site(s) => {
+            let addrs: Vec<String> = s.addresses.iter().map(|a| string(&a.to_string())).collect();
+            format!(
+                "{{\"type\":\"site\",\"addresses\":[{}],\"directives\":{}}}",
+                addrs.join(","),
+                directives(&s.directives)
+            )
+        }
+        Node::Directive(d) => directive(d),
+    }
+}
+
+fn directives(list: &[Directive]) -> String {
+    let items: Vec<String> = list.iter().map(directive).collect();
+    format!("[{}]", items.join(","))
+}
+
+fn directive(d: &Directive) -> String {
+    let matcher = d
+        .matcher
+        .as_ref()
+        .map_or_else(|| "null".to_string(), |m| string(&m.to_string()));
+    let args: Vec<String> = d.arguments.iter().map(argument).collect();
+    let block = d
+        .block
+        .as_ref()
+        .map_or_else(|| "null".to_string(), |b| directives(b));
+    format!(
+        "{{\"type\":\"directive\",\"name\":{},\"matcher\":{matcher},\
+         \"arguments\":[{}],\"block\":{block}}}",
+        string(&d.name),
+        args.join(",")
+    )
+}
+
+fn argument(arg: &Argument) -> String {
+    string(&arg.value())
+}
+
+fn string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for ch in s.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if u32::from(c) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", u32::from(c));
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
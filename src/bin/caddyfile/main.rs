@@ -0,0 +1,309 @@
+//! CLI tool to validate and format Caddyfile configuration files.
+
+use std::fs;
+use std::path::Path;
+use std::process::ExitCode;
+
+use caddyfile_rs::{FormatOptions, markdown};
+
+use crate::args::{ArgError, Parsed};
+
+mod args;
+mod completions;
+mod json;
+
+/// Tab width used to report visual error columns.
+const DEFAULT_TAB_WIDTH: usize = 4;
+
+fn main() -> ExitCode {
+    let argv: Vec<String> = std::env::args().skip(1).collect();
+    if argv.is_empty() || argv[0] == "--help" || argv[0] == "-h" {
+        args::print_usage();
+        return ExitCode::from(2);
+    }
+    match args::parse(&argv).and_then(|parsed| run(&parsed)) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            eprintln!("Run 'caddyfile --help' for usage.");
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn run(parsed: &Parsed) -> Result<ExitCode, ArgError> {
+    let positionals = &parsed.positionals;
+    let code = match parsed.command.name {
+        "query" => query(&positionals[0], &positionals[1], parsed.has("json")),
+        "merge-driver" => merge_driver(&positionals[0], &positionals[1], &positionals[2]),
+        "completions" => match completions::script(&positionals[0]) {
+            Some(script) => {
+                print!("{script}");
+                ExitCode::SUCCESS
+            }
+            None => {
+                return Err(ArgError::InvalidValue {
+                    flag: "shell",
+                    value: positionals[0].clone(),
+                });
+            }
+        },
+        command => {
+            let write = parsed.has("write");
+            let caddy = parsed.has("caddy");
+            let tab_width = parsed.value("tab-width")?.unwrap_or(DEFAULT_TAB_WIDTH);
+            let mut had_error = false;
+            for path in positionals {
+                let ok = fs::read_to_string(path).map_or_else(
+                    |e| {
+                        eprintln!("{path}: {e}");
+                        false
+                    },
+                    |content| match command {
+                        "validate" => validate_file(path, &content, tab_width),
+                        "fmt" => fmt_file(path, &content, write, caddy, tab_width),
+                        "check" => check_file(path, &content, caddy, tab_width),
+                        "sort" => sort_file(path, &content, write, tab_width),
+                        _ => print_placeholders(path, &content),
+                    },
+                );
+                had_error |= !ok;
+            }
+            if had_error {
+                ExitCode::FAILURE
+            } else {
+                ExitCode::SUCCESS
+            }
+        }
+    };
+    Ok(code)
+}
+
+/// Markdown files are processed through their Caddyfile code blocks.
+fn is_markdown(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("md") || ext.eq_ignore_ascii_case("markdown"))
+}
+
+/// Parse one file and print a summary.
+fn validate_file(path: &str, content: &str, tab_width: usize) -> bool {
+    if is_markdown(path) {
+        let errors = markdown::validate_markdown(content);
+        for e in &errors {
+            eprintln!("{path}: {e}");
+        }
+        if errors.is_empty() {
+            let blocks = markdown::code_blocks(content).len();
+            eprintln!("{path}: valid ({blocks} code block(s))");
+        }
+        return errors.is_empty();
+    }
+    match caddyfile_rs::parse_all_errors(content) {
+        (Some(cf), _) => {
+            let sites = cf.sites.len();
+            let snippets = cf.snippets.len();
+            let named_routes = cf.named_routes.len();
+            let global = if cf.global_options.is_some() {
+                ", global options"
+            } else {
+                ""
+            };
+            eprintln!(
+                "{path}: valid ({sites} site(s), \
+                 {snippets} snippet(s), \
+                 {named_routes} named route(s){global})"
+            );
+            true
+        }
+        (None, errors) => {
+            for e in errors {
+                eprintln!("{path}: {}", e.describe(content, tab_width));
+            }
+            false
+        }
+    }
+}
+
+/// Format one file (or a Markdown file's code blocks), keeping its
+/// indentation and line endings. `caddy` selects the `caddy fmt`
+/// compatible formatter for plain Caddyfiles.
+fn formatted(path: &str, content: &str, caddy: bool, tab_width: usize) -> Result<String, String> {
+    if is_markdown(path) {
+        markdown::format_markdown(content).map_err(|e| e.to_string())
+    } else if caddy {
+        Ok(caddyfile_rs::caddy_fmt(content))
+    } else {
+        let options = FormatOptions::from_source(content);
+        caddyfile_rs::parse_str(content)
+            .map(|cf| caddyfile_rs::format_with_options(&cf, &options))
+            .map_err(|e| e.describe(content, tab_width))
+    }
+}
+
+/// Format one file, printing it or writing it back.
+fn fmt_file(path: &str, content: &str, write: bool, caddy: bool, tab_width: usize) -> bool {
+    let output = match formatted(path, content, caddy, tab_width) {
+        Ok(output) => output,
+        Err(e) => {
+            eprintln!("{path}: {e}");
+            return false;
+        }
+    };
+    if write {
+        write_if_changed(path, content, &output, "formatted")
+    } else {
+        print!("{output}");
+        true
+    }
+}
+
+/// Check whether one file is already formatted.
+fn check_file(path: &str, content: &str, caddy: bool, tab_width: usize) -> bool {
+    match formatted(path, content, caddy, tab_width) {
+        Ok(output) if output == content => {
+            eprintln!("{path}: formatted");
+            true
+        }
+        Ok(_) => {
+            eprintln!("{path}: not formatted");
+            false
+        }
+        Err(e) => {
+            eprintln!("{path}: {e}");
+            false
+        }
+    }
+}
+
+/// Write `output` back to `path` unless it equals `content`.
+fn write_if_changed(path: &str, content: &str, output: &str, action: &str) -> bool {
+    if output == content {
+        return true;
+    }
+    match fs::write(path, output) {
+        Ok(()) => {
+            eprintln!("{path}: {action}");
+            true
+        }
+        Err(e) => {
+            eprintln!("{path}: {e}");
+            false
+        }
+    }
+}
+
+/// Print the runtime placeholders of one file, grouped by namespace.
+fn print_placeholders(path: &str, content: &str) -> bool {
+    match caddyfile_rs::tokenize(content) {
+        Ok(tokens) => {
+            let refs = caddyfile_rs::placeholder::scan(&tokens);
+            for (namespace, refs) in caddyfile_rs::placeholder::group_by_namespace(&refs) {
+                println!("{}", namespace.unwrap_or("(shorthand)"));
+                for r in refs {
+                    println!("  {{{}}}\t{path}:{}:{}", r.name, r.span.line, r.span.column);
+                }
+            }
+            true
+        }
+        Err(e) => {
+            eprintln!("{path}: {e}");
+            false
+        }
+    }
+}
+
+/// Canonically order one file, printing it or writing it back.
+fn sort_file(path: &str, content: &str, write: bool, tab_width: usize) -> bool {
+    let mut cf = match caddyfile_rs::parse_str(content) {
+        Ok(cf) => cf,
+        Err(e) => {
+            eprintln!("{path}: {}", e.describe(content, tab_width));
+            return false;
+        }
+    };
+    caddyfile_rs::canonical::canonicalize(&mut cf);
+    let sorted = caddyfile_rs::format(&cf);
+
+    if write {
+        write_if_changed(path, content, &sorted, "sorted")
+    } else {
+        print!("{sorted}");
+        true
+    }
+}
+
+/// Git merge driver: `merge-driver <base> <ours> <theirs>`.
+///
+/// Writes the merged result to `<ours>` and exits with 1 when conflict
+/// markers were written, as git expects.
+fn merge_driver(base: &str, ours: &str, theirs: &str) -> ExitCode {
+    let load = |path: &str| {
+        fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|c| caddyfile_rs::parse_str(&c).map_err(|e| e.to_string()))
+            .map_err(|e| eprintln!("{path}: {e}"))
+    };
+    let (Ok(b), Ok(o), Ok(t)) = (load(base), load(ours), load(theirs)) else {
+        return ExitCode::from(2);
+    };
+
+    let outcome = caddyfile_rs::merge::merge3(&b, &o, &t);
+    if let Err(e) = fs::write(ours, &outcome.text) {
+        eprintln!("{ours}: {e}");
+        return ExitCode::from(2);
+    }
+    if outcome.is_clean() {
+        ExitCode::SUCCESS
+    } else {
+        eprintln!("{ours}: {} conflict(s)", outcome.conflicts);
+        ExitCode::FAILURE
+    }
+}
+
+/// Evaluate a selector against one file.
+fn query(path: &str, selector: &str, json: bool) -> ExitCode {
+    use caddyfile_rs::selector::Selector;
+
+    let selector = match Selector::parse(selector) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            return ExitCode::from(2);
+        }
+    };
+    let cf = match fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|c| caddyfile_rs::parse_str(&c).map_err(|e| e.to_string()))
+    {
+        Ok(cf) => cf,
+        Err(e) => {
+            eprintln!("{path}: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let nodes: Vec<_> = selector
+        .select(&cf)
+        .iter()
+        .filter_map(|p| cf.node(p))
+        .collect();
+
+    if json {
+        let items: Vec<String> = nodes.iter().map(|n| json::node(*n)).collect();
+        println!("[{}]", items.join(","));
+    } else {
+        for (i, node) in nodes.iter().enumerate() {
+            if i > 0 {
+                println!();
+            }
+            print!("{}", caddyfile_rs::formatter::format_node(*node));
+        }
+    }
+
+    if nodes.is_empty() {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
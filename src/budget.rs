@@ -0,0 +1,483 @@
+//! Complexity budgets for generated configs.
+//!
+//! A `Budget` sets upper limits on the number of sites, block nesting
+//! depth, directives per site and formatted size. Limits are checked
+//! after inlining `import` of snippets defined in the same file, since
+//! that is what Caddy ends up loading. File imports are left as-is.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::ast::{Caddyfile, Directive, Snippet};
+use crate::format;
+
+/// Upper limits; `None` means unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Budget {
+    pub max_sites: Option<usize>,
+    pub max_depth: Option<usize>,
+    pub max_directives_per_site: Option<usize>,
+    pub max_size: Option<usize>,
+}
+
+/// A limit that was exceeded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    /// Too many site blocks.
+    Sites { count: usize, max: usize },
+    /// Blocks nested too deeply inside a site.
+    Depth {
+        site: String,
+        depth: usize,
+        max: usize,
+    },
+    /// Too many directives (counted recursively) in a site.
+    DirectivesPerSite {
+        site: String,
+        count: usize,
+        max: usize,
+    },
+    /// Formatted size in bytes after import expansion. If the expansion
+    /// is already over the limit before formatting, `bytes` is a lower
+    /// bound rather than the exact size.
+    Size { bytes: usize, max: usize },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Sites { count, max } => write!(f, "{count} sites exceed the limit of {max}"),
+            Self::Depth { site, depth, max } => {
+                write!(
+                    f,
+                    "{site}: nesting depth {depth} exceeds the limit of {max}"
+                )
+            }
+            Self::DirectivesPerSite { site, count, max } => {
+                write!(f, "{site}: {count} directives exceed the limit of {max}")
+            }
+            Self::Size { bytes, max } => {
+                write!(
+                    f,
+                    "expanded size of {bytes} bytes exceeds the limit of {max}"
+                )
+            }
+        }
+    }
+}
+
+impl Budget {
+    /// A budget with no limits.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            max_sites: None,
+            max_depth: None,
+            max_directives_per_site: None,
+            max_size: None,
+        }
+    }
+
+    /// Limit the number of site blocks.
+    #[must_use]
+    pub const fn max_sites(mut self, max: usize) -> Self {
+        self.max_sites = Some(max);
+        self
+    }
+
+    /// Limit block nesting depth; a site's own directives are depth 1.
+    #[must_use]
+    pub const fn max_depth(mut self, max: usize) -> Self {
+        self.max_depth = Some(max);
+        self
+    }
+
+    /// Limit the directives in one site, nested ones included.
+    #[must_use]
+    pub const fn max_directives_per_site(mut self, max: usize) -> Self {
+        self.max_directives_per_site = Some(max);
+        self
+    }
+
+    /// Limit the formatted size in bytes.
+    #[must_use]
+    pub const fn max_size(mut self, max: usize) -> Self {
+        self.max_size = Some(max);
+        self
+    }
+
+    /// Check a config against the budget.
+    ///
+    /// Imports are never materialised beyond the size limit: per-site
+    /// counts and depths come from memoized per-snippet totals, and the
+    /// config is only expanded for formatting when a lower bound on its
+    /// size still fits.
+    #[must_use]
+    pub fn check(&self, cf: &Caddyfile) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        let mut expansion = Expansion::new(&cf.snippets);
+
+        if let Some(max) = self.max_sites
+            && cf.sites.len() > max
+        {
+            violations.push(Violation::Sites {
+                count: cf.sites.len(),
+                max,
+            });
+        }
+
+        for site in &cf.sites {
+            let name = site
+                .addresses
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ");
+            let stats = expansion.stats(&site.directives);
+            if let Some(max) = self.max_depth
+                && stats.depth > max
+            {
+                violations.push(Violation::Depth {
+                    site: name.clone(),
+                    depth: stats.depth,
+                    max,
+                });
+            }
+            if let Some(max) = self.max_directives_per_site
+                && stats.count > max
+            {
+                violations.push(Violation::DirectivesPerSite {
+                    site: name,
+                    count: stats.count,
+                    max,
+                });
+            }
+        }
+
+        if let Some(max) = self.max_size {
+            let lower = expansion.min_bytes(cf);
+            let bytes = if lower > max {
+                lower
+            } else {
+                format(&expand_imports(cf)).len()
+            };
+            if bytes > max {
+                violations.push(Violation::Size { bytes, max });
+            }
+        }
+        violations
+    }
+}
+
+/// Totals for a directive list with imports inlined.
+#[derive(Debug, Clone, Copy, Default)]
+struct Stats {
+    /// Directives, counted recursively.
+    count: usize,
+    /// Block nesting depth.
+    depth: usize,
+    /// Lower bound on the formatted size: names and argument values.
+    bytes: usize,
+}
+
+impl Stats {
+    fn add(&mut self, other: Self) {
+        self.count = self.count.saturating_add(other.count);
+        self.depth = self.depth.max(other.depth);
+        self.bytes = self.bytes.saturating_add(other.bytes);
+    }
+}
+
+/// Computes expanded totals without cloning imported snippets.
+///
+/// A snippet's totals depend on which of the snippets it reaches are
+/// already being expanded, since those imports are cut short, so the
+/// memo is keyed by the snippet and that part of the stack.
+struct Expansion<'a> {
+    snippets: &'a [Snippet],
+    reach: HashMap<&'a str, HashSet<&'a str>>,
+    memo: HashMap<(&'a str, Vec<&'a str>), Stats>,
+    stack: Vec<&'a str>,
+}
+
+impl<'a> Expansion<'a> {
+    fn new(snippets: &'a [Snippet]) -> Self {
+        let direct: HashMap<&str, HashSet<&str>> = snippets
+            .iter()
+            .map(|s| {
+                let mut names = HashSet::new();
+                imports(&s.directives, snippets, &mut names);
+                (s.name.as_str(), names)
+            })
+            .collect();
+        let reach = direct
+            .keys()
+            .map(|&name| {
+                let mut seen = HashSet::new();
+                let mut todo = vec![name];
+                while let Some(next) = todo.pop() {
+                    todo.extend(direct[next].iter().filter(|&&s| seen.insert(s)));
+                }
+                (name, seen)
+            })
+            .collect();
+        Self {
+            snippets,
+            reach,
+            memo: HashMap::new(),
+            stack: Vec::new(),
+        }
+    }
+
+    fn stats(&mut self, directives: &'a [Directive]) -> Stats {
+        let mut total = Stats::default();
+        for d in directives {
+            if let Some(snippet) = imported(d, self.snippets, &self.stack) {
+                let stats = self.snippet(snippet);
+                total.add(stats);
+                continue;
+            }
+            let inner = d
+                .block
+                .as_deref()
+                .map(|block| self.stats(block))
+                .unwrap_or_default();
+            total.add(Stats {
+                count: inner.count.saturating_add(1),
+                depth: inner.depth + 1,
+                bytes: inner.bytes.saturating_add(line_bytes(d)),
+            });
+        }
+        total
+    }
+
+    fn snippet(&mut self, snippet: &'a Snippet) -> Stats {
+        let reach = &self.reach[snippet.name.as_str()];
+        let key = (
+            snippet.name.as_str(),
+            self.stack
+                .iter()
+                .copied()
+                .filter(|s| reach.contains(s))
+                .collect(),
+        );
+        if let Some(stats) = self.memo.get(&key) {
+            return *stats;
+        }
+        self.stack.push(&snippet.name);
+        let stats = self.stats(&snippet.directives);
+        self.stack.pop();
+        self.memo.insert(key, stats);
+        stats
+    }
+
+    /// Lower bound on `format(&expand_imports(cf)).len()`.
+    fn min_bytes(&mut self, cf: &'a Caddyfile) -> usize {
+        let mut total = Stats::default();
+        if let Some(global) = &cf.global_options {
+            total.add(self.stats(&global.directives));
+        }
+        for snippet in &cf.snippets {
+            total.add(Self::new(&[]).stats(&snippet.directives));
+        }
+        for route in &cf.named_routes {
+            total.add(self.stats(&route.directives));
+        }
+        for site in &cf.sites {
+            total.add(self.stats(&site.directives));
+        }
+        total.bytes
+    }
+}
+
+/// The snippet an `import` directive inlines, unless it is already being
+/// expanded.
+fn imported<'a>(d: &Directive, snippets: &'a [Snippet], stack: &[&str]) -> Option<&'a Snippet> {
+    (d.name == "import")
+        .then(|| d.arguments.first())
+        .flatten()
+        .and_then(|name| snippets.iter().find(|s| s.name == name.value()))
+        .filter(|s| !stack.contains(&s.name.as_str()))
+}
+
+/// Names of the snippets `directives` import, nested blocks included.
+fn imports<'a>(directives: &[Directive], snippets: &'a [Snippet], names: &mut HashSet<&'a str>) {
+    for d in directives {
+        if let Some(snippet) = imported(d, snippets, &[]) {
+            names.insert(&snippet.name);
+        }
+        if let Some(block) = &d.block {
+            imports(block, snippets, names);
+        }
+    }
+}
+
+/// Bytes a directive's own line takes at minimum once formatted.
+fn line_bytes(d: &Directive) -> usize {
+    d.arguments
+        .iter()
+        .fold(d.name.len() + 1, |n, arg| n + arg.value().len() + 1)
+}
+
+/// Inline `import <snippet>` wherever the snippet is defined in `cf`.
+fn expand_imports(cf: &Caddyfile) -> Caddyfile {
+    let mut out = cf.clone();
+    let mut stack = Vec::new();
+    if let Some(global) = &mut out.global_options {
+        global.directives = expand(&global.directives, &cf.snippets, &mut stack);
+    }
+    for route in &mut out.named_routes {
+        route.directives = expand(&route.directives, &cf.snippets, &mut stack);
+    }
+    for site in &mut out.sites {
+        site.directives = expand(&site.directives, &cf.snippets, &mut stack);
+    }
+    out
+}
+
+fn expand<'a>(
+    directives: &[Directive],
+    snippets: &'a [Snippet],
+    stack: &mut Vec<&'a str>,
+) -> Vec<Directive> {
+    let mut out = Vec::new();
+    for d in directives {
+        if let Some(snippet) = imported(d, snippets, stack) {
+            stack.push(&snippet.name);
+            out.extend(expand(&snippet.directives, snippets, stack));
+            stack.pop();
+            continue;
+        }
+        let mut d = d.clone();
+        if let Some(block) = &d.block {
+            d.block = Some(expand(block, snippets, stack));
+        }
+        out.push(d);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Write as _;
+
+    use super::*;
+    use crate::parse_str;
+
+    const CONFIG: &str = "(headers) {\n\
+                          \theader {\n\t\tX-A a\n\t\tX-B b\n\t}\n\
+                          }\n\n\
+                          a.com {\n\
+                          \timport headers\n\
+                          \thandle /api/* {\n\t\treverse_proxy app:3000\n\t}\n\
+                          }\n\n\
+                          b.com {\n\
+                          \trespond ok\n\
+                          }\n";
+
+    #[test]
+    fn within_budget() {
+        let cf = parse_str(CONFIG).unwrap();
+        let budget = Budget::new()
+            .max_sites(2)
+            .max_depth(2)
+            .max_directives_per_site(5)
+            .max_size(1024);
+        assert!(budget.check(&cf).is_empty());
+    }
+
+    #[test]
+    fn limits_apply_after_import_expansion() {
+        let cf = parse_str(CONFIG).unwrap();
+        let violations = Budget::new()
+            .max_sites(1)
+            .max_depth(1)
+            .max_directives_per_site(4)
+            .max_size(32)
+            .check(&cf);
+        assert_eq!(
+            violations[..3],
+            [
+                Violation::Sites { count: 2, max: 1 },
+                Violation::Depth {
+                    site: "a.com".to_string(),
+                    depth: 2,
+                    max: 1,
+                },
+                Violation::DirectivesPerSite {
+                    site: "a.com".to_string(),
+                    count: 5,
+                    max: 4,
+                },
+            ]
+        );
+        assert!(matches!(violations[3], Violation::Size { max: 32, .. }));
+        assert_eq!(violations.len(), 4);
+    }
+
+    #[test]
+    fn recursive_imports_terminate() {
+        let cf = parse_str("(a) {\n\timport a\n\tlog\n}\n\nx.com {\n\timport a\n}\n").unwrap();
+        let violations = Budget::new().max_directives_per_site(1).check(&cf);
+        assert_eq!(
+            violations,
+            [Violation::DirectivesPerSite {
+                site: "x.com".to_string(),
+                count: 2,
+                max: 1,
+            }]
+        );
+    }
+
+    #[test]
+    fn mutual_imports_count_per_site() {
+        let cf = parse_str(
+            "(a) {\n\timport b\n\tlog\n}\n\n\
+             (b) {\n\timport a\n\trespond ok\n}\n\n\
+             x.com {\n\timport a\n}\n\n\
+             y.com {\n\timport b\n}\n",
+        )
+        .unwrap();
+        let expanded = expand_imports(&cf);
+        assert!(expanded.sites.iter().all(|site| site.directives.len() == 3));
+        let violations = Budget::new().max_directives_per_site(2).check(&cf);
+        assert_eq!(
+            violations,
+            ["x.com", "y.com"].map(|site| Violation::DirectivesPerSite {
+                site: site.to_string(),
+                count: 3,
+                max: 2,
+            })
+        );
+    }
+
+    #[test]
+    fn nested_imports_are_not_materialised() {
+        let mut config = String::from("(s0) {\n\trespond ok\n}\n\n");
+        for i in 1..40 {
+            let prev = i - 1;
+            write!(
+                config,
+                "(s{i}) {{\n\timport s{prev}\n\timport s{prev}\n}}\n\n"
+            )
+            .unwrap();
+        }
+        config.push_str("x.com {\n\timport s39\n}\n");
+        let cf = parse_str(&config).unwrap();
+        let violations = Budget::new()
+            .max_depth(1)
+            .max_directives_per_site(100)
+            .max_size(4096)
+            .check(&cf);
+        assert_eq!(violations.len(), 2);
+        assert_eq!(
+            violations[0],
+            Violation::DirectivesPerSite {
+                site: "x.com".to_string(),
+                count: 1 << 39,
+                max: 100,
+            }
+        );
+        assert!(matches!(violations[1], Violation::Size { bytes, max: 4096 } if bytes > 4096));
+    }
+}
@@ -2,187 +2,477 @@
 //!
 //! Provides a fluent API for programmatically building a Caddyfile.
 
-use crate::ast::{
-    self, Argument, Caddyfile, Directive, GlobalOptions, Matcher, NamedRoute, SiteBlock, Snippet,
-};
+mod auth;
+mod block;
+mod caddyfile;
+mod directive;
+mod encode;
+mod error_pages;
+mod file_server;
+mod global;
+mod header;
+mod matcher;
+mod rate_limit;
+mod respond;
+mod reverse_proxy;
+mod site;
+
+pub use auth::BasicAuthBuilder;
+#[cfg(feature = "hash")]
+pub use auth::PasswordHashError;
+pub use block::Block;
+pub use directive::MatcherError;
+pub use encode::{EncodeBuilder, Encoding};
+pub use error_pages::ErrorPagesBuilder;
+pub use file_server::FileServerBuilder;
+pub use global::{GlobalLog, LogFile, LogLevel, ServerTimeouts, Servers};
+pub use header::HeaderBuilder;
+pub use matcher::MatcherDef;
+pub use rate_limit::{RateLimitBuilder, RateLimitZone};
+pub use respond::{Body, Respond};
+pub use reverse_proxy::{HttpTransport, ReverseProxyBuilder};
+
+use std::time::Duration;
+
+use crate::ast::{Directive, Matcher, SiteBlock};
+use crate::directives::{Import, option};
+
+/// Cross-origin resource sharing policy for `SiteBlock::cors`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CorsConfig {
+    /// Allowed origins such as `https://app.example.com`, or `*` for
+    /// any.
+    pub origins: Vec<String>,
+    /// Methods allowed in preflight responses.
+    pub methods: Vec<String>,
+    /// Request headers allowed in preflight responses.
+    pub headers: Vec<String>,
+    /// How long browsers may cache a preflight response.
+    pub max_age: Option<Duration>,
+    /// Allow cookies and credentials; the request's origin is echoed
+    /// instead of `*`, as browsers require.
+    pub credentials: bool,
+}
 
-impl Caddyfile {
-    /// Create a new empty Caddyfile.
-    #[must_use]
-    pub const fn new() -> Self {
-        Self {
-            global_options: None,
-            snippets: Vec::new(),
-            named_routes: Vec::new(),
-            sites: Vec::new(),
-        }
-    }
+/// Status of a `redir` directive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedirCode {
+    /// 302 Found; Caddy's default.
+    Temporary,
+    /// 301 Moved Permanently.
+    Permanent,
+    /// A 200 page redirecting with an HTML meta refresh.
+    Html,
+    /// Any other 3xx status.
+    Status(u16),
+}
 
-    /// Add a site block.
-    #[must_use]
-    pub fn site(mut self, block: SiteBlock) -> Self {
-        self.sites.push(block);
-        self
+impl std::fmt::Display for RedirCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Temporary => f.write_str("temporary"),
+            Self::Permanent => f.write_str("permanent"),
+            Self::Html => f.write_str("html"),
+            Self::Status(code) => write!(f, "{code}"),
+        }
     }
+}
 
-    /// Set the global options block.
-    #[must_use]
-    pub fn global(mut self, opts: GlobalOptions) -> Self {
-        self.global_options = Some(opts);
-        self
-    }
+/// A `redir [<matcher>] <to> <code>` directive.
+fn redir(matcher: Option<Matcher>, to: &str, code: RedirCode) -> Directive {
+    let mut d = Directive::new("redir").arg(to).arg(&code.to_string());
+    d.matcher = matcher;
+    d
+}
 
-    /// Add a snippet.
-    #[must_use]
-    pub fn snippet(mut self, snippet: Snippet) -> Self {
-        self.snippets.push(snippet);
-        self
+/// A `forward_auth <upstream>` directive checking requests at `uri`.
+fn forward_auth(upstream: &str, uri: &str, copy_headers: &[&str]) -> Directive {
+    let mut block = vec![option("uri", [uri])];
+    if !copy_headers.is_empty() {
+        block.push(option("copy_headers", copy_headers));
     }
+    Directive::new("forward_auth").arg(upstream).block(block)
+}
 
-    /// Add a named route.
-    #[must_use]
-    pub fn named_route(mut self, route: NamedRoute) -> Self {
-        self.named_routes.push(route);
-        self
-    }
+/// A `try_files` directive for `files`. Arguments with placeholders
+/// are quoted, so a leading `{` is never read as the start of a block.
+///
+/// ```
+/// use caddyfile_rs::{Caddyfile, SiteBlock, format};
+///
+/// let cf = Caddyfile::new().site(
+///     SiteBlock::new("example.com").try_files(["{path}", "{path}/", "/index.html"]),
+/// );
+/// assert!(format(&cf).contains("\ttry_files \"{path}\" \"{path}/\" /index.html\n"));
+/// ```
+#[must_use]
+pub fn try_files_directive<I, S>(files: I) -> Directive
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    files
+        .into_iter()
+        .fold(Directive::new("try_files"), |d, file| {
+            let file = file.as_ref();
+            if file.contains('{') {
+                d.quoted_arg(file)
+            } else {
+                d.arg(file)
+            }
+        })
 }
 
-impl Default for Caddyfile {
-    fn default() -> Self {
-        Self::new()
-    }
+/// Build a directive whose sub-block is filled in by a closure.
+fn nested<F>(name: &str, matcher: Option<Matcher>, f: F) -> Directive
+where
+    F: FnOnce(Block) -> Block,
+{
+    let mut d = Directive::new(name).block(f(Block::new()).directives);
+    d.matcher = matcher;
+    d
 }
 
-impl SiteBlock {
-    /// Create a new site block with one address.
-    #[must_use]
-    pub fn new(address: &str) -> Self {
-        Self {
-            addresses: vec![ast::parse_address(address)],
-            directives: Vec::new(),
+/// Builder methods shared by `SiteBlock` and `Block`, both of which
+/// collect directives through `push_directive`.
+macro_rules! directive_helpers {
+    ($ty:ty) => {
+        impl $ty {
+            /// Add a named matcher definition.
+            #[must_use]
+            pub fn matcher_def(self, def: MatcherDef) -> Self {
+                self.directive(def.build())
+            }
+
+            /// Add a `reverse_proxy` directive.
+            #[must_use]
+            pub fn reverse_proxy(self, upstream: &str) -> Self {
+                self.directive(Directive::new("reverse_proxy").arg(upstream))
+            }
+
+            /// Add a `reverse_proxy` directive from a `ReverseProxyBuilder`.
+            #[must_use]
+            pub fn reverse_proxy_with(self, proxy: ReverseProxyBuilder) -> Self {
+                self.directive(proxy.build())
+            }
+
+            /// Ask the SSO gateway at `upstream` whether to allow each request,
+            /// copying `copy_headers` from its answer into the request:
+            /// `forward_auth <upstream> { uri <uri> copy_headers ... }`.
+            ///
+            /// ```
+            /// use caddyfile_rs::{Caddyfile, SiteBlock, format};
+            ///
+            /// let site = SiteBlock::new("app.example.com")
+            ///     .forward_auth(
+            ///         "authelia:9091",
+            ///         "/api/authz/forward-auth",
+            ///         &["Remote-User", "Remote-Groups", "Remote-Email"],
+            ///     )
+            ///     .reverse_proxy("app:3000");
+            /// assert_eq!(
+            ///     format(&Caddyfile::new().site(site)),
+            ///     "app.example.com {\n\
+            ///      \tforward_auth authelia:9091 {\n\
+            ///      \t\turi /api/authz/forward-auth\n\
+            ///      \t\tcopy_headers Remote-User Remote-Groups Remote-Email\n\
+            ///      \t}\n\
+            ///      \n\
+            ///      \treverse_proxy app:3000\n\
+            ///      }\n"
+            /// );
+            /// ```
+            #[must_use]
+            pub fn forward_auth(self, upstream: &str, uri: &str, copy_headers: &[&str]) -> Self {
+                self.directive(forward_auth(upstream, uri, copy_headers))
+            }
+
+            /// Redirect requests matching `from` to `to`: `redir <from> <to> <code>`.
+            #[must_use]
+            pub fn redir(self, from: Matcher, to: &str, code: RedirCode) -> Self {
+                self.directive(redir(Some(from), to, code))
+            }
+
+            /// Redirect every request to `to`: `redir <to> <code>`.
+            #[must_use]
+            pub fn redirect_to(self, to: &str, code: RedirCode) -> Self {
+                self.directive(redir(None, to, code))
+            }
+
+            /// Add a `header` directive from a `HeaderBuilder`.
+            #[must_use]
+            pub fn header_with(self, header: HeaderBuilder) -> Self {
+                self.directive(header.build())
+            }
+
+            /// Add a `root * <path>` directive.
+            #[must_use]
+            pub fn root(self, path: &str) -> Self {
+                self.directive(Directive::new("root").matcher(Matcher::All).arg(path))
+            }
+
+            /// Add a `try_files` directive rewriting to the first of `files`
+            /// that exists; see `try_files_directive`.
+            #[must_use]
+            pub fn try_files<I, S>(self, files: I) -> Self
+            where
+                I: IntoIterator<Item = S>,
+                S: AsRef<str>,
+            {
+                self.directive(try_files_directive(files))
+            }
+
+            /// Add a `file_server` directive.
+            #[must_use]
+            pub fn file_server(self) -> Self {
+                self.directive(Directive::new("file_server"))
+            }
+
+            /// Add a `file_server` directive from a `FileServerBuilder`.
+            #[must_use]
+            pub fn file_server_with(self, file_server: FileServerBuilder) -> Self {
+                self.directive(file_server.build())
+            }
+
+            /// Run the named route `name`: `invoke <name>`.
+            #[must_use]
+            pub fn invoke(self, name: &str) -> Self {
+                self.directive(Directive::new("invoke").arg(name))
+            }
+
+            /// Import the snippet `name`: `import <name>`.
+            #[must_use]
+            pub fn import(self, name: &str) -> Self {
+                self.directive(Directive::from(&Import::new(name)))
+            }
+
+            /// Import the snippet `name`, filling its `{args[N]}` placeholders
+            /// with `args`.
+            #[must_use]
+            pub fn import_with_args<I, S>(self, name: &str, args: I) -> Self
+            where
+                I: IntoIterator<Item = S>,
+                S: AsRef<str>,
+            {
+                let import = args
+                    .into_iter()
+                    .fold(Import::new(name), |i, a| i.arg(a.as_ref()));
+                self.directive(Directive::from(&import))
+            }
+
+            /// Add an `encode gzip` directive.
+            #[must_use]
+            pub fn encode_gzip(self) -> Self {
+                self.directive(Directive::new("encode").arg("gzip"))
+            }
+
+            /// Add an `encode` directive for `encodings`, most preferred first.
+            #[must_use]
+            pub fn encode(self, encodings: impl IntoIterator<Item = Encoding>) -> Self {
+                self.encode_with(EncodeBuilder::new(encodings))
+            }
+
+            /// Add an `encode` directive from an `EncodeBuilder`.
+            #[must_use]
+            pub fn encode_with(self, encode: EncodeBuilder) -> Self {
+                self.directive(encode.build())
+            }
+
+            /// Add a status-only `respond` directive, for requests matching
+            /// `matcher` if given.
+            #[must_use]
+            pub fn respond(self, matcher: Option<Matcher>, status: u16) -> Self {
+                let mut respond = Respond::new().status(status);
+                if let Some(m) = matcher {
+                    respond = respond.matcher(m);
+                }
+                self.respond_with(respond)
+            }
+
+            /// Add a `respond` directive with a body. A `&str` body is written
+            /// as a heredoc when it spans several lines; see `Body::auto`.
+            ///
+            /// ```
+            /// use caddyfile_rs::{Caddyfile, Matcher, SiteBlock, format};
+            ///
+            /// let cf = Caddyfile::new().site(
+            ///     SiteBlock::new("example.com")
+            ///         .respond_body(Some(Matcher::path("/health")), 200, r#"{"status":"ok"}"#)
+            ///         .respond_body(None, 503, "{\n  \"status\": \"down\"\n}"),
+            /// );
+            /// assert!(format(&cf).contains("\trespond /health `{\"status\":\"ok\"}` 200\n"));
+            /// assert!(format(&cf).contains("\t\tbody <<JSON\n\t\t{\n"));
+            /// ```
+            #[must_use]
+            pub fn respond_body(
+                self,
+                matcher: Option<Matcher>,
+                status: u16,
+                body: impl Into<Body>,
+            ) -> Self {
+                let mut respond = Respond::new().status(status).body(body.into());
+                if let Some(m) = matcher {
+                    respond = respond.matcher(m);
+                }
+                self.respond_with(respond)
+            }
+
+            /// Add a `respond` directive from a `Respond` builder.
+            #[must_use]
+            pub fn respond_with(self, respond: Respond) -> Self {
+                self.directive(respond.build())
+            }
+
+            /// Add a `log` directive.
+            #[must_use]
+            pub fn log(self) -> Self {
+                self.directive(Directive::new("log"))
+            }
+
+            /// Add a nested `handle <matcher> { ... }` block.
+            ///
+            /// ```
+            /// use caddyfile_rs::{Caddyfile, Matcher, SiteBlock, format};
+            ///
+            /// let cf = Caddyfile::new().site(
+            ///     SiteBlock::new("example.com")
+            ///         .handle(Matcher::path("/api/*"), |h| h.reverse_proxy("api:8080"))
+            ///         .handle_default(|h| h.root("/srv").file_server()),
+            /// );
+            /// assert!(format(&cf).contains("\thandle /api/* {\n\t\treverse_proxy api:8080\n\t}\n"));
+            /// ```
+            #[must_use]
+            pub fn handle<F>(self, matcher: Matcher, f: F) -> Self
+            where
+                F: FnOnce(Block) -> Block,
+            {
+                self.directive(nested("handle", Some(matcher), f))
+            }
+
+            /// Add a nested `handle { ... }` fallback block without matcher.
+            #[must_use]
+            pub fn handle_default<F>(self, f: F) -> Self
+            where
+                F: FnOnce(Block) -> Block,
+            {
+                self.directive(nested("handle", None, f))
+            }
+
+            /// Add a nested `handle_path <path> { ... }` block, which strips
+            /// the matched prefix from the request path before the handlers
+            /// run. `path` must be a path matcher such as `/api/*`.
+            #[must_use]
+            pub fn handle_path<F>(self, path: &str, f: F) -> Self
+            where
+                F: FnOnce(Block) -> Block,
+            {
+                self.directive(nested("handle_path", Some(Matcher::path(path)), f))
+            }
+
+            /// Add a nested `route [<matcher>] { ... }` block.
+            #[must_use]
+            pub fn route<F>(self, matcher: Option<Matcher>, f: F) -> Self
+            where
+                F: FnOnce(Block) -> Block,
+            {
+                self.directive(nested("route", matcher, f))
+            }
         }
-    }
+    };
+}
 
-    /// Add another address to this site block.
-    #[must_use]
-    pub fn address(mut self, addr: &str) -> Self {
-        self.addresses.push(ast::parse_address(addr));
-        self
-    }
+directive_helpers!(Block);
+directive_helpers!(SiteBlock);
 
-    /// Add a directive to this site block.
-    #[must_use]
-    pub fn directive(mut self, d: Directive) -> Self {
-        self.directives.push(d);
-        self
-    }
+/// A byte size in one of the units Caddy accepts, printed as given:
+/// `ByteSize::mb(10)` is `10MB` and `ByteSize::kib(512)` is `512KiB`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteSize {
+    value: u64,
+    unit: &'static str,
+}
 
-    /// Add a `reverse_proxy` directive.
+impl ByteSize {
+    /// `value` bytes, printed without a unit.
     #[must_use]
-    pub fn reverse_proxy(self, upstream: &str) -> Self {
-        self.directive(Directive::new("reverse_proxy").arg(upstream))
+    pub const fn bytes(value: u64) -> Self {
+        Self { value, unit: "" }
     }
 
-    /// Add an `encode gzip` directive.
+    /// `value` kilobytes (1000 bytes).
     #[must_use]
-    pub fn encode_gzip(self) -> Self {
-        self.directive(Directive::new("encode").arg("gzip"))
+    pub const fn kb(value: u64) -> Self {
+        Self { value, unit: "KB" }
     }
 
-    /// Add a `basic_auth` directive with ACME exclusion.
+    /// `value` megabytes.
     #[must_use]
-    pub fn basic_auth(self, user: &str, hash: &str) -> Self {
-        let matcher_directive = Directive::new("@protected")
-            .arg("not")
-            .arg("path")
-            .arg("/.well-known/acme-challenge/*");
-
-        let auth_directive = Directive::new("basic_auth")
-            .matcher(Matcher::Named("protected".to_string()))
-            .block(vec![Directive::new(user).arg(hash)]);
-
-        self.directive(matcher_directive).directive(auth_directive)
+    pub const fn mb(value: u64) -> Self {
+        Self { value, unit: "MB" }
     }
 
-    /// Add security headers.
+    /// `value` gigabytes.
     #[must_use]
-    pub fn security_headers(self) -> Self {
-        self.directive(Directive::new("header").block(vec![
-            Directive::new("X-Content-Type-Options").quoted_arg("nosniff"),
-            Directive::new("X-Frame-Options").quoted_arg("DENY"),
-            Directive::new("X-XSS-Protection").quoted_arg("1; mode=block"),
-            Directive::new("Referrer-Policy").quoted_arg("strict-origin-when-cross-origin"),
-        ]))
+    pub const fn gb(value: u64) -> Self {
+        Self { value, unit: "GB" }
     }
 
-    /// Add a `tls` directive with arguments.
+    /// `value` kibibytes (1024 bytes).
     #[must_use]
-    pub fn tls(mut self, args: &[&str]) -> Self {
-        let mut d = Directive::new("tls");
-        for arg in args {
-            d = d.arg(arg);
-        }
-        self.directives.push(d);
-        self
+    pub const fn kib(value: u64) -> Self {
+        Self { value, unit: "KiB" }
     }
 
-    /// Add a `log` directive.
+    /// `value` mebibytes.
     #[must_use]
-    pub fn log(self) -> Self {
-        self.directive(Directive::new("log"))
+    pub const fn mib(value: u64) -> Self {
+        Self { value, unit: "MiB" }
     }
 
-    /// Add a `file_server` directive.
+    /// `value` gibibytes.
     #[must_use]
-    pub fn file_server(self) -> Self {
-        self.directive(Directive::new("file_server"))
+    pub const fn gib(value: u64) -> Self {
+        Self { value, unit: "GiB" }
     }
 }
 
-impl Directive {
-    /// Create a new directive with the given name.
-    #[must_use]
-    pub fn new(name: &str) -> Self {
-        Self {
-            name: name.to_string(),
-            matcher: None,
-            arguments: Vec::new(),
-            block: None,
-        }
-    }
-
-    /// Set a matcher on this directive.
-    #[must_use]
-    pub fn matcher(mut self, m: Matcher) -> Self {
-        self.matcher = Some(m);
-        self
+impl std::fmt::Display for ByteSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", self.value, self.unit)
     }
+}
 
-    /// Add an unquoted argument.
-    #[must_use]
-    pub fn arg(mut self, value: &str) -> Self {
-        self.arguments.push(Argument::Unquoted(value.to_string()));
-        self
+/// `duration` in Caddy's duration syntax, e.g. `1m30s` or `250ms`.
+pub(crate) fn duration_text(duration: Duration) -> String {
+    use std::fmt::Write as _;
+
+    if duration.subsec_nanos() != 0 {
+        let nanos = duration.as_nanos();
+        return if nanos % 1_000_000 == 0 {
+            format!("{}ms", nanos / 1_000_000)
+        } else if nanos % 1_000 == 0 {
+            format!("{}us", nanos / 1_000)
+        } else {
+            format!("{nanos}ns")
+        };
     }
-
-    /// Add a double-quoted argument.
-    #[must_use]
-    pub fn quoted_arg(mut self, value: &str) -> Self {
-        self.arguments.push(Argument::Quoted(value.to_string()));
-        self
+    let secs = duration.as_secs();
+    if secs == 0 {
+        return "0s".to_string();
     }
-
-    /// Set a sub-block of directives.
-    #[must_use]
-    pub fn block(mut self, directives: Vec<Self>) -> Self {
-        self.block = Some(directives);
-        self
+    let mut text = String::new();
+    for (n, unit) in [(secs / 3600, 'h'), (secs / 60 % 60, 'm'), (secs % 60, 's')] {
+        if n > 0 {
+            let _ = write!(text, "{n}{unit}");
+        }
     }
+    text
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ast::{Caddyfile, Directive, GlobalOptions, Matcher, SiteBlock};
     use crate::formatter;
 
     #[test]
@@ -244,9 +534,7 @@ mod tests {
     #[test]
     fn build_with_global_options() {
         let cf = Caddyfile::new()
-            .global(GlobalOptions {
-                directives: vec![Directive::new("email").arg("admin@example.com")],
-            })
+            .global(GlobalOptions::new().email("admin@example.com"))
             .site(SiteBlock::new("example.com").log());
 
         let result = formatter::format(&cf);
@@ -255,6 +543,86 @@ mod tests {
         assert!(result.contains("example.com {"));
     }
 
+    #[test]
+    fn duration_text_uses_caddy_units() {
+        assert_eq!(duration_text(Duration::from_secs(0)), "0s");
+        assert_eq!(duration_text(Duration::from_secs(90)), "1m30s");
+        assert_eq!(duration_text(Duration::from_secs(7200)), "2h");
+        assert_eq!(duration_text(Duration::from_millis(250)), "250ms");
+        assert_eq!(duration_text(Duration::from_micros(1500)), "1500us");
+    }
+
+    #[test]
+    fn nested_handle_blocks() {
+        let site = SiteBlock::new("example.com").route(None, |r| {
+            r.handle(Matcher::path("/api/*"), |h| h.reverse_proxy("api:8080"))
+                .handle_default(|h| h.respond(None, 404))
+        });
+        let route = &site.directives[0];
+        assert_eq!(route.name, "route");
+        let inner = route.block.as_ref().expect("route block");
+        assert_eq!(inner[0].matcher, Some(Matcher::Path("/api/*".to_string())));
+        assert_eq!(inner[1].matcher, None);
+    }
+
+    #[test]
+    fn push_in_place() {
+        let mut cf = Caddyfile::new();
+        for (host, upstream) in [("a.com", "a:80"), ("b.com", "b:80")] {
+            let mut site = SiteBlock::new(host);
+            site.push_address(&format!("www.{host}"));
+            let mut proxy = Directive::new("reverse_proxy");
+            proxy.push_arg(upstream);
+            proxy.push_directive(Directive::new("lb_policy").arg("first"));
+            site.push_directive(proxy);
+            cf.push_site(site);
+        }
+        let mut block = Block::new();
+        block.push_directive(Directive::new("log"));
+        let mut global = GlobalOptions::new();
+        global.push_directive(Directive::new("debug"));
+        cf.global_options = Some(global);
+
+        assert_eq!(
+            cf,
+            Caddyfile::new()
+                .global(GlobalOptions::new().debug())
+                .site(
+                    SiteBlock::new("a.com").address("www.a.com").directive(
+                        Directive::new("reverse_proxy")
+                            .arg("a:80")
+                            .block(vec![Directive::new("lb_policy").arg("first")]),
+                    )
+                )
+                .site(
+                    SiteBlock::new("b.com").address("www.b.com").directive(
+                        Directive::new("reverse_proxy")
+                            .arg("b:80")
+                            .block(vec![Directive::new("lb_policy").arg("first")]),
+                    )
+                )
+        );
+        assert_eq!(block, Block::new().log());
+    }
+
+    #[test]
+    fn collect_and_extend() {
+        let mut cf: Caddyfile = ["a.com", "b.com"].into_iter().map(SiteBlock::new).collect();
+        cf.extend([SiteBlock::new("c.com")]);
+        let mut site = SiteBlock::new("d.com");
+        site.extend(["log", "file_server"].into_iter().map(Directive::new));
+        cf.extend(std::iter::once(site));
+
+        assert_eq!(
+            cf,
+            Caddyfile::new()
+                .site(SiteBlock::new("a.com"))
+                .site(SiteBlock::new("b.com"))
+                .site(SiteBlock::new("c.com"))
+                .site(SiteBlock::new("d.com").log().file_server())
+        );
+    }
+
     #[test]
     fn build_default() {
         let cf = Caddyfile::default();
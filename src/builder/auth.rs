@@ -0,0 +1,163 @@
+//! The `basic_auth` directive.
+
+use crate::ast::{Directive, Matcher};
+use crate::directives::option;
+
+/// Fluent builder for the `basic_auth` directive.
+///
+/// Each `user` is followed by its password, either already hashed or,
+/// with the `hash` feature, in plain text to be hashed with bcrypt.
+///
+/// ```
+/// use caddyfile_rs::{BasicAuthBuilder, Caddyfile, SiteBlock, format};
+///
+/// let auth = BasicAuthBuilder::new()
+///     .realm("Staff only")
+///     .user("alice")
+///     .password_hash("$2a$14$alicehash")
+///     .user("bob")
+///     .password_hash("$2a$14$bobhash");
+/// let cf = Caddyfile::new().site(SiteBlock::new("example.com").basic_auth_with(auth));
+/// assert_eq!(
+///     format(&cf),
+///     "example.com {\n\
+///      \tbasic_auth bcrypt \"Staff only\" {\n\
+///      \t\talice $2a$14$alicehash\n\
+///      \t\tbob $2a$14$bobhash\n\
+///      \t}\n\
+///      }\n"
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BasicAuthBuilder {
+    matcher: Option<Matcher>,
+    realm: Option<String>,
+    /// Users and their password hashes.
+    users: Vec<(String, String)>,
+    #[cfg(feature = "hash")]
+    cost: u32,
+}
+
+/// Error produced by `BasicAuthBuilder::password_plain`.
+#[cfg(feature = "hash")]
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("cannot hash the password of '{user}': {reason}")]
+pub struct PasswordHashError {
+    pub user: String,
+    pub reason: String,
+}
+
+impl Default for BasicAuthBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BasicAuthBuilder {
+    /// Create a `basic_auth` directive without users.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            matcher: None,
+            realm: None,
+            users: Vec::new(),
+            #[cfg(feature = "hash")]
+            cost: bcrypt::DEFAULT_COST,
+        }
+    }
+
+    /// Set a matcher.
+    #[must_use]
+    pub fn matcher(mut self, m: Matcher) -> Self {
+        self.matcher = Some(m);
+        self
+    }
+
+    /// Set the realm shown by browsers in the login prompt.
+    #[must_use]
+    pub fn realm(mut self, realm: &str) -> Self {
+        self.realm = Some(realm.to_string());
+        self
+    }
+
+    /// Add a user; set its password with `password_hash` or
+    /// `password_plain`.
+    #[must_use]
+    pub fn user(mut self, name: &str) -> Self {
+        self.users.push((name.to_string(), String::new()));
+        self
+    }
+
+    /// Set the bcrypt hash of the last user's password, as printed by
+    /// `caddy hash-password`. Has no effect before `user`.
+    #[must_use]
+    pub fn password_hash(mut self, hash: &str) -> Self {
+        if let Some((_, password)) = self.users.last_mut() {
+            *password = hash.to_string();
+        }
+        self
+    }
+
+    /// Hash the last user's password with bcrypt. Has no effect
+    /// before `user`.
+    #[cfg(feature = "hash")]
+    pub fn password_plain(mut self, password: &str) -> Result<Self, PasswordHashError> {
+        if let Some((user, hash)) = self.users.last_mut() {
+            *hash = bcrypt::hash(password, self.cost).map_err(|e| PasswordHashError {
+                user: user.clone(),
+                reason: e.to_string(),
+            })?;
+        }
+        Ok(self)
+    }
+
+    /// Set the bcrypt cost used by `password_plain`; defaults to
+    /// `bcrypt::DEFAULT_COST`.
+    #[cfg(feature = "hash")]
+    #[must_use]
+    pub const fn cost(mut self, cost: u32) -> Self {
+        self.cost = cost;
+        self
+    }
+
+    /// Build the `basic_auth` directive.
+    #[must_use]
+    pub fn build(self) -> Directive {
+        let mut d = Directive::new("basic_auth");
+        d.matcher = self.matcher;
+        if let Some(realm) = &self.realm {
+            d = d.arg("bcrypt").quoted_arg(realm);
+        }
+        let users = self
+            .users
+            .iter()
+            .map(|(user, hash)| option(user, [hash]))
+            .collect();
+        d.block(users)
+    }
+}
+
+impl From<BasicAuthBuilder> for Directive {
+    fn from(auth: BasicAuthBuilder) -> Self {
+        auth.build()
+    }
+}
+
+#[cfg(all(test, feature = "hash"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basic_auth_hashes_plain_passwords() {
+        let d = BasicAuthBuilder::new()
+            .cost(4)
+            .user("admin")
+            .password_plain("secret")
+            .unwrap()
+            .build();
+        let block = d.block.expect("users block");
+        assert_eq!(block[0].name, "admin");
+        let hash = block[0].arguments[0].value();
+        assert!(bcrypt::verify("secret", &hash).unwrap());
+    }
+}
@@ -0,0 +1,32 @@
+//! The `Block` builder for nested directive lists.
+
+use crate::ast::Directive;
+
+/// Directive list under construction, handed to the closures of
+/// nesting helpers like `SiteBlock::handle` and `SiteBlock::route`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Block {
+    pub directives: Vec<Directive>,
+}
+
+impl Block {
+    /// Create an empty block.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            directives: Vec::new(),
+        }
+    }
+
+    /// Add a directive.
+    #[must_use]
+    pub fn directive(mut self, d: Directive) -> Self {
+        self.push_directive(d);
+        self
+    }
+
+    /// Add a directive in place.
+    pub fn push_directive(&mut self, d: Directive) {
+        self.directives.push(d);
+    }
+}
@@ -0,0 +1,197 @@
+//! Builder methods on `Caddyfile`.
+
+use super::RedirCode;
+use crate::ast::{Caddyfile, Comments, Directive, GlobalOptions, NamedRoute, SiteBlock, Snippet};
+
+impl Caddyfile {
+    /// Create a new empty Caddyfile.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            global_options: None,
+            snippets: Vec::new(),
+            named_routes: Vec::new(),
+            sites: Vec::new(),
+            trailing_comments: Vec::new(),
+        }
+    }
+
+    /// Add a site block.
+    #[must_use]
+    pub fn site(mut self, block: SiteBlock) -> Self {
+        self.push_site(block);
+        self
+    }
+
+    /// Add a site block in place.
+    ///
+    /// ```
+    /// use caddyfile_rs::{Caddyfile, SiteBlock};
+    ///
+    /// let mut cf = Caddyfile::new();
+    /// for host in ["a.example.com", "b.example.com"] {
+    ///     cf.push_site(SiteBlock::new(host).reverse_proxy("app:3000"));
+    /// }
+    /// assert_eq!(cf.sites.len(), 2);
+    /// ```
+    pub fn push_site(&mut self, block: SiteBlock) {
+        self.sites.push(block);
+    }
+
+    /// Set the global options block.
+    #[must_use]
+    pub fn global(mut self, opts: GlobalOptions) -> Self {
+        self.global_options = Some(opts);
+        self
+    }
+
+    /// Add a snippet.
+    #[must_use]
+    pub fn snippet(mut self, snippet: Snippet) -> Self {
+        self.push_snippet(snippet);
+        self
+    }
+
+    /// Add a snippet in place.
+    pub fn push_snippet(&mut self, snippet: Snippet) {
+        self.snippets.push(snippet);
+    }
+
+    /// Add a named route.
+    #[must_use]
+    pub fn named_route(mut self, route: NamedRoute) -> Self {
+        self.push_named_route(route);
+        self
+    }
+
+    /// Add a named route in place.
+    pub fn push_named_route(&mut self, route: NamedRoute) {
+        self.named_routes.push(route);
+    }
+
+    /// Add a `www.<apex>` site permanently redirecting to `apex`,
+    /// keeping the request URI.
+    ///
+    /// ```
+    /// use caddyfile_rs::{Caddyfile, format};
+    ///
+    /// let cf = Caddyfile::new().redirect_www_to_apex("example.com");
+    /// assert_eq!(
+    ///     format(&cf),
+    ///     "www.example.com {\n\tredir https://example.com{uri} permanent\n}\n"
+    /// );
+    /// ```
+    #[must_use]
+    pub fn redirect_www_to_apex(self, apex: &str) -> Self {
+        let target = format!("https://{apex}{{uri}}");
+        self.site(SiteBlock::new(&format!("www.{apex}")).redirect_to(&target, RedirCode::Permanent))
+    }
+
+    /// Define the named route `&(name)` with `directives` and invoke it
+    /// from the sites at `sites`.
+    ///
+    /// A route of that name is replaced. Each address is looked up
+    /// among the existing sites, and a site is added for any that is
+    /// not found; sites already invoking the route are left as is.
+    ///
+    /// ```
+    /// use caddyfile_rs::{Caddyfile, Directive, SiteBlock, format};
+    ///
+    /// let cf = Caddyfile::new()
+    ///     .site(SiteBlock::new("a.example.com").log())
+    ///     .named_route_with_invoke(
+    ///         "app",
+    ///         vec![Directive::new("reverse_proxy").arg("app:3000")],
+    ///         &["a.example.com", "b.example.com"],
+    ///     );
+    /// assert_eq!(
+    ///     format(&cf),
+    ///     "&(app) {\n\
+    ///      \treverse_proxy app:3000\n\
+    ///      }\n\
+    ///      \n\
+    ///      a.example.com {\n\
+    ///      \tlog\n\
+    ///      \tinvoke app\n\
+    ///      }\n\
+    ///      \n\
+    ///      b.example.com {\n\
+    ///      \tinvoke app\n\
+    ///      }\n"
+    /// );
+    /// ```
+    #[must_use]
+    pub fn named_route_with_invoke(
+        mut self,
+        name: &str,
+        directives: Vec<Directive>,
+        sites: &[&str],
+    ) -> Self {
+        match self.named_routes.iter_mut().find(|r| r.name == name) {
+            Some(route) => route.directives = directives,
+            None => self.push_named_route(NamedRoute {
+                name: name.to_string(),
+                directives,
+                comments: Comments::default(),
+            }),
+        }
+        let invoke = Directive::new("invoke").arg(name);
+        for &address in sites {
+            let site = self
+                .sites
+                .iter_mut()
+                .find(|s| s.addresses.iter().any(|a| a.to_string() == address));
+            match site {
+                Some(site) if !site.directives.contains(&invoke) => {
+                    site.push_directive(invoke.clone());
+                }
+                Some(_) => {}
+                None => self.push_site(SiteBlock::new(address).invoke(name)),
+            }
+        }
+        self
+    }
+
+    /// Add an `http://` site permanently redirecting every request to
+    /// HTTPS on the same host, for setups where `auto_https` is off.
+    #[must_use]
+    pub fn redirect_http_to_https(self) -> Self {
+        self.site(
+            SiteBlock::new("http://").redirect_to("https://{host}{uri}", RedirCode::Permanent),
+        )
+    }
+}
+
+impl Default for Caddyfile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Collect sites into a Caddyfile.
+///
+/// ```
+/// use caddyfile_rs::{Caddyfile, SiteBlock};
+///
+/// let hosts = ["a.example.com", "b.example.com"];
+/// let cf: Caddyfile = hosts
+///     .iter()
+///     .map(|host| SiteBlock::new(host).reverse_proxy("app:3000"))
+///     .collect();
+/// assert_eq!(cf.sites.len(), 2);
+/// ```
+impl FromIterator<SiteBlock> for Caddyfile {
+    fn from_iter<I: IntoIterator<Item = SiteBlock>>(iter: I) -> Self {
+        let mut cf = Self::new();
+        cf.extend(iter);
+        cf
+    }
+}
+
+impl Extend<SiteBlock> for Caddyfile {
+    fn extend<I: IntoIterator<Item = SiteBlock>>(&mut self, iter: I) {
+        for block in iter {
+            self.push_site(block);
+        }
+    }
+}
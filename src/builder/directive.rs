@@ -0,0 +1,244 @@
+//! Builder methods on `Directive` and conversions into its parts.
+
+use std::time::Duration;
+
+use super::{ByteSize, duration_text};
+use crate::ast::{Argument, Comments, Directive, Matcher};
+
+impl Directive {
+    /// Create a new directive with the given name.
+    #[must_use]
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            matcher: None,
+            arguments: Vec::new(),
+            block: None,
+            comments: Comments::default(),
+            continuations: Vec::new(),
+            blank_lines_before: 0,
+        }
+    }
+
+    /// Set a matcher on this directive.
+    #[must_use]
+    pub fn matcher(mut self, m: Matcher) -> Self {
+        self.matcher = Some(m);
+        self
+    }
+
+    /// Add an unquoted argument.
+    #[must_use]
+    pub fn arg(mut self, value: &str) -> Self {
+        self.arguments.push(Argument::Unquoted(value.to_string()));
+        self
+    }
+
+    /// Add a double-quoted argument.
+    #[must_use]
+    pub fn quoted_arg(mut self, value: &str) -> Self {
+        self.arguments.push(Argument::Quoted(value.to_string()));
+        self
+    }
+
+    /// Add a backtick-quoted argument, which may contain double quotes.
+    #[must_use]
+    pub fn backtick_arg(mut self, value: &str) -> Self {
+        self.arguments.push(Argument::Backtick(value.to_string()));
+        self
+    }
+
+    /// Add a heredoc argument delimited by `marker`.
+    #[must_use]
+    pub fn heredoc_arg(mut self, marker: &str, content: &str) -> Self {
+        self.arguments.push(Argument::Heredoc {
+            marker: marker.to_string(),
+            content: content.to_string(),
+        });
+        self
+    }
+
+    /// Add an integer argument.
+    #[must_use]
+    pub fn arg_int(self, value: u64) -> Self {
+        self.arg(&value.to_string())
+    }
+
+    /// Add a port number argument.
+    #[must_use]
+    pub fn arg_port(self, port: u16) -> Self {
+        self.arg_int(u64::from(port))
+    }
+
+    /// Add a duration argument in Caddy's syntax, e.g. `1m30s`.
+    #[must_use]
+    pub fn arg_duration(self, duration: Duration) -> Self {
+        self.arg(&duration_text(duration))
+    }
+
+    /// Add a byte size argument, e.g. `10MB`.
+    #[must_use]
+    pub fn arg_size(self, size: ByteSize) -> Self {
+        self.arg(&size.to_string())
+    }
+
+    /// Add `on` or `off`.
+    #[must_use]
+    pub fn arg_bool(self, value: bool) -> Self {
+        self.arg(if value { "on" } else { "off" })
+    }
+
+    /// Add an environment reference `{$NAME}`, or `{$NAME:default}`
+    /// with a default.
+    #[must_use]
+    pub fn env_arg(mut self, name: &str, default: Option<&str>) -> Self {
+        self.arguments.push(Argument::EnvVar {
+            name: name.to_string(),
+            default: default.map(str::to_string),
+        });
+        self
+    }
+
+    /// Set a sub-block of directives.
+    #[must_use]
+    pub fn block(mut self, directives: Vec<Self>) -> Self {
+        self.block = Some(directives);
+        self
+    }
+
+    /// Replace the argument at `index`, returning the previous value.
+    ///
+    /// Returns `None` and leaves the directive unchanged when `index`
+    /// is out of bounds.
+    pub fn set_arg(&mut self, index: usize, value: impl Into<Argument>) -> Option<Argument> {
+        self.arguments
+            .get_mut(index)
+            .map(|slot| std::mem::replace(slot, value.into()))
+    }
+
+    /// Append an argument in place.
+    pub fn push_arg(&mut self, value: impl Into<Argument>) {
+        self.arguments.push(value.into());
+    }
+
+    /// Append a directive to the sub-block in place, creating the block
+    /// if there is none.
+    pub fn push_directive(&mut self, d: Self) {
+        self.block.get_or_insert_with(Vec::new).push(d);
+    }
+
+    /// Remove the argument at `index`, returning it if it existed.
+    pub fn remove_arg(&mut self, index: usize) -> Option<Argument> {
+        (index < self.arguments.len()).then(|| self.arguments.remove(index))
+    }
+
+    /// Replace the matcher in place, returning the previous one.
+    pub const fn set_matcher(&mut self, matcher: Option<Matcher>) -> Option<Matcher> {
+        std::mem::replace(&mut self.matcher, matcher)
+    }
+}
+
+impl From<&str> for Argument {
+    fn from(s: &str) -> Self {
+        Self::Unquoted(s.to_string())
+    }
+}
+
+impl From<String> for Argument {
+    fn from(s: String) -> Self {
+        Self::Unquoted(s)
+    }
+}
+
+/// Error produced when a string is not a matcher token.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("'{0}' is not a matcher: expected '*', '@name' or a path starting with '/'")]
+pub struct MatcherError(pub String);
+
+impl Matcher {
+    /// A path matcher, e.g. `/api/*`.
+    #[must_use]
+    pub fn path(path: &str) -> Self {
+        Self::Path(path.to_string())
+    }
+
+    /// A reference to the named matcher `@name`; `name` is given
+    /// without the `@`.
+    #[must_use]
+    pub fn named(name: &str) -> Self {
+        Self::Named(name.to_string())
+    }
+}
+
+impl TryFrom<&str> for Matcher {
+    type Error = MatcherError;
+
+    /// Read `*` as `All`, `@name` as `Named` and `/path` as a path
+    /// matcher, the same tokens the parser takes as matchers.
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        if s == "*" {
+            Ok(Self::All)
+        } else if let Some(name) = s.strip_prefix('@')
+            && !name.is_empty()
+        {
+            Ok(Self::named(name))
+        } else if s.starts_with('/') {
+            Ok(Self::path(s))
+        } else {
+            Err(MatcherError(s.to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matcher_try_from_str() {
+        assert_eq!(Matcher::try_from("*"), Ok(Matcher::All));
+        assert_eq!(Matcher::try_from("@api"), Ok(Matcher::named("api")));
+        assert_eq!(Matcher::try_from("/api/*"), Ok(Matcher::path("/api/*")));
+        for token in ["api", "@", "", "example.com"] {
+            assert_eq!(
+                Matcher::try_from(token),
+                Err(MatcherError(token.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn directive_argument_mutation() {
+        let mut d = Directive::new("reverse_proxy").arg("app:3000");
+        let old = d.set_arg(0, "app:4000");
+        assert_eq!(old, Some(Argument::Unquoted("app:3000".to_string())));
+        assert_eq!(d.set_arg(5, "x"), None);
+
+        d.push_arg(Argument::Quoted("b".to_string()));
+        assert_eq!(d.arguments.len(), 2);
+        assert_eq!(d.remove_arg(1), Some(Argument::Quoted("b".to_string())));
+        assert_eq!(d.remove_arg(1), None);
+
+        assert_eq!(d.set_matcher(Some(Matcher::All)), None);
+        assert_eq!(d.set_matcher(None), Some(Matcher::All));
+        assert_eq!(d.arguments[0].value(), "app:4000");
+    }
+
+    #[test]
+    fn typed_args() {
+        let d = Directive::new("x")
+            .arg_int(42)
+            .arg_port(8080)
+            .arg_duration(Duration::from_secs(90))
+            .arg_size(ByteSize::mb(10))
+            .arg_size(ByteSize::kib(512))
+            .arg_size(ByteSize::bytes(64))
+            .arg_bool(true)
+            .arg_bool(false);
+        let args: Vec<_> = d.arguments.iter().map(Argument::value).collect();
+        assert_eq!(
+            args,
+            ["42", "8080", "1m30s", "10MB", "512KiB", "64", "on", "off"]
+        );
+    }
+}
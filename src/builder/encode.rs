@@ -0,0 +1,123 @@
+//! The `encode` directive.
+
+use crate::ast::{Directive, Matcher};
+use crate::directives::option;
+
+/// A response compression format of the `encode` directive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Zstd,
+    Gzip,
+}
+
+impl Encoding {
+    /// Format name as written in a Caddyfile.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Zstd => "zstd",
+            Self::Gzip => "gzip",
+        }
+    }
+}
+
+/// Fluent builder for the `encode` directive.
+///
+/// Formats are written inline unless one has a level, in which case
+/// each format goes on its own line of the block.
+///
+/// ```
+/// use caddyfile_rs::{Caddyfile, EncodeBuilder, Encoding, SiteBlock, format};
+///
+/// let encode = EncodeBuilder::new([Encoding::Zstd, Encoding::Gzip])
+///     .level(Encoding::Gzip, "6")
+///     .minimum_length(1024);
+/// let cf = Caddyfile::new().site(SiteBlock::new("example.com").encode_with(encode));
+/// assert_eq!(
+///     format(&cf),
+///     "example.com {\n\
+///      \tencode {\n\
+///      \t\tzstd\n\
+///      \t\tgzip 6\n\
+///      \t\tminimum_length 1024\n\
+///      \t}\n\
+///      }\n"
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncodeBuilder {
+    matcher: Option<Matcher>,
+    /// Formats in order of preference, with their levels.
+    encodings: Vec<(Encoding, Option<String>)>,
+    minimum_length: Option<usize>,
+}
+
+impl EncodeBuilder {
+    /// Create an `encode` directive for `encodings`, most preferred
+    /// first.
+    #[must_use]
+    pub fn new(encodings: impl IntoIterator<Item = Encoding>) -> Self {
+        Self {
+            matcher: None,
+            encodings: encodings.into_iter().map(|e| (e, None)).collect(),
+            minimum_length: None,
+        }
+    }
+
+    /// Set a matcher.
+    #[must_use]
+    pub fn matcher(mut self, m: Matcher) -> Self {
+        self.matcher = Some(m);
+        self
+    }
+
+    /// Set the compression level of `encoding`, adding it if it is not
+    /// listed yet. Gzip takes 1 to 9; zstd takes `fastest`, `better`,
+    /// `best` or `default`.
+    #[must_use]
+    pub fn level(mut self, encoding: Encoding, level: &str) -> Self {
+        let level = Some(level.to_string());
+        match self.encodings.iter_mut().find(|(e, _)| *e == encoding) {
+            Some(entry) => entry.1 = level,
+            None => self.encodings.push((encoding, level)),
+        }
+        self
+    }
+
+    /// Only compress responses of at least `bytes` bytes.
+    #[must_use]
+    pub const fn minimum_length(mut self, bytes: usize) -> Self {
+        self.minimum_length = Some(bytes);
+        self
+    }
+
+    /// Build the `encode` directive.
+    #[must_use]
+    pub fn build(self) -> Directive {
+        let mut d = Directive::new("encode");
+        d.matcher = self.matcher;
+        let mut block = Vec::new();
+        if self.encodings.iter().any(|(_, level)| level.is_some()) {
+            for (encoding, level) in self.encodings {
+                block.push(option(encoding.name(), level));
+            }
+        } else {
+            for (encoding, _) in self.encodings {
+                d = d.arg(encoding.name());
+            }
+        }
+        if let Some(bytes) = self.minimum_length {
+            block.push(option("minimum_length", [bytes]));
+        }
+        if !block.is_empty() {
+            d.block = Some(block);
+        }
+        d
+    }
+}
+
+impl From<EncodeBuilder> for Directive {
+    fn from(encode: EncodeBuilder) -> Self {
+        encode.build()
+    }
+}
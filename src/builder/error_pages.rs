@@ -0,0 +1,114 @@
+//! Custom error pages.
+
+use super::MatcherDef;
+use crate::ast::{Directive, Matcher};
+use crate::directives::{ErrorStatus, HandleErrors};
+
+/// Fluent builder for custom error pages: a `handle_errors` block
+/// rewriting each status to its page and serving it with
+/// `file_server`.
+///
+/// ```
+/// use caddyfile_rs::directives::ErrorStatus;
+/// use caddyfile_rs::{Caddyfile, ErrorPagesBuilder, SiteBlock, format};
+///
+/// let pages = ErrorPagesBuilder::new()
+///     .root("/srv/errors")
+///     .page(ErrorStatus::Code(404), "/404.html")
+///     .page(ErrorStatus::Class(5), "/5xx.html");
+/// let cf = Caddyfile::new().site(SiteBlock::new("example.com").error_pages_with(pages));
+/// assert_eq!(
+///     format(&cf),
+///     "example.com {\n\
+///      \thandle_errors {\n\
+///      \t\troot * /srv/errors\n\
+///      \t\t@404 expression `{err.status_code} == 404`\n\
+///      \t\trewrite @404 /404.html\n\
+///      \t\t@5xx expression `{err.status_code} >= 500 && {err.status_code} < 600`\n\
+///      \t\trewrite @5xx /5xx.html\n\
+///      \t\tfile_server\n\
+///      \t}\n\
+///      }\n"
+/// );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ErrorPagesBuilder {
+    root: Option<String>,
+    pages: Vec<(ErrorStatus, String)>,
+    fallback: Option<String>,
+}
+
+impl ErrorPagesBuilder {
+    /// Create a builder without pages.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            root: None,
+            pages: Vec::new(),
+            fallback: None,
+        }
+    }
+
+    /// Serve the pages from `root` instead of the site's root.
+    #[must_use]
+    pub fn root(mut self, root: &str) -> Self {
+        self.root = Some(root.to_string());
+        self
+    }
+
+    /// Answer errors matching `status` with the page at `path`. The
+    /// first matching page wins.
+    #[must_use]
+    pub fn page(mut self, status: ErrorStatus, path: &str) -> Self {
+        self.pages.push((status, path.to_string()));
+        self
+    }
+
+    /// Answer errors no page matches with the page at `path`.
+    #[must_use]
+    pub fn fallback(mut self, path: &str) -> Self {
+        self.fallback = Some(path.to_string());
+        self
+    }
+
+    /// Build the `handle_errors` directive.
+    #[must_use]
+    pub fn build(self) -> Directive {
+        let mut handle = HandleErrors::default();
+        if let Some(root) = self.root {
+            handle
+                .directives
+                .push(Directive::new("root").matcher(Matcher::All).arg(&root));
+        }
+        for (status, path) in self.pages {
+            let expr = match status {
+                ErrorStatus::Code(code) => format!("{{err.status_code}} == {code}"),
+                ErrorStatus::Class(class) => {
+                    let low = u16::from(class) * 100;
+                    format!(
+                        "{{err.status_code}} >= {low} && {{err.status_code}} < {}",
+                        low + 100
+                    )
+                }
+            };
+            let matcher = MatcherDef::new(&status.to_string()).expression(&expr);
+            let rewrite = Directive::new("rewrite")
+                .matcher(matcher.matcher())
+                .arg(&path);
+            handle.directives.extend([matcher.build(), rewrite]);
+        }
+        if let Some(path) = self.fallback {
+            handle
+                .directives
+                .push(Directive::new("rewrite").matcher(Matcher::All).arg(&path));
+        }
+        handle.directives.push(Directive::new("file_server"));
+        Directive::from(&handle)
+    }
+}
+
+impl From<ErrorPagesBuilder> for Directive {
+    fn from(pages: ErrorPagesBuilder) -> Self {
+        pages.build()
+    }
+}
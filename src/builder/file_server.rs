@@ -0,0 +1,100 @@
+//! The `file_server` directive.
+
+use crate::ast::{Directive, Matcher};
+use crate::directives::option;
+
+/// Fluent builder for the `file_server` directive.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileServerBuilder {
+    matcher: Option<Matcher>,
+    options: Vec<Directive>,
+}
+
+impl FileServerBuilder {
+    /// Create a `file_server` directive with Caddy's defaults.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            matcher: None,
+            options: Vec::new(),
+        }
+    }
+
+    /// Set a matcher.
+    #[must_use]
+    pub fn matcher(mut self, m: Matcher) -> Self {
+        self.matcher = Some(m);
+        self
+    }
+
+    /// Add a subdirective the builder has no method for.
+    #[must_use]
+    pub fn directive(mut self, d: Directive) -> Self {
+        self.options.push(d);
+        self
+    }
+
+    /// List the contents of directories without an index file.
+    #[must_use]
+    pub fn browse(self) -> Self {
+        self.directive(Directive::new("browse"))
+    }
+
+    /// Never serve files matching `patterns`, paths or globs.
+    #[must_use]
+    pub fn hide<I, S>(self, patterns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.directive(option(
+            "hide",
+            patterns.into_iter().map(|p| p.as_ref().to_string()),
+        ))
+    }
+
+    /// Serve the first of `files` that exists for a directory, instead
+    /// of `index.html`.
+    #[must_use]
+    pub fn index<I, S>(self, files: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.directive(option(
+            "index",
+            files.into_iter().map(|f| f.as_ref().to_string()),
+        ))
+    }
+
+    /// Serve precompressed sidecar files such as `page.html.gz`, trying
+    /// `formats` (`zstd`, `br`, `gzip`) in order.
+    #[must_use]
+    pub fn precompressed<I, S>(self, formats: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.directive(option(
+            "precompressed",
+            formats.into_iter().map(|f| f.as_ref().to_string()),
+        ))
+    }
+
+    /// Build the `file_server` directive.
+    #[must_use]
+    pub fn build(self) -> Directive {
+        let mut d = Directive::new("file_server");
+        d.matcher = self.matcher;
+        if !self.options.is_empty() {
+            d.block = Some(self.options);
+        }
+        d
+    }
+}
+
+impl From<FileServerBuilder> for Directive {
+    fn from(file_server: FileServerBuilder) -> Self {
+        file_server.build()
+    }
+}
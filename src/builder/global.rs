@@ -0,0 +1,531 @@
+//! Builders for the global options block.
+
+use std::time::Duration;
+
+use super::ByteSize;
+use crate::ast::{Comments, Directive, GlobalOptions};
+use crate::directives::{DnsProvider, option};
+use crate::global::DirectiveOrder;
+
+impl GlobalOptions {
+    /// Create an empty global options block.
+    ///
+    /// ```
+    /// use caddyfile_rs::{Caddyfile, GlobalOptions, format};
+    ///
+    /// let global = GlobalOptions::new()
+    ///     .email("ops@example.com")
+    ///     .admin_off()
+    ///     .servers(|s| s.protocols(["h1", "h2"]));
+    /// let cf = Caddyfile::new().global(global);
+    /// assert_eq!(
+    ///     format(&cf),
+    ///     "{\n\temail ops@example.com\n\tadmin off\n\tservers {\n\t\tprotocols h1 h2\n\t}\n}\n"
+    /// );
+    /// ```
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            directives: Vec::new(),
+            comments: Comments {
+                leading: Vec::new(),
+                trailing: None,
+                closing: Vec::new(),
+            },
+        }
+    }
+
+    /// Add an option.
+    #[must_use]
+    pub fn directive(mut self, d: Directive) -> Self {
+        self.push_directive(d);
+        self
+    }
+
+    /// Add an option in place.
+    pub fn push_directive(&mut self, d: Directive) {
+        self.directives.push(d);
+    }
+
+    /// Set the ACME account `email`.
+    #[must_use]
+    pub fn email(self, address: &str) -> Self {
+        self.directive(Directive::new("email").arg(address))
+    }
+
+    /// Set the `admin` endpoint address.
+    #[must_use]
+    pub fn admin(self, address: &str) -> Self {
+        self.directive(Directive::new("admin").arg(address))
+    }
+
+    /// Disable the admin endpoint: `admin off`.
+    #[must_use]
+    pub fn admin_off(self) -> Self {
+        self.admin("off")
+    }
+
+    /// Set the ACME CA directory URL: `acme_ca <url>`.
+    #[must_use]
+    pub fn acme_ca(self, url: &str) -> Self {
+        self.directive(Directive::new("acme_ca").arg(url))
+    }
+
+    /// Set the External Account Binding credentials some CAs require:
+    /// `acme_eab { key_id <id> mac_key <key> }`.
+    #[must_use]
+    pub fn acme_eab(self, key_id: &str, mac_key: &str) -> Self {
+        self.directive(Directive::new("acme_eab").block(vec![
+            option("key_id", [key_id]),
+            option("mac_key", [mac_key]),
+        ]))
+    }
+
+    /// Solve ACME challenges for every site with the DNS `provider`:
+    /// `acme_dns <provider> [args]`.
+    #[must_use]
+    pub fn acme_dns(self, provider: &DnsProvider) -> Self {
+        let mut d = Directive::from(provider);
+        d.name = "acme_dns".to_string();
+        self.directive(d)
+    }
+
+    /// Enable debug logging.
+    #[must_use]
+    pub fn debug(self) -> Self {
+        self.directive(Directive::new("debug"))
+    }
+
+    /// Set the port for HTTP: `http_port <port>`.
+    #[must_use]
+    pub fn http_port(self, port: u16) -> Self {
+        self.directive(Directive::new("http_port").arg_port(port))
+    }
+
+    /// Set the port for HTTPS: `https_port <port>`.
+    #[must_use]
+    pub fn https_port(self, port: u16) -> Self {
+        self.directive(Directive::new("https_port").arg_port(port))
+    }
+
+    /// Set `auto_https` to `off`, `disable_redirects`, `ignore_loaded_certs`
+    /// or `disable_certs`.
+    #[must_use]
+    pub fn auto_https(self, mode: &str) -> Self {
+        self.directive(Directive::new("auto_https").arg(mode))
+    }
+
+    /// Add an `order` option.
+    #[must_use]
+    pub fn order(self, order: &DirectiveOrder) -> Self {
+        self.directive(order.into())
+    }
+
+    /// Add a `servers { ... }` block filled in by a closure.
+    #[must_use]
+    pub fn servers<F>(self, f: F) -> Self
+    where
+        F: FnOnce(Servers) -> Servers,
+    {
+        self.directive(f(Servers::new()).build())
+    }
+
+    /// Add a global `log` option, configuring Caddy's own loggers,
+    /// filled in by a closure.
+    #[must_use]
+    pub fn log<F>(self, f: F) -> Self
+    where
+        F: FnOnce(GlobalLog) -> GlobalLog,
+    {
+        self.directive(f(GlobalLog::new()).build())
+    }
+}
+
+impl Default for GlobalOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Fluent builder for the global `servers` option.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use caddyfile_rs::{ByteSize, Caddyfile, Directive, GlobalOptions, format};
+///
+/// let global = GlobalOptions::new().servers(|s| {
+///     s.listener(":443")
+///         .listener_wrappers([Directive::new("proxy_protocol"), Directive::new("tls")])
+///         .timeouts(|t| t.read_header(Duration::from_secs(10)).idle(Duration::from_secs(120)))
+///         .max_header_size(ByteSize::kb(64))
+///         .trusted_proxies(["private_ranges"])
+/// });
+/// assert_eq!(
+///     format(&Caddyfile::new().global(global)),
+///     "{\n\
+///      \tservers :443 {\n\
+///      \t\tlistener_wrappers {\n\
+///      \t\t\tproxy_protocol\n\
+///      \t\t\ttls\n\
+///      \t\t}\n\
+///      \n\
+///      \t\ttimeouts {\n\
+///      \t\t\tread_header 10s\n\
+///      \t\t\tidle 2m\n\
+///      \t\t}\n\
+///      \n\
+///      \t\tmax_header_size 64KB\n\
+///      \t\ttrusted_proxies static private_ranges\n\
+///      \t}\n\
+///      }\n"
+/// );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Servers {
+    listener: Option<String>,
+    options: Vec<Directive>,
+}
+
+impl Servers {
+    /// Create an empty `servers` block.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a sub-option.
+    #[must_use]
+    pub fn directive(mut self, d: Directive) -> Self {
+        self.options.push(d);
+        self
+    }
+
+    /// Set the HTTP versions to serve: `protocols h1 h2 h3`.
+    #[must_use]
+    pub fn protocols<I, S>(self, protocols: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let d = protocols
+            .into_iter()
+            .fold(Directive::new("protocols"), |d, p| d.arg(p.as_ref()));
+        self.directive(d)
+    }
+
+    /// Apply the options only to the server listening on `address`,
+    /// e.g. `:443`, instead of to all servers.
+    #[must_use]
+    pub fn listener(mut self, address: &str) -> Self {
+        self.listener = Some(address.to_string());
+        self
+    }
+
+    /// Wrap the listener, in order, with `wrappers` such as
+    /// `proxy_protocol` or `tls`.
+    #[must_use]
+    pub fn listener_wrappers(self, wrappers: impl IntoIterator<Item = Directive>) -> Self {
+        self.directive(Directive::new("listener_wrappers").block(wrappers.into_iter().collect()))
+    }
+
+    /// Add a `timeouts { ... }` block filled in by a closure.
+    #[must_use]
+    pub fn timeouts<F>(self, f: F) -> Self
+    where
+        F: FnOnce(ServerTimeouts) -> ServerTimeouts,
+    {
+        self.directive(Directive::new("timeouts").block(f(ServerTimeouts::default()).options))
+    }
+
+    /// Largest request header the server reads.
+    #[must_use]
+    pub fn max_header_size(self, size: ByteSize) -> Self {
+        self.directive(Directive::new("max_header_size").arg_size(size))
+    }
+
+    /// Trust client IP headers from `ranges`, CIDR ranges or
+    /// `private_ranges`: `trusted_proxies static <ranges...>`.
+    #[must_use]
+    pub fn trusted_proxies<I, S>(self, ranges: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let d = ranges
+            .into_iter()
+            .fold(Directive::new("trusted_proxies").arg("static"), |d, r| {
+                d.arg(r.as_ref())
+            });
+        self.directive(d)
+    }
+
+    /// Build the `servers` directive.
+    #[must_use]
+    pub fn build(self) -> Directive {
+        let d = Directive::new("servers").block(self.options);
+        match self.listener {
+            Some(address) => d.arg(&address),
+            None => d,
+        }
+    }
+}
+
+/// Minimum severity of the entries a logger writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warn,
+    Error,
+    Panic,
+    Fatal,
+}
+
+impl LogLevel {
+    /// Level name as written in a Caddyfile.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Debug => "DEBUG",
+            Self::Info => "INFO",
+            Self::Warn => "WARN",
+            Self::Error => "ERROR",
+            Self::Panic => "PANIC",
+            Self::Fatal => "FATAL",
+        }
+    }
+}
+
+/// Fluent builder for the global `log` option, which configures
+/// Caddy's process-wide loggers; the site `log` directive configures
+/// access logs instead.
+///
+/// ```
+/// use caddyfile_rs::{ByteSize, Caddyfile, GlobalOptions, LogLevel, format};
+///
+/// let global = GlobalOptions::new().log(|l| {
+///     l.name("errors")
+///         .output_file("/var/log/caddy/errors.log", |f| {
+///             f.roll_size(ByteSize::mib(100)).roll_keep(5)
+///         })
+///         .format("json")
+///         .level(LogLevel::Error)
+///         .exclude(["http.log.access"])
+/// });
+/// assert_eq!(
+///     format(&Caddyfile::new().global(global)),
+///     "{\n\
+///      \tlog errors {\n\
+///      \t\toutput file /var/log/caddy/errors.log {\n\
+///      \t\t\troll_size 100MiB\n\
+///      \t\t\troll_keep 5\n\
+///      \t\t}\n\
+///      \n\
+///      \t\tformat json\n\
+///      \t\tlevel ERROR\n\
+///      \t\texclude http.log.access\n\
+///      \t}\n\
+///      }\n"
+/// );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GlobalLog {
+    name: Option<String>,
+    options: Vec<Directive>,
+}
+
+impl GlobalLog {
+    /// Create a builder for the default logger.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Configure the logger called `name` instead of the default one.
+    #[must_use]
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    /// Add a sub-option.
+    #[must_use]
+    pub fn directive(mut self, d: Directive) -> Self {
+        self.options.push(d);
+        self
+    }
+
+    /// Write to standard output.
+    #[must_use]
+    pub fn output_stdout(self) -> Self {
+        self.directive(option("output", ["stdout"]))
+    }
+
+    /// Write to standard error.
+    #[must_use]
+    pub fn output_stderr(self) -> Self {
+        self.directive(option("output", ["stderr"]))
+    }
+
+    /// Drop all entries.
+    #[must_use]
+    pub fn output_discard(self) -> Self {
+        self.directive(option("output", ["discard"]))
+    }
+
+    /// Write to the file at `path`, with rolling configured by a
+    /// closure; pass `|f| f` to keep Caddy's defaults.
+    #[must_use]
+    pub fn output_file<F>(self, path: &str, f: F) -> Self
+    where
+        F: FnOnce(LogFile) -> LogFile,
+    {
+        let mut d = option("output", ["file", path]);
+        let options = f(LogFile::default()).options;
+        if !options.is_empty() {
+            d.block = Some(options);
+        }
+        self.directive(d)
+    }
+
+    /// Set the encoder, such as `json` or `console`.
+    #[must_use]
+    pub fn format(self, format: &str) -> Self {
+        self.directive(option("format", [format]))
+    }
+
+    /// Set the minimum level of entries to write.
+    #[must_use]
+    pub fn level(self, level: LogLevel) -> Self {
+        self.directive(option("level", [level.name()]))
+    }
+
+    /// Only write entries from the loggers named in `loggers`.
+    #[must_use]
+    pub fn include<I, S>(self, loggers: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.directive(option(
+            "include",
+            loggers.into_iter().map(|l| l.as_ref().to_string()),
+        ))
+    }
+
+    /// Skip entries from the loggers named in `loggers`.
+    #[must_use]
+    pub fn exclude<I, S>(self, loggers: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.directive(option(
+            "exclude",
+            loggers.into_iter().map(|l| l.as_ref().to_string()),
+        ))
+    }
+
+    /// Build the `log` option.
+    #[must_use]
+    pub fn build(self) -> Directive {
+        let d = Directive::new("log").block(self.options);
+        match self.name {
+            Some(name) => d.arg(&name),
+            None => d,
+        }
+    }
+}
+
+impl From<GlobalLog> for Directive {
+    fn from(log: GlobalLog) -> Self {
+        log.build()
+    }
+}
+
+/// Rolling options of `output file`, handed to the closure of
+/// `GlobalLog::output_file`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LogFile {
+    options: Vec<Directive>,
+}
+
+impl LogFile {
+    /// Add an option.
+    #[must_use]
+    pub fn directive(mut self, d: Directive) -> Self {
+        self.options.push(d);
+        self
+    }
+
+    /// Size at which the file is rolled.
+    #[must_use]
+    pub fn roll_size(self, size: ByteSize) -> Self {
+        self.directive(Directive::new("roll_size").arg_size(size))
+    }
+
+    /// Number of rolled files to keep.
+    #[must_use]
+    pub fn roll_keep(self, count: u64) -> Self {
+        self.directive(Directive::new("roll_keep").arg_int(count))
+    }
+
+    /// How long to keep rolled files.
+    #[must_use]
+    pub fn roll_keep_for(self, age: Duration) -> Self {
+        self.directive(Directive::new("roll_keep_for").arg_duration(age))
+    }
+
+    /// Never roll the file.
+    #[must_use]
+    pub fn roll_disabled(self) -> Self {
+        self.directive(Directive::new("roll_disabled"))
+    }
+}
+
+/// Options of a `servers` block's `timeouts { ... }`, handed to the
+/// closure of `Servers::timeouts`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ServerTimeouts {
+    options: Vec<Directive>,
+}
+
+impl ServerTimeouts {
+    /// Add an option.
+    #[must_use]
+    pub fn directive(mut self, d: Directive) -> Self {
+        self.options.push(d);
+        self
+    }
+
+    /// Time allowed to read the request, including the body.
+    #[must_use]
+    pub fn read_body(self, timeout: Duration) -> Self {
+        self.directive(Directive::new("read_body").arg_duration(timeout))
+    }
+
+    /// Time allowed to read the request headers.
+    #[must_use]
+    pub fn read_header(self, timeout: Duration) -> Self {
+        self.directive(Directive::new("read_header").arg_duration(timeout))
+    }
+
+    /// Time allowed to write the response.
+    #[must_use]
+    pub fn write(self, timeout: Duration) -> Self {
+        self.directive(Directive::new("write").arg_duration(timeout))
+    }
+
+    /// Time a kept-alive connection may stay idle.
+    #[must_use]
+    pub fn idle(self, timeout: Duration) -> Self {
+        self.directive(Directive::new("idle").arg_duration(timeout))
+    }
+}
+
+impl From<Servers> for Directive {
+    fn from(servers: Servers) -> Self {
+        servers.build()
+    }
+}
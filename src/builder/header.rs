@@ -0,0 +1,159 @@
+//! The `header` directive.
+
+use std::time::Duration;
+
+use crate::ast::{Directive, Matcher};
+use crate::directives::{HeaderOp, HeaderOps};
+
+/// Fluent builder for the `header` directive, filling in a
+/// `directives::HeaderOps`.
+///
+/// A single operation is written inline, several go into a block.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use caddyfile_rs::{Caddyfile, HeaderBuilder, SiteBlock, format};
+///
+/// let header = HeaderBuilder::new()
+///     .hsts(Duration::from_secs(31_536_000))
+///     .csp("default-src 'self'")
+///     .delete("Server")
+///     .defer();
+/// let cf = Caddyfile::new().site(SiteBlock::new("example.com").header_with(header));
+/// assert_eq!(
+///     format(&cf),
+///     "example.com {\n\
+///      \theader {\n\
+///      \t\tStrict-Transport-Security \"max-age=31536000; includeSubDomains\"\n\
+///      \t\tContent-Security-Policy \"default-src 'self'\"\n\
+///      \t\t-Server\n\
+///      \t\tdefer\n\
+///      \t}\n\
+///      }\n"
+/// );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HeaderBuilder {
+    ops: HeaderOps,
+}
+
+impl HeaderBuilder {
+    /// Create a `header` directive without operations.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set a matcher.
+    #[must_use]
+    pub fn matcher(mut self, m: Matcher) -> Self {
+        self.ops.matcher = Some(m);
+        self
+    }
+
+    /// Add an operation.
+    #[must_use]
+    pub fn op(mut self, op: HeaderOp) -> Self {
+        self.ops.ops.push(op);
+        self
+    }
+
+    /// Replace `field` with `value`.
+    #[must_use]
+    pub fn set(self, field: &str, value: &str) -> Self {
+        self.op(HeaderOp::Set {
+            field: field.to_string(),
+            value: value.to_string(),
+        })
+    }
+
+    /// Add `value` to `field`: `+Field value`.
+    #[must_use]
+    pub fn add(self, field: &str, value: &str) -> Self {
+        self.op(HeaderOp::Add {
+            field: field.to_string(),
+            value: value.to_string(),
+        })
+    }
+
+    /// Remove `field`: `-Field`. A trailing `*` removes every field
+    /// with that prefix.
+    #[must_use]
+    pub fn delete(self, field: &str) -> Self {
+        self.op(HeaderOp::Delete {
+            field: field.to_string(),
+        })
+    }
+
+    /// Set `field` only if the response lacks it: `?Field value`.
+    #[must_use]
+    pub fn default_value(self, field: &str, value: &str) -> Self {
+        self.op(HeaderOp::Default {
+            field: field.to_string(),
+            value: value.to_string(),
+        })
+    }
+
+    /// Substitute `search` with `replace` in the values of `field`.
+    #[must_use]
+    pub fn replace(self, field: &str, search: &str, replace: &str) -> Self {
+        self.op(HeaderOp::Replace {
+            field: field.to_string(),
+            search: search.to_string(),
+            replace: replace.to_string(),
+        })
+    }
+
+    /// Apply the operations after the response is written, so they
+    /// also affect headers set by the handler.
+    #[must_use]
+    pub const fn defer(mut self) -> Self {
+        self.ops.defer = true;
+        self
+    }
+
+    /// Set `Strict-Transport-Security` for `max_age`, including
+    /// subdomains.
+    #[must_use]
+    pub fn hsts(self, max_age: Duration) -> Self {
+        let value = format!("max-age={}; includeSubDomains", max_age.as_secs());
+        self.set("Strict-Transport-Security", &value)
+    }
+
+    /// Set `Strict-Transport-Security` for `max_age`, including
+    /// subdomains and asking for inclusion in browser preload lists.
+    #[must_use]
+    pub fn hsts_preload(self, max_age: Duration) -> Self {
+        let value = format!("max-age={}; includeSubDomains; preload", max_age.as_secs());
+        self.set("Strict-Transport-Security", &value)
+    }
+
+    /// Set the `Content-Security-Policy`.
+    #[must_use]
+    pub fn csp(self, policy: &str) -> Self {
+        self.set("Content-Security-Policy", policy)
+    }
+
+    /// The headers of `SiteBlock::security_headers`, as a starting
+    /// point to customize.
+    #[must_use]
+    pub fn security_headers(self) -> Self {
+        self.set("X-Content-Type-Options", "nosniff")
+            .set("X-Frame-Options", "DENY")
+            .set("X-XSS-Protection", "1; mode=block")
+            .set("Referrer-Policy", "strict-origin-when-cross-origin")
+    }
+
+    /// Build the `header` directive.
+    #[must_use]
+    pub fn build(self) -> Directive {
+        Directive::from(&self.ops)
+    }
+}
+
+impl From<HeaderBuilder> for Directive {
+    fn from(header: HeaderBuilder) -> Self {
+        header.build()
+    }
+}
@@ -0,0 +1,209 @@
+//! Named matcher definitions.
+
+use crate::ast::{Directive, Matcher};
+
+/// Fluent builder for a named matcher definition (`@name ...`).
+///
+/// A single condition is emitted on one line (`@api path /api/*`),
+/// several conditions produce a block with one condition per line.
+///
+/// ```
+/// use caddyfile_rs::{Caddyfile, Directive, MatcherDef, SiteBlock, format};
+///
+/// let api = MatcherDef::new("api")
+///     .path("/api/*")
+///     .header("Accept", "application/json")
+///     .not(|m| m.path("/api/public/*"));
+/// let matcher = api.matcher();
+///
+/// let cf = Caddyfile::new().site(
+///     SiteBlock::new("example.com")
+///         .matcher_def(api)
+///         .directive(Directive::new("reverse_proxy").matcher(matcher).arg("api:8080")),
+/// );
+/// assert!(format(&cf).contains("\t\tnot path /api/public/*\n"));
+/// assert!(format(&cf).contains("\treverse_proxy @api api:8080\n"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatcherDef {
+    name: String,
+    conditions: Vec<Directive>,
+}
+
+impl MatcherDef {
+    /// Create an empty matcher definition named `@name`.
+    #[must_use]
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            conditions: Vec::new(),
+        }
+    }
+
+    /// Add a `not` condition, matching requests that fail the
+    /// conditions added by `f`. One condition is negated inline
+    /// (`not path /public/*`), several go into a `not { ... }` block.
+    #[must_use]
+    pub fn not<F>(self, f: F) -> Self
+    where
+        F: FnOnce(Self) -> Self,
+    {
+        let negated = f(Self::new(&self.name)).conditions;
+        self.condition(inline_or_block("not", negated))
+    }
+
+    /// Add an arbitrary condition, e.g. `Directive::new("file")`.
+    #[must_use]
+    pub fn condition(mut self, condition: Directive) -> Self {
+        self.conditions.push(condition);
+        self
+    }
+
+    /// Add a `path` condition.
+    #[must_use]
+    pub fn path(self, pattern: &str) -> Self {
+        self.condition(Directive::new("path").arg(pattern))
+    }
+
+    /// Add a `path_regexp` condition.
+    #[must_use]
+    pub fn path_regexp(self, regexp: &str) -> Self {
+        self.condition(Directive::new("path_regexp").quoted_arg(regexp))
+    }
+
+    /// Add a `host` condition.
+    #[must_use]
+    pub fn host(self, host: &str) -> Self {
+        self.condition(Directive::new("host").arg(host))
+    }
+
+    /// Add a `method` condition.
+    #[must_use]
+    pub fn method(self, method: &str) -> Self {
+        self.condition(Directive::new("method").arg(method))
+    }
+
+    /// Add a `header` condition.
+    #[must_use]
+    pub fn header(self, field: &str, value: &str) -> Self {
+        self.condition(Directive::new("header").arg(field).quoted_arg(value))
+    }
+
+    /// Add a `header_regexp` condition.
+    #[must_use]
+    pub fn header_regexp(self, field: &str, regexp: &str) -> Self {
+        self.condition(
+            Directive::new("header_regexp")
+                .arg(field)
+                .quoted_arg(regexp),
+        )
+    }
+
+    /// Add a `query` condition (`key=value`).
+    #[must_use]
+    pub fn query(self, key: &str, value: &str) -> Self {
+        self.condition(Directive::new("query").arg(&format!("{key}={value}")))
+    }
+
+    /// Add a `remote_ip` condition.
+    #[must_use]
+    pub fn remote_ip(self, range: &str) -> Self {
+        self.condition(Directive::new("remote_ip").arg(range))
+    }
+
+    /// Add a `client_ip` condition.
+    #[must_use]
+    pub fn client_ip(self, range: &str) -> Self {
+        self.condition(Directive::new("client_ip").arg(range))
+    }
+
+    /// Add a `protocol` condition.
+    #[must_use]
+    pub fn protocol(self, protocol: &str) -> Self {
+        self.condition(Directive::new("protocol").arg(protocol))
+    }
+
+    /// Add a CEL `expression` condition.
+    #[must_use]
+    pub fn expression(self, expr: &str) -> Self {
+        self.condition(Directive::new("expression").backtick_arg(expr))
+    }
+
+    /// Whether no condition has been added.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.conditions.is_empty()
+    }
+
+    /// Name of the matcher, without the `@` prefix.
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// A `Matcher::Named` referencing this definition.
+    #[must_use]
+    pub fn matcher(&self) -> Matcher {
+        Matcher::Named(self.name.clone())
+    }
+
+    /// Build the `@name` definition directive.
+    #[must_use]
+    pub fn build(self) -> Directive {
+        inline_or_block(&format!("@{}", self.name), self.conditions)
+    }
+}
+
+/// A directive `name` holding matcher `conditions`: on one line for a
+/// single condition without a block, as a block otherwise.
+fn inline_or_block(name: &str, mut conditions: Vec<Directive>) -> Directive {
+    if conditions.len() == 1 && conditions[0].block.is_none() {
+        let single = conditions.remove(0);
+        let mut d = Directive::new(name).arg(&single.name);
+        d.arguments.extend(single.arguments);
+        d
+    } else {
+        Directive::new(name).block(conditions)
+    }
+}
+
+impl From<MatcherDef> for Directive {
+    fn from(def: MatcherDef) -> Self {
+        def.build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matcher_def_single_condition_is_inline() {
+        let d = MatcherDef::new("api").path("/api/*").build();
+        assert_eq!(d.name, "@api");
+        assert_eq!(d.arguments.len(), 2);
+        assert!(d.block.is_none());
+    }
+
+    #[test]
+    fn matcher_def_negation() {
+        let d = MatcherDef::new("api")
+            .not(|m| m.path("/public/*"))
+            .method("GET")
+            .build();
+        let block = d.block.expect("block form");
+        assert_eq!(block[0].name, "not");
+        assert_eq!(block[0].arguments[0].value(), "path");
+        assert_eq!(block[1].name, "method");
+
+        let d = MatcherDef::new("internal")
+            .not(|m| m.remote_ip("10.0.0.0/8").method("GET"))
+            .build();
+        let block = d.block.expect("block form");
+        assert_eq!(block.len(), 1);
+        assert_eq!(block[0].name, "not");
+        let negated = block[0].block.as_ref().expect("not block");
+        assert_eq!(negated[0].name, "remote_ip");
+        assert_eq!(negated[1].name, "method");
+    }
+}
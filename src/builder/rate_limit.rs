@@ -0,0 +1,131 @@
+//! The `rate_limit` directive.
+
+use std::time::Duration;
+
+use super::duration_text;
+use crate::ast::{Directive, Matcher};
+use crate::directives::option;
+
+/// Fluent builder for the `rate_limit` directive of the
+/// `caddy-ratelimit` plugin.
+///
+/// The plugin's directive has no default position, so the config also
+/// needs an `order rate_limit ...` global option.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use caddyfile_rs::{Caddyfile, RateLimitBuilder, SiteBlock, format};
+///
+/// let limit = RateLimitBuilder::new().zone("api_zone", |z| {
+///     z.key("{remote_host}")
+///         .events(100)
+///         .window(Duration::from_secs(60))
+/// });
+/// let cf = Caddyfile::new().site(SiteBlock::new("api.example.com").rate_limit_with(limit));
+/// assert_eq!(
+///     format(&cf),
+///     "api.example.com {\n\
+///      \trate_limit {\n\
+///      \t\tzone api_zone {\n\
+///      \t\t\tkey \"{remote_host}\"\n\
+///      \t\t\tevents 100\n\
+///      \t\t\twindow 1m\n\
+///      \t\t}\n\
+///      \t}\n\
+///      }\n"
+/// );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RateLimitBuilder {
+    matcher: Option<Matcher>,
+    options: Vec<Directive>,
+}
+
+impl RateLimitBuilder {
+    /// Create a `rate_limit` directive without zones.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set a matcher.
+    #[must_use]
+    pub fn matcher(mut self, m: Matcher) -> Self {
+        self.matcher = Some(m);
+        self
+    }
+
+    /// Add a zone named `name`, configured by a closure.
+    #[must_use]
+    pub fn zone<F>(self, name: &str, f: F) -> Self
+    where
+        F: FnOnce(RateLimitZone) -> RateLimitZone,
+    {
+        let zone = f(RateLimitZone::default());
+        self.directive(Directive::new("zone").arg(name).block(zone.options))
+    }
+
+    /// Time between sweeps of expired rate limit state.
+    #[must_use]
+    pub fn sweep_interval(self, interval: Duration) -> Self {
+        self.directive(option("sweep_interval", [duration_text(interval)]))
+    }
+
+    /// Add an option the builder has no method for, such as
+    /// `distributed`.
+    #[must_use]
+    pub fn directive(mut self, d: Directive) -> Self {
+        self.options.push(d);
+        self
+    }
+
+    /// Build the `rate_limit` directive.
+    #[must_use]
+    pub fn build(self) -> Directive {
+        let mut d = Directive::new("rate_limit").block(self.options);
+        d.matcher = self.matcher;
+        d
+    }
+}
+
+impl From<RateLimitBuilder> for Directive {
+    fn from(rate_limit: RateLimitBuilder) -> Self {
+        rate_limit.build()
+    }
+}
+
+/// A `zone` of `rate_limit`, handed to the closure of
+/// `RateLimitBuilder::zone`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RateLimitZone {
+    options: Vec<Directive>,
+}
+
+impl RateLimitZone {
+    /// Placeholder whose value identifies a client, such as
+    /// `{remote_host}`.
+    #[must_use]
+    pub fn key(self, key: &str) -> Self {
+        self.directive(Directive::new("key").quoted_arg(key))
+    }
+
+    /// Number of requests allowed per window.
+    #[must_use]
+    pub fn events(self, events: u32) -> Self {
+        self.directive(option("events", [events]))
+    }
+
+    /// Length of the sliding window.
+    #[must_use]
+    pub fn window(self, window: Duration) -> Self {
+        self.directive(option("window", [duration_text(window)]))
+    }
+
+    /// Add an option the builder has no method for, such as `match`.
+    #[must_use]
+    pub fn directive(mut self, d: Directive) -> Self {
+        self.options.push(d);
+        self
+    }
+}
@@ -0,0 +1,192 @@
+//! The `respond` directive.
+
+use crate::ast::{Directive, Matcher};
+
+/// Response body for the `respond` directive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Body {
+    /// Inline body, emitted as a double-quoted argument.
+    Text(String),
+    /// Heredoc body: marker and content.
+    Heredoc(String, String),
+}
+
+impl Body {
+    /// Inline text body.
+    #[must_use]
+    pub fn text(content: &str) -> Self {
+        Self::Text(content.to_string())
+    }
+
+    /// Heredoc body delimited by `marker`.
+    #[must_use]
+    pub fn heredoc(marker: &str, content: &str) -> Self {
+        Self::Heredoc(marker.to_string(), content.to_string())
+    }
+
+    /// A heredoc body when `content` spans several lines, inline text
+    /// otherwise. The heredoc marker follows the content: `HTML` for
+    /// markup, `JSON` for objects and arrays, `TEXT` for anything
+    /// else, suffixed with a number if a line of `content` would close
+    /// a heredoc with that marker.
+    #[must_use]
+    pub fn auto(content: &str) -> Self {
+        if !content.contains('\n') {
+            return Self::text(content);
+        }
+        let base = match content.trim_start().chars().next() {
+            Some('<') => "HTML",
+            Some('{' | '[') => "JSON",
+            _ => "TEXT",
+        };
+        // A line closes the heredoc the way the lexer reads it: the
+        // marker after any indentation, then whitespace or line end.
+        let taken = |marker: &str| {
+            content.split('\n').any(|line| {
+                line.trim_start_matches([' ', '\t'])
+                    .strip_prefix(marker)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with([' ', '\t', '\r']))
+            })
+        };
+        let mut marker = base.to_string();
+        let mut n = 1;
+        while taken(&marker) {
+            n += 1;
+            marker = format!("{base}{n}");
+        }
+        Self::heredoc(&marker, content)
+    }
+}
+
+impl From<&str> for Body {
+    fn from(content: &str) -> Self {
+        Self::auto(content)
+    }
+}
+
+impl From<String> for Body {
+    fn from(content: String) -> Self {
+        Self::auto(&content)
+    }
+}
+
+/// Fluent builder for the `respond` directive.
+///
+/// Text bodies are emitted inline (`respond "OK" 200`), in backticks
+/// if they contain double quotes. Heredoc
+/// bodies and the `close` option go into a sub-block:
+///
+/// ```text
+/// respond 503 {
+///     body <<HTML
+///     ...
+///     HTML
+///     close
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Respond {
+    matcher: Option<Matcher>,
+    status: Option<u16>,
+    body: Option<Body>,
+    close: bool,
+}
+
+impl Respond {
+    /// Create an empty `respond` (status 200, no body).
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set a matcher.
+    #[must_use]
+    pub fn matcher(mut self, m: Matcher) -> Self {
+        self.matcher = Some(m);
+        self
+    }
+
+    /// Set the HTTP status code.
+    #[must_use]
+    pub const fn status(mut self, status: u16) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    /// Set the response body.
+    #[must_use]
+    pub fn body(mut self, body: Body) -> Self {
+        self.body = Some(body);
+        self
+    }
+
+    /// Close the client connection after responding.
+    #[must_use]
+    pub const fn close(mut self) -> Self {
+        self.close = true;
+        self
+    }
+
+    /// Build the `respond` directive.
+    #[must_use]
+    pub fn build(self) -> Directive {
+        let mut d = Directive::new("respond");
+        d.matcher = self.matcher;
+        let mut block = Vec::new();
+
+        match self.body {
+            // backticks spare escaping the quotes of JSON bodies
+            Some(Body::Text(text)) if text.contains('"') && !text.contains('`') => {
+                d = d.backtick_arg(&text);
+            }
+            Some(Body::Text(text)) => d = d.quoted_arg(&text),
+            Some(Body::Heredoc(marker, content)) => {
+                block.push(Directive::new("body").heredoc_arg(&marker, &content));
+            }
+            None => {}
+        }
+        if let Some(status) = self.status {
+            d = d.arg_int(u64::from(status));
+        }
+        if self.close {
+            block.push(Directive::new("close"));
+        }
+        if !block.is_empty() {
+            d.block = Some(block);
+        }
+        d
+    }
+}
+
+impl From<Respond> for Directive {
+    fn from(respond: Respond) -> Self {
+        respond.build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Argument;
+
+    #[test]
+    fn respond_text_body_is_inline() {
+        let d = Respond::new().status(404).body(Body::text("gone")).build();
+        assert_eq!(d.arguments[0], Argument::Quoted("gone".to_string()));
+        assert_eq!(d.arguments[1].value(), "404");
+        assert!(d.block.is_none());
+    }
+
+    #[test]
+    fn respond_heredoc_and_close_use_block() {
+        let d = Respond::new()
+            .status(503)
+            .body(Body::heredoc("HTML", "<h1>down</h1>"))
+            .close()
+            .build();
+        assert_eq!(d.arguments.len(), 1);
+        let block = d.block.expect("block");
+        assert_eq!(block[0].name, "body");
+        assert_eq!(block[1].name, "close");
+    }
+}
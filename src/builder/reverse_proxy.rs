@@ -0,0 +1,277 @@
+//! The `reverse_proxy` directive.
+
+use std::time::Duration;
+
+use super::duration_text;
+use crate::ast::{Directive, Matcher};
+use crate::directives::{LbPolicy, ReverseProxy, Transport, option};
+
+/// Fluent builder for the `reverse_proxy` directive, filling in a
+/// `directives::ReverseProxy`.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use caddyfile_rs::directives::LbPolicy;
+/// use caddyfile_rs::{Caddyfile, ReverseProxyBuilder, SiteBlock, format};
+///
+/// let proxy = ReverseProxyBuilder::new()
+///     .to("app1:8080")
+///     .to("app2:8080")
+///     .lb_policy(LbPolicy::RoundRobin)
+///     .health_uri("/healthz")
+///     .health_interval(Duration::from_secs(10))
+///     .header_up("Host", "{upstream_hostport}")
+///     .transport_http(|t| t.dial_timeout(Duration::from_secs(5)));
+/// let cf = Caddyfile::new().site(SiteBlock::new("example.com").reverse_proxy_with(proxy));
+/// assert_eq!(
+///     format(&cf),
+///     "example.com {\n\
+///      \treverse_proxy app1:8080 app2:8080 {\n\
+///      \t\tlb_policy round_robin\n\
+///      \t\thealth_uri /healthz\n\
+///      \t\thealth_interval 10s\n\n\
+///      \t\ttransport http {\n\
+///      \t\t\tdial_timeout 5s\n\
+///      \t\t}\n\n\
+///      \t\theader_up Host {upstream_hostport}\n\
+///      \t}\n\
+///      }\n"
+/// );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReverseProxyBuilder {
+    proxy: ReverseProxy,
+}
+
+impl ReverseProxyBuilder {
+    /// Create a `reverse_proxy` without upstreams.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set a matcher.
+    #[must_use]
+    pub fn matcher(mut self, m: Matcher) -> Self {
+        self.proxy.matcher = Some(m);
+        self
+    }
+
+    /// Add an upstream.
+    #[must_use]
+    pub fn to(mut self, upstream: &str) -> Self {
+        self.proxy.upstreams.push(upstream.to_string());
+        self
+    }
+
+    /// Set the load-balancing policy.
+    #[must_use]
+    pub fn lb_policy(mut self, policy: LbPolicy) -> Self {
+        self.proxy.lb_policy = Some(policy);
+        self
+    }
+
+    /// Balance over `upstreams` in proportion to their weights with
+    /// `lb_policy weighted_round_robin`, replacing any upstreams added
+    /// before.
+    #[must_use]
+    pub fn weighted<S: AsRef<str>>(
+        mut self,
+        upstreams: impl IntoIterator<Item = (S, u32)>,
+    ) -> Self {
+        let (names, weights): (Vec<String>, Vec<u32>) = upstreams
+            .into_iter()
+            .map(|(u, w)| (u.as_ref().to_string(), w))
+            .unzip();
+        self.proxy.upstreams = names;
+        self.lb_policy(LbPolicy::WeightedRoundRobin(weights))
+    }
+
+    /// Send `percent` of requests to `canary` and the rest to `stable`,
+    /// with the weights reduced to lowest terms.
+    ///
+    /// ```
+    /// use caddyfile_rs::ReverseProxyBuilder;
+    /// use caddyfile_rs::directives::{LbPolicy, ReverseProxy};
+    ///
+    /// let d = ReverseProxyBuilder::canary("app-v1:8080", "app-v2:8080", 10).build();
+    /// let rp = ReverseProxy::try_from(&d).unwrap();
+    /// assert_eq!(rp.lb_policy, Some(LbPolicy::WeightedRoundRobin(vec![9, 1])));
+    /// ```
+    #[must_use]
+    pub fn canary(stable: &str, canary: &str, percent: u8) -> Self {
+        let canary_weight = u32::from(percent.min(100));
+        let stable_weight = 100 - canary_weight;
+        let divisor = gcd(stable_weight, canary_weight);
+        Self::new().weighted([
+            (stable, stable_weight / divisor),
+            (canary, canary_weight / divisor),
+        ])
+    }
+
+    /// Retry a failed request on another upstream up to `retries` times.
+    #[must_use]
+    pub const fn lb_retries(mut self, retries: u32) -> Self {
+        self.proxy.lb_retries = Some(retries);
+        self
+    }
+
+    /// Keep retrying upstreams for up to `duration`.
+    #[must_use]
+    pub fn lb_try_duration(mut self, duration: Duration) -> Self {
+        self.proxy.lb_try_duration = Some(duration_text(duration));
+        self
+    }
+
+    /// Path requested by active health checks.
+    #[must_use]
+    pub fn health_uri(mut self, uri: &str) -> Self {
+        self.proxy.health_checks.active.uri = Some(uri.to_string());
+        self
+    }
+
+    /// Time between active health checks.
+    #[must_use]
+    pub fn health_interval(mut self, interval: Duration) -> Self {
+        self.proxy.health_checks.active.interval = Some(duration_text(interval));
+        self
+    }
+
+    /// Time to wait for an active health check response.
+    #[must_use]
+    pub fn health_timeout(mut self, timeout: Duration) -> Self {
+        self.proxy.health_checks.active.timeout = Some(duration_text(timeout));
+        self
+    }
+
+    /// Expected status of active health checks, such as `2xx`.
+    #[must_use]
+    pub fn health_status(mut self, status: &str) -> Self {
+        self.proxy.health_checks.active.status = Some(status.to_string());
+        self
+    }
+
+    /// Mark an upstream down after `fails` failed requests within
+    /// `duration`.
+    #[must_use]
+    pub fn passive_health(mut self, fails: u32, duration: Duration) -> Self {
+        self.proxy.health_checks.passive.max_fails = Some(fails);
+        self.proxy.health_checks.passive.fail_duration = Some(duration_text(duration));
+        self
+    }
+
+    /// Set a request header sent to the upstream.
+    #[must_use]
+    pub fn header_up(self, field: &str, value: &str) -> Self {
+        self.directive(option("header_up", [field, value]))
+    }
+
+    /// Set a response header sent back to the client.
+    #[must_use]
+    pub fn header_down(self, field: &str, value: &str) -> Self {
+        self.directive(option("header_down", [field, value]))
+    }
+
+    /// Use the `http` transport, configured by a closure.
+    #[must_use]
+    pub fn transport_http<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(HttpTransport) -> HttpTransport,
+    {
+        self.proxy.transport = Some(Transport {
+            protocol: "http".to_string(),
+            options: f(HttpTransport::default()).options,
+        });
+        self
+    }
+
+    /// Add a subdirective the builder has no method for.
+    #[must_use]
+    pub fn directive(mut self, d: Directive) -> Self {
+        self.proxy.other.push(d);
+        self
+    }
+
+    /// Build the `reverse_proxy` directive.
+    #[must_use]
+    pub fn build(self) -> Directive {
+        Directive::from(&self.proxy)
+    }
+}
+
+impl From<ReverseProxyBuilder> for Directive {
+    fn from(proxy: ReverseProxyBuilder) -> Self {
+        proxy.build()
+    }
+}
+
+/// Options of `transport http { ... }`, handed to the closure of
+/// `ReverseProxyBuilder::transport_http`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HttpTransport {
+    options: Vec<Directive>,
+}
+
+impl HttpTransport {
+    /// Add an option.
+    #[must_use]
+    pub fn directive(mut self, d: Directive) -> Self {
+        self.options.push(d);
+        self
+    }
+
+    /// Time to wait for a connection to the upstream.
+    #[must_use]
+    pub fn dial_timeout(self, timeout: Duration) -> Self {
+        self.directive(option("dial_timeout", [duration_text(timeout)]))
+    }
+
+    /// Time to wait for the upstream's response headers.
+    #[must_use]
+    pub fn response_header_timeout(self, timeout: Duration) -> Self {
+        self.directive(option("response_header_timeout", [duration_text(timeout)]))
+    }
+
+    /// Idle time before a kept-alive connection is closed.
+    #[must_use]
+    pub fn keepalive(self, idle: Duration) -> Self {
+        self.directive(option("keepalive", [duration_text(idle)]))
+    }
+
+    /// Connect to the upstream over TLS.
+    #[must_use]
+    pub fn tls(self) -> Self {
+        self.directive(Directive::new("tls"))
+    }
+
+    /// Server name sent in the TLS handshake with the upstream.
+    #[must_use]
+    pub fn tls_server_name(self, name: &str) -> Self {
+        self.directive(option("tls_server_name", [name]))
+    }
+
+    /// Skip verification of the upstream's certificate.
+    #[must_use]
+    pub fn tls_insecure_skip_verify(self) -> Self {
+        self.directive(Directive::new("tls_insecure_skip_verify"))
+    }
+
+    /// HTTP versions to use with the upstream, such as `h2c 2`.
+    #[must_use]
+    pub fn versions<I, S>(self, versions: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let versions: Vec<String> = versions
+            .into_iter()
+            .map(|v| v.as_ref().to_string())
+            .collect();
+        self.directive(option("versions", versions))
+    }
+}
+
+const fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
@@ -0,0 +1,260 @@
+//! Builder methods on `SiteBlock`.
+
+use std::time::Duration;
+
+use super::{
+    BasicAuthBuilder, CorsConfig, ErrorPagesBuilder, FileServerBuilder, HeaderBuilder, MatcherDef,
+    RateLimitBuilder,
+};
+use crate::ast::{self, Comments, Directive, Matcher, SiteBlock};
+use crate::directives::{DnsProvider, ErrorStatus, TlsConfig};
+
+impl SiteBlock {
+    /// Create a new site block with one address.
+    #[must_use]
+    pub fn new(address: &str) -> Self {
+        Self {
+            addresses: vec![ast::parse_address(address)],
+            directives: Vec::new(),
+            comments: Comments::default(),
+            braceless: false,
+        }
+    }
+
+    /// Add another address to this site block.
+    #[must_use]
+    pub fn address(mut self, addr: &str) -> Self {
+        self.push_address(addr);
+        self
+    }
+
+    /// Add another address in place.
+    pub fn push_address(&mut self, addr: &str) {
+        self.addresses.push(ast::parse_address(addr));
+    }
+
+    /// Add a directive to this site block.
+    #[must_use]
+    pub fn directive(mut self, d: Directive) -> Self {
+        self.push_directive(d);
+        self
+    }
+
+    /// Add a directive in place.
+    pub fn push_directive(&mut self, d: Directive) {
+        self.directives.push(d);
+    }
+
+    /// Add a `basic_auth` directive with ACME exclusion.
+    #[must_use]
+    pub fn basic_auth(self, user: &str, hash: &str) -> Self {
+        let matcher_directive = Directive::new("@protected")
+            .arg("not")
+            .arg("path")
+            .arg("/.well-known/acme-challenge/*");
+
+        let auth_directive = Directive::new("basic_auth")
+            .matcher(Matcher::Named("protected".to_string()))
+            .block(vec![Directive::new(user).arg(hash)]);
+
+        self.directive(matcher_directive).directive(auth_directive)
+    }
+
+    /// Add a `rate_limit` directive with one zone allowing `events`
+    /// requests per `window` for each distinct `key`.
+    #[must_use]
+    pub fn rate_limit(self, zone: &str, key: &str, events: u32, window: Duration) -> Self {
+        self.rate_limit_with(
+            RateLimitBuilder::new().zone(zone, |z| z.key(key).events(events).window(window)),
+        )
+    }
+
+    /// Add a `rate_limit` directive from a `RateLimitBuilder`.
+    #[must_use]
+    pub fn rate_limit_with(self, rate_limit: RateLimitBuilder) -> Self {
+        self.directive(rate_limit.build())
+    }
+
+    /// Add a `basic_auth` directive from a `BasicAuthBuilder`.
+    #[must_use]
+    pub fn basic_auth_with(self, auth: BasicAuthBuilder) -> Self {
+        self.directive(auth.build())
+    }
+
+    /// Answer cross-origin requests as `config` allows: matchers for
+    /// allowed origins, a `handle` answering preflight requests with
+    /// 204, and a `header` adding the CORS headers to other responses.
+    ///
+    /// ```
+    /// use caddyfile_rs::{Caddyfile, CorsConfig, SiteBlock, format};
+    ///
+    /// let cors = CorsConfig {
+    ///     origins: vec!["https://app.example.com".to_string()],
+    ///     methods: vec!["GET".to_string(), "POST".to_string()],
+    ///     ..CorsConfig::default()
+    /// };
+    /// let cf = Caddyfile::new().site(SiteBlock::new("api.example.com").cors(&cors));
+    /// assert!(format(&cf).contains(
+    ///     "\thandle @cors_preflight {\n\
+    ///      \t\theader {\n\
+    ///      \t\t\tAccess-Control-Allow-Origin {header.Origin}\n\
+    ///      \t\t\tAccess-Control-Allow-Methods \"GET, POST\"\n"
+    /// ));
+    /// ```
+    #[must_use]
+    pub fn cors(self, config: &CorsConfig) -> Self {
+        let any_origin = config.origins.iter().any(|o| o == "*");
+        let origin_conditions = |def: MatcherDef| {
+            if any_origin {
+                def
+            } else {
+                config
+                    .origins
+                    .iter()
+                    .fold(def, |def, origin| def.header("Origin", origin))
+            }
+        };
+        let preflight = origin_conditions(MatcherDef::new("cors_preflight").method("OPTIONS"));
+        let allowed = origin_conditions(MatcherDef::new("cors"));
+
+        let allow_origin = if any_origin && !config.credentials {
+            "*"
+        } else {
+            "{header.Origin}"
+        };
+        let response = HeaderBuilder::new().set("Access-Control-Allow-Origin", allow_origin);
+        let mut preflight_headers = response.clone();
+        if !config.methods.is_empty() {
+            preflight_headers =
+                preflight_headers.set("Access-Control-Allow-Methods", &config.methods.join(", "));
+        }
+        if !config.headers.is_empty() {
+            preflight_headers =
+                preflight_headers.set("Access-Control-Allow-Headers", &config.headers.join(", "));
+        }
+        if let Some(max_age) = config.max_age {
+            preflight_headers =
+                preflight_headers.set("Access-Control-Max-Age", &max_age.as_secs().to_string());
+        }
+        let finish = |mut headers: HeaderBuilder| {
+            if config.credentials {
+                headers = headers.set("Access-Control-Allow-Credentials", "true");
+            }
+            if allow_origin != "*" {
+                headers = headers.set("Vary", "Origin");
+            }
+            headers
+        };
+        let (response, preflight_headers) = (finish(response), finish(preflight_headers));
+
+        let site = self.matcher_def(preflight.clone());
+        let site = if any_origin {
+            site
+        } else {
+            site.matcher_def(allowed.clone())
+        };
+        let response = if any_origin {
+            response
+        } else {
+            response.matcher(allowed.matcher())
+        };
+        site.handle(preflight.matcher(), |h| {
+            h.directive(preflight_headers.build()).respond(None, 204)
+        })
+        .header_with(response)
+    }
+
+    /// Add security headers.
+    #[must_use]
+    pub fn security_headers(self) -> Self {
+        self.directive(Directive::new("header").block(vec![
+            Directive::new("X-Content-Type-Options").quoted_arg("nosniff"),
+            Directive::new("X-Frame-Options").quoted_arg("DENY"),
+            Directive::new("X-XSS-Protection").quoted_arg("1; mode=block"),
+            Directive::new("Referrer-Policy").quoted_arg("strict-origin-when-cross-origin"),
+        ]))
+    }
+
+    /// Add a `tls` directive with arguments.
+    #[must_use]
+    pub fn tls(mut self, args: &[&str]) -> Self {
+        let mut d = Directive::new("tls");
+        for arg in args {
+            d = d.arg(arg);
+        }
+        self.directives.push(d);
+        self
+    }
+
+    /// Obtain this site's certificates through the DNS challenge with
+    /// `provider`: `tls { dns <provider> [args] }`.
+    #[must_use]
+    pub fn tls_dns(self, provider: &DnsProvider) -> Self {
+        let tls = TlsConfig {
+            dns: Some(provider.clone()),
+            ..TlsConfig::default()
+        };
+        self.directive(Directive::from(&tls))
+    }
+
+    /// Add custom error pages, mapping each status code to the path of
+    /// its page; see `ErrorPagesBuilder`.
+    #[must_use]
+    pub fn error_pages<S: AsRef<str>>(self, pages: impl IntoIterator<Item = (u16, S)>) -> Self {
+        let pages = pages
+            .into_iter()
+            .fold(ErrorPagesBuilder::new(), |b, (code, path)| {
+                b.page(ErrorStatus::Code(code), path.as_ref())
+            });
+        self.error_pages_with(pages)
+    }
+
+    /// Add a `handle_errors` block from an `ErrorPagesBuilder`.
+    #[must_use]
+    pub fn error_pages_with(self, pages: ErrorPagesBuilder) -> Self {
+        self.directive(pages.build())
+    }
+
+    /// Serve the files under `root`: `root * <root>` followed by
+    /// `file_server`.
+    #[must_use]
+    pub fn static_files(self, root: &str) -> Self {
+        self.static_files_with(root, FileServerBuilder::new())
+    }
+
+    /// Serve the files under `root` with the options of `file_server`.
+    ///
+    /// ```
+    /// use caddyfile_rs::{Caddyfile, FileServerBuilder, SiteBlock, format};
+    ///
+    /// let files = FileServerBuilder::new()
+    ///     .browse()
+    ///     .hide([".git", "*.bak"])
+    ///     .precompressed(["zstd", "br", "gzip"]);
+    /// let cf = Caddyfile::new().site(SiteBlock::new("files.example.com").static_files_with("/srv/files", files));
+    /// assert_eq!(
+    ///     format(&cf),
+    ///     "files.example.com {\n\
+    ///      \troot * /srv/files\n\
+    ///      \n\
+    ///      \tfile_server {\n\
+    ///      \t\tbrowse\n\
+    ///      \t\thide .git *.bak\n\
+    ///      \t\tprecompressed zstd br gzip\n\
+    ///      \t}\n\
+    ///      }\n"
+    /// );
+    /// ```
+    #[must_use]
+    pub fn static_files_with(self, root: &str, file_server: FileServerBuilder) -> Self {
+        self.root(root).file_server_with(file_server)
+    }
+}
+
+impl Extend<Directive> for SiteBlock {
+    fn extend<I: IntoIterator<Item = Directive>>(&mut self, iter: I) {
+        for d in iter {
+            self.push_directive(d);
+        }
+    }
+}
@@ -0,0 +1,304 @@
+//! Byte-compatible port of `caddy fmt`.
+//!
+//! Caddy's formatter works on the source text rather than on a syntax
+//! tree: it re-indents lines by brace nesting, collapses runs of spaces
+//! and blank lines, and moves braces onto their own lines, but keeps
+//! everything else as written. `caddy_fmt` reproduces its output,
+//! quirks included, for projects that check formatting with both tools.
+//! Unlike [`crate::format`] it accepts input that does not parse.
+
+/// Format `input` exactly as `caddy fmt` does.
+#[must_use]
+pub fn caddy_fmt(input: &str) -> String {
+    let mut f = Formatter::new();
+    for ch in input.trim().chars() {
+        f.push(ch);
+    }
+    let mut out = f.out.out.trim().to_string();
+    out.push('\n');
+    out
+}
+
+/// Output buffer with the state shared by every step.
+struct Output {
+    out: String,
+    /// Last character written.
+    last: char,
+    beginning_of_line: bool,
+    nesting: usize,
+}
+
+impl Output {
+    fn write(&mut self, ch: char) {
+        self.out.push(ch);
+        self.last = ch;
+    }
+
+    fn indent(&mut self) {
+        for _ in 0..self.nesting {
+            self.write('\t');
+        }
+    }
+
+    fn next_line(&mut self) {
+        self.write('\n');
+        self.beginning_of_line = true;
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Normal,
+    Comment,
+    Quoted,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Heredoc {
+    Closed,
+    /// A token started with `<`.
+    Start,
+    /// Reading the marker after `<<`.
+    Opening,
+    /// Copying the body until the closing marker.
+    Opened,
+}
+
+/// An open brace waiting to be written.
+#[derive(Clone, Copy)]
+struct OpenBrace {
+    pending: bool,
+    written: bool,
+    /// A space came before the brace on the same line.
+    space: bool,
+}
+
+struct Formatter {
+    out: Output,
+    /// The previous character was whitespace.
+    space: bool,
+    escaped: bool,
+    mode: Mode,
+    brace: OpenBrace,
+    new_lines: usize,
+    heredoc: Heredoc,
+    marker: Vec<char>,
+    closing: Vec<char>,
+}
+
+impl Formatter {
+    const fn new() -> Self {
+        Self {
+            out: Output {
+                out: String::new(),
+                last: '\0',
+                beginning_of_line: true,
+                nesting: 0,
+            },
+            space: true,
+            escaped: false,
+            mode: Mode::Normal,
+            brace: OpenBrace {
+                pending: false,
+                written: false,
+                space: false,
+            },
+            new_lines: 0,
+            heredoc: Heredoc::Closed,
+            marker: Vec::new(),
+            closing: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, ch: char) {
+        if self.heredoc(ch) || self.literal(ch) {
+            return;
+        }
+        if self.mode == Mode::Normal && self.space && ch == '"' {
+            self.mode = Mode::Quoted;
+        }
+        if ch.is_whitespace() {
+            self.space = true;
+            if ch == '\n' {
+                self.new_lines += 1;
+            }
+            return;
+        }
+        let space_prior = self.space;
+        self.space = false;
+        self.token(ch, space_prior);
+    }
+
+    /// Handle heredoc markers and bodies; true when `ch` was consumed.
+    fn heredoc(&mut self, ch: char) -> bool {
+        match self.heredoc {
+            Heredoc::Closed => false,
+            Heredoc::Start if ch == '<' => {
+                self.out.write(ch);
+                self.heredoc = Heredoc::Opening;
+                true
+            }
+            Heredoc::Start => {
+                self.heredoc = Heredoc::Closed;
+                false
+            }
+            Heredoc::Opening if ch == '\n' => {
+                let valid = !self.marker.is_empty()
+                    && self
+                        .marker
+                        .iter()
+                        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-'));
+                if valid {
+                    self.heredoc = Heredoc::Opened;
+                    self.out.write(ch);
+                } else {
+                    self.marker.clear();
+                    self.heredoc = Heredoc::Closed;
+                    self.out.next_line();
+                }
+                true
+            }
+            Heredoc::Opening if ch.is_whitespace() => {
+                // a plain token starting with `<<`
+                self.marker.clear();
+                self.heredoc = Heredoc::Closed;
+                false
+            }
+            Heredoc::Opening => {
+                self.marker.push(ch);
+                self.out.write(ch);
+                true
+            }
+            Heredoc::Opened => {
+                self.closing.push(ch);
+                if self.closing.len() > self.marker.len() + 1 {
+                    self.closing.remove(0);
+                }
+                let done =
+                    ch.is_whitespace() && self.closing[..self.closing.len() - 1] == self.marker[..];
+                if done {
+                    self.marker.clear();
+                    self.closing.clear();
+                    self.heredoc = Heredoc::Closed;
+                    return false;
+                }
+                self.out.write(ch);
+                if ch == '\n' {
+                    self.closing.clear();
+                }
+                true
+            }
+        }
+    }
+
+    /// Copy comments, escapes and quoted text; true when `ch` was
+    /// consumed.
+    fn literal(&mut self, ch: char) -> bool {
+        if self.mode == Mode::Comment {
+            if ch == '\n' {
+                self.mode = Mode::Normal;
+                self.space = true;
+                self.out.next_line();
+            } else {
+                self.out.write(ch);
+            }
+            return true;
+        }
+        if !self.escaped && ch == '\\' {
+            if self.space {
+                self.out.write(' ');
+                self.space = false;
+            }
+            self.out.write(ch);
+            self.escaped = true;
+            return true;
+        }
+        if self.escaped {
+            self.out.write(ch);
+            self.escaped = false;
+            return true;
+        }
+        if self.mode == Mode::Quoted {
+            if ch == '"' {
+                self.mode = Mode::Normal;
+            }
+            self.out.write(ch);
+            return true;
+        }
+        false
+    }
+
+    /// Write the non-space character `ch` of a token.
+    fn token(&mut self, ch: char, space_prior: bool) {
+        if ch == '#' {
+            self.mode = Mode::Comment;
+        }
+
+        if self.brace.pending && space_prior && !self.brace.written {
+            if self.out.nesting == 0 && self.out.last == '}' {
+                self.out.next_line();
+                self.out.next_line();
+            }
+            self.brace.pending = false;
+            if self.out.beginning_of_line {
+                self.out.indent();
+            } else if !self.brace.space {
+                self.out.write(' ');
+            }
+            self.out.write('{');
+            self.brace.written = true;
+            self.out.next_line();
+            self.new_lines = 0;
+            // caddy caps indentation for absurdly nested input
+            if self.out.nesting < 10 {
+                self.out.nesting += 1;
+            }
+        }
+
+        if ch == '{' {
+            self.brace = OpenBrace {
+                pending: true,
+                written: false,
+                space: space_prior && !self.out.beginning_of_line,
+            };
+            if self.brace.space {
+                self.out.write(' ');
+            }
+            return;
+        }
+        if ch == '}' && (space_prior || !self.brace.pending) {
+            if self.out.last != '\n' {
+                self.out.next_line();
+            }
+            self.out.nesting = self.out.nesting.saturating_sub(1);
+            self.out.indent();
+            self.out.write('}');
+            self.new_lines = 0;
+            return;
+        }
+
+        for _ in 0..self.new_lines.min(2) {
+            self.out.next_line();
+        }
+        self.new_lines = 0;
+        if self.out.beginning_of_line {
+            self.out.indent();
+            if self.out.nesting == 0 && self.out.last == '}' {
+                self.out.next_line();
+                self.out.next_line();
+            }
+        }
+        if !self.out.beginning_of_line && space_prior {
+            self.out.write(' ');
+        }
+        if self.brace.pending && !self.brace.written {
+            self.out.write('{');
+            self.brace.written = true;
+        }
+        if ch == '<' && space_prior && self.mode == Mode::Normal {
+            self.heredoc = Heredoc::Start;
+        }
+        self.out.write(ch);
+        self.out.beginning_of_line = false;
+    }
+}
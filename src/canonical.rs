@@ -0,0 +1,559 @@
+//! Canonical ordering of a Caddyfile for diff-stable output.
+//!
+//! Snippets and named routes are sorted by name, sites by their first
+//! address, and the top-level directives of sites by class:
+//! `import` lines, matcher definitions, site options (`bind`, `tls`,
+//! `log`, `handle_errors`), then handler directives in Caddy's default
+//! order. Unknown (plugin) directives go last. Sorting is stable, so
+//! repeated directives such as several `handle` blocks keep their
+//! relative order, and `route` blocks are never reordered internally.
+//! Snippet bodies keep their order too, since a snippet may be imported
+//! into a `route` or other block whose order matters.
+//!
+//! `normalize` is a lighter pass that leaves directive order alone: it
+//! sorts blocks and site address lists and drops repeated `import`s.
+//!
+//! `Caddyfile::fingerprint` hashes this canonical form, so configs that
+//! differ only in layout, quoting or block order share a fingerprint.
+//! `Caddyfile::semantically_equals` compares two configs the same way
+//! but keeps directive order significant.
+
+use crate::ast::{Argument, Caddyfile, Comments, Directive, Matcher};
+use crate::global::DirectiveOrder;
+
+/// Caddy's default handler directive order.
+pub const DIRECTIVE_ORDER: &[&str] = &[
+    "tracing",
+    "map",
+    "vars",
+    "fs",
+    "root",
+    "log_append",
+    "skip_log",
+    "log_skip",
+    "log_name",
+    "header",
+    "copy_response_headers",
+    "request_body",
+    "redir",
+    "method",
+    "rewrite",
+    "uri",
+    "try_files",
+    "basicauth",
+    "basic_auth",
+    "forward_auth",
+    "request_header",
+    "encode",
+    "push",
+    "intercept",
+    "templates",
+    "invoke",
+    "handle",
+    "handle_path",
+    "route",
+    "abort",
+    "error",
+    "copy_response",
+    "respond",
+    "metrics",
+    "reverse_proxy",
+    "php_fastcgi",
+    "file_server",
+    "acme_server",
+];
+
+/// Site-level directives that configure the site rather than handle
+/// requests, in canonical order.
+pub(crate) const SITE_OPTIONS: &[&str] = &["bind", "tls", "log", "handle_errors"];
+
+/// Sort a Caddyfile into canonical order in place.
+pub fn canonicalize(cf: &mut Caddyfile) {
+    cf.snippets.sort_by(|a, b| a.name.cmp(&b.name));
+    cf.named_routes.sort_by(|a, b| a.name.cmp(&b.name));
+    for site in &mut cf.sites {
+        sort_directives(&mut site.directives);
+    }
+    cf.sites.sort_by_cached_key(|site| {
+        site.addresses
+            .first()
+            .map(ToString::to_string)
+            .unwrap_or_default()
+    });
+}
+
+/// Normalize a Caddyfile in place without reordering directives.
+///
+/// Snippets and named routes are sorted by name, each site's addresses
+/// are sorted and deduplicated, sites are sorted by their first address,
+/// and an `import` repeating an earlier one in the same block is
+/// removed.
+pub fn normalize(cf: &mut Caddyfile) {
+    cf.snippets.sort_by(|a, b| a.name.cmp(&b.name));
+    cf.named_routes.sort_by(|a, b| a.name.cmp(&b.name));
+    if let Some(global) = &mut cf.global_options {
+        dedupe_imports(&mut global.directives);
+    }
+    for snippet in &mut cf.snippets {
+        dedupe_imports(&mut snippet.directives);
+    }
+    for route in &mut cf.named_routes {
+        dedupe_imports(&mut route.directives);
+    }
+    for site in &mut cf.sites {
+        site.addresses.sort_by_cached_key(ToString::to_string);
+        site.addresses
+            .dedup_by(|a, b| a.to_string() == b.to_string());
+        dedupe_imports(&mut site.directives);
+    }
+    cf.sites.sort_by_cached_key(|site| {
+        site.addresses
+            .first()
+            .map(ToString::to_string)
+            .unwrap_or_default()
+    });
+}
+
+/// Drop `import` directives with the same arguments as an earlier one,
+/// in `directives` and every nested block.
+fn dedupe_imports(directives: &mut Vec<Directive>) {
+    let mut seen = Vec::new();
+    directives.retain(|d| {
+        if d.name != "import" {
+            return true;
+        }
+        if seen.contains(&d.arguments) {
+            return false;
+        }
+        seen.push(d.arguments.clone());
+        true
+    });
+    for d in directives {
+        if let Some(block) = &mut d.block {
+            dedupe_imports(block);
+        }
+    }
+}
+
+/// Stable-sort a directive list by canonical rank.
+pub fn sort_directives(directives: &mut [Directive]) {
+    directives.sort_by_key(|d| rank(&d.name));
+}
+
+/// Stable-sort the directives of every site by Caddy's directive order,
+/// as changed by the global `order` options.
+///
+/// Unlike `canonicalize`, blocks stay where they are. Snippets are left
+/// alone, as their order matters when imported into a `route`. `order`
+/// options that cannot be read or whose target is unknown are ignored.
+pub fn sort_by_directive_order(cf: &mut Caddyfile) {
+    let mut order: Vec<String> = DIRECTIVE_ORDER.iter().map(ToString::to_string).collect();
+    if let Some(global) = &cf.global_options {
+        let options = global.directives.iter().filter(|d| d.name == "order");
+        for option in options.filter_map(|d| DirectiveOrder::try_from(d).ok()) {
+            option.apply(&mut order);
+        }
+    }
+    for site in &mut cf.sites {
+        site.directives.sort_by_key(|d| rank_in(&d.name, &order));
+    }
+}
+
+/// Canonical sort key of a directive name: `(class, index)`.
+#[must_use]
+pub fn rank(name: &str) -> (u8, usize) {
+    rank_in(name, DIRECTIVE_ORDER)
+}
+
+/// `rank` with `order` as the handler directive order.
+fn rank_in<S: AsRef<str>>(name: &str, order: &[S]) -> (u8, usize) {
+    if name == "import" {
+        return (0, 0);
+    }
+    if name.starts_with('@') {
+        return (1, 0);
+    }
+    if let Some(i) = SITE_OPTIONS.iter().position(|n| *n == name) {
+        return (2, i);
+    }
+    order
+        .iter()
+        .position(|n| n.as_ref() == name)
+        .map_or((4, 0), |i| (3, i))
+}
+
+impl Caddyfile {
+    /// Stable 64-bit hash of the canonical form.
+    ///
+    /// Insensitive to whitespace, quoting style, global option order and
+    /// the order of independent blocks and site directives. The value is
+    /// stable across releases and platforms (FNV-1a), so it can be
+    /// stored to detect real configuration changes.
+    #[must_use]
+    pub fn fingerprint(&self) -> u64 {
+        let mut cf = self.clone();
+        canonicalize(&mut cf);
+        if let Some(global) = &mut cf.global_options {
+            global.directives.sort_by(|a, b| a.name.cmp(&b.name));
+        }
+
+        let mut h = Fnv::new();
+        if let Some(global) = &cf.global_options {
+            h.tag(b'g');
+            h.directives(&global.directives);
+        }
+        for snippet in &cf.snippets {
+            h.tag(b's');
+            h.str(&snippet.name);
+            h.directives(&snippet.directives);
+        }
+        for route in &cf.named_routes {
+            h.tag(b'r');
+            h.str(&route.name);
+            h.directives(&route.directives);
+        }
+        for site in &cf.sites {
+            h.tag(b'a');
+            for addr in &site.addresses {
+                h.str(&addr.to_string());
+            }
+            h.directives(&site.directives);
+        }
+        h.0
+    }
+
+    /// `fingerprint` as 16 lowercase hex digits, for storing in files,
+    /// labels or annotations.
+    #[must_use]
+    pub fn fingerprint_hex(&self) -> String {
+        format!("{:016x}", self.fingerprint())
+    }
+
+    /// Whether `self` and `other` configure Caddy the same way.
+    ///
+    /// Ignores comments, blank lines and other layout, the quoting style
+    /// of arguments with the same value, the order of snippets, named
+    /// routes, sites and global options, and the spelling of addresses
+    /// (host case, address order within a site, default schemes and ports).
+    /// Directive order within a block is significant.
+    #[must_use]
+    pub fn semantically_equals(&self, other: &Self) -> bool {
+        semantic_form(self) == semantic_form(other)
+    }
+}
+
+/// `cf` with everything `semantically_equals` ignores normalized away.
+fn semantic_form(cf: &Caddyfile) -> Caddyfile {
+    let mut cf = cf.clone();
+    cf.trailing_comments.clear();
+    if let Some(global) = &mut cf.global_options {
+        global.comments = Comments::default();
+        global.directives.sort_by(|a, b| a.name.cmp(&b.name));
+        semantic_directives(&mut global.directives);
+    }
+    cf.snippets.sort_by(|a, b| a.name.cmp(&b.name));
+    for snippet in &mut cf.snippets {
+        snippet.comments = Comments::default();
+        semantic_directives(&mut snippet.directives);
+    }
+    cf.named_routes.sort_by(|a, b| a.name.cmp(&b.name));
+    for route in &mut cf.named_routes {
+        route.comments = Comments::default();
+        semantic_directives(&mut route.directives);
+    }
+    for site in &mut cf.sites {
+        site.comments = Comments::default();
+        site.braceless = false;
+        for addr in &mut site.addresses {
+            *addr = addr.normalize();
+        }
+        site.addresses.sort_by_cached_key(ToString::to_string);
+        site.addresses.dedup();
+        semantic_directives(&mut site.directives);
+    }
+    cf.sites.sort_by_cached_key(|site| {
+        site.addresses
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+    });
+    cf
+}
+
+fn semantic_directives(directives: &mut [Directive]) {
+    for d in directives {
+        d.comments = Comments::default();
+        d.continuations.clear();
+        d.blank_lines_before = 0;
+        for arg in &mut d.arguments {
+            *arg = Argument::Unquoted(arg.value().into_owned());
+        }
+        if let Some(block) = &mut d.block {
+            semantic_directives(block);
+        }
+    }
+}
+
+/// 64-bit FNV-1a, fed with tagged, length-prefixed fields.
+struct Fnv(u64);
+
+impl Fnv {
+    const fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 ^= u64::from(b);
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    fn tag(&mut self, tag: u8) {
+        self.bytes(&[tag]);
+    }
+
+    fn str(&mut self, s: &str) {
+        self.bytes(&(s.len() as u64).to_le_bytes());
+        self.bytes(s.as_bytes());
+    }
+
+    fn directives(&mut self, directives: &[Directive]) {
+        self.tag(b'{');
+        for d in directives {
+            self.tag(b'd');
+            self.str(&d.name);
+            match &d.matcher {
+                None => {}
+                Some(Matcher::All) => self.tag(b'*'),
+                Some(m) => {
+                    self.tag(b'm');
+                    self.str(&m.to_string());
+                }
+            }
+            for arg in &d.arguments {
+                self.tag(b'v');
+                self.str(&arg.value());
+            }
+            if let Some(block) = &d.block {
+                self.directives(block);
+            }
+        }
+        self.tag(b'}');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{format, parse_str};
+
+    #[test]
+    fn directive_classes() {
+        assert!(rank("import") < rank("@api"));
+        assert!(rank("@api") < rank("tls"));
+        assert!(rank("tls") < rank("root"));
+        assert!(rank("root") < rank("reverse_proxy"));
+        assert!(rank("reverse_proxy") < rank("rate_limit"));
+    }
+
+    #[test]
+    fn canonical_output() {
+        let mut cf = parse_str(
+            "b.com {\n\
+             \tfile_server\n\
+             \thandle /b/* {\n\t\trespond 2\n\t}\n\
+             \thandle /a/* {\n\t\trespond 1\n\t}\n\
+             \troot * /srv\n\
+             \timport common\n\
+             }\n\
+             \n\
+             a.com {\n\
+             \treverse_proxy app:3000\n\
+             \ttls internal\n\
+             }\n\
+             \n\
+             (zz) {\n\tlog\n}\n\
+             \n\
+             (common) {\n\tencode gzip\n}\n",
+        )
+        .unwrap();
+        canonicalize(&mut cf);
+
+        assert_eq!(
+            format(&cf),
+            "(common) {\n\tencode gzip\n}\n\n\
+             (zz) {\n\tlog\n}\n\n\
+             a.com {\n\ttls internal\n\treverse_proxy app:3000\n}\n\n\
+             b.com {\n\
+             \timport common\n\
+             \troot * /srv\n\
+             \n\
+             \thandle /b/* {\n\t\trespond 2\n\t}\n\
+             \n\
+             \thandle /a/* {\n\t\trespond 1\n\t}\n\
+             \n\
+             \tfile_server\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn normalize_keeps_directive_order() {
+        let mut cf = parse_str(
+            "www.b.com, b.com, www.b.com {\n\
+             \timport common\n\
+             \treverse_proxy app:3000\n\
+             \timport common\n\
+             \thandle {\n\t\timport api\n\t\timport api\n\t}\n\
+             \timport logging\n\
+             }\n\
+             \n\
+             a.com {\n\trespond ok\n}\n\
+             \n\
+             (common) {\n\tencode gzip\n}\n",
+        )
+        .unwrap();
+        normalize(&mut cf);
+
+        assert_eq!(
+            format(&cf),
+            "(common) {\n\tencode gzip\n}\n\n\
+             a.com {\n\trespond ok\n}\n\n\
+             b.com, www.b.com {\n\
+             \timport common\n\
+             \treverse_proxy app:3000\n\
+             \n\
+             \thandle {\n\t\timport api\n\t}\n\
+             \n\
+             \timport logging\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn sort_by_directive_order_with_overrides() {
+        let mut cf = parse_str(
+            "{\n\torder rate_limit before basic_auth\n\torder respond first\n}\n\n\
+             a.com {\n\
+             \tfile_server\n\
+             \tbasic_auth {\n\t\tbob hash\n\t}\n\
+             \trate_limit 10r/s\n\
+             \ttls internal\n\
+             \trespond /health ok\n\
+             \troot * /srv\n\
+             \tunknown_plugin\n\
+             }\n",
+        )
+        .unwrap();
+        sort_by_directive_order(&mut cf);
+        let names: Vec<_> = cf.sites[0]
+            .directives
+            .iter()
+            .map(|d| d.name.as_str())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "tls",
+                "respond",
+                "root",
+                "rate_limit",
+                "basic_auth",
+                "file_server",
+                "unknown_plugin"
+            ]
+        );
+    }
+
+    #[test]
+    fn snippet_bodies_keep_their_order() {
+        let source = "(steps) {\n\trespond ok\n\theader X-A b\n}\n\n\
+                      a.com {\n\troute {\n\t\timport steps\n\t}\n}\n";
+        let mut cf = parse_str(source).unwrap();
+        canonicalize(&mut cf);
+        assert_eq!(format(&cf), source);
+        sort_by_directive_order(&mut cf);
+        assert_eq!(format(&cf), source);
+    }
+
+    #[test]
+    fn semantic_equality() {
+        let a = parse_str(
+            "# sites\n\
+             (common) {\n\tencode gzip\n}\n\n\
+             www.Example.com, https://example.com:443 {\n\
+             \timport common\n\
+             \trespond \"ok\" 200\n\
+             }\n\n\
+             b.com {\n\tlog\n}\n",
+        )
+        .unwrap();
+        let b = parse_str(
+            "b.com {\n\tlog # access\n}\n\n\
+             https://example.com www.example.com {\n\
+             \timport common\n\n\
+             \trespond ok \\\n\t\t200\n\
+             }\n\n\
+             (common) {\n\tencode `gzip`\n}\n",
+        )
+        .unwrap();
+        assert!(a.semantically_equals(&b));
+
+        let c = parse_str(&format(&b).replace("200", "204")).unwrap();
+        assert!(!a.semantically_equals(&c));
+        let d = parse_str(&format(&b).replace(
+            "\timport common\n\trespond ok 200",
+            "\trespond ok 200\n\timport common",
+        ))
+        .unwrap();
+        assert!(!a.semantically_equals(&d));
+    }
+
+    #[test]
+    fn semantic_equality_of_addresses() {
+        let site = |addr: &str| parse_str(&format!("{addr} {{\n\tlog\n}}\n")).unwrap();
+        assert!(site("example.com").semantically_equals(&site("https://example.com")));
+        assert!(site("example.com").semantically_equals(&site("example.com:443")));
+        assert!(site(":80").semantically_equals(&site("http://")));
+        assert!(!site("example.com").semantically_equals(&site("http://example.com")));
+    }
+
+    #[test]
+    fn fingerprint_ignores_layout() {
+        let a = parse_str(
+            "{\n\temail a@b.c\n\tadmin off\n}\n\n\
+             b.com {\n\treverse_proxy app:3000\n\ttls internal\n}\n\n\
+             a.com {\n\trespond \"ok\" 200\n}\n",
+        )
+        .unwrap();
+        let b = parse_str(
+            "{\n  admin off\n  email a@b.c\n}\n\
+             a.com {\n  respond ok 200\n}\n\
+             b.com {\n  tls internal\n  reverse_proxy app:3000\n}\n",
+        )
+        .unwrap();
+        assert_eq!(a.fingerprint(), b.fingerprint());
+
+        let c = parse_str("a.com {\n\trespond ok 201\n}\n").unwrap();
+        let d = parse_str("a.com {\n\trespond ok 200\n}\n").unwrap();
+        assert_ne!(c.fingerprint(), d.fingerprint());
+    }
+
+    #[test]
+    fn fingerprint_is_stable() {
+        let cf = parse_str("example.com {\n\treverse_proxy app:3000\n}\n").unwrap();
+        // Pinned: a change here breaks stored fingerprints.
+        assert_eq!(format!("{:016x}", cf.fingerprint()), "8e8b0c6891f6195e");
+        assert_eq!(cf.fingerprint_hex(), "8e8b0c6891f6195e");
+    }
+
+    #[test]
+    fn route_contents_keep_order() {
+        let mut cf =
+            parse_str("a.com {\n\troute {\n\t\trespond 1\n\t\troot * /x\n\t}\n}\n").unwrap();
+        let before = cf.clone();
+        canonicalize(&mut cf);
+        assert_eq!(cf, before);
+    }
+}
@@ -0,0 +1,121 @@
+//! Typed models of common directives.
+//!
+//! Each model is read from a generic `Directive` with `TryFrom` and
+//! converts back with `From`. Subdirectives the model does not cover
+//! are kept in an `other` list so nothing is lost on the way back.
+
+mod handle_errors;
+mod header;
+mod import;
+mod reverse_proxy;
+mod tls;
+
+pub use handle_errors::{ErrorStatus, HandleErrors};
+pub use header::{HeaderOp, HeaderOps};
+pub use import::{ArgsPlaceholder, Import, snippet_args};
+pub use reverse_proxy::{
+    ActiveHealthCheck, HealthChecks, LbPolicy, PassiveHealthCheck, ReverseProxy, Transport,
+};
+pub use tls::{DnsProvider, TlsConfig};
+
+use std::str::FromStr;
+
+use crate::ast::{Argument, Comments, Directive};
+use crate::formatter::needs_quotes;
+
+/// Error produced when a directive does not fit its typed model.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum DirectiveError {
+    /// The directive has a different name.
+    #[error("expected '{expected}' directive, found '{found}'")]
+    WrongName {
+        expected: &'static str,
+        found: String,
+    },
+    /// An option is missing its value.
+    #[error("{directive}: '{option}' requires a value")]
+    MissingValue {
+        directive: &'static str,
+        option: String,
+    },
+    /// An option has a value the model cannot represent.
+    #[error("{directive}: invalid value '{value}' for '{option}'")]
+    InvalidValue {
+        directive: &'static str,
+        option: String,
+        value: String,
+    },
+}
+
+/// Check that `d` is named `name`.
+fn expect_name(d: &Directive, name: &'static str) -> Result<(), DirectiveError> {
+    if d.name == name {
+        Ok(())
+    } else {
+        Err(DirectiveError::WrongName {
+            expected: name,
+            found: d.name.clone(),
+        })
+    }
+}
+
+/// Argument values of subdirective `d`.
+///
+/// A value starting with `/`, `*` or `@` is lexed as a matcher, so the
+/// matcher is read back as the first value.
+pub(crate) fn values(d: &Directive) -> Vec<String> {
+    d.matcher
+        .iter()
+        .map(ToString::to_string)
+        .chain(d.arguments.iter().map(|a| a.value().into_owned()))
+        .collect()
+}
+
+/// The single value of subdirective `d` of `directive`.
+fn value(directive: &'static str, d: &Directive) -> Result<String, DirectiveError> {
+    values(d)
+        .into_iter()
+        .next()
+        .ok_or_else(|| DirectiveError::MissingValue {
+            directive,
+            option: d.name.clone(),
+        })
+}
+
+/// The value of subdirective `d` parsed as `T`.
+fn parsed<T: FromStr>(directive: &'static str, d: &Directive) -> Result<T, DirectiveError> {
+    let text = value(directive, d)?;
+    text.parse().map_err(|_| DirectiveError::InvalidValue {
+        directive,
+        option: d.name.clone(),
+        value: text,
+    })
+}
+
+/// A subdirective `name` with `args`, quoted only where a bare token
+/// would not read back as the same value.
+pub(crate) fn option<I, S>(name: &str, args: I) -> Directive
+where
+    I: IntoIterator<Item = S>,
+    S: ToString,
+{
+    Directive {
+        name: name.to_string(),
+        matcher: None,
+        arguments: args
+            .into_iter()
+            .map(|a| {
+                let a = a.to_string();
+                if needs_quotes(&a) {
+                    Argument::Quoted(a)
+                } else {
+                    Argument::Unquoted(a)
+                }
+            })
+            .collect(),
+        block: None,
+        comments: Comments::default(),
+        continuations: Vec::new(),
+        blank_lines_before: 0,
+    }
+}
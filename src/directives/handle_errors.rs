@@ -0,0 +1,157 @@
+//! The `handle_errors` directive.
+
+use std::fmt;
+use std::str::FromStr;
+
+use super::{DirectiveError, expect_name, option, values};
+use crate::ast::Directive;
+
+const NAME: &str = "handle_errors";
+
+/// A typed `handle_errors` block, run when a handler returns an error.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HandleErrors {
+    /// Status filters; empty means every error.
+    pub statuses: Vec<ErrorStatus>,
+    /// Directives run for matching errors.
+    pub directives: Vec<Directive>,
+}
+
+/// A status filter such as `404` or `5xx`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorStatus {
+    Code(u16),
+    /// A class of codes, `4xx` is `Class(4)`.
+    Class(u8),
+}
+
+impl ErrorStatus {
+    /// Whether `status` falls under this filter.
+    #[must_use]
+    pub fn matches(self, status: u16) -> bool {
+        match self {
+            Self::Code(code) => code == status,
+            Self::Class(class) => status / 100 == u16::from(class),
+        }
+    }
+}
+
+impl fmt::Display for ErrorStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Code(code) => write!(f, "{code}"),
+            Self::Class(class) => write!(f, "{class}xx"),
+        }
+    }
+}
+
+impl FromStr for ErrorStatus {
+    type Err = DirectiveError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || DirectiveError::InvalidValue {
+            directive: NAME,
+            option: NAME.to_string(),
+            value: s.to_string(),
+        };
+        let status = match s.strip_suffix("xx") {
+            Some(class) => class.parse().map(Self::Class),
+            None if s.len() == 3 => s.parse().map(Self::Code),
+            None => return Err(invalid()),
+        }
+        .map_err(|_| invalid())?;
+        match status {
+            Self::Code(100..=599) | Self::Class(1..=5) => Ok(status),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl HandleErrors {
+    /// A `handle_errors` block for the given statuses.
+    #[must_use]
+    pub fn new(statuses: impl IntoIterator<Item = ErrorStatus>) -> Self {
+        Self {
+            statuses: statuses.into_iter().collect(),
+            directives: Vec::new(),
+        }
+    }
+
+    /// Append a directive to the block.
+    #[must_use]
+    pub fn directive(mut self, d: Directive) -> Self {
+        self.directives.push(d);
+        self
+    }
+
+    /// Whether an error with `status` is handled by this block.
+    #[must_use]
+    pub fn handles(&self, status: u16) -> bool {
+        self.statuses.is_empty() || self.statuses.iter().any(|s| s.matches(status))
+    }
+}
+
+impl TryFrom<&Directive> for HandleErrors {
+    type Error = DirectiveError;
+
+    fn try_from(d: &Directive) -> Result<Self, Self::Error> {
+        expect_name(d, NAME)?;
+        Ok(Self {
+            statuses: values(d)
+                .iter()
+                .map(|s| s.parse())
+                .collect::<Result<_, _>>()?,
+            directives: d.block.clone().unwrap_or_default(),
+        })
+    }
+}
+
+impl From<&HandleErrors> for Directive {
+    fn from(handle: &HandleErrors) -> Self {
+        let mut d = option(NAME, &handle.statuses);
+        d.block = Some(handle.directives.clone());
+        d
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{format, parse_str};
+
+    const INPUT: &str = "a.com {\n\
+                         \thandle_errors 404 410 5xx {\n\
+                         \t\trewrite * /error.html\n\
+                         \t\tfile_server\n\
+                         \t}\n\
+                         }\n";
+
+    #[test]
+    fn parses_status_filters() {
+        let cf = parse_str(INPUT).unwrap();
+        let handle = HandleErrors::try_from(&cf.sites[0].directives[0]).unwrap();
+        assert_eq!(
+            handle.statuses,
+            [
+                ErrorStatus::Code(404),
+                ErrorStatus::Code(410),
+                ErrorStatus::Class(5)
+            ]
+        );
+        assert_eq!(handle.directives.len(), 2);
+        assert!(handle.handles(503));
+        assert!(!handle.handles(403));
+        assert!(HandleErrors::default().handles(403));
+
+        let out = crate::Caddyfile::new()
+            .site(crate::SiteBlock::new("a.com").directive(Directive::from(&handle)));
+        assert_eq!(format(&out), INPUT);
+    }
+
+    #[test]
+    fn rejects_bad_status() {
+        for status in ["40", "600", "6xx", "abc"] {
+            assert!(status.parse::<ErrorStatus>().is_err(), "{status}");
+        }
+    }
+}
@@ -0,0 +1,291 @@
+//! The `header` directive.
+
+use super::{DirectiveError, expect_name, option, values};
+use crate::ast::{Directive, Matcher};
+
+const NAME: &str = "header";
+
+/// A typed `header` directive: response header operations.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HeaderOps {
+    pub matcher: Option<Matcher>,
+    pub ops: Vec<HeaderOp>,
+    /// Apply the operations after the response is written (`defer`,
+    /// or a `>` prefix on any field).
+    pub defer: bool,
+}
+
+/// One header operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HeaderOp {
+    /// `Field value` replaces the field.
+    Set { field: String, value: String },
+    /// `+Field value` adds a value.
+    Add { field: String, value: String },
+    /// `-Field` removes the field; a trailing `*` removes a prefix.
+    Delete { field: String },
+    /// `?Field value` sets the field only if it is missing.
+    Default { field: String, value: String },
+    /// `Field find replace` substitutes within existing values.
+    Replace {
+        field: String,
+        search: String,
+        replace: String,
+    },
+}
+
+impl HeaderOp {
+    /// Header field the operation applies to.
+    #[must_use]
+    pub fn field(&self) -> &str {
+        match self {
+            Self::Set { field, .. }
+            | Self::Add { field, .. }
+            | Self::Delete { field }
+            | Self::Default { field, .. }
+            | Self::Replace { field, .. } => field,
+        }
+    }
+
+    /// Read an operation from a field token and its values. Returns the
+    /// operation and whether it carried the `>` defer prefix.
+    fn parse(token: &str, args: &[String]) -> Result<(Self, bool), DirectiveError> {
+        let invalid = || DirectiveError::InvalidValue {
+            directive: NAME,
+            option: token.to_string(),
+            value: args.join(" "),
+        };
+        let (prefix, field) = match token.chars().next() {
+            Some(c @ ('+' | '-' | '?' | '>')) => (Some(c), token[1..].to_string()),
+            _ => (None, token.to_string()),
+        };
+        if field.is_empty() {
+            return Err(invalid());
+        }
+        let value = || args.first().cloned().unwrap_or_default();
+        let op = match (prefix, args) {
+            (Some('-'), []) => Self::Delete { field },
+            (Some('+'), [_]) => Self::Add {
+                field,
+                value: value(),
+            },
+            (Some('?'), [_]) => Self::Default {
+                field,
+                value: value(),
+            },
+            (None | Some('>'), [] | [_]) => Self::Set {
+                field,
+                value: value(),
+            },
+            (None | Some('>'), [search, replace]) => Self::Replace {
+                field,
+                search: search.clone(),
+                replace: replace.clone(),
+            },
+            _ => return Err(invalid()),
+        };
+        Ok((op, prefix == Some('>')))
+    }
+
+    /// The field token and values as written in a Caddyfile.
+    fn to_args(&self) -> Vec<String> {
+        match self {
+            Self::Set { field, value } => vec![field.clone(), value.clone()],
+            Self::Add { field, value } => vec![format!("+{field}"), value.clone()],
+            Self::Delete { field } => vec![format!("-{field}")],
+            Self::Default { field, value } => vec![format!("?{field}"), value.clone()],
+            Self::Replace {
+                field,
+                search,
+                replace,
+            } => vec![field.clone(), search.clone(), replace.clone()],
+        }
+    }
+}
+
+impl HeaderOps {
+    /// An empty `header` directive.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            matcher: None,
+            ops: Vec::new(),
+            defer: false,
+        }
+    }
+
+    /// Append an operation.
+    #[must_use]
+    pub fn op(mut self, op: HeaderOp) -> Self {
+        self.ops.push(op);
+        self
+    }
+
+    fn push(&mut self, token: &str, args: &[String]) -> Result<(), DirectiveError> {
+        let (op, defer) = HeaderOp::parse(token, args)?;
+        self.defer |= defer;
+        self.ops.push(op);
+        Ok(())
+    }
+}
+
+impl TryFrom<&Directive> for HeaderOps {
+    type Error = DirectiveError;
+
+    fn try_from(d: &Directive) -> Result<Self, Self::Error> {
+        expect_name(d, NAME)?;
+        let mut ops = Self {
+            matcher: d.matcher.clone(),
+            ..Self::new()
+        };
+        let args: Vec<String> = d.arguments.iter().map(|a| a.value().into_owned()).collect();
+        if let Some((field, rest)) = args.split_first() {
+            ops.push(field, rest)?;
+        }
+        for sub in d.block.iter().flatten() {
+            if sub.name == "defer" {
+                ops.defer = true;
+            } else {
+                ops.push(&sub.name, &values(sub))?;
+            }
+        }
+        Ok(ops)
+    }
+}
+
+impl From<&HeaderOps> for Directive {
+    fn from(ops: &HeaderOps) -> Self {
+        let mut d = match ops.ops.as_slice() {
+            [single] if !ops.defer => option(NAME, single.to_args()),
+            _ => {
+                let mut block: Vec<Self> = ops
+                    .ops
+                    .iter()
+                    .map(|op| {
+                        let mut args = op.to_args();
+                        let field = args.remove(0);
+                        option(&field, args)
+                    })
+                    .collect();
+                if ops.defer {
+                    block.push(Self::new("defer"));
+                }
+                let mut d = Self::new(NAME);
+                d.block = Some(block);
+                d
+            }
+        };
+        d.matcher.clone_from(&ops.matcher);
+        d
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{format, parse_str};
+
+    fn header(input: &str) -> Result<HeaderOps, DirectiveError> {
+        let cf = parse_str(input).unwrap();
+        HeaderOps::try_from(&cf.sites[0].directives[0])
+    }
+
+    #[test]
+    fn block_operations() {
+        let ops = header(
+            "a.com {\n\theader {\n\
+             \t\tStrict-Transport-Security max-age=31536000\n\
+             \t\t+Link \"</style.css>; rel=preload\"\n\
+             \t\t-Server\n\
+             \t\t-X-Debug-*\n\
+             \t\t?Cache-Control no-cache\n\
+             \t\tLocation http:// https://\n\
+             \t\tdefer\n\
+             \t}\n}\n",
+        )
+        .unwrap();
+        assert!(ops.defer);
+        assert_eq!(
+            ops.ops,
+            [
+                HeaderOp::Set {
+                    field: "Strict-Transport-Security".to_string(),
+                    value: "max-age=31536000".to_string(),
+                },
+                HeaderOp::Add {
+                    field: "Link".to_string(),
+                    value: "</style.css>; rel=preload".to_string(),
+                },
+                HeaderOp::Delete {
+                    field: "Server".to_string()
+                },
+                HeaderOp::Delete {
+                    field: "X-Debug-*".to_string()
+                },
+                HeaderOp::Default {
+                    field: "Cache-Control".to_string(),
+                    value: "no-cache".to_string(),
+                },
+                HeaderOp::Replace {
+                    field: "Location".to_string(),
+                    search: "http://".to_string(),
+                    replace: "https://".to_string(),
+                },
+            ]
+        );
+        assert_eq!(HeaderOps::try_from(&Directive::from(&ops)).unwrap(), ops);
+
+        let cf = crate::Caddyfile::new()
+            .site(crate::SiteBlock::new("a.com").directive(Directive::from(&ops)));
+        let reparsed = parse_str(&format(&cf)).unwrap();
+        assert_eq!(
+            HeaderOps::try_from(&reparsed.sites[0].directives[0]).unwrap(),
+            ops
+        );
+    }
+
+    #[test]
+    fn inline_and_defer_prefix() {
+        let ops = header("a.com {\n\theader /api/* >X-Api 1\n}\n").unwrap();
+        assert_eq!(ops.matcher, Some(Matcher::Path("/api/*".to_string())));
+        assert!(ops.defer);
+        assert_eq!(ops.ops[0].field(), "X-Api");
+
+        let ops = HeaderOps::new().op(HeaderOp::Delete {
+            field: "Server".to_string(),
+        });
+        let cf = crate::Caddyfile::new()
+            .site(crate::SiteBlock::new("a.com").directive(Directive::from(&ops)));
+        assert_eq!(format(&cf), "a.com {\n\theader -Server\n}\n");
+    }
+
+    #[test]
+    fn special_values_round_trip() {
+        let ops = HeaderOps::new()
+            .op(HeaderOp::Set {
+                field: "X-Color".to_string(),
+                value: "#fff".to_string(),
+            })
+            .op(HeaderOp::Set {
+                field: "X-Brace".to_string(),
+                value: "{".to_string(),
+            })
+            .op(HeaderOp::Set {
+                field: "X-Close".to_string(),
+                value: "}".to_string(),
+            })
+            .op(HeaderOp::Set {
+                field: "X-Tick".to_string(),
+                value: "`a`".to_string(),
+            });
+        let cf = crate::Caddyfile::new()
+            .site(crate::SiteBlock::new("a.com").directive(Directive::from(&ops)));
+        assert_eq!(header(&format(&cf)).unwrap(), ops);
+    }
+
+    #[test]
+    fn errors() {
+        assert!(header("a.com {\n\theader -Server x\n}\n").is_err());
+        assert!(header("a.com {\n\theader + x\n}\n").is_err());
+    }
+}
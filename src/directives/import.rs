@@ -0,0 +1,230 @@
+//! The `import` directive and the `{args[N]}` placeholders it fills.
+
+use super::{DirectiveError, expect_name, option, values};
+use crate::ast::{Caddyfile, Directive, Matcher, Snippet};
+use crate::placeholder::find_in;
+
+const NAME: &str = "import";
+
+/// A typed `import` directive: `import <snippet|file> [args...]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Import {
+    /// Snippet name or file path (possibly a glob).
+    pub target: String,
+    /// Arguments substituted for `{args[N]}` in the imported tokens.
+    pub args: Vec<String>,
+    /// Tokens passed to `{block}` by `import name { ... }`.
+    pub block: Option<Vec<Directive>>,
+}
+
+/// An `{args[...]}` placeholder in a snippet or imported file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgsPlaceholder {
+    /// `{args[N]}`, or the older `{args.N}`.
+    Index(usize),
+    /// `{args[start:end]}` with optional bounds; `{args[:]}` is every
+    /// argument.
+    Range {
+        start: Option<usize>,
+        end: Option<usize>,
+    },
+}
+
+impl Import {
+    /// `import <target>` without arguments.
+    #[must_use]
+    pub fn new(target: &str) -> Self {
+        Self {
+            target: target.to_string(),
+            args: Vec::new(),
+            block: None,
+        }
+    }
+
+    /// Append an argument.
+    #[must_use]
+    pub fn arg(mut self, value: &str) -> Self {
+        self.args.push(value.to_string());
+        self
+    }
+
+    /// The snippet this import refers to, or `None` for a file import.
+    #[must_use]
+    pub fn snippet<'a>(&self, cf: &'a Caddyfile) -> Option<&'a Snippet> {
+        cf.snippets.iter().find(|s| s.name == self.target)
+    }
+}
+
+impl TryFrom<&Directive> for Import {
+    type Error = DirectiveError;
+
+    fn try_from(d: &Directive) -> Result<Self, Self::Error> {
+        expect_name(d, NAME)?;
+        let mut args = values(d);
+        if args.is_empty() {
+            return Err(DirectiveError::MissingValue {
+                directive: NAME,
+                option: NAME.to_string(),
+            });
+        }
+        let target = args.remove(0);
+        Ok(Self {
+            target,
+            args,
+            block: d.block.clone(),
+        })
+    }
+}
+
+impl From<&Import> for Directive {
+    fn from(import: &Import) -> Self {
+        let mut d = option(NAME, std::iter::once(&import.target).chain(&import.args));
+        d.block.clone_from(&import.block);
+        d
+    }
+}
+
+impl ArgsPlaceholder {
+    /// Parse a placeholder name such as `args[0]`, `args[1:]` or
+    /// `args.0`, without the braces.
+    #[must_use]
+    pub fn parse(name: &str) -> Option<Self> {
+        if let Some(index) = name.strip_prefix("args.") {
+            return index.parse().ok().map(Self::Index);
+        }
+        let inner = name.strip_prefix("args[")?.strip_suffix(']')?;
+        let bound = |s: &str| -> Option<Option<usize>> {
+            if s.is_empty() {
+                Some(None)
+            } else {
+                s.parse().ok().map(Some)
+            }
+        };
+        match inner.split_once(':') {
+            Some((start, end)) => Some(Self::Range {
+                start: bound(start)?,
+                end: bound(end)?,
+            }),
+            None => inner.parse().ok().map(Self::Index),
+        }
+    }
+
+    /// The arguments this placeholder stands for.
+    #[must_use]
+    pub fn select<'a>(&self, args: &'a [String]) -> &'a [String] {
+        let (start, end) = match *self {
+            Self::Index(i) => (i, i + 1),
+            Self::Range { start, end } => (start.unwrap_or(0), end.unwrap_or(args.len())),
+        };
+        let end = end.min(args.len());
+        args.get(start.min(end)..end).unwrap_or_default()
+    }
+
+    /// Number of arguments an import needs for this placeholder to be
+    /// filled.
+    #[must_use]
+    pub fn min_args(&self) -> usize {
+        match *self {
+            Self::Index(i) => i + 1,
+            Self::Range { start, .. } => start.unwrap_or(0),
+        }
+    }
+}
+
+/// Every `{args[...]}` placeholder in `snippet`, in source order.
+#[must_use]
+pub fn snippet_args(snippet: &Snippet) -> Vec<ArgsPlaceholder> {
+    let mut out = Vec::new();
+    collect_args(&snippet.directives, &mut out);
+    out
+}
+
+fn collect_args(directives: &[Directive], out: &mut Vec<ArgsPlaceholder>) {
+    for d in directives {
+        let matcher = match &d.matcher {
+            Some(Matcher::Paths(paths)) => paths.join(" "),
+            Some(m) => m.to_string(),
+            None => String::new(),
+        };
+        let texts = [d.name.clone(), matcher]
+            .into_iter()
+            .chain(d.arguments.iter().map(|a| a.value().into_owned()));
+        for text in texts {
+            out.extend(
+                find_in(&text)
+                    .into_iter()
+                    .filter_map(|(_, name)| ArgsPlaceholder::parse(&name)),
+            );
+        }
+        if let Some(block) = &d.block {
+            collect_args(block, out);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+
+    const INPUT: &str = "\
+(proxy) {
+\treverse_proxy \"{args[0]}\" {
+\t\theader_up Host \"{args.1}\"
+\t}
+\trespond \"{args[2:]}\"
+}
+
+a.com {
+\timport proxy app:8080 a.com extra more
+\timport sites/*.caddy
+}
+";
+
+    #[test]
+    fn reads_import_arguments() {
+        let cf = parse_str(INPUT).unwrap();
+        let import = Import::try_from(&cf.sites[0].directives[0]).unwrap();
+        assert_eq!(import.target, "proxy");
+        assert_eq!(import.args, ["app:8080", "a.com", "extra", "more"]);
+        assert_eq!(import.snippet(&cf).unwrap().name, "proxy");
+        assert_eq!(Import::try_from(&Directive::from(&import)).unwrap(), import);
+
+        let file = Import::try_from(&cf.sites[0].directives[1]).unwrap();
+        assert!(file.snippet(&cf).is_none());
+        assert!(Import::try_from(&Directive::new("import")).is_err());
+    }
+
+    #[test]
+    fn finds_snippet_placeholders() {
+        let cf = parse_str(INPUT).unwrap();
+        let placeholders = snippet_args(&cf.snippets[0]);
+        assert_eq!(
+            placeholders,
+            [
+                ArgsPlaceholder::Index(0),
+                ArgsPlaceholder::Index(1),
+                ArgsPlaceholder::Range {
+                    start: Some(2),
+                    end: None
+                },
+            ]
+        );
+        let args = ["a", "b", "c", "d"].map(String::from);
+        assert_eq!(placeholders[2].select(&args), ["c", "d"]);
+        assert_eq!(placeholders[0].select(&args), ["a"]);
+        assert!(ArgsPlaceholder::Index(9).select(&args).is_empty());
+        assert_eq!(
+            placeholders.iter().map(ArgsPlaceholder::min_args).max(),
+            Some(2)
+        );
+        assert_eq!(ArgsPlaceholder::parse("args[x]"), None);
+        assert_eq!(
+            ArgsPlaceholder::parse("args[:]"),
+            Some(ArgsPlaceholder::Range {
+                start: None,
+                end: None
+            })
+        );
+    }
+}
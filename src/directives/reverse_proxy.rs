@@ -0,0 +1,443 @@
+//! The `reverse_proxy` directive.
+
+use std::fmt;
+
+use super::{DirectiveError, expect_name, option, parsed, value, values};
+use crate::address::{AddressError, UpstreamAddress, parse_upstream_address};
+use crate::ast::{Comments, Directive, Matcher};
+
+const NAME: &str = "reverse_proxy";
+
+/// A typed `reverse_proxy` directive.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReverseProxy {
+    pub matcher: Option<Matcher>,
+    /// Upstreams from the arguments and any `to` subdirectives.
+    pub upstreams: Vec<String>,
+    pub lb_policy: Option<LbPolicy>,
+    pub lb_retries: Option<u32>,
+    pub lb_try_duration: Option<String>,
+    pub lb_try_interval: Option<String>,
+    pub health_checks: HealthChecks,
+    pub transport: Option<Transport>,
+    /// Subdirectives not modelled above, such as `header_up`.
+    pub other: Vec<Directive>,
+}
+
+/// Load-balancing policy (`lb_policy`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LbPolicy {
+    Random,
+    RandomChoose(u32),
+    First,
+    RoundRobin,
+    WeightedRoundRobin(Vec<u32>),
+    LeastConn,
+    IpHash,
+    ClientIpHash,
+    UriHash,
+    /// Hash of a query parameter.
+    Query(String),
+    /// Hash of a request header.
+    Header(String),
+    /// Sticky sessions through a cookie, optionally signed with a secret.
+    Cookie {
+        name: Option<String>,
+        secret: Option<String>,
+    },
+}
+
+/// Active and passive health checking.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HealthChecks {
+    pub active: ActiveHealthCheck,
+    pub passive: PassiveHealthCheck,
+}
+
+/// Active health checks (`health_*` options).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ActiveHealthCheck {
+    pub uri: Option<String>,
+    pub upstream: Option<String>,
+    pub port: Option<u16>,
+    pub interval: Option<String>,
+    pub timeout: Option<String>,
+    pub status: Option<String>,
+    pub body: Option<String>,
+    pub passes: Option<u32>,
+    pub fails: Option<u32>,
+}
+
+/// Passive health checks.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PassiveHealthCheck {
+    pub fail_duration: Option<String>,
+    pub max_fails: Option<u32>,
+    pub unhealthy_status: Vec<String>,
+    pub unhealthy_latency: Option<String>,
+    pub unhealthy_request_count: Option<u32>,
+}
+
+/// A `transport` sub-block such as `transport http { ... }`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transport {
+    /// Transport module, e.g. `http` or `fastcgi`.
+    pub protocol: String,
+    pub options: Vec<Directive>,
+}
+
+impl ActiveHealthCheck {
+    /// Whether any active health check option is set.
+    #[must_use]
+    pub fn is_enabled(&self) -> bool {
+        *self != Self::default()
+    }
+}
+
+impl PassiveHealthCheck {
+    /// Whether any passive health check option is set.
+    #[must_use]
+    pub fn is_enabled(&self) -> bool {
+        *self != Self::default()
+    }
+}
+
+impl fmt::Display for LbPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())?;
+        for arg in self.args() {
+            write!(f, " {arg}")?;
+        }
+        Ok(())
+    }
+}
+
+impl LbPolicy {
+    /// Policy name as written after `lb_policy`.
+    #[must_use]
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Random => "random",
+            Self::RandomChoose(_) => "random_choose",
+            Self::First => "first",
+            Self::RoundRobin => "round_robin",
+            Self::WeightedRoundRobin(_) => "weighted_round_robin",
+            Self::LeastConn => "least_conn",
+            Self::IpHash => "ip_hash",
+            Self::ClientIpHash => "client_ip_hash",
+            Self::UriHash => "uri_hash",
+            Self::Query(_) => "query",
+            Self::Header(_) => "header",
+            Self::Cookie { .. } => "cookie",
+        }
+    }
+
+    fn args(&self) -> Vec<String> {
+        match self {
+            Self::RandomChoose(n) => vec![n.to_string()],
+            Self::WeightedRoundRobin(weights) => weights.iter().map(ToString::to_string).collect(),
+            Self::Query(s) | Self::Header(s) => vec![s.clone()],
+            Self::Cookie { name, secret } => name.iter().chain(secret).cloned().collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn from_directive(d: &Directive) -> Result<Self, DirectiveError> {
+        let args = values(d);
+        let invalid = || DirectiveError::InvalidValue {
+            directive: NAME,
+            option: d.name.clone(),
+            value: args.join(" "),
+        };
+        let number = |s: &String| s.parse::<u32>().map_err(|_| invalid());
+        let (policy, rest) = args
+            .split_first()
+            .ok_or_else(|| DirectiveError::MissingValue {
+                directive: NAME,
+                option: d.name.clone(),
+            })?;
+        Ok(match (policy.as_str(), rest) {
+            ("random", []) => Self::Random,
+            ("random_choose", [n]) => Self::RandomChoose(number(n)?),
+            ("first", []) => Self::First,
+            ("round_robin", []) => Self::RoundRobin,
+            ("weighted_round_robin", weights) if !weights.is_empty() => {
+                Self::WeightedRoundRobin(weights.iter().map(number).collect::<Result<_, _>>()?)
+            }
+            ("least_conn", []) => Self::LeastConn,
+            ("ip_hash", []) => Self::IpHash,
+            ("client_ip_hash", []) => Self::ClientIpHash,
+            ("uri_hash", []) => Self::UriHash,
+            ("query", [key]) => Self::Query(key.clone()),
+            ("header", [field]) => Self::Header(field.clone()),
+            ("cookie", [] | [_] | [_, _]) => Self::Cookie {
+                name: rest.first().cloned(),
+                secret: rest.get(1).cloned(),
+            },
+            _ => return Err(invalid()),
+        })
+    }
+}
+
+impl ActiveHealthCheck {
+    /// Apply `health_*` option `d`; returns `false` if it is not one.
+    fn apply(&mut self, d: &Directive) -> Result<bool, DirectiveError> {
+        match d.name.as_str() {
+            "health_uri" => self.uri = Some(value(NAME, d)?),
+            "health_upstream" => self.upstream = Some(value(NAME, d)?),
+            "health_port" => self.port = Some(parsed(NAME, d)?),
+            "health_interval" => self.interval = Some(value(NAME, d)?),
+            "health_timeout" => self.timeout = Some(value(NAME, d)?),
+            "health_status" => self.status = Some(value(NAME, d)?),
+            "health_body" => self.body = Some(value(NAME, d)?),
+            "health_passes" => self.passes = Some(parsed(NAME, d)?),
+            "health_fails" => self.fails = Some(parsed(NAME, d)?),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    fn to_directives(&self, out: &mut Vec<Directive>) {
+        let options = [
+            ("health_uri", self.uri.clone()),
+            ("health_upstream", self.upstream.clone()),
+            ("health_port", self.port.map(|p| p.to_string())),
+            ("health_interval", self.interval.clone()),
+            ("health_timeout", self.timeout.clone()),
+            ("health_status", self.status.clone()),
+            ("health_body", self.body.clone()),
+            ("health_passes", self.passes.map(|n| n.to_string())),
+            ("health_fails", self.fails.map(|n| n.to_string())),
+        ];
+        for (name, value) in options {
+            if let Some(value) = value {
+                out.push(option(name, [value]));
+            }
+        }
+    }
+}
+
+impl PassiveHealthCheck {
+    /// Apply a passive health option `d`; returns `false` if it is not one.
+    fn apply(&mut self, d: &Directive) -> Result<bool, DirectiveError> {
+        match d.name.as_str() {
+            "fail_duration" => self.fail_duration = Some(value(NAME, d)?),
+            "max_fails" => self.max_fails = Some(parsed(NAME, d)?),
+            "unhealthy_status" => self.unhealthy_status.extend(values(d)),
+            "unhealthy_latency" => self.unhealthy_latency = Some(value(NAME, d)?),
+            "unhealthy_request_count" => self.unhealthy_request_count = Some(parsed(NAME, d)?),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    fn to_directives(&self, out: &mut Vec<Directive>) {
+        if let Some(v) = &self.fail_duration {
+            out.push(option("fail_duration", [v]));
+        }
+        if let Some(n) = self.max_fails {
+            out.push(option("max_fails", [n]));
+        }
+        if !self.unhealthy_status.is_empty() {
+            out.push(option("unhealthy_status", &self.unhealthy_status));
+        }
+        if let Some(v) = &self.unhealthy_latency {
+            out.push(option("unhealthy_latency", [v]));
+        }
+        if let Some(n) = self.unhealthy_request_count {
+            out.push(option("unhealthy_request_count", [n]));
+        }
+    }
+}
+
+impl ReverseProxy {
+    /// A `reverse_proxy` to the given upstreams.
+    #[must_use]
+    pub fn new<S: ToString>(upstreams: impl IntoIterator<Item = S>) -> Self {
+        Self {
+            upstreams: upstreams.into_iter().map(|u| u.to_string()).collect(),
+            ..Self::default()
+        }
+    }
+
+    /// The upstreams parsed as addresses.
+    pub fn upstream_addresses(&self) -> Result<Vec<UpstreamAddress>, AddressError> {
+        self.upstreams
+            .iter()
+            .map(|u| parse_upstream_address(u))
+            .collect()
+    }
+}
+
+impl TryFrom<&Directive> for ReverseProxy {
+    type Error = DirectiveError;
+
+    fn try_from(d: &Directive) -> Result<Self, Self::Error> {
+        expect_name(d, NAME)?;
+        let mut rp = Self {
+            matcher: d.matcher.clone(),
+            upstreams: d.arguments.iter().map(|a| a.value().into_owned()).collect(),
+            ..Self::default()
+        };
+        for sub in d.block.iter().flatten() {
+            if rp.health_checks.active.apply(sub)? || rp.health_checks.passive.apply(sub)? {
+                continue;
+            }
+            match sub.name.as_str() {
+                "to" => rp.upstreams.extend(values(sub)),
+                "lb_policy" => rp.lb_policy = Some(LbPolicy::from_directive(sub)?),
+                "lb_retries" => rp.lb_retries = Some(parsed(NAME, sub)?),
+                "lb_try_duration" => rp.lb_try_duration = Some(value(NAME, sub)?),
+                "lb_try_interval" => rp.lb_try_interval = Some(value(NAME, sub)?),
+                "transport" => {
+                    rp.transport = Some(Transport {
+                        protocol: value(NAME, sub)?,
+                        options: sub.block.clone().unwrap_or_default(),
+                    });
+                }
+                _ => rp.other.push(sub.clone()),
+            }
+        }
+        Ok(rp)
+    }
+}
+
+impl From<&ReverseProxy> for Directive {
+    fn from(rp: &ReverseProxy) -> Self {
+        let mut block = Vec::new();
+        if let Some(policy) = &rp.lb_policy {
+            let mut args = vec![policy.name().to_string()];
+            args.extend(policy.args());
+            block.push(option("lb_policy", args));
+        }
+        if let Some(n) = rp.lb_retries {
+            block.push(option("lb_retries", [n]));
+        }
+        if let Some(v) = &rp.lb_try_duration {
+            block.push(option("lb_try_duration", [v]));
+        }
+        if let Some(v) = &rp.lb_try_interval {
+            block.push(option("lb_try_interval", [v]));
+        }
+        rp.health_checks.active.to_directives(&mut block);
+        rp.health_checks.passive.to_directives(&mut block);
+        if let Some(transport) = &rp.transport {
+            let mut d = option("transport", [&transport.protocol]);
+            if !transport.options.is_empty() {
+                d.block = Some(transport.options.clone());
+            }
+            block.push(d);
+        }
+        block.extend(rp.other.iter().cloned());
+
+        let mut d = option(NAME, &rp.upstreams);
+        d.matcher.clone_from(&rp.matcher);
+        d.block = (!block.is_empty()).then_some(block);
+        d.comments = Comments::default();
+        d
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{format, parse_str};
+
+    const CONFIG: &str = "a.com {\n\
+                          \treverse_proxy /api/* app1:80 app2:80 {\n\
+                          \t\tto app3:80\n\
+                          \t\tlb_policy cookie lb secret\n\
+                          \t\tlb_retries 2\n\
+                          \t\thealth_uri /healthz\n\
+                          \t\thealth_port 8080\n\
+                          \t\tmax_fails 3\n\
+                          \t\tunhealthy_status 5xx 429\n\
+                          \t\theader_up Host \"{upstream_hostport}\"\n\n\
+                          \t\ttransport http {\n\t\t\tdial_timeout 5s\n\t\t}\n\
+                          \t}\n\
+                          }\n";
+
+    fn directive(input: &str) -> Directive {
+        parse_str(input).unwrap().sites[0].directives[0].clone()
+    }
+
+    #[test]
+    fn extracts_settings() {
+        let rp = ReverseProxy::try_from(&directive(CONFIG)).unwrap();
+        assert_eq!(rp.matcher, Some(Matcher::Path("/api/*".to_string())));
+        assert_eq!(rp.upstreams, ["app1:80", "app2:80", "app3:80"]);
+        let addresses = rp.upstream_addresses().unwrap();
+        assert_eq!(addresses[2].host, "app3");
+        assert_eq!(addresses[2].port, Some(80));
+        assert_eq!(
+            rp.lb_policy,
+            Some(LbPolicy::Cookie {
+                name: Some("lb".to_string()),
+                secret: Some("secret".to_string()),
+            })
+        );
+        assert_eq!(rp.lb_retries, Some(2));
+        assert_eq!(rp.health_checks.active.uri.as_deref(), Some("/healthz"));
+        assert_eq!(rp.health_checks.active.port, Some(8080));
+        assert_eq!(rp.health_checks.passive.max_fails, Some(3));
+        assert_eq!(rp.health_checks.passive.unhealthy_status, ["5xx", "429"]);
+        let transport = rp.transport.as_ref().unwrap();
+        assert_eq!(transport.protocol, "http");
+        assert_eq!(transport.options[0].name, "dial_timeout");
+        assert_eq!(rp.other.len(), 1);
+        assert_eq!(rp.other[0].name, "header_up");
+    }
+
+    #[test]
+    fn converts_back() {
+        let rp = ReverseProxy::try_from(&directive(CONFIG)).unwrap();
+        let d = Directive::from(&rp);
+        assert_eq!(ReverseProxy::try_from(&d).unwrap(), rp);
+
+        let simple = ReverseProxy {
+            lb_policy: Some(LbPolicy::WeightedRoundRobin(vec![3, 1])),
+            ..ReverseProxy::new(["a:80", "b:80"])
+        };
+        let cf = crate::Caddyfile::new()
+            .site(crate::SiteBlock::new("x.com").directive(Directive::from(&simple)));
+        assert_eq!(
+            format(&cf),
+            "x.com {\n\treverse_proxy a:80 b:80 {\n\t\tlb_policy weighted_round_robin 3 1\n\t}\n}\n"
+        );
+    }
+
+    #[test]
+    fn special_values_round_trip() {
+        let rp = ReverseProxy::new(["#fff", "{", "}", "`a`", "a:80"]);
+        let cf = crate::Caddyfile::new()
+            .site(crate::SiteBlock::new("x.com").directive(Directive::from(&rp)));
+        let text = format(&cf);
+        assert_eq!(
+            text,
+            "x.com {\n\treverse_proxy \"#fff\" \"{\" \"}\" \"`a`\" a:80\n}\n"
+        );
+        let back = ReverseProxy::try_from(&directive(&text)).unwrap();
+        assert_eq!(back, rp);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            ReverseProxy::try_from(&Directive::new("file_server")),
+            Err(DirectiveError::WrongName {
+                expected: "reverse_proxy",
+                found: "file_server".to_string(),
+            })
+        );
+        let d = directive("a.com {\n\treverse_proxy app {\n\t\tlb_policy sometimes\n\t}\n}\n");
+        assert!(matches!(
+            ReverseProxy::try_from(&d),
+            Err(DirectiveError::InvalidValue { .. })
+        ));
+        let d = directive("a.com {\n\treverse_proxy app {\n\t\thealth_port x\n\t}\n}\n");
+        assert_eq!(
+            ReverseProxy::try_from(&d).unwrap_err().to_string(),
+            "reverse_proxy: invalid value 'x' for 'health_port'"
+        );
+    }
+}
@@ -0,0 +1,269 @@
+//! The `tls` directive.
+
+use super::{DirectiveError, expect_name, option, value, values};
+use crate::ast::Directive;
+
+const NAME: &str = "tls";
+
+/// A typed `tls` directive.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TlsConfig {
+    /// Certificate file, given together with `key_file`.
+    pub cert_file: Option<String>,
+    pub key_file: Option<String>,
+    /// ACME account email.
+    pub email: Option<String>,
+    /// Use Caddy's internal, locally-trusted CA (`tls internal`).
+    pub internal: bool,
+    /// Obtain certificates during the first handshake.
+    pub on_demand: bool,
+    pub protocol_min: Option<String>,
+    pub protocol_max: Option<String>,
+    pub ciphers: Vec<String>,
+    pub curves: Vec<String>,
+    pub alpn: Vec<String>,
+    pub key_type: Option<String>,
+    /// ACME directory URL.
+    pub ca: Option<String>,
+    pub dns: Option<DnsProvider>,
+    /// Subdirectives not modelled above, such as `client_auth`.
+    pub other: Vec<Directive>,
+}
+
+/// DNS provider for the ACME DNS challenge, `dns <name> [args] { ... }`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DnsProvider {
+    pub name: String,
+    pub args: Vec<String>,
+    pub options: Vec<Directive>,
+}
+
+impl TlsConfig {
+    /// `tls internal`.
+    #[must_use]
+    pub fn internal() -> Self {
+        Self {
+            internal: true,
+            ..Self::default()
+        }
+    }
+
+    /// `tls <cert_file> <key_file>`.
+    #[must_use]
+    pub fn files(cert_file: &str, key_file: &str) -> Self {
+        Self {
+            cert_file: Some(cert_file.to_string()),
+            key_file: Some(key_file.to_string()),
+            ..Self::default()
+        }
+    }
+}
+
+impl DnsProvider {
+    /// Provider `name` with arguments `args`.
+    #[must_use]
+    pub fn new<I, S>(name: &str, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        Self {
+            name: name.to_string(),
+            args: args.into_iter().map(|a| a.as_ref().to_string()).collect(),
+            options: Vec::new(),
+        }
+    }
+
+    /// Provider `name` taking its API token from the environment
+    /// variable `var`: `dns <name> {$<var>}`.
+    ///
+    /// ```
+    /// use caddyfile_rs::directives::{DnsProvider, TlsConfig};
+    /// use caddyfile_rs::{Caddyfile, Directive, SiteBlock, format};
+    ///
+    /// let tls = TlsConfig {
+    ///     dns: Some(DnsProvider::env_token("cloudflare", "CF_API_TOKEN")),
+    ///     ..TlsConfig::default()
+    /// };
+    /// let cf = Caddyfile::new().site(SiteBlock::new("example.com").directive(Directive::from(&tls)));
+    /// assert_eq!(
+    ///     format(&cf),
+    ///     "example.com {\n\ttls {\n\t\tdns cloudflare {$CF_API_TOKEN}\n\t}\n}\n"
+    /// );
+    /// ```
+    #[must_use]
+    pub fn env_token(name: &str, var: &str) -> Self {
+        Self::new(name, [format!("{{${var}}}")])
+    }
+}
+
+impl From<&DnsProvider> for Directive {
+    fn from(dns: &DnsProvider) -> Self {
+        let mut d = option("dns", std::iter::once(&dns.name).chain(&dns.args));
+        if !dns.options.is_empty() {
+            d.block = Some(dns.options.clone());
+        }
+        d
+    }
+}
+
+impl TryFrom<&Directive> for TlsConfig {
+    type Error = DirectiveError;
+
+    fn try_from(d: &Directive) -> Result<Self, Self::Error> {
+        expect_name(d, NAME)?;
+        let mut tls = Self::default();
+        match values(d).as_slice() {
+            [] => {}
+            [mode] if mode == "internal" => tls.internal = true,
+            [email] if email.contains('@') => tls.email = Some(email.clone()),
+            [cert, key] => {
+                tls.cert_file = Some(cert.clone());
+                tls.key_file = Some(key.clone());
+            }
+            args => {
+                return Err(DirectiveError::InvalidValue {
+                    directive: NAME,
+                    option: NAME.to_string(),
+                    value: args.join(" "),
+                });
+            }
+        }
+        for sub in d.block.iter().flatten() {
+            match sub.name.as_str() {
+                "protocols" => {
+                    tls.protocol_min = Some(value(NAME, sub)?);
+                    tls.protocol_max = values(sub).get(1).cloned();
+                }
+                "ciphers" => tls.ciphers.extend(values(sub)),
+                "curves" => tls.curves.extend(values(sub)),
+                "alpn" => tls.alpn.extend(values(sub)),
+                "key_type" => tls.key_type = Some(value(NAME, sub)?),
+                "ca" => tls.ca = Some(value(NAME, sub)?),
+                "on_demand" => tls.on_demand = true,
+                "dns" => {
+                    tls.dns = Some(DnsProvider {
+                        name: value(NAME, sub)?,
+                        args: values(sub).split_off(1),
+                        options: sub.block.clone().unwrap_or_default(),
+                    });
+                }
+                _ => tls.other.push(sub.clone()),
+            }
+        }
+        Ok(tls)
+    }
+}
+
+impl From<&TlsConfig> for Directive {
+    fn from(tls: &TlsConfig) -> Self {
+        let mut args = Vec::new();
+        if tls.internal {
+            args.push("internal");
+        } else if let Some(email) = &tls.email {
+            args.push(email);
+        } else if let (Some(cert), Some(key)) = (&tls.cert_file, &tls.key_file) {
+            args.extend([cert.as_str(), key.as_str()]);
+        }
+
+        let mut block = Vec::new();
+        if let Some(min) = &tls.protocol_min {
+            block.push(option(
+                "protocols",
+                std::iter::once(min).chain(&tls.protocol_max),
+            ));
+        }
+        for (name, list) in [
+            ("ciphers", &tls.ciphers),
+            ("curves", &tls.curves),
+            ("alpn", &tls.alpn),
+        ] {
+            if !list.is_empty() {
+                block.push(option(name, list));
+            }
+        }
+        if let Some(key_type) = &tls.key_type {
+            block.push(option("key_type", [key_type]));
+        }
+        if let Some(ca) = &tls.ca {
+            block.push(option("ca", [ca]));
+        }
+        if let Some(dns) = &tls.dns {
+            block.push(dns.into());
+        }
+        if tls.on_demand {
+            block.push(Self::new("on_demand"));
+        }
+        block.extend(tls.other.iter().cloned());
+
+        let mut d = option(NAME, args);
+        d.block = (!block.is_empty()).then_some(block);
+        d
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{format, parse_str};
+
+    fn tls(input: &str) -> Result<TlsConfig, DirectiveError> {
+        let cf = parse_str(input).unwrap();
+        TlsConfig::try_from(&cf.sites[0].directives[0])
+    }
+
+    #[test]
+    fn extracts_settings() {
+        let config = tls("a.com {\n\
+                          \ttls ops@example.com {\n\
+                          \t\tprotocols tls1.2 tls1.3\n\
+                          \t\tciphers TLS_AES_128_GCM_SHA256 TLS_AES_256_GCM_SHA384\n\
+                          \t\tcurves x25519\n\
+                          \t\tdns cloudflare \"{env.CF_API_TOKEN}\"\n\
+                          \t\ton_demand\n\
+                          \t\tclient_auth {\n\t\t\tmode require\n\t\t}\n\
+                          \t}\n\
+                          }\n")
+        .unwrap();
+        assert_eq!(config.email.as_deref(), Some("ops@example.com"));
+        assert_eq!(config.protocol_min.as_deref(), Some("tls1.2"));
+        assert_eq!(config.protocol_max.as_deref(), Some("tls1.3"));
+        assert_eq!(config.ciphers.len(), 2);
+        assert_eq!(config.curves, ["x25519"]);
+        let dns = config.dns.as_ref().unwrap();
+        assert_eq!(dns.name, "cloudflare");
+        assert_eq!(dns.args, ["{env.CF_API_TOKEN}"]);
+        assert!(config.on_demand);
+        assert_eq!(config.other[0].name, "client_auth");
+
+        let d = Directive::from(&config);
+        assert_eq!(TlsConfig::try_from(&d).unwrap(), config);
+    }
+
+    #[test]
+    fn modes() {
+        assert!(tls("a.com {\n\ttls internal\n}\n").unwrap().internal);
+        assert_eq!(
+            tls("a.com {\n\ttls /etc/cert.pem /etc/key.pem\n}\n").unwrap(),
+            TlsConfig::files("/etc/cert.pem", "/etc/key.pem")
+        );
+        assert!(matches!(
+            tls("a.com {\n\ttls a b c\n}\n"),
+            Err(DirectiveError::InvalidValue { .. })
+        ));
+    }
+
+    #[test]
+    fn converts_back() {
+        let config = TlsConfig {
+            on_demand: true,
+            ..TlsConfig::internal()
+        };
+        let cf = crate::Caddyfile::new()
+            .site(crate::SiteBlock::new("a.com").directive(Directive::from(&config)));
+        assert_eq!(
+            format(&cf),
+            "a.com {\n\ttls internal {\n\t\ton_demand\n\t}\n}\n"
+        );
+    }
+}
@@ -0,0 +1,382 @@
+//! Resolution of `{$VAR}` and `{$VAR:default}` environment references.
+//!
+//! Caddy substitutes environment variables before parsing. An
+//! `EnvResolver` does the same for an already-parsed `Caddyfile`, looking
+//! values up in an ordered list of sources: explicit maps, the process
+//! environment and, with the `dotenv` feature, `.env` files. The first
+//! source that defines a variable wins. Undefined variables without a
+//! default resolve to an empty string, as in Caddy.
+
+use std::collections::HashMap;
+
+use crate::ast::{self, Address, Argument, Caddyfile, Directive, Matcher};
+
+/// Error produced while loading environment sources.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum EnvError {
+    /// A `.env` file could not be read or parsed.
+    #[error("{path}: {message}")]
+    Dotenv { path: String, message: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Source {
+    Map(HashMap<String, String>),
+    Process,
+}
+
+/// Ordered set of variable sources.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EnvResolver {
+    sources: Vec<Source>,
+}
+
+impl EnvResolver {
+    /// Create a resolver with no sources.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            sources: Vec::new(),
+        }
+    }
+
+    /// Add an explicit map of variables.
+    #[must_use]
+    pub fn map<K, V>(mut self, vars: impl IntoIterator<Item = (K, V)>) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.sources.push(Source::Map(
+            vars.into_iter()
+                .map(|(k, v)| (k.into(), v.into()))
+                .collect(),
+        ));
+        self
+    }
+
+    /// Add the process environment.
+    #[must_use]
+    pub fn process_env(mut self) -> Self {
+        self.sources.push(Source::Process);
+        self
+    }
+
+    /// Add variables from a `.env` file.
+    ///
+    /// Call repeatedly for layered files; put the most specific file
+    /// (e.g. `.env.local`) first since earlier sources take precedence.
+    #[cfg(feature = "dotenv")]
+    pub fn dotenv(mut self, path: impl AsRef<std::path::Path>) -> Result<Self, EnvError> {
+        let path = path.as_ref();
+        let to_error = |e: dotenvy::Error| EnvError::Dotenv {
+            path: path.display().to_string(),
+            message: e.to_string(),
+        };
+        let vars = dotenvy::from_path_iter(path)
+            .map_err(to_error)?
+            .collect::<Result<HashMap<_, _>, _>>()
+            .map_err(to_error)?;
+        self.sources.push(Source::Map(vars));
+        Ok(self)
+    }
+
+    /// Look a variable up in the sources, in order.
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<String> {
+        self.sources.iter().find_map(|source| match source {
+            Source::Map(vars) => vars.get(name).cloned(),
+            Source::Process => std::env::var(name).ok(),
+        })
+    }
+
+    /// Replace every `{$VAR}` / `{$VAR:default}` reference in `text`.
+    #[must_use]
+    pub fn substitute(&self, text: &str) -> String {
+        let mut out = String::with_capacity(text.len());
+        for segment in segments(text) {
+            match segment {
+                Segment::Text(text) => out.push_str(text),
+                Segment::Env(var) => match self.get(&var.name) {
+                    Some(value) => out.push_str(&value),
+                    None => out.push_str(var.default.as_deref().unwrap_or_default()),
+                },
+            }
+        }
+        out
+    }
+}
+
+/// An environment reference `{$NAME}` or `{$NAME:default}`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct EnvRef {
+    pub name: String,
+    pub default: Option<String>,
+}
+
+/// Part of a value: literal text or an embedded environment reference.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment<'a> {
+    Text(&'a str),
+    Env(EnvRef),
+}
+
+/// Split `text` into literal text and the `{$VAR}` references embedded
+/// in it, as in a quoted `"Bearer {$TOKEN}"`.
+#[must_use]
+pub fn segments(text: &str) -> Vec<Segment<'_>> {
+    let mut out = Vec::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find("{$") {
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+        let inner = &rest[start + 2..start + len];
+        let (name, default) = inner
+            .split_once(':')
+            .map_or((inner, None), |(n, d)| (n, Some(d)));
+
+        if start > 0 {
+            out.push(Segment::Text(&rest[..start]));
+        }
+        out.push(Segment::Env(EnvRef {
+            name: name.to_string(),
+            default: default.map(str::to_string),
+        }));
+        rest = &rest[start + len + 1..];
+    }
+
+    if !rest.is_empty() {
+        out.push(Segment::Text(rest));
+    }
+    out
+}
+
+/// Every environment reference in `caddyfile`, in source order.
+///
+/// References embedded in quoted strings, backtick strings and
+/// heredocs are included, as are those in addresses, directive names
+/// and matchers.
+#[must_use]
+pub fn references(caddyfile: &Caddyfile) -> Vec<EnvRef> {
+    let mut out = Vec::new();
+    if let Some(global) = &caddyfile.global_options {
+        directive_references(&global.directives, &mut out);
+    }
+    for snippet in &caddyfile.snippets {
+        directive_references(&snippet.directives, &mut out);
+    }
+    for route in &caddyfile.named_routes {
+        directive_references(&route.directives, &mut out);
+    }
+    for site in &caddyfile.sites {
+        for addr in &site.addresses {
+            text_references(&addr.to_string(), &mut out);
+        }
+        directive_references(&site.directives, &mut out);
+    }
+    out
+}
+
+fn directive_references(directives: &[Directive], out: &mut Vec<EnvRef>) {
+    for d in directives {
+        text_references(&d.name, out);
+        match &d.matcher {
+            Some(Matcher::Path(p) | Matcher::Named(p)) => text_references(p, out),
+            Some(Matcher::Paths(paths)) => {
+                for p in paths {
+                    text_references(p, out);
+                }
+            }
+            Some(Matcher::Not { args, .. }) => {
+                for arg in args {
+                    argument_references(arg, out);
+                }
+            }
+            Some(Matcher::All) | None => {}
+        }
+        for arg in &d.arguments {
+            argument_references(arg, out);
+        }
+        if let Some(block) = &d.block {
+            directive_references(block, out);
+        }
+    }
+}
+
+fn argument_references(arg: &Argument, out: &mut Vec<EnvRef>) {
+    out.extend(
+        arg.segments()
+            .into_iter()
+            .filter_map(|segment| match segment {
+                Segment::Env(var) => Some(var),
+                Segment::Text(_) => None,
+            }),
+    );
+}
+
+fn text_references(text: &str, out: &mut Vec<EnvRef>) {
+    out.extend(
+        segments(text)
+            .into_iter()
+            .filter_map(|segment| match segment {
+                Segment::Env(var) => Some(var),
+                Segment::Text(_) => None,
+            }),
+    );
+}
+
+/// Return a copy of `caddyfile` with all environment references resolved.
+///
+/// Addresses, directive names, matchers and argument values are
+/// substituted. Addresses are re-parsed so that a variable expanding to
+/// `host:port` yields a proper port.
+#[must_use]
+pub fn resolve_env(caddyfile: &Caddyfile, resolver: &EnvResolver) -> Caddyfile {
+    let mut cf = caddyfile.clone();
+    if let Some(global) = &mut cf.global_options {
+        resolve_directives(&mut global.directives, resolver);
+    }
+    for snippet in &mut cf.snippets {
+        resolve_directives(&mut snippet.directives, resolver);
+    }
+    for route in &mut cf.named_routes {
+        resolve_directives(&mut route.directives, resolver);
+    }
+    for site in &mut cf.sites {
+        for addr in &mut site.addresses {
+            let text = addr.to_string();
+            if text.contains("{$") {
+                let text = resolver.substitute(&text);
+                *addr = if addr.raw.is_some() {
+                    Address::raw(text)
+                } else {
+                    ast::parse_address(&text)
+                };
+            }
+        }
+        resolve_directives(&mut site.directives, resolver);
+    }
+    cf
+}
+
+fn resolve_directives(directives: &mut [Directive], resolver: &EnvResolver) {
+    for d in directives {
+        d.name = resolver.substitute(&d.name);
+        match &mut d.matcher {
+            Some(Matcher::Path(p) | Matcher::Named(p)) => *p = resolver.substitute(p),
+            Some(Matcher::Paths(paths)) => {
+                for p in paths {
+                    *p = resolver.substitute(p);
+                }
+            }
+            Some(Matcher::Not { args, .. }) => {
+                for arg in args {
+                    resolve_argument(arg, resolver);
+                }
+            }
+            Some(Matcher::All) | None => {}
+        }
+        for arg in &mut d.arguments {
+            resolve_argument(arg, resolver);
+        }
+        if let Some(block) = &mut d.block {
+            resolve_directives(block, resolver);
+        }
+    }
+}
+
+fn resolve_argument(arg: &mut Argument, resolver: &EnvResolver) {
+    match arg {
+        Argument::Unquoted(s) | Argument::Quoted(s) | Argument::Backtick(s) => {
+            *s = resolver.substitute(s);
+        }
+        Argument::Heredoc { content, .. } => *content = resolver.substitute(content),
+        Argument::EnvVar { name, default } => {
+            let value = resolver.get(name).or_else(|| default.take());
+            *arg = Argument::Unquoted(value.unwrap_or_default());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{format, parse_str};
+
+    #[test]
+    fn substitute_with_defaults() {
+        let env = EnvResolver::new().map([("HOST", "app")]);
+        assert_eq!(env.substitute("{$HOST}:{$PORT:8080}"), "app:8080");
+        assert_eq!(env.substitute("x{$MISSING}y"), "xy");
+        assert_eq!(env.substitute("{$UNCLOSED"), "{$UNCLOSED");
+    }
+
+    #[test]
+    fn references_in_quoted_strings() {
+        let cf = parse_str(
+            "{$DOMAIN} {\n\theader Authorization \"Bearer {$TOKEN}\"\n\trespond `{$A:x}-{$B}` {$CODE:200}\n}\n",
+        )
+        .unwrap();
+        let names: Vec<_> = references(&cf).into_iter().map(|r| r.name).collect();
+        assert_eq!(names, ["DOMAIN", "TOKEN", "A", "B", "CODE"]);
+
+        let header = &cf.sites[0].directives[0].arguments[1];
+        assert_eq!(
+            header.segments(),
+            [
+                Segment::Text("Bearer "),
+                Segment::Env(EnvRef {
+                    name: "TOKEN".to_string(),
+                    default: None
+                }),
+            ]
+        );
+        assert_eq!(segments("{$A:x}").len(), 1);
+        assert_eq!(segments("{$UNCLOSED"), [Segment::Text("{$UNCLOSED")]);
+    }
+
+    #[test]
+    fn earlier_sources_win() {
+        let env = EnvResolver::new()
+            .map([("A", "first")])
+            .map([("A", "second"), ("B", "b")]);
+        assert_eq!(env.get("A").as_deref(), Some("first"));
+        assert_eq!(env.get("B").as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn resolve_addresses_and_arguments() {
+        let cf = parse_str("{$DOMAIN} {\n\treverse_proxy {$UPSTREAM:app:3000}\n}\n").unwrap();
+        let env = EnvResolver::new().map([("DOMAIN", "example.com:8443")]);
+        let resolved = resolve_env(&cf, &env);
+        assert_eq!(resolved.sites[0].addresses[0].port, Some(8443));
+        assert_eq!(
+            format(&resolved),
+            "example.com:8443 {\n\treverse_proxy app:3000\n}\n"
+        );
+    }
+
+    #[cfg(feature = "dotenv")]
+    #[test]
+    fn dotenv_file_source() {
+        let dir = std::env::temp_dir().join(format!("caddyfile-rs-env-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let local = dir.join(".env.local");
+        let shared = dir.join(".env");
+        std::fs::write(&local, "PORT=9000\n").unwrap();
+        std::fs::write(&shared, "PORT=8080\nHOST=app\n").unwrap();
+
+        let env = EnvResolver::new()
+            .dotenv(&local)
+            .and_then(|e| e.dotenv(&shared))
+            .expect("load dotenv files");
+        assert_eq!(env.substitute("{$HOST}:{$PORT}"), "app:9000");
+
+        let missing = EnvResolver::new().dotenv(dir.join("nope"));
+        assert!(matches!(missing, Err(EnvError::Dotenv { .. })));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,491 @@
+//! Parser for the CEL expressions used by `expression` matchers.
+//!
+//! Covers the subset found in Caddyfiles: literals, placeholders,
+//! identifiers, member access, calls, lists, and unary, binary and
+//! conditional operators. Type checking is left to Caddy.
+
+use std::str::FromStr;
+
+use crate::ast::{BinaryOp, Directive, Expression, Literal, UnaryOp};
+
+/// Error produced while parsing an expression.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ExpressionError {
+    /// Input ended in the middle of an expression.
+    #[error("unexpected end of expression")]
+    UnexpectedEnd,
+    /// A character or token that does not fit the grammar.
+    #[error("unexpected '{found}' at offset {offset}")]
+    Unexpected { found: String, offset: usize },
+    /// String literal without its closing quote.
+    #[error("unterminated string at offset {0}")]
+    UnterminatedString(usize),
+    /// Placeholder without its closing brace.
+    #[error("unterminated placeholder at offset {0}")]
+    UnterminatedPlaceholder(usize),
+    /// Parentheses, lists or unary operators nested deeper than
+    /// `MAX_DEPTH`.
+    #[error("expression nested deeper than {max} levels")]
+    TooDeep { max: usize },
+}
+
+/// Deepest nesting of sub-expressions `parse_expression` accepts;
+/// deeper input fails with `ExpressionError::TooDeep` instead of
+/// exhausting the stack.
+pub const MAX_DEPTH: usize = 128;
+
+/// Parse a CEL expression.
+///
+/// # Errors
+///
+/// Returns an `ExpressionError` if the input is not a well-formed
+/// expression.
+pub fn parse_expression(input: &str) -> Result<Expression, ExpressionError> {
+    let tokens = lex(input)?;
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        depth: 0,
+    };
+    let expr = parser.conditional()?;
+    match parser.tokens.get(parser.pos) {
+        Some((tok, offset)) => Err(unexpected(tok, *offset)),
+        None => Ok(expr),
+    }
+}
+
+impl FromStr for Expression {
+    type Err = ExpressionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_expression(s)
+    }
+}
+
+/// The expression of a matcher definition, either the inline form
+/// `@name expression ...` or an `expression ...` line inside a
+/// `@name { ... }` block. Returns `None` for any other directive.
+#[must_use]
+pub fn matcher_expression(directive: &Directive) -> Option<Result<Expression, ExpressionError>> {
+    let args = match directive.arguments.split_first() {
+        Some((first, rest)) if directive.name.starts_with('@') && first.value() == "expression" => {
+            rest
+        }
+        _ if directive.name == "expression" => &directive.arguments[..],
+        _ => return None,
+    };
+    let source = args
+        .iter()
+        .map(|a| a.value().into_owned())
+        .collect::<Vec<_>>()
+        .join(" ");
+    Some(parse_expression(&source))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Tok {
+    Number(String),
+    Str(String),
+    Placeholder(String),
+    Ident(String),
+    Punct(&'static str),
+}
+
+const PUNCTS: &[&str] = &[
+    "||", "&&", "==", "!=", "<=", ">=", "<", ">", "!", "+", "-", "*", "/", "%", "(", ")", "[", "]",
+    ",", ".", "?", ":",
+];
+
+fn unexpected(tok: &Tok, offset: usize) -> ExpressionError {
+    let found = match tok {
+        Tok::Number(s) | Tok::Ident(s) => s.clone(),
+        Tok::Str(s) => format!("'{s}'"),
+        Tok::Placeholder(s) => format!("{{{s}}}"),
+        Tok::Punct(p) => (*p).to_string(),
+    };
+    ExpressionError::Unexpected { found, offset }
+}
+
+fn lex(input: &str) -> Result<Vec<(Tok, usize)>, ExpressionError> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+    while let Some(&(start, ch)) = chars.peek() {
+        if ch.is_whitespace() {
+            chars.next();
+        } else if ch.is_ascii_digit() {
+            let mut end = start;
+            while let Some(&(i, c)) = chars.peek() {
+                let fraction = c == '.' && input[i + 1..].starts_with(|d: char| d.is_ascii_digit());
+                if !(c.is_ascii_digit() || fraction) {
+                    break;
+                }
+                end = i + c.len_utf8();
+                chars.next();
+            }
+            tokens.push((Tok::Number(input[start..end].to_string()), start));
+        } else if ch.is_alphabetic() || ch == '_' {
+            let mut end = start;
+            while let Some(&(i, c)) = chars.peek() {
+                if !(c.is_alphanumeric() || c == '_') {
+                    break;
+                }
+                end = i + c.len_utf8();
+                chars.next();
+            }
+            tokens.push((Tok::Ident(input[start..end].to_string()), start));
+        } else if ch == '\'' || ch == '"' {
+            chars.next();
+            tokens.push((Tok::Str(string(&mut chars, ch, start)?), start));
+        } else if ch == '{' {
+            let len = input[start..]
+                .find('}')
+                .ok_or(ExpressionError::UnterminatedPlaceholder(start))?;
+            let name = &input[start + 1..start + len];
+            if name.is_empty() || name.contains(char::is_whitespace) {
+                return Err(ExpressionError::UnterminatedPlaceholder(start));
+            }
+            tokens.push((Tok::Placeholder(name.to_string()), start));
+            while chars.next_if(|&(i, _)| i <= start + len).is_some() {}
+        } else {
+            let punct = PUNCTS
+                .iter()
+                .find(|p| input[start..].starts_with(**p))
+                .ok_or_else(|| ExpressionError::Unexpected {
+                    found: ch.to_string(),
+                    offset: start,
+                })?;
+            tokens.push((Tok::Punct(punct), start));
+            for _ in 0..punct.len() {
+                chars.next();
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Read a string literal body after its opening `quote`.
+fn string(
+    chars: &mut std::iter::Peekable<std::str::CharIndices<'_>>,
+    quote: char,
+    start: usize,
+) -> Result<String, ExpressionError> {
+    let mut value = String::new();
+    loop {
+        match chars.next() {
+            None => return Err(ExpressionError::UnterminatedString(start)),
+            Some((_, c)) if c == quote => return Ok(value),
+            Some((_, '\\')) => match chars.next() {
+                None => return Err(ExpressionError::UnterminatedString(start)),
+                Some((_, 'n')) => value.push('\n'),
+                Some((_, 't')) => value.push('\t'),
+                Some((_, c)) => value.push(c),
+            },
+            Some((_, c)) => value.push(c),
+        }
+    }
+}
+
+struct Parser {
+    tokens: Vec<(Tok, usize)>,
+    pos: usize,
+    /// Nesting depth of the sub-expression being parsed.
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Tok> {
+        self.tokens.get(self.pos).map(|(tok, _)| tok)
+    }
+
+    fn eat(&mut self, punct: &str) -> bool {
+        if matches!(self.peek(), Some(Tok::Punct(p)) if *p == punct) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, punct: &str) -> Result<(), ExpressionError> {
+        if self.eat(punct) {
+            Ok(())
+        } else {
+            Err(self.error())
+        }
+    }
+
+    fn error(&self) -> ExpressionError {
+        self.tokens
+            .get(self.pos)
+            .map_or(ExpressionError::UnexpectedEnd, |(tok, offset)| {
+                unexpected(tok, *offset)
+            })
+    }
+
+    /// Run `parse` one nesting level deeper.
+    fn nested(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<Expression, ExpressionError>,
+    ) -> Result<Expression, ExpressionError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(ExpressionError::TooDeep { max: MAX_DEPTH });
+        }
+        self.depth += 1;
+        let expr = parse(self);
+        self.depth -= 1;
+        expr
+    }
+
+    fn conditional(&mut self) -> Result<Expression, ExpressionError> {
+        self.nested(Self::conditional_inner)
+    }
+
+    fn conditional_inner(&mut self) -> Result<Expression, ExpressionError> {
+        let condition = self.binary(1)?;
+        if !self.eat("?") {
+            return Ok(condition);
+        }
+        let then = self.conditional()?;
+        self.expect(":")?;
+        let otherwise = self.conditional()?;
+        Ok(Expression::Conditional {
+            condition: Box::new(condition),
+            then: Box::new(then),
+            otherwise: Box::new(otherwise),
+        })
+    }
+
+    fn binary_op(&self) -> Option<BinaryOp> {
+        Some(match self.peek()? {
+            Tok::Punct("||") => BinaryOp::Or,
+            Tok::Punct("&&") => BinaryOp::And,
+            Tok::Punct("==") => BinaryOp::Eq,
+            Tok::Punct("!=") => BinaryOp::Ne,
+            Tok::Punct("<") => BinaryOp::Lt,
+            Tok::Punct("<=") => BinaryOp::Le,
+            Tok::Punct(">") => BinaryOp::Gt,
+            Tok::Punct(">=") => BinaryOp::Ge,
+            Tok::Ident(word) if word == "in" => BinaryOp::In,
+            Tok::Punct("+") => BinaryOp::Add,
+            Tok::Punct("-") => BinaryOp::Sub,
+            Tok::Punct("*") => BinaryOp::Mul,
+            Tok::Punct("/") => BinaryOp::Div,
+            Tok::Punct("%") => BinaryOp::Rem,
+            _ => return None,
+        })
+    }
+
+    /// Precedence climbing over operators binding at least `min`.
+    fn binary(&mut self, min: u8) -> Result<Expression, ExpressionError> {
+        let mut left = self.unary()?;
+        while let Some(op) = self.binary_op().filter(|op| op.precedence() >= min) {
+            self.pos += 1;
+            let right = self.binary(op.precedence() + 1)?;
+            left = Expression::Binary {
+                op,
+                left: Box::new(left),
+                right: Box::new(right),
+            };
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expression, ExpressionError> {
+        let op = if self.eat("!") {
+            UnaryOp::Not
+        } else if self.eat("-") {
+            UnaryOp::Neg
+        } else {
+            return self.postfix();
+        };
+        Ok(Expression::Unary {
+            op,
+            operand: Box::new(self.nested(Self::unary)?),
+        })
+    }
+
+    fn postfix(&mut self) -> Result<Expression, ExpressionError> {
+        let mut expr = self.primary()?;
+        loop {
+            if self.eat(".") {
+                let Some(Tok::Ident(name)) = self.peek().cloned() else {
+                    return Err(self.error());
+                };
+                self.pos += 1;
+                expr = if self.eat("(") {
+                    Expression::Call {
+                        target: Some(Box::new(expr)),
+                        name,
+                        args: self.list(")")?,
+                    }
+                } else {
+                    Expression::Member {
+                        target: Box::new(expr),
+                        field: name,
+                    }
+                };
+            } else if self.eat("[") {
+                let index = self.conditional()?;
+                self.expect("]")?;
+                expr = Expression::Index {
+                    target: Box::new(expr),
+                    index: Box::new(index),
+                };
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expression, ExpressionError> {
+        let tok = self.peek().cloned().ok_or(ExpressionError::UnexpectedEnd)?;
+        self.pos += 1;
+        Ok(match tok {
+            Tok::Number(n) => Expression::Literal(Literal::Number(n)),
+            Tok::Str(s) => Expression::Literal(Literal::String(s)),
+            Tok::Placeholder(name) => Expression::Placeholder(name),
+            Tok::Ident(word) => match word.as_str() {
+                "true" => Expression::Literal(Literal::Bool(true)),
+                "false" => Expression::Literal(Literal::Bool(false)),
+                "null" => Expression::Literal(Literal::Null),
+                _ if self.eat("(") => Expression::Call {
+                    target: None,
+                    name: word,
+                    args: self.list(")")?,
+                },
+                _ => Expression::Ident(word),
+            },
+            Tok::Punct("(") => {
+                let expr = self.conditional()?;
+                self.expect(")")?;
+                expr
+            }
+            Tok::Punct("[") => Expression::List(self.list("]")?),
+            Tok::Punct(_) => {
+                self.pos -= 1;
+                return Err(self.error());
+            }
+        })
+    }
+
+    /// Comma-separated expressions up to and including `close`.
+    fn list(&mut self, close: &str) -> Result<Vec<Expression>, ExpressionError> {
+        let mut items = Vec::new();
+        if self.eat(close) {
+            return Ok(items);
+        }
+        loop {
+            items.push(self.conditional()?);
+            if self.eat(close) {
+                return Ok(items);
+            }
+            self.expect(",")?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+
+    fn roundtrip(input: &str) -> String {
+        parse_expression(input).unwrap().to_string()
+    }
+
+    #[test]
+    fn precedence_and_associativity() {
+        let expr = parse_expression("{err.status_code} == 404 || 1 + 2 * 3 > x").unwrap();
+        let Expression::Binary { op, left, right } = expr else {
+            panic!("expected binary");
+        };
+        assert_eq!(op, BinaryOp::Or);
+        assert_eq!(
+            *left,
+            Expression::Binary {
+                op: BinaryOp::Eq,
+                left: Box::new(Expression::Placeholder("err.status_code".to_string())),
+                right: Box::new(Expression::Literal(Literal::Number("404".to_string()))),
+            }
+        );
+        assert_eq!(right.to_string(), "1 + 2 * 3 > x");
+        assert_eq!(roundtrip("(1 + 2) * 3"), "(1 + 2) * 3");
+        assert_eq!(roundtrip("1 - (2 - 3)"), "1 - (2 - 3)");
+        assert_eq!(roundtrip("((1 - 2)) - 3"), "1 - 2 - 3");
+    }
+
+    #[test]
+    fn calls_members_and_literals() {
+        assert_eq!(
+            roundtrip("{path}.startsWith(\"/api\") && !(method in ['GET','HEAD'])"),
+            "{path}.startsWith('/api') && !(method in ['GET', 'HEAD'])"
+        );
+        assert_eq!(
+            roundtrip("path('/x/*') ? request.headers['X-A'][0] : null"),
+            "path('/x/*') ? request.headers['X-A'][0] : null"
+        );
+        assert_eq!(roundtrip("-1.5 != 'it\\'s'"), "-1.5 != 'it\\'s'");
+        let expr = parse_expression("{a} == {b}.size() && true").unwrap();
+        assert_eq!(expr.placeholders(), ["a", "b"]);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(parse_expression("1 +"), Err(ExpressionError::UnexpectedEnd));
+        assert_eq!(
+            parse_expression("a b"),
+            Err(ExpressionError::Unexpected {
+                found: "b".to_string(),
+                offset: 2
+            })
+        );
+        assert_eq!(
+            parse_expression("'abc"),
+            Err(ExpressionError::UnterminatedString(0))
+        );
+        assert_eq!(
+            parse_expression("{path == 1"),
+            Err(ExpressionError::UnterminatedPlaceholder(0))
+        );
+        assert!(matches!(
+            parse_expression("a ~ b"),
+            Err(ExpressionError::Unexpected { offset: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn nesting_is_bounded() {
+        let too_deep = Err(ExpressionError::TooDeep { max: MAX_DEPTH });
+        let parens = format!("{}1{}", "(".repeat(100_000), ")".repeat(100_000));
+        assert_eq!(parse_expression(&parens), too_deep);
+        assert_eq!(parse_expression(&"!".repeat(100_000)), too_deep);
+        assert_eq!(parse_expression(&"[".repeat(100_000)), too_deep);
+
+        let ok = format!("{}1{}", "(".repeat(50), ")".repeat(50));
+        assert_eq!(roundtrip(&ok), "1");
+        assert_eq!(roundtrip(&format!("{}true", "!".repeat(50))).len(), 54);
+    }
+
+    #[test]
+    fn matcher_definitions() {
+        let cf = parse_str(
+            "a.com {\n\
+             \t@notfound expression `{err.status_code} == 404`\n\
+             \t@get {\n\t\texpression \"{method} == 'GET'\"\n\t}\n\
+             \tlog\n\
+             }\n",
+        )
+        .unwrap();
+        let site = &cf.sites[0].directives;
+        assert_eq!(
+            matcher_expression(&site[0]).unwrap().unwrap().to_string(),
+            "{err.status_code} == 404"
+        );
+        let block = site[1].block.as_ref().unwrap();
+        assert_eq!(
+            matcher_expression(&block[0]).unwrap().unwrap().to_string(),
+            "{method} == 'GET'"
+        );
+        assert!(matcher_expression(&site[2]).is_none());
+    }
+}
@@ -2,7 +2,362 @@
 //!
 //! Produces tab-indented output with consistent spacing between blocks.
 
-use crate::ast::{Address, Caddyfile, Directive, GlobalOptions, NamedRoute, SiteBlock, Snippet};
+use std::collections::HashSet;
+use std::fmt;
+use std::io;
+use std::ops::Range;
+
+use crate::ast::{
+    Address, Argument, Caddyfile, Comments, Directive, GlobalOptions, NamedRoute, SiteBlock,
+    Snippet,
+};
+use crate::canonical;
+use crate::lexer::tokenize;
+use crate::selector::Node;
+use crate::token::TokenKind;
+
+/// Options controlling how an AST is printed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatOptions {
+    /// When to print a lone site without braces around its directives.
+    pub brace_style: BraceStyle,
+    /// Re-emit `\` line continuations recorded by the parser.
+    pub continuations: bool,
+    /// Pad the arguments of `header` and `map` block entries so their
+    /// values line up.
+    pub align_columns: bool,
+    /// Where blank lines go.
+    pub spacing: Spacing,
+    /// Wrap directive arguments onto `\` continuation lines to keep
+    /// lines within this many columns, counting a tab as four.
+    pub max_line_width: Option<usize>,
+    /// Line terminator written after every line.
+    pub line_ending: LineEnding,
+    /// What to do with unquoted arguments that would not read back as
+    /// a single argument.
+    pub quoting: Quoting,
+    /// Reorder directives before printing.
+    pub directive_sort: DirectiveSort,
+    /// Indentation of each block level.
+    pub indent: Indent,
+    /// How directives with an empty block are printed.
+    pub empty_blocks: EmptyBlocks,
+}
+
+/// Indentation unit of formatted output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Indent {
+    /// One tab per level, as `caddy fmt` does.
+    #[default]
+    Tabs,
+    /// The given number of spaces per level.
+    Spaces(usize),
+}
+
+impl Indent {
+    /// Indentation text of block nesting `level`.
+    fn prefix(self, level: usize) -> String {
+        match self {
+            Self::Tabs => "\t".repeat(level),
+            Self::Spaces(n) => " ".repeat(n * level),
+        }
+    }
+}
+
+/// Indentation and line endings found in a source text by
+/// [`detect_style`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourceStyle {
+    pub indent: Indent,
+    pub line_ending: LineEnding,
+}
+
+/// Detect the indentation and line endings of `source`.
+///
+/// Indentation is whichever of tabs and spaces starts more lines, with
+/// the space width taken as the largest common divisor of the indented
+/// lines' widths; it is `Indent::Tabs` when no line is indented. Line
+/// endings are CRLF when most lines end in `\r\n`.
+#[must_use]
+pub fn detect_style(source: &str) -> SourceStyle {
+    let (mut tab_lines, mut space_lines, mut width) = (0usize, 0usize, 0usize);
+    let (mut crlf, mut lf) = (0usize, 0usize);
+    for line in source.split_inclusive('\n') {
+        if line.ends_with("\r\n") {
+            crlf += 1;
+        } else if line.ends_with('\n') {
+            lf += 1;
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+        if line.starts_with('\t') {
+            tab_lines += 1;
+        } else {
+            let spaces = line.len() - line.trim_start_matches(' ').len();
+            if spaces > 0 {
+                space_lines += 1;
+                width = gcd(width, spaces);
+            }
+        }
+    }
+    SourceStyle {
+        indent: if space_lines > tab_lines {
+            Indent::Spaces(width)
+        } else {
+            Indent::Tabs
+        },
+        line_ending: if crlf > lf {
+            LineEnding::Crlf
+        } else {
+            LineEnding::Lf
+        },
+    }
+}
+
+const fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+/// Order of the directives of sites and snippets in formatted output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DirectiveSort {
+    /// Keep the order of the AST.
+    #[default]
+    Source,
+    /// Caddy's default directive order with the global `order` options
+    /// applied; see `canonical::sort_by_directive_order`.
+    Caddy,
+}
+
+/// Handling of unquoted arguments that need quotes, such as
+/// `Argument::Unquoted("two words")`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Quoting {
+    /// Print arguments as they are.
+    #[default]
+    Preserve,
+    /// Print such arguments double-quoted, escaping as needed.
+    Auto,
+    /// Reject such arguments in `try_format_with_options`;
+    /// `format_with_options` prints them as they are.
+    Strict,
+}
+
+/// Error produced by [`try_format_with_options`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum FormatError {
+    /// An unquoted argument would not parse back as one argument.
+    #[error("argument '{value}' of '{directive}' must be quoted")]
+    NeedsQuotes { directive: String, value: String },
+}
+
+/// Line terminator of formatted output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LineEnding {
+    /// `\n`
+    #[default]
+    Lf,
+    /// `\r\n`
+    Crlf,
+}
+
+impl LineEnding {
+    /// The terminator's text.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Lf => "\n",
+            Self::Crlf => "\r\n",
+        }
+    }
+}
+
+/// Printing of a directive block with no directives, such as
+/// `Directive::block(vec![])`. Blocks holding comments are always
+/// expanded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EmptyBlocks {
+    /// `name {` and `}` on separate lines.
+    #[default]
+    Expanded,
+    /// `name {}` on one line.
+    Collapsed,
+    /// Drop the braces and print `name` alone; the block does not
+    /// survive a reparse.
+    Omitted,
+}
+
+/// Whether a Caddyfile with exactly one site block prints it without
+/// braces. Files with several sites always use braces.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BraceStyle {
+    /// Always print braces.
+    #[default]
+    Always,
+    /// Omit the braces when the site was written without them; see
+    /// `SiteBlock::braceless`.
+    Auto,
+    /// Omit the braces of a lone site.
+    NeverForSingleSite,
+}
+
+/// Blank-line and final-newline policy of the formatter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Spacing {
+    /// Blank line before and after directives that have a block.
+    pub around_blocks: bool,
+    /// Blank line between top-level blocks.
+    pub between_blocks: bool,
+    /// Keep up to this many blank lines between directives where the
+    /// source had them; see `ParseOptions::blank_lines`.
+    pub max_blank_lines: usize,
+    /// End the output with a line terminator.
+    pub final_newline: bool,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FormatOptions {
+    /// Default options.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            brace_style: BraceStyle::Always,
+            continuations: false,
+            align_columns: false,
+            spacing: Spacing {
+                around_blocks: true,
+                between_blocks: true,
+                max_blank_lines: 0,
+                final_newline: true,
+            },
+            max_line_width: None,
+            line_ending: LineEnding::Lf,
+            quoting: Quoting::Preserve,
+            directive_sort: DirectiveSort::Source,
+            indent: Indent::Tabs,
+            empty_blocks: EmptyBlocks::Expanded,
+        }
+    }
+
+    /// Set when a lone site block is printed without braces.
+    #[must_use]
+    pub const fn brace_style(mut self, style: BraceStyle) -> Self {
+        self.brace_style = style;
+        self
+    }
+
+    /// Default options with the indentation and line endings detected
+    /// in `source`, so formatting keeps a file's existing style.
+    #[must_use]
+    pub fn from_source(source: &str) -> Self {
+        let style = detect_style(source);
+        Self::new()
+            .indent(style.indent)
+            .line_ending(style.line_ending)
+    }
+
+    /// Set the indentation unit.
+    #[must_use]
+    pub const fn indent(mut self, indent: Indent) -> Self {
+        self.indent = indent;
+        self
+    }
+
+    /// Set how empty directive blocks are printed.
+    #[must_use]
+    pub const fn empty_blocks(mut self, empty: EmptyBlocks) -> Self {
+        self.empty_blocks = empty;
+        self
+    }
+
+    /// Omit the braces of a lone site block; shorthand for
+    /// `BraceStyle::NeverForSingleSite`.
+    #[must_use]
+    pub const fn braceless_single_site(mut self, braceless: bool) -> Self {
+        self.brace_style = if braceless {
+            BraceStyle::NeverForSingleSite
+        } else {
+            BraceStyle::Always
+        };
+        self
+    }
+
+    /// Keep arguments on the continued lines they were written on.
+    #[must_use]
+    pub const fn continuations(mut self, keep: bool) -> Self {
+        self.continuations = keep;
+        self
+    }
+
+    /// Line up the columns of `header` and `map` blocks.
+    #[must_use]
+    pub const fn align_columns(mut self, align: bool) -> Self {
+        self.align_columns = align;
+        self
+    }
+
+    /// Separate directives that have a block from their neighbours.
+    #[must_use]
+    pub const fn blank_lines_around_blocks(mut self, blank: bool) -> Self {
+        self.spacing.around_blocks = blank;
+        self
+    }
+
+    /// Keep at most `max` consecutive blank lines from the source.
+    #[must_use]
+    pub const fn max_blank_lines(mut self, max: usize) -> Self {
+        self.spacing.max_blank_lines = max;
+        self
+    }
+
+    /// Wrap arguments that would run past `width` columns.
+    #[must_use]
+    pub const fn max_line_width(mut self, width: usize) -> Self {
+        self.max_line_width = Some(width);
+        self
+    }
+
+    /// End lines with `ending`.
+    #[must_use]
+    pub const fn line_ending(mut self, ending: LineEnding) -> Self {
+        self.line_ending = ending;
+        self
+    }
+
+    /// Set how arguments that need quotes are handled.
+    #[must_use]
+    pub const fn quoting(mut self, quoting: Quoting) -> Self {
+        self.quoting = quoting;
+        self
+    }
+
+    /// Set the order of the directives of sites and snippets.
+    #[must_use]
+    pub const fn directive_sort(mut self, sort: DirectiveSort) -> Self {
+        self.directive_sort = sort;
+        self
+    }
+
+    /// End the output with a line terminator.
+    #[must_use]
+    pub const fn final_newline(mut self, newline: bool) -> Self {
+        self.spacing.final_newline = newline;
+        self
+    }
+
+    /// Separate top-level blocks with a blank line.
+    #[must_use]
+    pub const fn blank_line_between_blocks(mut self, blank: bool) -> Self {
+        self.spacing.between_blocks = blank;
+        self
+    }
+}
 
 /// Format a `Caddyfile` AST into a valid Caddyfile string.
 ///
@@ -10,67 +365,529 @@ use crate::ast::{Address, Caddyfile, Directive, GlobalOptions, NamedRoute, SiteB
 /// and preserves quoting style from `Argument` variants.
 #[must_use]
 pub fn format(caddyfile: &Caddyfile) -> String {
+    format_with_options(caddyfile, &FormatOptions::default())
+}
+
+/// Format a `Caddyfile` AST with explicit options.
+#[must_use]
+pub fn format_with_options(caddyfile: &Caddyfile, options: &FormatOptions) -> String {
     let mut out = String::new();
-    let mut first_block = caddyfile.global_options.as_ref().is_none_or(|global| {
-        format_global_options(&mut out, global);
-        false
-    });
+    let _ = format_into_with_options(caddyfile, options, &mut out);
+    out
+}
+
+/// Format a `Caddyfile` AST straight into `writer`, one top-level block
+/// at a time, without building the whole text first.
+pub fn format_to<W: io::Write>(caddyfile: &Caddyfile, writer: &mut W) -> io::Result<()> {
+    format_to_with_options(caddyfile, &FormatOptions::default(), writer)
+}
+
+/// `format_to` with explicit options.
+pub fn format_to_with_options<W: io::Write>(
+    caddyfile: &Caddyfile,
+    options: &FormatOptions,
+    writer: &mut W,
+) -> io::Result<()> {
+    write_blocks(caddyfile, options, &mut |text| {
+        writer.write_all(text.as_bytes())
+    })
+}
+
+/// Format a `Caddyfile` AST into a `fmt::Write`, such as a `String` or
+/// a `fmt::Formatter`.
+pub fn format_into<W: fmt::Write>(caddyfile: &Caddyfile, writer: &mut W) -> fmt::Result {
+    format_into_with_options(caddyfile, &FormatOptions::default(), writer)
+}
+
+/// `format_into` with explicit options.
+pub fn format_into_with_options<W: fmt::Write>(
+    caddyfile: &Caddyfile,
+    options: &FormatOptions,
+    writer: &mut W,
+) -> fmt::Result {
+    write_blocks(caddyfile, options, &mut |text| writer.write_str(text))
+}
+
+/// Format each top-level block into its own buffer and pass it on to
+/// `write`.
+fn write_blocks<E>(
+    caddyfile: &Caddyfile,
+    options: &FormatOptions,
+    write: &mut dyn FnMut(&str) -> Result<(), E>,
+) -> Result<(), E> {
+    if options.directive_sort == DirectiveSort::Caddy {
+        let mut sorted = caddyfile.clone();
+        canonical::sort_by_directive_order(&mut sorted);
+        let options = options.clone().directive_sort(DirectiveSort::Source);
+        return write_blocks(&sorted, &options, write);
+    }
+    let mut sink = Sink {
+        write,
+        line_ending: options.line_ending,
+        held_newline: false,
+    };
+    let separator = if options.spacing.between_blocks {
+        "\n"
+    } else {
+        ""
+    };
+    let mut out = String::new();
+    // no separator before the first block
+    let mut first_block = caddyfile.global_options.is_none();
+    if let Some(global) = &caddyfile.global_options {
+        format_global_options(&mut out, global, options);
+        sink.block(&mut out)?;
+    }
 
     for snippet in &caddyfile.snippets {
         if !first_block {
-            out.push('\n');
+            out.push_str(separator);
         }
-        format_snippet(&mut out, snippet);
+        format_snippet(&mut out, snippet, options);
+        sink.block(&mut out)?;
         first_block = false;
     }
 
     for route in &caddyfile.named_routes {
         if !first_block {
-            out.push('\n');
+            out.push_str(separator);
         }
-        format_named_route(&mut out, route);
+        format_named_route(&mut out, route, options);
+        sink.block(&mut out)?;
         first_block = false;
     }
 
+    let braceless = match (options.brace_style, caddyfile.sites.as_slice()) {
+        (BraceStyle::Auto, [site]) => site.braceless,
+        (BraceStyle::NeverForSingleSite, [_]) => true,
+        _ => false,
+    };
     for site in &caddyfile.sites {
         if !first_block {
-            out.push('\n');
+            out.push_str(separator);
+        }
+        if braceless {
+            format_braceless_site(&mut out, site, options);
+        } else {
+            format_site_block(&mut out, site, options);
         }
-        format_site_block(&mut out, site);
+        sink.block(&mut out)?;
         first_block = false;
     }
 
+    if !caddyfile.trailing_comments.is_empty() {
+        if !first_block {
+            out.push_str(separator);
+        }
+        format_comment_lines(&mut out, &caddyfile.trailing_comments, 0, options);
+        sink.block(&mut out)?;
+    }
+
     // Trailing newline
-    if !out.ends_with('\n') {
-        out.push('\n');
+    if options.spacing.final_newline {
+        (sink.write)(sink.line_ending.as_str())?;
+    }
+    Ok(())
+}
+
+/// Writes formatted blocks with the requested line endings, holding
+/// back the newline after the last one so the final newline can be
+/// left out.
+struct Sink<'a, E> {
+    write: &'a mut dyn FnMut(&str) -> Result<(), E>,
+    line_ending: LineEnding,
+    held_newline: bool,
+}
+
+impl<E> Sink<'_, E> {
+    /// Write and clear `text`, a run of whole lines.
+    fn block(&mut self, text: &mut String) -> Result<(), E> {
+        if text.is_empty() {
+            return Ok(());
+        }
+        if self.held_newline {
+            (self.write)(self.line_ending.as_str())?;
+        }
+        let body = text.strip_suffix('\n').unwrap_or(text);
+        match self.line_ending {
+            LineEnding::Lf => (self.write)(body)?,
+            LineEnding::Crlf => (self.write)(&body.replace('\n', "\r\n"))?,
+        }
+        self.held_newline = text.ends_with('\n');
+        text.clear();
+        Ok(())
+    }
+}
+
+/// Format a `Caddyfile` AST with explicit options, failing on
+/// arguments that need quotes when `options.quoting` is
+/// [`Quoting::Strict`].
+pub fn try_format_with_options(
+    caddyfile: &Caddyfile,
+    options: &FormatOptions,
+) -> Result<String, FormatError> {
+    if options.quoting == Quoting::Strict {
+        let global = caddyfile.global_options.iter().map(|g| &g.directives);
+        let snippets = caddyfile.snippets.iter().map(|s| &s.directives);
+        let routes = caddyfile.named_routes.iter().map(|r| &r.directives);
+        let sites = caddyfile.sites.iter().map(|s| &s.directives);
+        for directives in global.chain(snippets).chain(routes).chain(sites) {
+            check_quoting(directives)?;
+        }
+    }
+    Ok(format_with_options(caddyfile, options))
+}
+
+fn check_quoting(directives: &[Directive]) -> Result<(), FormatError> {
+    for directive in directives {
+        for arg in &directive.arguments {
+            if let Argument::Unquoted(value) = arg {
+                if needs_quotes(value) {
+                    return Err(FormatError::NeedsQuotes {
+                        directive: directive.name.clone(),
+                        value: value.clone(),
+                    });
+                }
+            }
+        }
+        if let Some(block) = &directive.block {
+            check_quoting(block)?;
+        }
+    }
+    Ok(())
+}
+
+/// Whether `value` printed bare would not lex back as one word.
+pub(crate) fn needs_quotes(value: &str) -> bool {
+    tokenize(value).map_or(true, |tokens| {
+        !matches!(tokens.as_slice(), [t]
+            if t.text == value && matches!(t.kind, TokenKind::Word | TokenKind::EnvVar { .. }))
+    })
+}
+
+/// The text of `arg` under the quoting policy of `options`. Heredoc
+/// bodies and closing markers are indented by `prefix`, the indentation
+/// of their directive, which Caddy strips again when reading them.
+fn argument_text(arg: &Argument, prefix: &str, options: &FormatOptions) -> String {
+    match arg {
+        Argument::Unquoted(value) if options.quoting == Quoting::Auto && needs_quotes(value) => {
+            Argument::Quoted(value.clone()).to_string()
+        }
+        Argument::Heredoc { marker, content } if !prefix.is_empty() => {
+            let mut text = format!("<<{marker}");
+            for line in content.split('\n') {
+                text.push('\n');
+                if !line.is_empty() {
+                    text.push_str(prefix);
+                }
+                text.push_str(line);
+            }
+            format!("{text}\n{prefix}{marker}")
+        }
+        _ => arg.to_string(),
+    }
+}
+
+/// A replacement of the bytes in `range` of a source text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub range: Range<usize>,
+    pub new_text: String,
+}
+
+/// Edits that turn `original` into the formatted text of `caddyfile`.
+///
+/// Unchanged lines are left alone and each changed run of lines is cut
+/// down to the bytes that differ, so an editor applying the edits keeps
+/// cursor positions and undo history outside them. Edits are sorted and
+/// do not overlap; their ranges refer to `original`.
+///
+/// Formats with the default options; use `compute_edits_with_options`
+/// with `FormatOptions::from_source(original)` to keep a file's own
+/// indentation and line endings.
+#[must_use]
+pub fn compute_edits(original: &str, caddyfile: &Caddyfile) -> Vec<TextEdit> {
+    compute_edits_with_options(original, caddyfile, &FormatOptions::default())
+}
+
+/// Edits that turn `original` into the text of `caddyfile` formatted
+/// with `options`.
+///
+/// ```
+/// use caddyfile_rs::{FormatOptions, compute_edits_with_options, parse_str};
+///
+/// let original = "a.com {\r\n    log\r\n    encode   gzip\r\n}\r\n";
+/// let cf = parse_str(original).unwrap();
+/// let options = FormatOptions::from_source(original);
+/// let edits = compute_edits_with_options(original, &cf, &options);
+/// assert_eq!(edits.len(), 1);
+/// assert_eq!(&original[edits[0].range.clone()], "  ");
+/// ```
+#[must_use]
+pub fn compute_edits_with_options(
+    original: &str,
+    caddyfile: &Caddyfile,
+    options: &FormatOptions,
+) -> Vec<TextEdit> {
+    diff_lines(original, &format_with_options(caddyfile, options))
+}
+
+/// Line-based diff of `old` and `new` as edits on `old`.
+fn diff_lines(old: &str, new: &str) -> Vec<TextEdit> {
+    let a: Vec<&str> = old.split_inclusive('\n').collect();
+    let b: Vec<&str> = new.split_inclusive('\n').collect();
+    let mut common = Vec::new();
+    common_lines(&a, &b, (0, 0), &mut common);
+    common.push((a.len(), b.len()));
+
+    let mut edits = Vec::new();
+    let (mut i, mut j, mut offset) = (0, 0, 0);
+    for (ci, cj) in common {
+        let hunk = Hunk {
+            start: offset,
+            old: a[i..ci].concat(),
+            new: b[j..cj].concat(),
+        };
+        offset += hunk.old.len() + a.get(ci).map_or(0, |line| line.len());
+        hunk.flush(&mut edits);
+        (i, j) = (ci + 1, cj + 1);
+    }
+    edits
+}
+
+/// Push the index pairs of the lines `a` and `b` keep in a shortest
+/// edit script, shifted by `at`, onto `out` in order.
+///
+/// Uses Myers' divide and conquer over the middle of the edit path, so
+/// memory stays linear in the number of lines.
+fn common_lines(a: &[&str], b: &[&str], at: (usize, usize), out: &mut Vec<(usize, usize)>) {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    out.extend((0..prefix).map(|k| (at.0 + k, at.1 + k)));
+    let (a, b) = (&a[prefix..], &b[prefix..]);
+    let suffix = a
+        .iter()
+        .rev()
+        .zip(b.iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let (a, b) = (&a[..a.len() - suffix], &b[..b.len() - suffix]);
+    let at = (at.0 + prefix, at.1 + prefix);
+    if let Some((x, y)) = middle(a, b) {
+        common_lines(&a[..x], &b[..y], at, out);
+        common_lines(&a[x..], &b[y..], (at.0 + x, at.1 + y), out);
+    }
+    out.extend((0..suffix).map(|k| (at.0 + a.len() + k, at.1 + b.len() + k)));
+}
+
+/// A point on a shortest edit path from the start to the end of `a`
+/// and `b`, found by searching forward from the start and backward from
+/// the end until the two searches meet. `None` when no line is shared.
+fn middle(a: &[&str], b: &[&str]) -> Option<(usize, usize)> {
+    let lines: HashSet<&str> = b.iter().copied().collect();
+    if !a.iter().any(|line| lines.contains(line)) {
+        return None;
+    }
+    let (width, height) = (a.len(), b.len());
+    let delta = isize::try_from(width).ok()? - isize::try_from(height).ok()?;
+    let diagonals = -isize::try_from(height).ok()?..=isize::try_from(width).ok()?;
+    let mut forward = vec![None; width + height + 1];
+    let mut backward = vec![None; width + height + 1];
+    for edits in 0..=isize::try_from((width + height).div_ceil(2)).ok()? {
+        for k in (-edits..=edits)
+            .step_by(2)
+            .filter(|k| diagonals.contains(k))
+        {
+            let Some(x) = extend(&mut forward, edits, k, (width, height), |x, y| a[x] == b[y])
+            else {
+                continue;
+            };
+            if delta % 2 != 0
+                && (1 - edits..edits).contains(&(delta - k))
+                && reach(&backward, delta - k, height).is_some_and(|back| x + back >= width)
+            {
+                return Some((x, x.checked_add_signed(-k)?));
+            }
+        }
+        for k in (-edits..=edits)
+            .step_by(2)
+            .filter(|k| diagonals.contains(k))
+        {
+            let Some(back) = extend(&mut backward, edits, k, (width, height), |x, y| {
+                a[width - 1 - x] == b[height - 1 - y]
+            }) else {
+                continue;
+            };
+            if delta % 2 == 0
+                && (-edits..=edits).contains(&(delta - k))
+                && let Some(x) = reach(&forward, delta - k, height)
+                && x + back >= width
+            {
+                return Some((x, x.checked_add_signed(k - delta)?));
+            }
+        }
     }
+    None
+}
+
+/// The furthest `x` stored for diagonal `k` (`x - y`).
+fn reach(frontier: &[Option<usize>], k: isize, height: usize) -> Option<usize> {
+    let slot = usize::try_from(k.checked_add_unsigned(height)?).ok()?;
+    frontier.get(slot).copied().flatten()
+}
 
+/// Extend the furthest path on diagonal `k` to `edits` edits, then
+/// along the lines that match from there. Returns its new `x`, or
+/// `None` if no such path ends on `k` inside the grid.
+fn extend(
+    frontier: &mut [Option<usize>],
+    edits: isize,
+    k: isize,
+    (width, height): (usize, usize),
+    same: impl Fn(usize, usize) -> bool,
+) -> Option<usize> {
+    let slot = usize::try_from(k.checked_add_unsigned(height)?).ok()?;
+    let start = if edits == 0 {
+        Some(0)
+    } else {
+        let down = (k < edits)
+            .then(|| reach(frontier, k + 1, height))
+            .flatten()
+            .filter(|&x| x.checked_add_signed(-k).is_some_and(|y| y <= height));
+        let right = (k > -edits)
+            .then(|| reach(frontier, k - 1, height))
+            .flatten()
+            .map(|x| x + 1)
+            .filter(|&x| x <= width);
+        down.max(right)
+    };
+    frontier[slot] = None;
+    let mut x = start?;
+    let mut y = x.checked_add_signed(-k)?;
+    while x < width && y < height && same(x, y) {
+        x += 1;
+        y += 1;
+    }
+    frontier[slot] = Some(x);
+    Some(x)
+}
+
+/// A run of changed lines.
+struct Hunk {
+    start: usize,
+    old: String,
+    new: String,
+}
+
+impl Hunk {
+    /// Push the hunk, minus the text it shares at either end, as an
+    /// edit.
+    fn flush(self, edits: &mut Vec<TextEdit>) {
+        if self.old.is_empty() && self.new.is_empty() {
+            return;
+        }
+        let (old, new) = (self.old.as_bytes(), self.new.as_bytes());
+        let mut prefix = old.iter().zip(new).take_while(|(x, y)| x == y).count();
+        while !self.old.is_char_boundary(prefix) {
+            prefix -= 1;
+        }
+        let mut suffix = old[prefix..]
+            .iter()
+            .rev()
+            .zip(new[prefix..].iter().rev())
+            .take_while(|(x, y)| x == y)
+            .count();
+        while !self.old.is_char_boundary(old.len() - suffix) {
+            suffix -= 1;
+        }
+        edits.push(TextEdit {
+            range: self.start + prefix..self.start + old.len() - suffix,
+            new_text: self.new[prefix..new.len() - suffix].to_string(),
+        });
+    }
+}
+
+/// Format a single node (block or directive) found via a selector.
+///
+/// Directives are emitted without leading indentation.
+#[must_use]
+pub fn format_node(node: Node<'_>) -> String {
+    let mut out = String::new();
+    let options = &FormatOptions::new();
+    match node {
+        Node::Global(global) => format_global_options(&mut out, global, options),
+        Node::Snippet(snippet) => format_snippet(&mut out, snippet, options),
+        Node::NamedRoute(route) => format_named_route(&mut out, route, options),
+        Node::Site(site) => format_site_block(&mut out, site, options),
+        Node::Directive(directive) => format_directive(&mut out, directive, 0, options),
+    }
     out
 }
 
-fn format_global_options(out: &mut String, global: &GlobalOptions) {
-    out.push_str("{\n");
-    format_directives(out, &global.directives, 1);
-    out.push_str("}\n");
+/// Prints the node as `format_node` does.
+macro_rules! display_node {
+    ($($ty:ident => $variant:ident),* $(,)?) => {$(
+        impl fmt::Display for $ty {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(&format_node(Node::$variant(self)))
+            }
+        }
+    )*};
+}
+
+display_node!(
+    GlobalOptions => Global,
+    Snippet => Snippet,
+    NamedRoute => NamedRoute,
+    SiteBlock => Site,
+    Directive => Directive,
+);
+
+impl fmt::Display for Caddyfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        format_into(self, f)
+    }
+}
+
+fn format_global_options(out: &mut String, global: &GlobalOptions, options: &FormatOptions) {
+    format_comment_lines(out, &global.comments.leading, 0, options);
+    out.push('{');
+    format_block_body(out, &global.directives, &global.comments, options);
 }
 
-fn format_snippet(out: &mut String, snippet: &Snippet) {
+fn format_snippet(out: &mut String, snippet: &Snippet, options: &FormatOptions) {
+    format_comment_lines(out, &snippet.comments.leading, 0, options);
     out.push('(');
     out.push_str(&snippet.name);
-    out.push_str(") {\n");
-    format_directives(out, &snippet.directives, 1);
-    out.push_str("}\n");
+    out.push_str(") {");
+    format_block_body(out, &snippet.directives, &snippet.comments, options);
 }
 
-fn format_named_route(out: &mut String, route: &NamedRoute) {
+fn format_named_route(out: &mut String, route: &NamedRoute, options: &FormatOptions) {
+    format_comment_lines(out, &route.comments.leading, 0, options);
     out.push_str("&(");
     out.push_str(&route.name);
-    out.push_str(") {\n");
-    format_directives(out, &route.directives, 1);
+    out.push_str(") {");
+    format_block_body(out, &route.directives, &route.comments, options);
+}
+
+/// Write a top-level block after its `{`: the trailing comment, the
+/// directives and closing comments, and the `}`.
+fn format_block_body(
+    out: &mut String,
+    directives: &[Directive],
+    comments: &Comments,
+    options: &FormatOptions,
+) {
+    format_trailing_comment(out, comments.trailing.as_deref());
+    out.push('\n');
+    format_directives(out, directives, 1, options);
+    format_comment_lines(out, &comments.closing, 1, options);
     out.push_str("}\n");
 }
 
-fn format_site_block(out: &mut String, site: &SiteBlock) {
+fn format_site_block(out: &mut String, site: &SiteBlock, options: &FormatOptions) {
+    format_comment_lines(out, &site.comments.leading, 0, options);
+
     // Addresses
     for (i, addr) in site.addresses.iter().enumerate() {
         if i > 0 {
@@ -79,45 +896,190 @@ fn format_site_block(out: &mut String, site: &SiteBlock) {
         format_address(out, addr);
     }
 
-    out.push_str(" {\n");
-    format_directives_with_spacing(out, &site.directives, 1);
+    out.push_str(" {");
+    format_trailing_comment(out, site.comments.trailing.as_deref());
+    out.push('\n');
+    format_directives_with_spacing(out, &site.directives, 1, options);
+    format_comment_lines(out, &site.comments.closing, 1, options);
     out.push_str("}\n");
 }
 
+/// A site without braces; its directives run to the end of the file.
+fn format_braceless_site(out: &mut String, site: &SiteBlock, options: &FormatOptions) {
+    format_comment_lines(out, &site.comments.leading, 0, options);
+    for (i, addr) in site.addresses.iter().enumerate() {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        format_address(out, addr);
+    }
+    format_trailing_comment(out, site.comments.trailing.as_deref());
+    out.push('\n');
+    format_directives_with_spacing(out, &site.directives, 0, options);
+    format_comment_lines(out, &site.comments.closing, 0, options);
+}
+
+/// Write whole-line comments at `indent`.
+fn format_comment_lines(
+    out: &mut String,
+    comments: &[String],
+    indent: usize,
+    options: &FormatOptions,
+) {
+    for comment in comments {
+        out.push_str(&options.indent.prefix(indent));
+        out.push_str(comment);
+        out.push('\n');
+    }
+}
+
+fn format_trailing_comment(out: &mut String, comment: Option<&str>) {
+    if let Some(comment) = comment {
+        out.push(' ');
+        out.push_str(comment);
+    }
+}
+
 fn format_address(out: &mut String, addr: &Address) {
     use std::fmt::Write as _;
     let _ = write!(out, "{addr}");
 }
 
-fn format_directives(out: &mut String, directives: &[Directive], indent: usize) {
-    for directive in directives {
-        format_directive(out, directive, indent);
+fn format_directives(
+    out: &mut String,
+    directives: &[Directive],
+    indent: usize,
+    options: &FormatOptions,
+) {
+    for (i, directive) in directives.iter().enumerate() {
+        push_blank_lines(out, kept_blank_lines(i, directive, options));
+        format_directive(out, directive, indent, options);
     }
 }
 
 /// Format directives with blank lines between directives
-/// that have sub-blocks.
-fn format_directives_with_spacing(out: &mut String, directives: &[Directive], indent: usize) {
+/// that have sub-blocks, unless disabled in `options`.
+fn format_directives_with_spacing(
+    out: &mut String,
+    directives: &[Directive],
+    indent: usize,
+    options: &FormatOptions,
+) {
     let mut prev_had_block = false;
 
     for (i, directive) in directives.iter().enumerate() {
-        let has_block = directive.block.is_some();
+        let has_block = block_lines(directive, options).is_some();
 
         // Blank line before directive with block, or after
         // one that had a block
-        if i > 0 && (has_block || prev_had_block) {
-            out.push('\n');
-        }
+        let around_block = i > 0 && options.spacing.around_blocks && (has_block || prev_had_block);
+        push_blank_lines(
+            out,
+            kept_blank_lines(i, directive, options).max(usize::from(around_block)),
+        );
 
-        format_directive(out, directive, indent);
+        format_directive(out, directive, indent, options);
         prev_had_block = has_block;
     }
 }
 
-fn format_directive(out: &mut String, directive: &Directive, indent: usize) {
+/// Blank lines from the source kept before the `i`th directive of a
+/// block.
+fn kept_blank_lines(i: usize, directive: &Directive, options: &FormatOptions) -> usize {
+    if i == 0 {
+        0
+    } else {
+        directive
+            .blank_lines_before
+            .min(options.spacing.max_blank_lines)
+    }
+}
+
+/// Format the entries of a `header` or `map` block with each column
+/// padded to its widest cell. Entries with a block or a multi-line
+/// argument are printed as usual and left out of the widths.
+fn format_aligned(out: &mut String, block: &[Directive], indent: usize, options: &FormatOptions) {
+    let rows: Vec<Option<Vec<String>>> = block.iter().map(|d| aligned_cells(d, options)).collect();
+    let mut widths: Vec<usize> = Vec::new();
+    for cells in rows.iter().flatten() {
+        // the last cell of a row is not padded
+        for (j, cell) in cells.iter().enumerate().take(cells.len() - 1) {
+            if j == widths.len() {
+                widths.push(0);
+            }
+            widths[j] = widths[j].max(cell.chars().count());
+        }
+    }
+
+    let prefix = options.indent.prefix(indent);
+    for (i, (directive, cells)) in block.iter().zip(&rows).enumerate() {
+        push_blank_lines(out, kept_blank_lines(i, directive, options));
+        let Some(cells) = cells else {
+            format_directive(out, directive, indent, options);
+            continue;
+        };
+        format_comment_lines(out, &directive.comments.leading, indent, options);
+        let mut line = prefix.clone();
+        for (j, cell) in cells.iter().enumerate() {
+            if j > 0 {
+                let pad = widths[j - 1] - cells[j - 1].chars().count();
+                line.extend(std::iter::repeat_n(' ', pad + 1));
+            }
+            line.push_str(cell);
+        }
+        out.push_str(&line);
+        format_trailing_comment(out, directive.comments.trailing.as_deref());
+        out.push('\n');
+    }
+}
+
+/// The columns of a block entry: its name and matcher, then each
+/// argument. `None` if the entry cannot be aligned.
+fn aligned_cells(directive: &Directive, options: &FormatOptions) -> Option<Vec<String>> {
+    if directive.block.is_some() {
+        return None;
+    }
+    let mut head = directive.name.clone();
+    if let Some(matcher) = &directive.matcher {
+        head = format!("{head} {matcher}");
+    }
+    let cells: Vec<String> = std::iter::once(head)
+        .chain(
+            directive
+                .arguments
+                .iter()
+                .map(|a| argument_text(a, "", options)),
+        )
+        .collect();
+    if cells.iter().any(|c| c.contains('\n')) {
+        None
+    } else {
+        Some(cells)
+    }
+}
+
+/// Columns taken by `text` on one line, with tabs four wide.
+fn line_width(text: &str) -> usize {
+    text.chars().map(|c| if c == '\t' { 4 } else { 1 }).sum()
+}
+
+fn push_blank_lines(out: &mut String, count: usize) {
+    for _ in 0..count {
+        out.push('\n');
+    }
+}
+
+fn format_directive(
+    out: &mut String,
+    directive: &Directive,
+    indent: usize,
+    options: &FormatOptions,
+) {
     use std::fmt::Write as _;
 
-    let prefix = "\t".repeat(indent);
+    format_comment_lines(out, &directive.comments.leading, indent, options);
+
+    let prefix = options.indent.prefix(indent);
     out.push_str(&prefix);
     out.push_str(&directive.name);
 
@@ -126,26 +1088,60 @@ fn format_directive(out: &mut String, directive: &Directive, indent: usize) {
         let _ = write!(out, " {matcher}");
     }
 
-    // Arguments
-    for arg in &directive.arguments {
-        let _ = write!(out, " {arg}");
+    // Arguments, continued lines one level deeper
+    let mut width = line_width(&out[out.rfind('\n').map_or(0, |i| i + 1)..]);
+    for (i, arg) in directive.arguments.iter().enumerate() {
+        let arg = argument_text(arg, &prefix, options);
+        let kept = options.continuations && directive.continuations.contains(&i);
+        let too_long = options
+            .max_line_width
+            .is_some_and(|max| i > 0 && width + 1 + line_width(&arg) > max);
+        if kept || too_long {
+            let continued = options.indent.prefix(indent + 1);
+            let _ = write!(out, " \\\n{continued}{arg}");
+            width = line_width(&format!("{continued}{arg}"));
+        } else {
+            let _ = write!(out, " {arg}");
+            width += 1 + line_width(&arg);
+        }
+        if let Some(last) = arg.rfind('\n') {
+            width = line_width(&arg[last + 1..]);
+        }
     }
 
     // Sub-block
-    if let Some(block) = &directive.block {
-        out.push_str(" {\n");
-        format_directives_with_spacing(out, block, indent + 1);
+    if let Some(block) = block_lines(directive, options) {
+        out.push_str(" {");
+        format_trailing_comment(out, directive.comments.trailing.as_deref());
+        out.push('\n');
+        if options.align_columns && matches!(directive.name.as_str(), "header" | "map") {
+            format_aligned(out, block, indent + 1, options);
+        } else {
+            format_directives_with_spacing(out, block, indent + 1, options);
+        }
+        format_comment_lines(out, &directive.comments.closing, indent + 1, options);
         out.push_str(&prefix);
         out.push_str("}\n");
     } else {
+        if directive.block.is_some() && options.empty_blocks == EmptyBlocks::Collapsed {
+            out.push_str(" {}");
+        }
+        format_trailing_comment(out, directive.comments.trailing.as_deref());
         out.push('\n');
     }
 }
 
+/// The block of `directive` when it is printed over several lines.
+fn block_lines<'a>(directive: &'a Directive, options: &FormatOptions) -> Option<&'a [Directive]> {
+    let block = directive.block.as_deref()?;
+    let empty = block.is_empty() && directive.comments.closing.is_empty();
+    (!empty || options.empty_blocks == EmptyBlocks::Expanded).then_some(block)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::ast::{Argument, Scheme};
+    use crate::ast::{Argument, Comments, Scheme};
 
     #[test]
     fn simple_site() {
@@ -159,14 +1155,21 @@ mod tests {
                     host: "example.com".to_string(),
                     port: None,
                     path: None,
+                    raw: None,
                 }],
                 directives: vec![Directive {
                     name: "log".to_string(),
                     matcher: None,
                     arguments: Vec::new(),
                     block: None,
+                    comments: Comments::default(),
+                    continuations: Vec::new(),
+                    blank_lines_before: 0,
                 }],
+                comments: Comments::default(),
+                braceless: false,
             }],
+            trailing_comments: Vec::new(),
         };
 
         let result = format(&cf);
@@ -185,6 +1188,7 @@ mod tests {
                     host: "example.com".to_string(),
                     port: None,
                     path: None,
+                    raw: None,
                 }],
                 directives: vec![
                     Directive {
@@ -192,6 +1196,9 @@ mod tests {
                         matcher: None,
                         arguments: vec![Argument::Unquoted("gzip".to_string())],
                         block: None,
+                        comments: Comments::default(),
+                        continuations: Vec::new(),
+                        blank_lines_before: 0,
                     },
                     Directive {
                         name: "header".to_string(),
@@ -202,16 +1209,28 @@ mod tests {
                             matcher: None,
                             arguments: vec![Argument::Quoted("DENY".to_string())],
                             block: None,
+                            comments: Comments::default(),
+                            continuations: Vec::new(),
+                            blank_lines_before: 0,
                         }]),
+                        comments: Comments::default(),
+                        continuations: Vec::new(),
+                        blank_lines_before: 0,
                     },
                     Directive {
                         name: "log".to_string(),
                         matcher: None,
                         arguments: Vec::new(),
                         block: None,
+                        comments: Comments::default(),
+                        continuations: Vec::new(),
+                        blank_lines_before: 0,
                     },
                 ],
+                comments: Comments::default(),
+                braceless: false,
             }],
+            trailing_comments: Vec::new(),
         };
 
         let result = format(&cf);
@@ -238,7 +1257,11 @@ example.com {
                     matcher: None,
                     arguments: vec![Argument::Unquoted("admin@example.com".to_string())],
                     block: None,
+                    comments: Comments::default(),
+                    continuations: Vec::new(),
+                    blank_lines_before: 0,
                 }],
+                comments: Comments::default(),
             }),
             snippets: Vec::new(),
             named_routes: Vec::new(),
@@ -248,14 +1271,21 @@ example.com {
                     host: "example.com".to_string(),
                     port: None,
                     path: None,
+                    raw: None,
                 }],
                 directives: vec![Directive {
                     name: "log".to_string(),
                     matcher: None,
                     arguments: Vec::new(),
                     block: None,
+                    comments: Comments::default(),
+                    continuations: Vec::new(),
+                    blank_lines_before: 0,
                 }],
+                comments: Comments::default(),
+                braceless: false,
             }],
+            trailing_comments: Vec::new(),
         };
 
         let result = format(&cf);
@@ -283,14 +1313,21 @@ example.com {
                     host: "example.com".to_string(),
                     port: None,
                     path: None,
+                    raw: None,
                 }],
                 directives: vec![Directive {
                     name: "respond".to_string(),
                     matcher: None,
                     arguments: vec![Argument::Quoted("hello \"world\"".to_string())],
                     block: None,
+                    comments: Comments::default(),
+                    continuations: Vec::new(),
+                    blank_lines_before: 0,
                 }],
+                comments: Comments::default(),
+                braceless: false,
             }],
+            trailing_comments: Vec::new(),
         };
 
         let result = format(&cf);
@@ -309,15 +1346,44 @@ example.com {
                     host: "example.com".to_string(),
                     port: Some(443),
                     path: None,
+                    raw: None,
                 }],
                 directives: Vec::new(),
+                comments: Comments::default(),
+                braceless: false,
             }],
+            trailing_comments: Vec::new(),
         };
 
         let result = format(&cf);
         assert!(result.contains("https://example.com:443"));
     }
 
+    #[test]
+    fn format_node_directive_without_indent() {
+        let d = Directive {
+            name: "handle".to_string(),
+            matcher: None,
+            arguments: Vec::new(),
+            block: Some(vec![Directive {
+                name: "respond".to_string(),
+                matcher: None,
+                arguments: vec![Argument::Unquoted("204".to_string())],
+                block: None,
+                comments: Comments::default(),
+                continuations: Vec::new(),
+                blank_lines_before: 0,
+            }]),
+            comments: Comments::default(),
+            continuations: Vec::new(),
+            blank_lines_before: 0,
+        };
+        assert_eq!(
+            format_node(Node::Directive(&d)),
+            "handle {\n\trespond 204\n}\n"
+        );
+    }
+
     #[test]
     fn trailing_newline() {
         let cf = Caddyfile {
@@ -325,9 +1391,47 @@ example.com {
             snippets: Vec::new(),
             named_routes: Vec::new(),
             sites: Vec::new(),
+            trailing_comments: Vec::new(),
         };
 
         let result = format(&cf);
         assert!(result.ends_with('\n'));
     }
+
+    fn apply(old: &str, edits: &[TextEdit]) -> String {
+        let mut text = old.to_string();
+        for edit in edits.iter().rev() {
+            text.replace_range(edit.range.clone(), &edit.new_text);
+        }
+        text
+    }
+
+    /// `count` numbered lines, upper-cased where `upper` holds.
+    fn numbered(count: usize, upper: impl Fn(usize) -> bool) -> String {
+        use std::fmt::Write as _;
+
+        let mut text = String::new();
+        for i in 0..count {
+            let name = if upper(i) { "LINE" } else { "line" };
+            writeln!(text, "\t{name}{i}").unwrap();
+        }
+        text
+    }
+
+    #[test]
+    fn diff_lines_of_large_inputs() {
+        // every line changes: one edit, without a table over all pairs
+        let old = numbered(20_000, |_| false);
+        let new = old.replace('\t', "    ");
+        let edits = diff_lines(&old, &new);
+        assert_eq!(edits.len(), 1);
+        assert_eq!(apply(&old, &edits), new);
+
+        // every other line changes: one edit per changed line
+        let old = numbered(2_000, |_| false);
+        let new = numbered(2_000, |i| i % 2 == 1);
+        let edits = diff_lines(&old, &new);
+        assert_eq!(edits.len(), 1_000);
+        assert_eq!(apply(&old, &edits), new);
+    }
 }
@@ -0,0 +1,538 @@
+//! Typed views over well-known global options.
+//!
+//! Covers the certificate-management blocks `pki`, `storage` and
+//! `on_demand_tls`, and the `order` option. Each type can be extracted
+//! from the generic `Directive` found in `GlobalOptions` and converted
+//! back into one.
+
+use crate::ast::{Directive, GlobalOptions};
+use crate::directives::{option, values};
+
+/// Error produced when a global option does not match its expected shape.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum OptionError {
+    /// The directive has a different name than the typed option.
+    #[error("expected '{expected}' option, got '{found}'")]
+    WrongName {
+        expected: &'static str,
+        found: String,
+    },
+    /// An option or sub-option is missing a required argument.
+    #[error("'{option}' is missing an argument")]
+    MissingArgument { option: String },
+    /// A sub-option that the typed model does not know about.
+    #[error("unknown '{parent}' sub-option: {name}")]
+    UnknownSubOption { parent: &'static str, name: String },
+    /// An argument the typed model cannot represent.
+    #[error("invalid '{option}' argument: {value}")]
+    InvalidArgument { option: String, value: String },
+}
+
+/// The `pki` global option: certificate authorities managed by Caddy.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Pki {
+    pub authorities: Vec<CertificateAuthority>,
+}
+
+/// A `ca [<id>] { ... }` entry inside `pki`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CertificateAuthority {
+    /// CA identifier; Caddy defaults to `local` when omitted.
+    pub id: Option<String>,
+    pub name: Option<String>,
+    pub root_cn: Option<String>,
+    pub intermediate_cn: Option<String>,
+    pub intermediate_lifetime: Option<String>,
+    pub root: Option<KeyPair>,
+    pub intermediate: Option<KeyPair>,
+}
+
+/// Certificate and key location for a CA `root` or `intermediate`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyPair {
+    pub format: Option<String>,
+    pub cert: Option<String>,
+    pub key: Option<String>,
+}
+
+/// The `storage` global option.
+///
+/// Storage modules accept free-form sub-options, so these are kept
+/// as generic directives.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Storage {
+    /// Storage module name (`file_system`, `redis`, ...).
+    pub module: String,
+    /// Inline arguments after the module name.
+    pub args: Vec<String>,
+    /// Sub-options from the block, in source order.
+    pub options: Vec<Directive>,
+}
+
+/// The `on_demand_tls` global option.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OnDemandTls {
+    pub ask: Option<String>,
+    /// Permission module and its arguments.
+    pub permission: Option<Vec<String>>,
+    pub interval: Option<String>,
+    pub burst: Option<String>,
+}
+
+/// The `order` global option: `order <directive> first|last` or
+/// `order <directive> before|after <other>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirectiveOrder {
+    pub directive: String,
+    pub position: OrderPosition,
+}
+
+/// Where an `order` option places its directive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OrderPosition {
+    First,
+    Last,
+    Before(String),
+    After(String),
+}
+
+impl GlobalOptions {
+    /// Find the first global option with the given name.
+    #[must_use]
+    pub fn find(&self, name: &str) -> Option<&Directive> {
+        self.directives.iter().find(|d| d.name == name)
+    }
+
+    /// Typed `pki` option, if present.
+    pub fn pki(&self) -> Result<Option<Pki>, OptionError> {
+        self.find("pki").map(Pki::try_from).transpose()
+    }
+
+    /// Typed `storage` option, if present.
+    pub fn storage(&self) -> Result<Option<Storage>, OptionError> {
+        self.find("storage").map(Storage::try_from).transpose()
+    }
+
+    /// Typed `on_demand_tls` option, if present.
+    pub fn on_demand_tls(&self) -> Result<Option<OnDemandTls>, OptionError> {
+        self.find("on_demand_tls")
+            .map(OnDemandTls::try_from)
+            .transpose()
+    }
+
+    /// Every `order` option, in source order.
+    pub fn directive_order(&self) -> Result<Vec<DirectiveOrder>, OptionError> {
+        self.directives
+            .iter()
+            .filter(|d| d.name == "order")
+            .map(DirectiveOrder::try_from)
+            .collect()
+    }
+}
+
+impl DirectiveOrder {
+    /// Move `directive` within `order`, a list of directive names such
+    /// as `canonical::DIRECTIVE_ORDER`. Returns `false`, leaving `order`
+    /// unchanged, when the `before`/`after` target is not in the list.
+    pub fn apply(&self, order: &mut Vec<String>) -> bool {
+        let mut moved: Vec<String> = order
+            .iter()
+            .filter(|n| **n != self.directive)
+            .cloned()
+            .collect();
+        let index = match &self.position {
+            OrderPosition::First => 0,
+            OrderPosition::Last => moved.len(),
+            OrderPosition::Before(target) | OrderPosition::After(target) => {
+                let Some(i) = moved.iter().position(|n| n == target) else {
+                    return false;
+                };
+                if matches!(self.position, OrderPosition::After(_)) {
+                    i + 1
+                } else {
+                    i
+                }
+            }
+        };
+        moved.insert(index, self.directive.clone());
+        *order = moved;
+        true
+    }
+}
+
+impl Storage {
+    /// First argument of the named sub-option, if present.
+    #[must_use]
+    pub fn option(&self, name: &str) -> Option<String> {
+        self.options
+            .iter()
+            .find(|d| d.name == name)
+            .and_then(|d| values(d).into_iter().next())
+    }
+}
+
+impl TryFrom<&Directive> for Pki {
+    type Error = OptionError;
+
+    fn try_from(d: &Directive) -> Result<Self, Self::Error> {
+        expect_name(d, "pki")?;
+        let mut pki = Self::default();
+        for sub in d.block.iter().flatten() {
+            if sub.name != "ca" {
+                return Err(unknown("pki", sub));
+            }
+            pki.authorities.push(parse_ca(sub)?);
+        }
+        Ok(pki)
+    }
+}
+
+impl From<&Pki> for Directive {
+    fn from(pki: &Pki) -> Self {
+        let cas = pki
+            .authorities
+            .iter()
+            .map(|ca| {
+                let d = option("ca", &ca.id);
+                let mut block = Vec::new();
+                push_opt(&mut block, "name", ca.name.as_deref());
+                push_opt(&mut block, "root_cn", ca.root_cn.as_deref());
+                push_opt(&mut block, "intermediate_cn", ca.intermediate_cn.as_deref());
+                push_opt(
+                    &mut block,
+                    "intermediate_lifetime",
+                    ca.intermediate_lifetime.as_deref(),
+                );
+                if let Some(root) = &ca.root {
+                    block.push(key_pair_directive("root", root));
+                }
+                if let Some(inter) = &ca.intermediate {
+                    block.push(key_pair_directive("intermediate", inter));
+                }
+                d.block(block)
+            })
+            .collect();
+        Self::new("pki").block(cas)
+    }
+}
+
+impl TryFrom<&Directive> for Storage {
+    type Error = OptionError;
+
+    fn try_from(d: &Directive) -> Result<Self, Self::Error> {
+        expect_name(d, "storage")?;
+        let mut args = values(d).into_iter();
+        let module = args.next().ok_or_else(|| missing("storage"))?;
+        Ok(Self {
+            module,
+            args: args.collect(),
+            options: d.block.clone().unwrap_or_default(),
+        })
+    }
+}
+
+impl From<&Storage> for Directive {
+    fn from(storage: &Storage) -> Self {
+        let mut d = option(
+            "storage",
+            std::iter::once(&storage.module).chain(&storage.args),
+        );
+        if !storage.options.is_empty() {
+            d = d.block(storage.options.clone());
+        }
+        d
+    }
+}
+
+impl TryFrom<&Directive> for OnDemandTls {
+    type Error = OptionError;
+
+    fn try_from(d: &Directive) -> Result<Self, Self::Error> {
+        expect_name(d, "on_demand_tls")?;
+        let mut odt = Self::default();
+        for sub in d.block.iter().flatten() {
+            match sub.name.as_str() {
+                "ask" => odt.ask = Some(first_arg(sub)?),
+                "interval" => odt.interval = Some(first_arg(sub)?),
+                "burst" => odt.burst = Some(first_arg(sub)?),
+                "permission" => {
+                    let args = values(sub);
+                    if args.is_empty() {
+                        return Err(missing(&sub.name));
+                    }
+                    odt.permission = Some(args);
+                }
+                _ => return Err(unknown("on_demand_tls", sub)),
+            }
+        }
+        Ok(odt)
+    }
+}
+
+impl From<&OnDemandTls> for Directive {
+    fn from(odt: &OnDemandTls) -> Self {
+        let mut block = Vec::new();
+        push_opt(&mut block, "ask", odt.ask.as_deref());
+        if let Some(permission) = &odt.permission {
+            block.push(option("permission", permission));
+        }
+        push_opt(&mut block, "interval", odt.interval.as_deref());
+        push_opt(&mut block, "burst", odt.burst.as_deref());
+        Self::new("on_demand_tls").block(block)
+    }
+}
+
+impl TryFrom<&Directive> for DirectiveOrder {
+    type Error = OptionError;
+
+    fn try_from(d: &Directive) -> Result<Self, Self::Error> {
+        expect_name(d, "order")?;
+        let args = values(d);
+        let position = match args.as_slice() {
+            [_, pos] if pos == "first" => OrderPosition::First,
+            [_, pos] if pos == "last" => OrderPosition::Last,
+            [_, pos, target] if pos == "before" => OrderPosition::Before(target.clone()),
+            [_, pos, target] if pos == "after" => OrderPosition::After(target.clone()),
+            [] | [_] => return Err(missing("order")),
+            _ => {
+                return Err(OptionError::InvalidArgument {
+                    option: "order".to_string(),
+                    value: args[1..].join(" "),
+                });
+            }
+        };
+        Ok(Self {
+            directive: args[0].clone(),
+            position,
+        })
+    }
+}
+
+impl From<&DirectiveOrder> for Directive {
+    fn from(order: &DirectiveOrder) -> Self {
+        let name = order.directive.as_str();
+        match &order.position {
+            OrderPosition::First => option("order", [name, "first"]),
+            OrderPosition::Last => option("order", [name, "last"]),
+            OrderPosition::Before(target) => option("order", [name, "before", target]),
+            OrderPosition::After(target) => option("order", [name, "after", target]),
+        }
+    }
+}
+
+fn parse_ca(d: &Directive) -> Result<CertificateAuthority, OptionError> {
+    let mut ca = CertificateAuthority {
+        id: values(d).into_iter().next(),
+        ..CertificateAuthority::default()
+    };
+    for sub in d.block.iter().flatten() {
+        match sub.name.as_str() {
+            "name" => ca.name = Some(first_arg(sub)?),
+            "root_cn" => ca.root_cn = Some(first_arg(sub)?),
+            "intermediate_cn" => ca.intermediate_cn = Some(first_arg(sub)?),
+            "intermediate_lifetime" => ca.intermediate_lifetime = Some(first_arg(sub)?),
+            "root" => ca.root = Some(parse_key_pair(sub, "root")?),
+            "intermediate" => ca.intermediate = Some(parse_key_pair(sub, "intermediate")?),
+            _ => return Err(unknown("ca", sub)),
+        }
+    }
+    Ok(ca)
+}
+
+fn parse_key_pair(d: &Directive, parent: &'static str) -> Result<KeyPair, OptionError> {
+    let mut pair = KeyPair::default();
+    for sub in d.block.iter().flatten() {
+        match sub.name.as_str() {
+            "format" => pair.format = Some(first_arg(sub)?),
+            "cert" => pair.cert = Some(first_arg(sub)?),
+            "key" => pair.key = Some(first_arg(sub)?),
+            _ => return Err(unknown(parent, sub)),
+        }
+    }
+    Ok(pair)
+}
+
+fn key_pair_directive(name: &str, pair: &KeyPair) -> Directive {
+    let mut block = Vec::new();
+    push_opt(&mut block, "format", pair.format.as_deref());
+    push_opt(&mut block, "cert", pair.cert.as_deref());
+    push_opt(&mut block, "key", pair.key.as_deref());
+    Directive::new(name).block(block)
+}
+
+fn push_opt(block: &mut Vec<Directive>, name: &str, value: Option<&str>) {
+    if let Some(v) = value {
+        block.push(option(name, [v]));
+    }
+}
+
+fn expect_name(d: &Directive, expected: &'static str) -> Result<(), OptionError> {
+    if d.name == expected {
+        Ok(())
+    } else {
+        Err(OptionError::WrongName {
+            expected,
+            found: d.name.clone(),
+        })
+    }
+}
+
+fn first_arg(d: &Directive) -> Result<String, OptionError> {
+    values(d).into_iter().next().ok_or_else(|| missing(&d.name))
+}
+
+fn missing(option: &str) -> OptionError {
+    OptionError::MissingArgument {
+        option: option.to_string(),
+    }
+}
+
+fn unknown(parent: &'static str, d: &Directive) -> OptionError {
+    OptionError::UnknownSubOption {
+        parent,
+        name: d.name.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+
+    fn global(input: &str) -> GlobalOptions {
+        parse_str(input)
+            .expect("parse failed")
+            .global_options
+            .expect("global options")
+    }
+
+    #[test]
+    fn pki_authorities() {
+        let go = global(
+            "{\n\tpki {\n\t\tca corp {\n\t\t\tname \"Corp CA\"\n\
+             \t\t\troot {\n\t\t\t\tcert /certs/root.pem\n\t\t\t\tkey /certs/root.key\n\
+             \t\t\t}\n\t\t}\n\t}\n}\n",
+        );
+        let pki = go.pki().expect("valid pki").expect("pki present");
+        let ca = &pki.authorities[0];
+        assert_eq!(ca.id.as_deref(), Some("corp"));
+        assert_eq!(ca.name.as_deref(), Some("Corp CA"));
+        let root = ca.root.as_ref().expect("root");
+        assert_eq!(root.cert.as_deref(), Some("/certs/root.pem"));
+        assert_eq!(root.key.as_deref(), Some("/certs/root.key"));
+    }
+
+    #[test]
+    fn quoted_values_round_trip() {
+        let input = "{\n\
+                     \tpki {\n\
+                     \t\tca corp {\n\
+                     \t\t\tname \"Corp CA\"\n\
+                     \t\t\troot_cn \"Corp Root\"\n\
+                     \t\t}\n\
+                     \t}\n\
+                     \tstorage file_system \"/data/caddy store\"\n\
+                     \ton_demand_tls {\n\
+                     \t\task \"http://localhost:9123/ask?a b\"\n\
+                     \t}\n\
+                     }\n";
+        let go = global(input);
+        let pki = Directive::from(&go.pki().unwrap().unwrap());
+        let storage = Directive::from(&go.storage().unwrap().unwrap());
+        let odt = Directive::from(&go.on_demand_tls().unwrap().unwrap());
+        let rebuilt = crate::Caddyfile::new().global(GlobalOptions {
+            directives: vec![pki, storage, odt],
+            ..GlobalOptions::new()
+        });
+        let output = crate::format(&rebuilt);
+        assert_eq!(output, input);
+        assert_eq!(global(&output), go);
+    }
+
+    #[test]
+    fn storage_inline_and_block() {
+        let go = global("{\n\tstorage file_system /data/caddy\n}\n");
+        let storage = go.storage().unwrap().unwrap();
+        assert_eq!(storage.module, "file_system");
+        assert_eq!(storage.args, ["/data/caddy"]);
+
+        let go = global("{\n\tstorage redis {\n\t\thost 10.0.0.1\n\t\tport 6379\n\t}\n}\n");
+        let storage = go.storage().unwrap().unwrap();
+        assert_eq!(storage.module, "redis");
+        assert_eq!(storage.option("port").as_deref(), Some("6379"));
+    }
+
+    #[test]
+    fn on_demand_tls_ask() {
+        let go = global("{\n\ton_demand_tls {\n\t\task http://localhost:9123/ask\n\t}\n}\n");
+        let odt = go.on_demand_tls().unwrap().unwrap();
+        assert_eq!(odt.ask.as_deref(), Some("http://localhost:9123/ask"));
+        assert_eq!(Directive::from(&odt), go.directives[0]);
+    }
+
+    #[test]
+    fn unknown_sub_option_is_an_error() {
+        let go = global("{\n\ton_demand_tls {\n\t\tbogus 1\n\t}\n}\n");
+        assert_eq!(
+            go.on_demand_tls(),
+            Err(OptionError::UnknownSubOption {
+                parent: "on_demand_tls",
+                name: "bogus".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn directive_order() {
+        let go = global(
+            "{\n\torder authenticate before respond\n\torder rate_limit after basic_auth\n\
+             \torder cache first\n}\n",
+        );
+        let orders = go.directive_order().unwrap();
+        assert_eq!(
+            orders[0],
+            DirectiveOrder {
+                directive: "authenticate".to_string(),
+                position: OrderPosition::Before("respond".to_string()),
+            }
+        );
+        assert_eq!(orders[2].position, OrderPosition::First);
+        assert_eq!(Directive::from(&orders[1]), go.directives[1]);
+
+        let mut order: Vec<String> = ["basic_auth", "respond"].map(String::from).to_vec();
+        for o in &orders {
+            assert!(o.apply(&mut order));
+        }
+        assert_eq!(
+            order,
+            [
+                "cache",
+                "basic_auth",
+                "rate_limit",
+                "authenticate",
+                "respond"
+            ]
+        );
+        let unknown = DirectiveOrder {
+            directive: "x".to_string(),
+            position: OrderPosition::After("missing".to_string()),
+        };
+        assert!(!unknown.apply(&mut order));
+
+        let go = global("{\n\torder cache sometime\n}\n");
+        assert_eq!(
+            go.directive_order(),
+            Err(OptionError::InvalidArgument {
+                option: "order".to_string(),
+                value: "sometime".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn absent_options_are_none() {
+        let go = global("{\n\temail a@b.c\n}\n");
+        assert_eq!(go.pki(), Ok(None));
+        assert_eq!(go.storage(), Ok(None));
+    }
+}
@@ -0,0 +1,195 @@
+//! Semantic classification of tokens for syntax highlighting.
+//!
+//! `highlight` works on the token stream rather than the AST, so a
+//! document that does not parse (as it often does not while being
+//! edited) is still classified. Use it with `tokenize_with_recovery` to
+//! build editor highlighting or LSP semantic tokens.
+
+use crate::placeholder::find_in;
+use crate::token::{Span, Token, TokenKind};
+
+/// Highlight category of a token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HighlightKind {
+    /// Site address, such as `example.com` or `:8080`.
+    Address,
+    /// Name of a snippet `(name)` or named route `&(name)` definition.
+    Snippet,
+    /// Directive, subdirective, global option or matcher module name.
+    Directive,
+    /// Matcher token (`*`, `/path`, `@name`) or matcher definition.
+    Matcher,
+    /// Environment reference `{$VAR}`.
+    EnvVar,
+    /// Unquoted argument that is a single `{placeholder}`.
+    Placeholder,
+    /// Quoted, backtick or heredoc string.
+    String,
+    /// Any other unquoted argument.
+    Argument,
+    /// Comment.
+    Comment,
+    /// Brace or comma.
+    Punctuation,
+}
+
+/// A classified token.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Highlight {
+    pub span: Span,
+    pub kind: HighlightKind,
+}
+
+/// Classify `tokens`, one highlight per token. Newlines and whitespace
+/// are skipped.
+///
+/// Placeholders inside longer words and strings are not split out;
+/// `placeholder::scan` locates them.
+#[must_use]
+pub fn highlight(tokens: &[Token]) -> Vec<Highlight> {
+    let mut out = Vec::new();
+    let mut depth = 0usize;
+    // Position of the next word on its line, comments and commas aside
+    let mut position = 0;
+    let mut matcher_line = false;
+    for token in tokens {
+        let kind = match &token.kind {
+            TokenKind::Whitespace => continue,
+            TokenKind::Newline => {
+                position = 0;
+                matcher_line = false;
+                continue;
+            }
+            TokenKind::Comment => HighlightKind::Comment,
+            TokenKind::Comma => HighlightKind::Punctuation,
+            TokenKind::OpenBrace => {
+                depth += 1;
+                HighlightKind::Punctuation
+            }
+            TokenKind::CloseBrace => {
+                depth = depth.saturating_sub(1);
+                HighlightKind::Punctuation
+            }
+            kind => {
+                position += 1;
+                match kind {
+                    TokenKind::EnvVar { .. } => HighlightKind::EnvVar,
+                    TokenKind::Word if depth > 0 && position == 1 => {
+                        matcher_line = token.text.starts_with('@');
+                        word_kind(&token.text, depth, position)
+                    }
+                    // the module of a single-line matcher definition
+                    TokenKind::Word if matcher_line && position == 2 => HighlightKind::Directive,
+                    TokenKind::Word => word_kind(&token.text, depth, position),
+                    _ => HighlightKind::String,
+                }
+            }
+        };
+        out.push(Highlight {
+            span: token.span.clone(),
+            kind,
+        });
+    }
+    out
+}
+
+/// Kind of the word `text`, the `position`th on its line, at block
+/// nesting `depth`.
+fn word_kind(text: &str, depth: usize, position: usize) -> HighlightKind {
+    let definition =
+        (text.starts_with('(') || text.starts_with("&(")) && text.ends_with(')') && position == 1;
+    match (depth, position) {
+        (0, _) if definition => HighlightKind::Snippet,
+        (0, _) => HighlightKind::Address,
+        (_, 1) if text.starts_with('@') => HighlightKind::Matcher,
+        (_, 1) => HighlightKind::Directive,
+        (_, 2) if text == "*" || text.starts_with('/') || text.starts_with('@') => {
+            HighlightKind::Matcher
+        }
+        _ if is_placeholder(text) => HighlightKind::Placeholder,
+        _ => HighlightKind::Argument,
+    }
+}
+
+/// Whether `text` is exactly one `{placeholder}`.
+fn is_placeholder(text: &str) -> bool {
+    matches!(find_in(text).as_slice(), [(0, name)] if name.len() + 2 == text.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::{tokenize, tokenize_with_recovery, tokenize_with_trivia};
+
+    fn kinds(input: &str) -> Vec<(String, HighlightKind)> {
+        let tokens = tokenize(input).unwrap();
+        let texts = tokens
+            .iter()
+            .filter(|t| t.kind != TokenKind::Newline)
+            .map(|t| t.text.clone());
+        texts
+            .zip(highlight(&tokens).into_iter().map(|h| h.kind))
+            .collect()
+    }
+
+    #[test]
+    fn classifies_sites_and_directives() {
+        use HighlightKind::*;
+        let input = "(common) {\n\tencode gzip\n}\n\n\
+                     # main site\n\
+                     a.com, b.com {\n\
+                     \t@api path /api/*\n\
+                     \treverse_proxy @api {$UPSTREAM} {\n\
+                     \t\theader_up Host {host}\n\
+                     \t}\n\
+                     \trespond /health \"ok\" 200\n\
+                     }\n";
+        let got = kinds(input);
+        let expected = [
+            ("(common)", Snippet),
+            ("{", Punctuation),
+            ("encode", Directive),
+            ("gzip", Argument),
+            ("}", Punctuation),
+            ("# main site", Comment),
+            ("a.com", Address),
+            (",", Punctuation),
+            ("b.com", Address),
+            ("{", Punctuation),
+            ("@api", Matcher),
+            ("path", Directive),
+            ("/api/*", Argument),
+            ("reverse_proxy", Directive),
+            ("@api", Matcher),
+            ("{$UPSTREAM}", EnvVar),
+            ("{", Punctuation),
+            ("header_up", Directive),
+            ("Host", Argument),
+            ("{host}", Placeholder),
+            ("}", Punctuation),
+            ("respond", Directive),
+            ("/health", Matcher),
+            ("ok", String),
+            ("200", Argument),
+            ("}", Punctuation),
+        ];
+        assert_eq!(
+            got,
+            expected
+                .map(|(text, kind)| (text.to_string(), kind))
+                .to_vec()
+        );
+    }
+
+    #[test]
+    fn skips_trivia_and_survives_errors() {
+        let tokens = tokenize_with_trivia("a.com {\n\tlog\n}").unwrap();
+        assert_eq!(highlight(&tokens).len(), 4);
+
+        let (tokens, errors) = tokenize_with_recovery("a.com {\n\trespond \"oops\n}\n");
+        assert_eq!(errors.len(), 1);
+        let highlights = highlight(&tokens);
+        assert_eq!(highlights[2].kind, HighlightKind::Directive);
+        assert_eq!(highlights[2].span, Span::new(2, 2));
+    }
+}
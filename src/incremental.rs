@@ -0,0 +1,241 @@
+//! Incremental re-lexing for editors.
+//!
+//! A `TokenBuffer` keeps the source text together with its tokens. After
+//! an edit, `relex` lexes again from the start of the first affected line
+//! and stops as soon as it reaches a newline that lines up with the old
+//! token stream. Tokens after that point are kept and only have their
+//! line numbers shifted.
+
+use std::ops::Range;
+
+use crate::lexer::{LexError, Lexer};
+use crate::token::{Token, TokenKind};
+
+/// Source text and its tokens, kept in sync across edits.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenBuffer {
+    source: String,
+    tokens: Vec<Token>,
+    /// Byte offset of each token in `source`.
+    starts: Vec<usize>,
+}
+
+impl TokenBuffer {
+    /// Tokenize `source`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first `LexError` in `source`.
+    pub fn new(source: impl Into<String>) -> Result<Self, LexError> {
+        let source = source.into();
+        let tokens = Lexer::new(&source).collect::<Result<Vec<_>, _>>()?;
+        let mut lines = LineCursor::new(&source);
+        let starts = tokens.iter().map(|t| lines.offset(t)).collect();
+        Ok(Self {
+            source,
+            tokens,
+            starts,
+        })
+    }
+
+    /// The current source text.
+    #[must_use]
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The tokens of the current source text.
+    #[must_use]
+    pub fn tokens(&self) -> &[Token] {
+        &self.tokens
+    }
+
+    /// Replace the bytes in `range` with `new_text` and re-lex the
+    /// affected part of the source.
+    ///
+    /// Returns the indices of the tokens that were re-lexed; every token
+    /// outside that range is unchanged apart from its line number.
+    ///
+    /// # Errors
+    ///
+    /// Returns the `LexError` of the edited source. The buffer is left
+    /// as it was before the edit.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `range` is out of bounds or does not fall on character
+    /// boundaries.
+    pub fn relex(&mut self, range: Range<usize>, new_text: &str) -> Result<Range<usize>, LexError> {
+        let mut source = self.source.clone();
+        source.replace_range(range.clone(), new_text);
+        let edit_end = range.start + new_text.len();
+
+        // Restart at the first token of the line holding the last token
+        // that starts before the edit; lexer state is fresh there. A
+        // `continued` first token follows a line holding only a
+        // continuation, so back up another line for the lexer to see it.
+        let before = self.starts.partition_point(|&s| s < range.start);
+        let line_start = |end: usize| {
+            (0..end)
+                .rev()
+                .find(|&i| i == 0 || self.tokens[i - 1].kind == TokenKind::Newline)
+                .unwrap_or(0)
+        };
+        let mut restart = line_start(before);
+        while restart > 0 && self.tokens[restart].continued {
+            restart = line_start(restart);
+        }
+        let mut lexer = if before == 0 {
+            Lexer::new(&source)
+        } else {
+            Lexer::resume(&source, self.starts[restart], &self.tokens[restart].span)
+        };
+
+        let mut lines = LineCursor::new(&source);
+        let mut tokens = Vec::new();
+        let mut starts = Vec::new();
+        let mut resync = None;
+        while let Some(token) = lexer.next_token() {
+            let token = token?;
+            let start = lines.offset(&token);
+            let newline = token.kind == TokenKind::Newline;
+            tokens.push(token);
+            starts.push(start);
+            if newline && start >= edit_end {
+                let old = start - new_text.len() + range.len();
+                if let Ok(k) = self.starts.binary_search(&old) {
+                    if self.tokens[k].kind == TokenKind::Newline {
+                        resync = Some(k + 1);
+                        break;
+                    }
+                }
+            }
+        }
+
+        let resync = resync.unwrap_or(self.tokens.len());
+        let relexed = restart..restart + tokens.len();
+        let added = lines_in(new_text);
+        let removed = lines_in(&self.source[range.clone()]);
+        let mut tail = self.tokens.split_off(resync);
+        for token in &mut tail {
+            token.span.line = token.span.line + added - removed;
+        }
+        let tail_starts = self.starts.split_off(resync);
+        self.tokens.truncate(restart);
+        self.starts.truncate(restart);
+        self.tokens.extend(tokens);
+        self.tokens.extend(tail);
+        self.starts.extend(starts);
+        self.starts.extend(
+            tail_starts
+                .into_iter()
+                .map(|s| s - range.len() + new_text.len()),
+        );
+        self.source = source;
+        Ok(relexed)
+    }
+}
+
+fn lines_in(text: &str) -> usize {
+    text.bytes().filter(|&b| b == b'\n').count()
+}
+
+/// Maps token spans to byte offsets, moving forward through the source.
+struct LineCursor<'a> {
+    source: &'a [u8],
+    line: usize,
+    line_start: usize,
+}
+
+impl<'a> LineCursor<'a> {
+    fn new(source: &'a str) -> Self {
+        let bom = if source.starts_with('\u{feff}') { 3 } else { 0 };
+        Self {
+            source: source.as_bytes(),
+            line: 1,
+            line_start: bom,
+        }
+    }
+
+    /// Byte offset of `token`, which must not come before the previous
+    /// token passed in.
+    fn offset(&mut self, token: &Token) -> usize {
+        while self.line < token.span.line {
+            let rest = &self.source[self.line_start..];
+            let len = rest.iter().position(|&b| b == b'\n').unwrap_or(rest.len());
+            self.line_start += len + 1;
+            self.line += 1;
+        }
+        self.line_start + token.span.column - 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+
+    const SOURCE: &str = "a.com {\n\trespond \"hi\"\n\tlog\n}\n\nb.com {\n\tfile_server\n}\n";
+
+    fn check(buffer: &TokenBuffer) {
+        assert_eq!(buffer.tokens(), tokenize(buffer.source()).unwrap());
+    }
+
+    #[test]
+    fn relexes_only_the_edited_line() {
+        let mut buffer = TokenBuffer::new(SOURCE).unwrap();
+        let at = SOURCE.find("hi").unwrap();
+        let relexed = buffer.relex(at..at + 2, "hello there").unwrap();
+        check(&buffer);
+        assert_eq!(relexed, 3..6);
+        assert_eq!(buffer.tokens()[4].text, "hello there");
+    }
+
+    #[test]
+    fn shifts_lines_after_the_edit() {
+        let mut buffer = TokenBuffer::new(SOURCE).unwrap();
+        let at = SOURCE.find("\tlog").unwrap();
+        buffer
+            .relex(at..at, "\tencode gzip\n\theader X-A b\n")
+            .unwrap();
+        check(&buffer);
+        assert_eq!(buffer.tokens().last().unwrap().span.line, 10);
+
+        let at = buffer.source().find("\theader").unwrap();
+        buffer.relex(at..at + "\theader X-A b\n".len(), "").unwrap();
+        check(&buffer);
+    }
+
+    #[test]
+    fn edits_spanning_tokens() {
+        let mut buffer = TokenBuffer::new(SOURCE).unwrap();
+        // a heredoc spanning several new lines
+        let at = SOURCE.find("log").unwrap();
+        buffer.relex(at..at, "respond <<EOF\nx\nEOF\n\t").unwrap();
+        check(&buffer);
+        buffer.relex(0..0, "{\n\tdebug\n}\n\n").unwrap();
+        check(&buffer);
+        let end = buffer.source().len();
+        buffer.relex(end - 2..end, "}\n").unwrap();
+        check(&buffer);
+    }
+
+    #[test]
+    fn edits_after_a_continuation_line() {
+        let source = "a.com {\n\treverse_proxy app:1\n\t\\\n\tapp:2\n}\n";
+        let mut buffer = TokenBuffer::new(source).unwrap();
+        let at = source.find("app:2").unwrap() + "app:2".len();
+        buffer.relex(at..at, " # c").unwrap();
+        check(&buffer);
+        assert!(buffer.tokens()[6].continued);
+    }
+
+    #[test]
+    fn failed_edit_keeps_buffer() {
+        let mut buffer = TokenBuffer::new(SOURCE).unwrap();
+        let at = SOURCE.find("log").unwrap();
+        assert!(buffer.relex(at..at, "\"").is_err());
+        assert_eq!(buffer.source(), SOURCE);
+        check(&buffer);
+    }
+}
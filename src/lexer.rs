@@ -2,7 +2,9 @@
 //!
 //! Handles strings, braces, comments, and whitespace-delimited words.
 
+use std::collections::VecDeque;
 use std::fmt;
+use std::sync::Arc;
 
 use crate::token::{Span, Token, TokenKind};
 
@@ -19,6 +21,9 @@ pub enum LexErrorKind {
     EmptyHeredocMarker,
     /// Byte that cannot start any token.
     UnexpectedCharacter(char),
+    /// Input passed to `tokenize_bytes` is not valid UTF-8 from byte
+    /// `offset` on.
+    InvalidUtf8 { offset: usize },
 }
 
 impl fmt::Display for LexErrorKind {
@@ -43,13 +48,16 @@ impl fmt::Display for LexErrorKind {
             Self::UnexpectedCharacter(ch) => {
                 write!(f, "unexpected character: {ch}")
             }
+            Self::InvalidUtf8 { offset } => {
+                write!(f, "invalid UTF-8 at byte {offset}")
+            }
         }
     }
 }
 
 /// Error produced during lexing.
 #[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
-#[error("{kind} at line {}, column {}", span.line, span.column)]
+#[error("{kind} at {span}")]
 pub struct LexError {
     pub kind: LexErrorKind,
     pub span: Span,
@@ -65,15 +73,110 @@ pub fn tokenize(input: &str) -> Result<Vec<Token>, LexError> {
     Lexer::new(input).tokenize()
 }
 
-struct Lexer<'a> {
+/// Tokenize raw bytes, such as a file read from an untrusted source.
+///
+/// # Errors
+///
+/// Returns `LexErrorKind::InvalidUtf8` pointing at the first invalid
+/// byte instead of replacing it, and otherwise the same errors as
+/// `tokenize`.
+pub fn tokenize_bytes(input: &[u8]) -> Result<Vec<Token>, LexError> {
+    match std::str::from_utf8(input) {
+        Ok(input) => tokenize(input),
+        Err(e) => {
+            let offset = e.valid_up_to();
+            let before = &input[..offset];
+            let line_start = before
+                .iter()
+                .rposition(|&b| b == b'\n')
+                .map_or(0, |i| i + 1);
+            let bom = if line_start == 0 && before.starts_with(&[0xEF, 0xBB, 0xBF]) {
+                3
+            } else {
+                0
+            };
+            Err(LexError {
+                kind: LexErrorKind::InvalidUtf8 { offset },
+                span: Span::new(
+                    before.split(|&b| b == b'\n').count(),
+                    offset - line_start - bom + 1,
+                ),
+            })
+        }
+    }
+}
+
+/// Tokenize, recording `file` in every span so errors and tokens point
+/// at the file they came from.
+pub fn tokenize_named(input: &str, file: &str) -> Result<Vec<Token>, LexError> {
+    Lexer::new(input).file(file).tokenize()
+}
+
+/// Tokenize, keeping layout as `TokenKind::Whitespace` tokens.
+///
+/// Runs of spaces, tabs and line continuations become whitespace tokens
+/// holding their exact source text, and newline tokens keep a `\r\n`
+/// line ending. Together with the token spans this lets a tool restore
+/// the original layout. The parser does not accept whitespace tokens;
+/// drop them with `Token::is_trivia` before parsing.
+pub fn tokenize_with_trivia(input: &str) -> Result<Vec<Token>, LexError> {
+    Lexer::new(input).trivia(true).tokenize()
+}
+
+/// Tokenize, recovering from errors.
+///
+/// A token that fails to lex is dropped along with the rest of its
+/// line, and lexing resumes on the next line. Returns the tokens that
+/// were read along with every error.
+#[must_use]
+pub fn tokenize_with_recovery(input: &str) -> (Vec<Token>, Vec<LexError>) {
+    let mut tokens = Vec::new();
+    let mut errors = Vec::new();
+    for result in Lexer::new(input) {
+        match result {
+            Ok(token) => tokens.push(token),
+            Err(e) => errors.push(e),
+        }
+    }
+    (tokens, errors)
+}
+
+/// A pull-based lexer yielding one token at a time.
+///
+/// `tokenize` and friends collect every token up front; a `Lexer` lets
+/// a custom parser, highlighter or REPL read tokens as it needs them.
+/// It is also an iterator over `Result<Token, LexError>`. After an
+/// error the rest of the line is skipped, as in `tokenize_with_recovery`.
+///
+/// ```
+/// use caddyfile_rs::{Lexer, TokenKind};
+///
+/// let mut lexer = Lexer::new("example.com {\n\tlog\n}\n");
+/// assert_eq!(lexer.peek().unwrap().as_ref().unwrap().text, "example.com");
+/// let site = lexer.next_token().unwrap().unwrap();
+/// assert_eq!(site.text, "example.com");
+/// assert_eq!(lexer.next_token().unwrap().unwrap().kind, TokenKind::OpenBrace);
+/// assert_eq!(lexer.position().column, 14);
+/// ```
+pub struct Lexer<'a> {
     input: &'a [u8],
     pos: usize,
     line: usize,
     col: usize,
+    /// Emit whitespace tokens and exact line endings.
+    trivia: bool,
+    /// File name recorded in every span.
+    file: Option<Arc<str>>,
+    /// A line continuation was skipped since the last token.
+    continued: bool,
+    /// Tokens read ahead by `peek`.
+    pending: VecDeque<Result<Token, LexError>>,
 }
 
 impl<'a> Lexer<'a> {
-    fn new(input: &'a str) -> Self {
+    /// A lexer at the start of `input`, past any byte order mark.
+    #[must_use]
+    pub fn new(input: &'a str) -> Self {
         let bytes = input.as_bytes();
         let start = if bytes.starts_with(&[0xEF, 0xBB, 0xBF]) {
             3
@@ -85,101 +188,215 @@ impl<'a> Lexer<'a> {
             pos: start,
             line: 1,
             col: 1,
+            trivia: false,
+            file: None,
+            continued: false,
+            pending: VecDeque::new(),
         }
     }
 
-    fn tokenize(mut self) -> Result<Vec<Token>, LexError> {
-        let mut tokens = Vec::new();
+    /// A lexer over `input` continuing at byte `pos`, which `span`
+    /// locates. `pos` must be the start of a line's first token.
+    pub(crate) fn resume(input: &'a str, pos: usize, span: &Span) -> Self {
+        let mut lexer = Self::new(input);
+        lexer.pos = pos;
+        lexer.line = span.line;
+        lexer.col = span.column;
+        lexer
+    }
 
-        while self.pos < self.input.len() {
-            let ch = self.input[self.pos];
+    /// Record `file` in every span.
+    #[must_use]
+    pub fn file(mut self, file: &str) -> Self {
+        self.file = Some(file.into());
+        self
+    }
 
-            match ch {
-                b'\n' => {
-                    tokens.push(self.make_token(TokenKind::Newline, "\n".to_string()));
-                    self.advance();
-                }
-                b'\r' => {
+    /// Emit whitespace tokens and exact line endings, as
+    /// `tokenize_with_trivia` does.
+    #[must_use]
+    pub const fn trivia(mut self, trivia: bool) -> Self {
+        self.trivia = trivia;
+        self
+    }
+
+    /// The next token, or `None` at the end of the input.
+    pub fn next_token(&mut self) -> Option<Result<Token, LexError>> {
+        if self.pending.is_empty() {
+            self.fill();
+        }
+        self.pending.pop_front()
+    }
+
+    /// The token `next_token` will return, without consuming it.
+    pub fn peek(&mut self) -> Option<&Result<Token, LexError>> {
+        if self.pending.is_empty() {
+            self.fill();
+        }
+        self.pending.front()
+    }
+
+    /// Byte offset of the first unread byte. Tokens held by `peek` have
+    /// already been read.
+    #[must_use]
+    pub const fn offset(&self) -> usize {
+        self.pos
+    }
+
+    /// Line and column of the first unread byte.
+    #[must_use]
+    pub fn position(&self) -> Span {
+        self.span()
+    }
+
+    /// Whether every token has been returned.
+    #[must_use]
+    pub fn is_done(&self) -> bool {
+        self.pending.is_empty() && self.pos >= self.input.len()
+    }
+
+    /// Read until a token or error is pending or the input ends.
+    fn fill(&mut self) {
+        let mut tokens = Vec::new();
+        while tokens.is_empty() && self.pos < self.input.len() {
+            let (pos, line, col) = (self.pos, self.line, self.col);
+            if let Err(e) = self.push_token(&mut tokens) {
+                (self.pos, self.line, self.col) = (pos, line, col);
+                while self.peek_byte().is_some_and(|b| b != b'\n') {
                     self.advance();
-                    if self.peek() == Some(b'\n') {
-                        self.advance();
-                    }
-                    tokens.push(Self::make_token_at(
-                        TokenKind::Newline,
-                        "\n".to_string(),
-                        self.line - 1,
-                        self.col,
-                    ));
                 }
-                b' ' | b'\t' => {
+                self.pending.push_back(Err(e));
+                return;
+            }
+        }
+        self.pending.extend(tokens.into_iter().map(Ok));
+    }
+
+    fn tokenize(mut self) -> Result<Vec<Token>, LexError> {
+        let mut tokens = Vec::new();
+        while self.pos < self.input.len() {
+            self.push_token(&mut tokens)?;
+        }
+        Ok(tokens)
+    }
+
+    /// Read the next token, flagging it when a line continuation came
+    /// before it.
+    fn push_token(&mut self, tokens: &mut Vec<Token>) -> Result<(), LexError> {
+        let first = tokens.len();
+        self.read_token(tokens)?;
+        if self.continued {
+            if let Some(token) = tokens[first..].iter_mut().find(|t| !t.is_trivia()) {
+                token.continued = true;
+                self.continued = false;
+            }
+        }
+        Ok(())
+    }
+
+    /// Read the token (or skip the whitespace) at the current position.
+    fn read_token(&mut self, tokens: &mut Vec<Token>) -> Result<(), LexError> {
+        let ch = self.input[self.pos];
+
+        match ch {
+            b'\n' => {
+                tokens.push(self.make_token(TokenKind::Newline, "\n".to_string()));
+                self.advance();
+            }
+            b'\r' => {
+                let span = self.span();
+                self.advance();
+                let crlf = self.peek_byte() == Some(b'\n');
+                if crlf {
                     self.advance();
                 }
-                b'#' => {
-                    tokens.push(self.read_comment());
-                }
-                b'{' => {
-                    if self.try_read_env_var(&mut tokens) {
-                        // consumed as env var
-                    } else {
-                        tokens.push(self.make_token(TokenKind::OpenBrace, "{".to_string()));
-                        self.advance();
-                    }
-                }
-                b'}' => {
-                    tokens.push(self.make_token(TokenKind::CloseBrace, "}".to_string()));
+                let text = if self.trivia && crlf { "\r\n" } else { "\n" };
+                tokens.push(Token {
+                    kind: TokenKind::Newline,
+                    text: text.to_string(),
+                    span,
+                    continued: false,
+                });
+            }
+            b' ' | b'\t' if self.trivia => {
+                tokens.push(self.read_whitespace());
+            }
+            b'\\' if self.trivia && self.at_continuation() => {
+                tokens.push(self.read_whitespace());
+            }
+            b' ' | b'\t' => {
+                self.advance();
+            }
+            b'#' => {
+                tokens.push(self.read_comment());
+            }
+            b'{' => {
+                if self.try_read_env_var(tokens) {
+                    // consumed as env var
+                } else if self.at_placeholder() {
+                    tokens.push(self.read_word()?);
+                } else {
+                    tokens.push(self.make_token(TokenKind::OpenBrace, "{".to_string()));
                     self.advance();
                 }
-                b'"' => {
-                    tokens.push(self.read_quoted_string()?);
-                }
-                b'`' => {
-                    tokens.push(self.read_backtick_string()?);
-                }
-                b'\\' if self.peek_at(1) == Some(b'\n') => {
-                    // line continuation
-                    self.advance(); // skip backslash
-                    self.advance(); // skip newline
-                }
-                b'\\' if self.peek_at(1) == Some(b'\r') => {
-                    self.advance();
+            }
+            b'}' => {
+                tokens.push(self.make_token(TokenKind::CloseBrace, "}".to_string()));
+                self.advance();
+            }
+            b',' => {
+                tokens.push(self.make_token(TokenKind::Comma, ",".to_string()));
+                self.advance();
+            }
+            b'"' => {
+                tokens.push(self.read_quoted_string()?);
+            }
+            b'`' => {
+                tokens.push(self.read_backtick_string()?);
+            }
+            b'\\' if self.peek_at(1) == Some(b'\n') => {
+                // line continuation
+                self.continued = true;
+                self.advance(); // skip backslash
+                self.advance(); // skip newline
+            }
+            b'\\' if self.peek_at(1) == Some(b'\r') => {
+                self.continued = true;
+                self.advance();
+                self.advance();
+                if self.peek_byte() == Some(b'\n') {
                     self.advance();
-                    if self.peek() == Some(b'\n') {
-                        self.advance();
-                    }
-                }
-                _ => {
-                    tokens.push(self.read_word()?);
                 }
             }
+            _ => {
+                tokens.push(self.read_word()?);
+            }
         }
+        Ok(())
+    }
 
-        Ok(tokens)
+    fn span(&self) -> Span {
+        self.span_at(self.line, self.col)
     }
 
-    const fn span(&self) -> Span {
+    fn span_at(&self, line: usize, column: usize) -> Span {
         Span {
-            line: self.line,
-            column: self.col,
+            line,
+            column,
+            file: self.file.clone(),
         }
     }
 
-    const fn make_token(&self, kind: TokenKind, text: String) -> Token {
+    fn make_token(&self, kind: TokenKind, text: String) -> Token {
         Token {
             kind,
             text,
             span: self.span(),
+            continued: false,
         }
     }
 
-    const fn make_token_at(kind: TokenKind, text: String, line: usize, col: usize) -> Token {
-        Token {
-            kind,
-            text,
-            span: Span { line, column: col },
-        }
-    }
-
-    fn peek(&self) -> Option<u8> {
+    fn peek_byte(&self) -> Option<u8> {
         self.input.get(self.pos).copied()
     }
 
@@ -187,6 +404,37 @@ impl<'a> Lexer<'a> {
         self.input.get(self.pos + offset).copied()
     }
 
+    /// The character starting at the current position.
+    fn peek_char(&self) -> Option<char> {
+        let first = self.peek_byte()?;
+        let len = match first {
+            0xF0.. => 4,
+            0xE0.. => 3,
+            0xC0.. => 2,
+            _ => 1,
+        };
+        let end = (self.pos + len).min(self.input.len());
+        Some(
+            std::str::from_utf8(&self.input[self.pos..end])
+                .ok()
+                .and_then(|s| s.chars().next())
+                .unwrap_or(char::REPLACEMENT_CHARACTER),
+        )
+    }
+
+    /// Move to byte offset `end`, counting the lines in between.
+    fn advance_to(&mut self, end: usize) {
+        let skipped = &self.input[self.pos..end];
+        match skipped.iter().rposition(|&b| b == b'\n') {
+            Some(last) => {
+                self.line += skipped.split(|&b| b == b'\n').count() - 1;
+                self.col = skipped.len() - last;
+            }
+            None => self.col += skipped.len(),
+        }
+        self.pos = end;
+    }
+
     fn advance(&mut self) {
         if self.pos < self.input.len() {
             if self.input[self.pos] == b'\n' {
@@ -199,12 +447,57 @@ impl<'a> Lexer<'a> {
         }
     }
 
+    /// Whether a `{name}` placeholder starts at the current position: a
+    /// `{` closed on the same word, with no whitespace or nested brace.
+    fn at_placeholder(&self) -> bool {
+        let rest = &self.input[self.pos + 1..];
+        let end = rest
+            .iter()
+            .position(|b| matches!(b, b'{' | b'}' | b' ' | b'\t' | b'\n' | b'\r'));
+        matches!(end, Some(len) if len > 0 && rest[len] == b'}')
+    }
+
+    /// Whether a `\` line continuation starts at the current position.
+    fn at_continuation(&self) -> bool {
+        self.peek_byte() == Some(b'\\') && matches!(self.peek_at(1), Some(b'\n' | b'\r'))
+    }
+
+    fn read_whitespace(&mut self) -> Token {
+        let span = self.span();
+        let start = self.pos;
+        loop {
+            match self.peek_byte() {
+                Some(b' ' | b'\t') => self.advance(),
+                Some(b'\\') if self.at_continuation() => {
+                    self.continued = true;
+                    self.advance();
+                    if self.peek_byte() == Some(b'\r') {
+                        self.advance();
+                    }
+                    if self.peek_byte() == Some(b'\n') {
+                        self.advance();
+                    }
+                }
+                _ => break,
+            }
+        }
+        Token {
+            kind: TokenKind::Whitespace,
+            text: String::from_utf8_lossy(&self.input[start..self.pos]).into_owned(),
+            span,
+            continued: false,
+        }
+    }
+
     fn read_comment(&mut self) -> Token {
         let start_line = self.line;
         let start_col = self.col;
         let start = self.pos;
 
-        while self.pos < self.input.len() && self.input[self.pos] != b'\n' {
+        while self.pos < self.input.len()
+            && self.input[self.pos] != b'\n'
+            && !(self.input[self.pos] == b'\r' && self.peek_at(1) == Some(b'\n'))
+        {
             self.pos += 1;
             self.col += 1;
         }
@@ -214,130 +507,67 @@ impl<'a> Lexer<'a> {
         Token {
             kind: TokenKind::Comment,
             text,
-            span: Span {
-                line: start_line,
-                column: start_col,
-            },
+            span: self.span_at(start_line, start_col),
+            continued: false,
         }
     }
 
     fn read_quoted_string(&mut self) -> Result<Token, LexError> {
-        let start_line = self.line;
-        let start_col = self.col;
-        self.advance(); // skip opening quote
-
-        let mut value = String::new();
+        let span = self.span();
+        let start = self.pos + 1;
+        let mut end = start;
+        let mut escaped = false;
         loop {
-            match self.peek() {
+            match self.input.get(end) {
                 None => {
                     return Err(LexError {
                         kind: LexErrorKind::UnterminatedString,
-                        span: Span {
-                            line: start_line,
-                            column: start_col,
-                        },
+                        span,
                     });
                 }
                 Some(b'\\') => {
-                    self.advance();
-                    match self.peek() {
-                        Some(b'n') => {
-                            value.push('\n');
-                            self.advance();
-                        }
-                        Some(b't') => {
-                            value.push('\t');
-                            self.advance();
-                        }
-                        Some(b'r') => {
-                            value.push('\r');
-                            self.advance();
-                        }
-                        Some(b'"') => {
-                            value.push('"');
-                            self.advance();
-                        }
-                        Some(b'\\') => {
-                            value.push('\\');
-                            self.advance();
-                        }
-                        Some(c) => {
-                            value.push('\\');
-                            value.push(char::from(c));
-                            self.advance();
-                        }
-                        None => {
-                            value.push('\\');
-                        }
-                    }
-                }
-                Some(b'"') => {
-                    self.advance();
-                    break;
-                }
-                Some(c) => {
-                    if c == b'\n' {
-                        // track newlines inside strings
-                        self.advance();
-                        value.push('\n');
-                    } else {
-                        value.push(char::from(c));
-                        self.advance();
-                    }
+                    escaped = true;
+                    end += 2;
                 }
+                Some(b'"') => break,
+                Some(_) => end += 1,
             }
         }
 
+        let raw = String::from_utf8_lossy(&self.input[start..end]);
+        let text = if escaped {
+            unescape(&raw)
+        } else {
+            raw.into_owned()
+        };
+        self.advance_to(end + 1);
+
         Ok(Token {
             kind: TokenKind::QuotedString,
-            text: value,
-            span: Span {
-                line: start_line,
-                column: start_col,
-            },
+            text,
+            span,
+            continued: false,
         })
     }
 
     fn read_backtick_string(&mut self) -> Result<Token, LexError> {
-        let start_line = self.line;
-        let start_col = self.col;
-        self.advance(); // skip opening backtick
+        let span = self.span();
+        let start = self.pos + 1;
+        let Some(len) = self.input[start..].iter().position(|&b| b == b'`') else {
+            return Err(LexError {
+                kind: LexErrorKind::UnterminatedBacktick,
+                span,
+            });
+        };
 
-        let mut value = String::new();
-        loop {
-            match self.peek() {
-                None => {
-                    return Err(LexError {
-                        kind: LexErrorKind::UnterminatedBacktick,
-                        span: Span {
-                            line: start_line,
-                            column: start_col,
-                        },
-                    });
-                }
-                Some(b'`') => {
-                    self.advance();
-                    break;
-                }
-                Some(c) => {
-                    if c == b'\n' {
-                        self.advance();
-                        value.push('\n');
-                    } else {
-                        value.push(char::from(c));
-                        self.advance();
-                    }
-                }
-            }
-        }
+        let text = String::from_utf8_lossy(&self.input[start..start + len]).into_owned();
+        self.advance_to(start + len + 1);
 
         Ok(Token {
             kind: TokenKind::BacktickString,
-            text: value,
-            span: Span {
-                line: start_line,
-                column: start_col,
-            },
+            text,
+            span,
+            continued: false,
         })
     }
 
@@ -367,7 +597,7 @@ impl<'a> Lexer<'a> {
         }
         let name = String::from_utf8_lossy(&self.input[name_start..self.pos]).into_owned();
 
-        let default = if self.peek() == Some(b':') {
+        let default = if self.peek_byte() == Some(b':') {
             self.pos += 1;
             self.col += 1;
             let def_start = self.pos;
@@ -383,7 +613,7 @@ impl<'a> Lexer<'a> {
             None
         };
 
-        if self.peek() != Some(b'}') {
+        if self.peek_byte() != Some(b'}') {
             // Not a valid env var, restore position
             self.pos = save_pos;
             self.line = save_line;
@@ -399,10 +629,8 @@ impl<'a> Lexer<'a> {
         tokens.push(Token {
             kind: TokenKind::EnvVar { name, default },
             text,
-            span: Span {
-                line: start_line,
-                column: start_col,
-            },
+            span: self.span_at(start_line, start_col),
+            continued: false,
         });
 
         true
@@ -418,60 +646,48 @@ impl<'a> Lexer<'a> {
             return self.read_heredoc(start_line, start_col);
         }
 
-        while self.pos < self.input.len() {
-            let ch = self.input[self.pos];
+        // Columns count bytes, so the word is scanned byte by byte and
+        // the position moved once at the end.
+        let mut end = start;
+        while let Some(&ch) = self.input.get(end) {
             match ch {
                 b' ' | b'\t' | b'\n' | b'\r' => break,
-                b'{' | b'}' => {
-                    // check for {$ env var or placeholder
-                    if ch == b'{' && self.peek_at(1) == Some(b'$') {
-                        break;
-                    }
-                    // standalone brace at start means it's
-                    // a brace token, not part of a word
-                    if self.pos == start {
-                        break;
-                    }
-                    // otherwise it could be a placeholder like
-                    // {path} inside a word - consume it
-                    self.pos += 1;
-                    self.col += 1;
-                }
-                b'\\' => {
-                    // escaped character
-                    self.pos += 1;
-                    self.col += 1;
-                    if self.pos < self.input.len() {
-                        self.pos += 1;
-                        self.col += 1;
-                    }
-                }
-                _ => {
-                    self.pos += 1;
-                    self.col += 1;
+                // a trailing comma is its own token
+                b',' if matches!(
+                    self.input.get(end + 1),
+                    None | Some(b' ' | b'\t' | b'\n' | b'\r')
+                ) =>
+                {
+                    break;
                 }
+                // an env var starts a new token
+                b'{' if self.input.get(end + 1) == Some(&b'$') => break,
+                // a standalone brace at the start is a brace token, a
+                // placeholder like {path} is part of the word
+                b'{' | b'}' if end == start && !self.at_placeholder() => break,
+                // escaped character
+                b'\\' => end = (end + 2).min(self.input.len()),
+                _ => end += 1,
             }
         }
+        self.advance_to(end);
 
         let text = String::from_utf8_lossy(&self.input[start..self.pos]).into_owned();
 
         if text.is_empty() {
             return Err(LexError {
-                kind: LexErrorKind::UnexpectedCharacter(char::from(self.input[start])),
-                span: Span {
-                    line: start_line,
-                    column: start_col,
-                },
+                kind: LexErrorKind::UnexpectedCharacter(
+                    self.peek_char().unwrap_or(char::REPLACEMENT_CHARACTER),
+                ),
+                span: self.span_at(start_line, start_col),
             });
         }
 
         Ok(Token {
             kind: TokenKind::Word,
             text,
-            span: Span {
-                line: start_line,
-                column: start_col,
-            },
+            span: self.span_at(start_line, start_col),
+            continued: false,
         })
     }
 
@@ -496,74 +712,115 @@ impl<'a> Lexer<'a> {
         if marker.is_empty() {
             return Err(LexError {
                 kind: LexErrorKind::EmptyHeredocMarker,
-                span: Span {
-                    line: start_line,
-                    column: start_col,
-                },
+                span: self.span_at(start_line, start_col),
             });
         }
 
         // Skip to next line
-        if self.peek() == Some(b'\r') {
+        if self.peek_byte() == Some(b'\r') {
             self.advance();
         }
-        if self.peek() == Some(b'\n') {
+        if self.peek_byte() == Some(b'\n') {
             self.advance();
         }
 
-        // Read content until marker on its own line
+        // Read content until a line starting with the marker; tokens may
+        // follow it on the same line (`HTML 200`).
         let content_start = self.pos;
 
         while self.pos < self.input.len() {
             let line_start = self.pos;
-            // read one line
-            while self.pos < self.input.len() && self.input[self.pos] != b'\n' {
-                self.pos += 1;
-                self.col += 1;
-            }
-
-            let line = String::from_utf8_lossy(&self.input[line_start..self.pos]);
-            let trimmed = line.trim();
-
-            if trimmed == marker {
+            let line_end = self.input[line_start..]
+                .iter()
+                .position(|&b| b == b'\n')
+                .map_or(self.input.len(), |len| line_start + len);
+            let line = &self.input[line_start..line_end];
+            let indent = line
+                .iter()
+                .take_while(|b| matches!(b, b' ' | b'\t'))
+                .count();
+            let after = indent + marker.len();
+
+            if line[indent..].starts_with(marker.as_bytes())
+                && matches!(line.get(after), None | Some(b' ' | b'\t' | b'\r'))
+            {
                 let content =
                     String::from_utf8_lossy(&self.input[content_start..line_start]).into_owned();
-                // Remove trailing newline from content
-                let content = content
-                    .strip_suffix('\n')
-                    .or_else(|| content.strip_suffix("\r\n"))
-                    .unwrap_or(&content)
-                    .to_string();
-
-                if self.peek() == Some(b'\n') {
-                    self.advance();
-                }
+                // Body lines end in `\n` whatever the file's line endings,
+                // so CRLF files format the same on every pass.
+                let content = content.replace("\r\n", "\n");
+                let content = content.strip_suffix('\n').unwrap_or(&content);
+                let content = strip_padding(content, &line[..indent]);
+
+                self.advance_to(line_start + after);
 
                 return Ok(Token {
                     kind: TokenKind::Heredoc { marker },
                     text: content,
-                    span: Span {
-                        line: start_line,
-                        column: start_col,
-                    },
+                    span: self.span_at(start_line, start_col),
+                    continued: false,
                 });
             }
 
-            if self.peek() == Some(b'\n') {
-                self.advance();
-            }
+            self.advance_to((line_end + 1).min(self.input.len()));
         }
 
         Err(LexError {
             kind: LexErrorKind::UnterminatedHeredoc { marker },
-            span: Span {
-                line: start_line,
-                column: start_col,
-            },
+            span: self.span_at(start_line, start_col),
         })
     }
 }
 
+impl Iterator for Lexer<'_> {
+    type Item = Result<Token, LexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_token()
+    }
+}
+
+/// Remove the closing marker's indentation `padding` from each line of
+/// a heredoc body, as Caddy does. Lines without it are kept as they are.
+fn strip_padding(content: &str, padding: &[u8]) -> String {
+    let padding = std::str::from_utf8(padding).unwrap_or_default();
+    if padding.is_empty() {
+        return content.to_string();
+    }
+    content
+        .split('\n')
+        .map(|line| line.strip_prefix(padding).unwrap_or(line))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Decode the escapes of a quoted string body. Unknown escapes keep
+/// their backslash.
+fn unescape(raw: &str) -> String {
+    let mut out = String::with_capacity(raw.len());
+    let mut chars = raw.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        let decoded = match chars.peek() {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('r') => '\r',
+            Some('"') => '"',
+            Some('\\') => '\\',
+            _ => {
+                out.push('\\');
+                continue;
+            }
+        };
+        out.push(decoded);
+        chars.next();
+    }
+    out
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -610,6 +867,24 @@ mod tests {
         assert_eq!(tokens[0].text, "raw string");
     }
 
+    #[test]
+    fn commas() {
+        let tokens = tokenize("a.com, b.com ,c GET,POST").expect("should tokenize");
+        let kinds: Vec<_> = tokens.iter().map(|t| t.kind.clone()).collect();
+        assert_eq!(
+            kinds,
+            [
+                TokenKind::Word,
+                TokenKind::Comma,
+                TokenKind::Word,
+                TokenKind::Comma,
+                TokenKind::Word,
+                TokenKind::Word,
+            ]
+        );
+        assert_eq!(tokens[5].text, "GET,POST");
+    }
+
     #[test]
     fn comment() {
         let tokens = tokenize("log # access log\nfile_server").expect("should tokenize");
@@ -653,6 +928,21 @@ mod tests {
         assert_eq!(tokens[1].text, "Hello World");
     }
 
+    #[test]
+    fn heredoc_trailing_arguments() {
+        let tokens =
+            tokenize("respond <<HTML\n<p>hi</p>\n  HTML 200\nlog").expect("should tokenize");
+        assert_eq!(tokens[1].text, "<p>hi</p>");
+        assert_eq!(tokens[2].text, "200");
+        assert_eq!(tokens[2].span, Span::new(3, 8));
+        assert_eq!(tokens[3].kind, TokenKind::Newline);
+        assert_eq!(tokens[4].text, "log");
+
+        // a line merely starting with the marker is content
+        let tokens = tokenize("respond <<EOF\nEOFX\nEOF").expect("should tokenize");
+        assert_eq!(tokens[1].text, "EOFX");
+    }
+
     #[test]
     fn unterminated_quote() {
         let result = tokenize("\"unclosed");
@@ -667,6 +957,13 @@ mod tests {
         assert_eq!(tokens.len(), 2);
         assert_eq!(tokens[0].text, "reverse_proxy");
         assert_eq!(tokens[1].text, "app:3000");
+        assert!(tokens[1].continued);
+        assert!(!tokens[0].continued);
+
+        let tokens = tokenize_with_trivia("a \\\n\tb").expect("should tokenize");
+        assert_eq!(tokens[1].kind, TokenKind::Whitespace);
+        assert!(!tokens[1].continued);
+        assert!(tokens[2].continued);
     }
 
     #[test]
@@ -694,4 +991,123 @@ mod tests {
         assert_eq!(tokens[3].span.line, 2);
         assert_eq!(tokens[3].span.column, 3);
     }
+
+    #[test]
+    fn spans_after_multiline_strings() {
+        let tokens = tokenize("\"a\nb\\q\" `x\ny` c\\ d").expect("should tokenize");
+        assert_eq!(tokens[0].text, "a\nb\\q");
+        assert_eq!(tokens[1].span, Span::new(2, 6));
+        assert_eq!(tokens[2].text, "c\\ d");
+        assert_eq!(tokens[2].span, Span::new(3, 4));
+    }
+
+    #[test]
+    fn bare_placeholders_are_words() {
+        let tokens =
+            tokenize("header_up Host {upstream_hostport}\nroot {vars.root}/www {\n}").unwrap();
+        let kinds: Vec<_> = tokens.iter().map(|t| (&t.kind, t.text.as_str())).collect();
+        assert_eq!(
+            kinds,
+            [
+                (&TokenKind::Word, "header_up"),
+                (&TokenKind::Word, "Host"),
+                (&TokenKind::Word, "{upstream_hostport}"),
+                (&TokenKind::Newline, "\n"),
+                (&TokenKind::Word, "root"),
+                (&TokenKind::Word, "{vars.root}/www"),
+                (&TokenKind::OpenBrace, "{"),
+                (&TokenKind::Newline, "\n"),
+                (&TokenKind::CloseBrace, "}"),
+            ]
+        );
+        let tokens = tokenize("a {}").unwrap();
+        assert_eq!(tokens[1].kind, TokenKind::OpenBrace);
+    }
+
+    #[test]
+    fn named_spans() {
+        let tokens = tokenize_named("a {\n}", "Caddyfile").unwrap();
+        assert!(
+            tokens
+                .iter()
+                .all(|t| t.span.file.as_deref() == Some("Caddyfile"))
+        );
+        let err = tokenize_named("\n\"open", "sites/a.caddy").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unterminated quoted string at line 2, column 1 in sites/a.caddy"
+        );
+    }
+
+    #[test]
+    fn utf8_content() {
+        let tokens = tokenize("café.example.com {\n\trespond \"héllo 🌍\" `naïve`\n}").unwrap();
+        assert_eq!(tokens[0].text, "café.example.com");
+        assert_eq!(tokens[4].text, "héllo 🌍");
+        assert_eq!(tokens[5].text, "naïve");
+        assert_eq!(tokens[5].span.column, 24);
+        let tokens = tokenize(r#""\é""#).unwrap();
+        assert_eq!(tokens[0].text, r"\é");
+    }
+
+    #[test]
+    fn trivia_tokens() {
+        let input = "a  b\t\\\n c # note\r\n";
+        let tokens = tokenize_with_trivia(input).unwrap();
+        let texts: Vec<_> = tokens.iter().map(|t| t.text.as_str()).collect();
+        assert_eq!(
+            texts,
+            ["a", "  ", "b", "\t\\\n ", "c", " ", "# note", "\r\n"]
+        );
+        assert_eq!(tokens[3].span, Span::new(1, 5));
+        assert_eq!(tokens[4].span, Span::new(2, 2));
+
+        let mut plain = tokenize(input).unwrap();
+        plain[4].text = "\r\n".to_string();
+        let kept: Vec<_> = tokens.into_iter().filter(|t| !t.is_trivia()).collect();
+        assert_eq!(kept, plain);
+    }
+
+    #[test]
+    fn invalid_utf8_bytes() {
+        let err = tokenize_bytes(b"a.com {\n\trespond \"\xff\"\n}").unwrap_err();
+        assert_eq!(err.kind, LexErrorKind::InvalidUtf8 { offset: 18 });
+        assert_eq!(err.span, Span::new(2, 11));
+
+        let tokens = tokenize_bytes("respond \"\u{e9}\"".as_bytes()).expect("should tokenize");
+        assert_eq!(tokens[1].text, "\u{e9}");
+    }
+
+    #[test]
+    fn pull_lexer() {
+        let mut lexer = Lexer::new("a \"b\nc d").file("x.caddy").trivia(true);
+        assert_eq!(lexer.next_token().unwrap().unwrap().text, "a");
+        assert_eq!(
+            lexer.peek().unwrap().as_ref().unwrap().kind,
+            TokenKind::Whitespace
+        );
+        assert_eq!(lexer.offset(), 2);
+        lexer.next_token();
+        let err = lexer.next_token().unwrap().unwrap_err();
+        assert_eq!(err.kind, LexErrorKind::UnterminatedString);
+        assert_eq!(err.span.file.as_deref(), Some("x.caddy"));
+        assert_eq!((lexer.position().line, lexer.position().column), (1, 5));
+        let rest: Vec<_> = lexer.map(|t| t.unwrap().text).collect();
+        assert_eq!(rest, ["\n", "c", " ", "d"]);
+    }
+
+    #[test]
+    fn recovery_resumes_on_next_line() {
+        let (tokens, errors) = tokenize_with_recovery("a \"b\nc <<\nd\n");
+        let texts: Vec<_> = tokens.iter().map(|t| t.text.as_str()).collect();
+        assert_eq!(texts, ["a", "\n", "c", "\n", "d", "\n"]);
+        let kinds: Vec<_> = errors.into_iter().map(|e| e.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                LexErrorKind::UnterminatedString,
+                LexErrorKind::EmptyHeredocMarker
+            ]
+        );
+    }
 }
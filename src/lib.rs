@@ -41,21 +41,66 @@
     clippy::module_name_repetitions
 )]
 
+pub mod address;
 pub mod ast;
+pub mod budget;
 pub mod builder;
+pub mod caddy_fmt;
+pub mod canonical;
+pub mod directives;
+pub mod env;
+pub mod expression;
 pub mod formatter;
+pub mod global;
+pub mod highlight;
+pub mod incremental;
 pub mod lexer;
+pub mod markdown;
+pub mod merge;
+pub mod overlay;
 pub mod parser;
+pub mod placeholder;
+pub mod presets;
+pub mod roundtrip;
+pub mod routing;
+pub mod selector;
+#[cfg(feature = "testing")]
+pub mod testing;
 pub mod token;
+pub mod validate;
+pub mod vars;
 
+pub use address::{AddressError, UpstreamAddress, parse_upstream_address};
 pub use ast::{
-    Address, Argument, Caddyfile, Directive, GlobalOptions, Matcher, NamedRoute, Scheme, SiteBlock,
-    Snippet, parse_address,
+    Address, Argument, Caddyfile, Comments, Directive, Expression, GlobalOptions, Matcher,
+    NamedRoute, Scheme, SiteBlock, Snippet, parse_address,
 };
-pub use formatter::format;
-pub use lexer::{LexError, LexErrorKind, tokenize};
-pub use parser::{ParseError, ParseErrorKind, parse};
+#[cfg(feature = "hash")]
+pub use builder::PasswordHashError;
+pub use builder::{
+    BasicAuthBuilder, Block, Body, ByteSize, CorsConfig, EncodeBuilder, Encoding,
+    ErrorPagesBuilder, FileServerBuilder, GlobalLog, HeaderBuilder, HttpTransport, LogFile,
+    LogLevel, MatcherDef, MatcherError, RateLimitBuilder, RateLimitZone, RedirCode, Respond,
+    ReverseProxyBuilder, ServerTimeouts, Servers,
+};
+pub use caddy_fmt::caddy_fmt;
+pub use formatter::{
+    BraceStyle, DirectiveSort, EmptyBlocks, FormatError, FormatOptions, Indent, LineEnding,
+    Quoting, SourceStyle, Spacing, TextEdit, compute_edits, compute_edits_with_options,
+    detect_style, format, format_into, format_into_with_options, format_to, format_to_with_options,
+    format_with_options, try_format_with_options,
+};
+pub use incremental::TokenBuffer;
+pub use lexer::{
+    LexError, LexErrorKind, Lexer, tokenize, tokenize_bytes, tokenize_named,
+    tokenize_with_recovery, tokenize_with_trivia,
+};
+pub use parser::{
+    ParseError, ParseErrorKind, ParseOptions, parse, parse_with_options, parse_with_recovery,
+};
+pub use roundtrip::{RoundtripReport, verify_roundtrip};
 pub use token::{Span, Token, TokenKind};
+pub use validate::{ValidationError, ValidationErrorKind};
 
 /// Unified error type covering both lexing and parsing.
 #[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
@@ -68,8 +113,68 @@ pub enum Error {
     Parse(#[from] ParseError),
 }
 
+impl Error {
+    /// Source location of the error.
+    #[must_use]
+    pub const fn span(&self) -> &Span {
+        match self {
+            Self::Lex(e) => &e.span,
+            Self::Parse(e) => &e.span,
+        }
+    }
+
+    /// Describe the error with both its raw column and the column an
+    /// editor shows with the given tab width, e.g.
+    /// `expected '}' at line 3, column 2 (visual column 5)`.
+    #[must_use]
+    pub fn describe(&self, source: &str, tab_width: usize) -> String {
+        let span = self.span();
+        let kind = match self {
+            Self::Lex(e) => e.kind.to_string(),
+            Self::Parse(e) => e.kind.to_string(),
+        };
+        let visual = span.visual_column(source, tab_width);
+        if visual == span.column {
+            self.to_string()
+        } else {
+            format!("{kind} at {span} (visual column {visual})")
+        }
+    }
+}
+
 /// Tokenize and parse a Caddyfile source string in one step.
 pub fn parse_str(input: &str) -> Result<Caddyfile, Error> {
     let tokens = tokenize(input)?;
     Ok(parse(&tokens)?)
 }
+
+/// Tokenize and parse, recording `file` in token and error spans.
+pub fn parse_str_named(input: &str, file: &str) -> Result<Caddyfile, Error> {
+    let tokens = tokenize_named(input, file)?;
+    Ok(parse(&tokens)?)
+}
+
+/// Tokenize and parse, collecting every lexer and parser error instead
+/// of stopping at the first.
+///
+/// The `Caddyfile` is returned only when there are no errors; use
+/// `parse_with_recovery` to get at a partial AST.
+#[must_use]
+pub fn parse_all_errors(input: &str) -> (Option<Caddyfile>, Vec<Error>) {
+    let (tokens, lex_errors) = tokenize_with_recovery(input);
+    let (caddyfile, parse_errors) = parse_with_recovery(&tokens, &ParseOptions::default());
+    let mut errors: Vec<Error> = lex_errors.into_iter().map(Error::from).collect();
+    errors.extend(parse_errors.into_iter().map(Error::from));
+    errors.sort_by_key(|e| {
+        let span = e.span();
+        (span.line, span.column)
+    });
+    let caddyfile = errors.is_empty().then_some(caddyfile);
+    (caddyfile, errors)
+}
+
+/// Tokenize and parse a Caddyfile source string with explicit options.
+pub fn parse_str_with_options(input: &str, options: &ParseOptions) -> Result<Caddyfile, Error> {
+    let tokens = tokenize(input)?;
+    Ok(parse_with_options(&tokens, options)?)
+}
@@ -0,0 +1,201 @@
+//! Caddyfile code blocks embedded in Markdown.
+//!
+//! Fenced blocks whose info string is `caddy` or `caddyfile` (with
+//! backticks or tildes) are validated and reformatted in place; the rest
+//! of the document is left untouched byte for byte.
+
+use std::ops::Range;
+
+use crate::{Error, format, parse_str};
+
+/// Info-string languages treated as Caddyfile source.
+const LANGUAGES: &[&str] = &["caddy", "caddyfile", "Caddyfile"];
+
+/// A Caddyfile code block found in a Markdown document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeBlock {
+    /// 1-based line of the opening fence.
+    pub line: usize,
+    /// Block contents with the fence indentation removed.
+    pub content: String,
+    /// Byte range of the contents within the document.
+    pub range: Range<usize>,
+    indent: usize,
+}
+
+/// Error in an embedded code block.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("code block at line {line}: {source}")]
+pub struct MarkdownError {
+    /// 1-based line of the opening fence.
+    pub line: usize,
+    pub source: Error,
+}
+
+/// An open fence while scanning.
+struct Fence {
+    marker: String,
+    line: usize,
+    indent: usize,
+    start: usize,
+    caddy: bool,
+}
+
+/// Find every Caddyfile code block in a Markdown document.
+///
+/// An unterminated fence runs to the end of the document.
+#[must_use]
+pub fn code_blocks(markdown: &str) -> Vec<CodeBlock> {
+    let mut blocks = Vec::new();
+    let mut open: Option<Fence> = None;
+    let mut offset = 0;
+
+    for (i, line) in markdown.split_inclusive('\n').enumerate() {
+        let start = offset;
+        offset += line.len();
+        let trimmed = line.trim_start_matches(' ');
+        let indent = line.len() - trimmed.len();
+        if indent >= 4 {
+            continue;
+        }
+        let marker: String = trimmed
+            .chars()
+            .take_while(|&c| c == '`' || c == '~')
+            .collect();
+        let rest = trimmed[marker.len()..].trim();
+
+        match &open {
+            None if is_fence(&marker) => {
+                let lang = rest.split_whitespace().next().unwrap_or_default();
+                open = Some(Fence {
+                    marker,
+                    line: i + 1,
+                    indent,
+                    start: offset,
+                    caddy: LANGUAGES.contains(&lang),
+                });
+            }
+            Some(fence) if marker.starts_with(fence.marker.as_str()) && rest.is_empty() => {
+                blocks.extend(close(open.take(), markdown, start));
+            }
+            _ => {}
+        }
+    }
+    blocks.extend(close(open, markdown, markdown.len()));
+    blocks
+}
+
+fn is_fence(marker: &str) -> bool {
+    marker.len() >= 3 && (marker.bytes().all(|b| b == b'`') || marker.bytes().all(|b| b == b'~'))
+}
+
+fn close(fence: Option<Fence>, markdown: &str, end: usize) -> Option<CodeBlock> {
+    let fence = fence.filter(|f| f.caddy)?;
+    let range = fence.start..end.max(fence.start);
+    let content = markdown[range.clone()]
+        .split_inclusive('\n')
+        .map(|l| {
+            let strip = l.len() - l.trim_start_matches(' ').len();
+            &l[strip.min(fence.indent)..]
+        })
+        .collect();
+    Some(CodeBlock {
+        line: fence.line,
+        content,
+        range,
+        indent: fence.indent,
+    })
+}
+
+/// Parse every Caddyfile code block, collecting the errors.
+#[must_use]
+pub fn validate_markdown(markdown: &str) -> Vec<MarkdownError> {
+    code_blocks(markdown)
+        .into_iter()
+        .filter_map(|b| {
+            parse_str(&b.content).err().map(|source| MarkdownError {
+                line: b.line,
+                source,
+            })
+        })
+        .collect()
+}
+
+/// Reformat every Caddyfile code block, leaving the rest untouched.
+///
+/// Fails on the first block that does not parse.
+pub fn format_markdown(markdown: &str) -> Result<String, MarkdownError> {
+    let mut out = String::with_capacity(markdown.len());
+    let mut last = 0;
+    for block in code_blocks(markdown) {
+        let cf = parse_str(&block.content).map_err(|source| MarkdownError {
+            line: block.line,
+            source,
+        })?;
+        out.push_str(&markdown[last..block.range.start]);
+        let pad = " ".repeat(block.indent);
+        for line in format(&cf).split_inclusive('\n') {
+            if line != "\n" {
+                out.push_str(&pad);
+            }
+            out.push_str(line);
+        }
+        last = block.range.end;
+    }
+    out.push_str(&markdown[last..]);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOC: &str = "# Setup\n\
+        \n\
+        ```caddy\n\
+        example.com {\n\
+        reverse_proxy app:3000\n\
+        }\n\
+        ```\n\
+        \n\
+        ```sh\n\
+        caddy run\n\
+        ```\n\
+        \n\
+        - item\n\
+        \x20 ~~~caddyfile\n\
+        \x20 :80 {\n\
+        \x20 respond 1\n\
+        \x20 }\n\
+        \x20 ~~~\n";
+
+    #[test]
+    fn finds_caddy_blocks_only() {
+        let blocks = code_blocks(DOC);
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].line, 3);
+        assert_eq!(
+            blocks[0].content,
+            "example.com {\nreverse_proxy app:3000\n}\n"
+        );
+        assert_eq!(blocks[1].line, 14);
+        assert_eq!(blocks[1].content, ":80 {\nrespond 1\n}\n");
+    }
+
+    #[test]
+    fn formats_blocks_in_place() {
+        let out = format_markdown(DOC).unwrap();
+        assert!(out.contains("```caddy\nexample.com {\n\treverse_proxy app:3000\n}\n```\n"));
+        assert!(out.contains("```sh\ncaddy run\n```\n"));
+        assert!(out.contains("  ~~~caddyfile\n  :80 {\n  \trespond 1\n  }\n  ~~~\n"));
+        assert_eq!(format_markdown(&out).unwrap(), out);
+    }
+
+    #[test]
+    fn reports_block_line() {
+        let errors = validate_markdown("text\n\n```caddy\na.com {\n```\n");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line, 3);
+        assert!(errors[0].to_string().starts_with("code block at line 3: "));
+    }
+}
@@ -0,0 +1,638 @@
+//! Structural three-way merge of Caddyfiles.
+//!
+//! Blocks are matched by identity rather than by line: snippets and
+//! named routes by name, sites by their addresses, and directives by
+//! name and matcher (plus the imported name for `import`), numbered by
+//! occurrence. A side that left a node unchanged from the base yields
+//! to the other side; nodes both sides changed are merged recursively
+//! through their sub-blocks. When that is not possible the whole
+//! directive or block is wrapped in git-style conflict markers.
+//!
+//! `Caddyfile::merge` and `SiteBlock::merge` combine configs without a
+//! base, for files composed from several modules in code; a
+//! `MergePolicy` decides what happens to nodes both sides define.
+
+use crate::ast::{Caddyfile, Comments, Directive, GlobalOptions, NamedRoute, SiteBlock, Snippet};
+use crate::formatter::format_node;
+use crate::selector::Node;
+
+const OURS: &str = "<<<<<<< ours";
+const SEPARATOR: &str = "=======";
+const THEIRS: &str = ">>>>>>> theirs";
+
+/// Result of a three-way merge.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergeOutcome {
+    /// Merged Caddyfile text, including conflict markers if any.
+    pub text: String,
+    /// Number of conflicting nodes.
+    pub conflicts: usize,
+}
+
+impl MergeOutcome {
+    /// Whether the merge produced no conflicts.
+    #[must_use]
+    pub const fn is_clean(&self) -> bool {
+        self.conflicts == 0
+    }
+}
+
+/// Merge `ours` and `theirs`, both derived from `base`.
+#[must_use]
+pub fn merge3(base: &Caddyfile, ours: &Caddyfile, theirs: &Caddyfile) -> MergeOutcome {
+    let mut conflicts = 0;
+    let mut chunks = Vec::new();
+
+    let global = merge_list(
+        base.global_options.as_slice(),
+        ours.global_options.as_slice(),
+        theirs.global_options.as_slice(),
+        &mut conflicts,
+    );
+    push_chunks(&mut chunks, global, |g| Node::Global(g));
+    let snippets = merge_list(
+        &base.snippets,
+        &ours.snippets,
+        &theirs.snippets,
+        &mut conflicts,
+    );
+    push_chunks(&mut chunks, snippets, |s| Node::Snippet(s));
+    let routes = merge_list(
+        &base.named_routes,
+        &ours.named_routes,
+        &theirs.named_routes,
+        &mut conflicts,
+    );
+    push_chunks(&mut chunks, routes, |r| Node::NamedRoute(r));
+    let sites = merge_list(&base.sites, &ours.sites, &theirs.sites, &mut conflicts);
+    push_chunks(&mut chunks, sites, |s| Node::Site(s));
+
+    let mut text = clean_markers(&chunks.join("\n"));
+    if text.is_empty() {
+        text.push('\n');
+    }
+    MergeOutcome { text, conflicts }
+}
+
+/// Outcome for one node.
+enum Merged<T> {
+    Clean(T),
+    Conflict(Option<T>, Option<T>),
+}
+
+/// A node that can take part in a keyed three-way merge.
+trait Mergeable: Clone + PartialEq {
+    /// Identity of the node among its siblings, before numbering.
+    fn key(&self) -> String;
+
+    /// Merge a node both sides changed.
+    fn merge_changed(
+        base: &Self,
+        ours: &Self,
+        theirs: &Self,
+        conflicts: &mut usize,
+    ) -> Merged<Self>;
+}
+
+macro_rules! impl_block {
+    ($ty:ty, $key:expr) => {
+        impl Mergeable for $ty {
+            fn key(&self) -> String {
+                $key(self)
+            }
+
+            fn merge_changed(
+                base: &Self,
+                ours: &Self,
+                theirs: &Self,
+                conflicts: &mut usize,
+            ) -> Merged<Self> {
+                let mut merged = ours.clone();
+                merged.directives = merge_directives(
+                    &base.directives,
+                    &ours.directives,
+                    &theirs.directives,
+                    conflicts,
+                );
+                Merged::Clean(merged)
+            }
+        }
+    };
+}
+
+impl_block!(GlobalOptions, |_: &GlobalOptions| String::new());
+impl_block!(Snippet, |s: &Snippet| s.name.clone());
+impl_block!(NamedRoute, |r: &NamedRoute| r.name.clone());
+impl_block!(SiteBlock, |s: &SiteBlock| {
+    s.addresses
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+});
+
+impl Mergeable for Directive {
+    fn key(&self) -> String {
+        let mut key = self.name.clone();
+        if let Some(m) = &self.matcher {
+            key.push(' ');
+            key.push_str(&m.to_string());
+        }
+        if self.name == "import"
+            && let Some(first) = self.arguments.first()
+        {
+            key.push(' ');
+            key.push_str(&first.value());
+        }
+        key
+    }
+
+    fn merge_changed(
+        base: &Self,
+        ours: &Self,
+        theirs: &Self,
+        conflicts: &mut usize,
+    ) -> Merged<Self> {
+        let head = |d: &Self| (d.name.clone(), d.matcher.clone(), d.arguments.clone());
+        let Some((name, matcher, arguments)) = pick(&head(base), &head(ours), &head(theirs)) else {
+            return Merged::Conflict(Some(ours.clone()), Some(theirs.clone()));
+        };
+        let block = match (&base.block, &ours.block, &theirs.block) {
+            (Some(b), Some(o), Some(t)) => Some(merge_directives(b, o, t, conflicts)),
+            (b, o, t) => match pick(b, o, t) {
+                Some(block) => block,
+                None => return Merged::Conflict(Some(ours.clone()), Some(theirs.clone())),
+            },
+        };
+        // Comment edits on both sides are not worth a conflict.
+        let comments = pick(&base.comments, &ours.comments, &theirs.comments)
+            .unwrap_or_else(|| ours.comments.clone());
+        Merged::Clean(Self {
+            name,
+            matcher,
+            arguments,
+            block,
+            comments,
+            continuations: Vec::new(),
+            blank_lines_before: 0,
+        })
+    }
+}
+
+/// Plain three-way choice: the side that differs from the base wins.
+fn pick<T: Clone + PartialEq>(base: &T, ours: &T, theirs: &T) -> Option<T> {
+    if ours == theirs || theirs == base {
+        Some(ours.clone())
+    } else if ours == base {
+        Some(theirs.clone())
+    } else {
+        None
+    }
+}
+
+/// Keys numbered by occurrence so repeated directives stay distinct.
+fn keys<T: Mergeable>(items: &[T]) -> Vec<(String, &T)> {
+    let mut seen: Vec<String> = Vec::new();
+    items
+        .iter()
+        .map(|item| {
+            let key = item.key();
+            let n = seen.iter().filter(|k| **k == key).count();
+            seen.push(key.clone());
+            (format!("{key}#{n}"), item)
+        })
+        .collect()
+}
+
+fn find<'a, T>(list: &[(String, &'a T)], key: &str) -> Option<&'a T> {
+    list.iter().find(|(k, _)| k == key).map(|(_, v)| *v)
+}
+
+fn merge_list<T: Mergeable>(
+    base: &[T],
+    ours: &[T],
+    theirs: &[T],
+    conflicts: &mut usize,
+) -> Vec<Merged<T>> {
+    let base = keys(base);
+    let ours = keys(ours);
+    let theirs = keys(theirs);
+
+    // Our order, with nodes only present in theirs placed after
+    // their predecessor there.
+    let mut order: Vec<&str> = ours.iter().map(|(k, _)| k.as_str()).collect();
+    let mut at = 0;
+    for (key, _) in &theirs {
+        if let Some(pos) = order.iter().position(|k| k == key) {
+            at = pos + 1;
+        } else {
+            order.insert(at, key);
+            at += 1;
+        }
+    }
+
+    let mut out = Vec::new();
+    for key in order {
+        let (b, o, t) = (find(&base, key), find(&ours, key), find(&theirs, key));
+        let merged = if o == t || t == b {
+            o.cloned().map(Merged::Clean)
+        } else if o == b {
+            t.cloned().map(Merged::Clean)
+        } else if let (Some(b), Some(o), Some(t)) = (b, o, t) {
+            Some(T::merge_changed(b, o, t, conflicts))
+        } else {
+            Some(Merged::Conflict(o.cloned(), t.cloned()))
+        };
+        if let Some(merged) = merged {
+            if matches!(merged, Merged::Conflict(..)) {
+                *conflicts += 1;
+            }
+            out.push(merged);
+        }
+    }
+    out
+}
+
+/// Merge directive lists, inlining conflicts as marker directives.
+fn merge_directives(
+    base: &[Directive],
+    ours: &[Directive],
+    theirs: &[Directive],
+    conflicts: &mut usize,
+) -> Vec<Directive> {
+    let marker = |name: &str| Directive {
+        name: name.to_string(),
+        matcher: None,
+        arguments: Vec::new(),
+        block: None,
+        comments: Comments::default(),
+        continuations: Vec::new(),
+        blank_lines_before: 0,
+    };
+    let mut out = Vec::new();
+    for merged in merge_list(base, ours, theirs, conflicts) {
+        match merged {
+            Merged::Clean(d) => out.push(d),
+            Merged::Conflict(o, t) => {
+                out.push(marker(OURS));
+                out.extend(o);
+                out.push(marker(SEPARATOR));
+                out.extend(t);
+                out.push(marker(THEIRS));
+            }
+        }
+    }
+    out
+}
+
+fn push_chunks<T>(chunks: &mut Vec<String>, merged: Vec<Merged<T>>, node: impl Fn(&T) -> Node<'_>) {
+    for m in merged {
+        chunks.push(match m {
+            Merged::Clean(v) => format_node(node(&v)),
+            Merged::Conflict(o, t) => {
+                let side = |v: Option<T>| v.map(|v| format_node(node(&v))).unwrap_or_default();
+                format!("{OURS}\n{}{SEPARATOR}\n{}{THEIRS}\n", side(o), side(t))
+            }
+        });
+    }
+}
+
+/// How `Caddyfile::merge` handles a node both files define.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Duplicates {
+    /// Append the second node's directives to the first. A global
+    /// option without a block takes the second value.
+    #[default]
+    Combine,
+    /// Keep the first node and drop the second.
+    KeepFirst,
+    /// Replace the first node with the second, in place.
+    KeepLast,
+    /// Fail with a `MergeError`.
+    Reject,
+}
+
+/// Duplicate handling for each kind of node in `Caddyfile::merge`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MergePolicy {
+    /// Global options with the same name; options with a block must
+    /// also have the same arguments, so `servers :80` and
+    /// `servers :443` are distinct.
+    pub global_options: Duplicates,
+    /// Sites sharing an address, and snippets or named routes sharing
+    /// a name.
+    pub blocks: Duplicates,
+}
+
+/// Error produced by `Caddyfile::merge` under `Duplicates::Reject`.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum MergeError {
+    #[error("global option '{0}' is defined twice")]
+    GlobalOption(String),
+    #[error("snippet '{0}' is defined twice")]
+    Snippet(String),
+    #[error("named route '{0}' is defined twice")]
+    NamedRoute(String),
+    #[error("site address '{0}' is defined twice")]
+    Address(String),
+}
+
+impl Caddyfile {
+    /// Add the global options, snippets, named routes and sites of
+    /// `other` after this file's, resolving duplicates with `policy`.
+    ///
+    /// ```
+    /// use caddyfile_rs::merge::MergePolicy;
+    /// use caddyfile_rs::{Caddyfile, GlobalOptions, SiteBlock, format};
+    ///
+    /// let base = Caddyfile::new()
+    ///     .global(GlobalOptions::new().email("ops@example.com"))
+    ///     .site(SiteBlock::new("example.com").encode_gzip());
+    /// let module = Caddyfile::new()
+    ///     .site(SiteBlock::new("example.com").reverse_proxy("app:3000"))
+    ///     .site(SiteBlock::new("api.example.com").reverse_proxy("api:8080"));
+    ///
+    /// let cf = base.merge(module, MergePolicy::default()).unwrap();
+    /// assert_eq!(
+    ///     format(&cf),
+    ///     "{\n\
+    ///      \temail ops@example.com\n\
+    ///      }\n\
+    ///      \n\
+    ///      example.com {\n\
+    ///      \tencode gzip\n\
+    ///      \treverse_proxy app:3000\n\
+    ///      }\n\
+    ///      \n\
+    ///      api.example.com {\n\
+    ///      \treverse_proxy api:8080\n\
+    ///      }\n"
+    /// );
+    /// ```
+    pub fn merge(mut self, other: Self, policy: MergePolicy) -> Result<Self, MergeError> {
+        self.global_options = match (self.global_options, other.global_options) {
+            (Some(mut ours), Some(theirs)) => {
+                for option in theirs.directives {
+                    merge_option(&mut ours.directives, option, policy.global_options)?;
+                }
+                Some(ours)
+            }
+            (ours, theirs) => ours.or(theirs),
+        };
+        merge_blocks(
+            &mut self.snippets,
+            other.snippets,
+            policy.blocks,
+            |a, b| (a.name == b.name).then(|| MergeError::Snippet(b.name.clone())),
+            |a, b| a.directives.extend(b.directives),
+        )?;
+        merge_blocks(
+            &mut self.named_routes,
+            other.named_routes,
+            policy.blocks,
+            |a, b| (a.name == b.name).then(|| MergeError::NamedRoute(b.name.clone())),
+            |a, b| a.directives.extend(b.directives),
+        )?;
+        merge_blocks(
+            &mut self.sites,
+            other.sites,
+            policy.blocks,
+            |a, b| {
+                b.addresses
+                    .iter()
+                    .find(|addr| a.addresses.contains(addr))
+                    .map(|addr| MergeError::Address(addr.to_string()))
+            },
+            merge_site,
+        )?;
+        Ok(self)
+    }
+}
+
+impl SiteBlock {
+    /// Append the directives of `other`, and add its addresses this
+    /// site does not have yet.
+    #[must_use]
+    pub fn merge(mut self, other: Self) -> Self {
+        merge_site(&mut self, other);
+        self
+    }
+}
+
+fn merge_site(site: &mut SiteBlock, other: SiteBlock) {
+    for address in other.addresses {
+        if !site.addresses.contains(&address) {
+            site.addresses.push(address);
+        }
+    }
+    site.directives.extend(other.directives);
+}
+
+fn merge_option(
+    options: &mut Vec<Directive>,
+    option: Directive,
+    policy: Duplicates,
+) -> Result<(), MergeError> {
+    let same = |d: &Directive| {
+        d.name == option.name
+            && ((d.block.is_none() && option.block.is_none()) || d.arguments == option.arguments)
+    };
+    let Some(existing) = options.iter_mut().find(|d| same(d)) else {
+        options.push(option);
+        return Ok(());
+    };
+    match policy {
+        Duplicates::Combine => match (&mut existing.block, option.block) {
+            (Some(block), Some(more)) => block.extend(more),
+            (_, block) => *existing = Directive { block, ..option },
+        },
+        Duplicates::KeepFirst => {}
+        Duplicates::KeepLast => *existing = option,
+        Duplicates::Reject => return Err(MergeError::GlobalOption(option.name)),
+    }
+    Ok(())
+}
+
+/// Add `theirs` to `ours`; `duplicate` returns the error to report
+/// when its arguments are the same node.
+fn merge_blocks<T>(
+    ours: &mut Vec<T>,
+    theirs: Vec<T>,
+    policy: Duplicates,
+    duplicate: impl Fn(&T, &T) -> Option<MergeError>,
+    combine: impl Fn(&mut T, T),
+) -> Result<(), MergeError> {
+    for node in theirs {
+        let Some((existing, error)) = ours
+            .iter_mut()
+            .find_map(|o| duplicate(o, &node).map(|e| (o, e)))
+        else {
+            ours.push(node);
+            continue;
+        };
+        match policy {
+            Duplicates::Combine => combine(existing, node),
+            Duplicates::KeepFirst => {}
+            Duplicates::KeepLast => *existing = node,
+            Duplicates::Reject => return Err(error),
+        }
+    }
+    Ok(())
+}
+
+/// Move marker lines to column 0 and drop the blank lines the
+/// formatter puts around them.
+fn clean_markers(text: &str) -> String {
+    let is_marker = |l: &str| [OURS, SEPARATOR, THEIRS].contains(&l.trim_start());
+    let lines: Vec<&str> = text.lines().collect();
+    let mut out = String::with_capacity(text.len());
+    for (i, line) in lines.iter().enumerate() {
+        let after_open = i > 0 && [OURS, SEPARATOR].contains(&lines[i - 1].trim_start());
+        let before_close = lines
+            .get(i + 1)
+            .is_some_and(|next| [SEPARATOR, THEIRS].contains(&next.trim_start()));
+        if line.is_empty() && (after_open || before_close) {
+            continue;
+        }
+        out.push_str(if is_marker(line) {
+            line.trim_start()
+        } else {
+            line
+        });
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{format, parse_str};
+
+    fn merge(base: &str, ours: &str, theirs: &str) -> MergeOutcome {
+        merge3(
+            &parse_str(base).unwrap(),
+            &parse_str(ours).unwrap(),
+            &parse_str(theirs).unwrap(),
+        )
+    }
+
+    const BASE: &str = "a.com {\n\
+                        \tencode gzip\n\
+                        \treverse_proxy app:3000\n\
+                        }\n";
+
+    #[test]
+    fn independent_changes_merge_cleanly() {
+        let out = merge(
+            BASE,
+            "a.com {\n\tencode zstd gzip\n\treverse_proxy app:3000\n}\n",
+            "(common) {\n\tlog\n}\n\n\
+             a.com {\n\timport common\n\tencode gzip\n\treverse_proxy app:3000\n\tfile_server\n}\n",
+        );
+        assert!(out.is_clean());
+        assert_eq!(
+            out.text,
+            "(common) {\n\tlog\n}\n\n\
+             a.com {\n\timport common\n\tencode zstd gzip\n\treverse_proxy app:3000\n\tfile_server\n}\n"
+        );
+    }
+
+    #[test]
+    fn deletion_and_nested_changes() {
+        let base = "a.com {\n\tlog\n\thandle /api/* {\n\t\trespond 1\n\t\theader X-A a\n\t}\n}\n";
+        let out = merge(
+            base,
+            "a.com {\n\thandle /api/* {\n\t\trespond 2\n\t\theader X-A a\n\t}\n}\n",
+            "a.com {\n\tlog\n\thandle /api/* {\n\t\trespond 1\n\t\theader X-A b\n\t}\n}\n",
+        );
+        assert!(out.is_clean());
+        let expected =
+            parse_str("a.com {\n\thandle /api/* {\n\t\trespond 2\n\t\theader X-A b\n\t}\n}\n")
+                .unwrap();
+        assert_eq!(out.text, format(&expected));
+    }
+
+    #[test]
+    fn conflicts_are_scoped_to_directives() {
+        let out = merge(
+            BASE,
+            "a.com {\n\tencode gzip\n\treverse_proxy app:4000\n}\n",
+            "a.com {\n\tencode gzip\n\treverse_proxy app:5000\n}\n",
+        );
+        assert_eq!(out.conflicts, 1);
+        assert_eq!(
+            out.text,
+            "a.com {\n\
+             \tencode gzip\n\
+             <<<<<<< ours\n\
+             \treverse_proxy app:4000\n\
+             =======\n\
+             \treverse_proxy app:5000\n\
+             >>>>>>> theirs\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn delete_versus_modify_conflicts_on_block() {
+        let out = merge(
+            BASE,
+            "",
+            "a.com {\n\tencode gzip\n\treverse_proxy app:5000\n}\n",
+        );
+        assert_eq!(out.conflicts, 1);
+        assert!(out.text.starts_with("<<<<<<< ours\n=======\na.com {\n"));
+    }
+
+    #[test]
+    fn two_way_merge_policies() {
+        let first = parse_str(
+            "{\n\temail a@example.com\n\tservers :443 {\n\t\tprotocols h1\n\t}\n}\n\n\
+             (common) {\n\tencode gzip\n}\n\n\
+             a.com {\n\tlog\n}\n",
+        )
+        .unwrap();
+        let second = parse_str(
+            "{\n\temail b@example.com\n\tservers :443 {\n\t\ttimeouts {\n\t\t\tidle 1m\n\t\t}\n\t}\n\tservers :80\n}\n\n\
+             (common) {\n\theader -Server\n}\n\n\
+             a.com, www.a.com {\n\treverse_proxy app:3000\n}\n",
+        )
+        .unwrap();
+        let merged = |global_options, blocks| {
+            let policy = MergePolicy {
+                global_options,
+                blocks,
+            };
+            first
+                .clone()
+                .merge(second.clone(), policy)
+                .map(|cf| format(&cf))
+        };
+
+        assert_eq!(
+            merged(Duplicates::Combine, Duplicates::Combine).unwrap(),
+            "{\n\temail b@example.com\n\tservers :443 {\n\t\tprotocols h1\n\n\t\ttimeouts {\n\t\t\tidle 1m\n\t\t}\n\t}\n\tservers :80\n}\n\n\
+             (common) {\n\tencode gzip\n\theader -Server\n}\n\n\
+             a.com, www.a.com {\n\tlog\n\treverse_proxy app:3000\n}\n"
+        );
+        assert_eq!(
+            merged(Duplicates::KeepFirst, Duplicates::KeepFirst).unwrap(),
+            "{\n\temail a@example.com\n\tservers :443 {\n\t\tprotocols h1\n\t}\n\tservers :80\n}\n\n\
+             (common) {\n\tencode gzip\n}\n\n\
+             a.com {\n\tlog\n}\n"
+        );
+        let last = merged(Duplicates::KeepLast, Duplicates::KeepLast).unwrap();
+        assert!(last.starts_with("{\n\temail b@example.com\n\tservers :443 {\n\t\ttimeouts {"));
+        assert!(last.ends_with("a.com, www.a.com {\n\treverse_proxy app:3000\n}\n"));
+
+        assert_eq!(
+            merged(Duplicates::Reject, Duplicates::Combine),
+            Err(MergeError::GlobalOption("email".into()))
+        );
+        assert_eq!(
+            merged(Duplicates::Combine, Duplicates::Reject),
+            Err(MergeError::Snippet("common".into()))
+        );
+    }
+}
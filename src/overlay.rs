@@ -0,0 +1,283 @@
+//! Environment overlays applied on top of a base Caddyfile.
+//!
+//! An `Overlay` is an ordered list of edits (add a site, override
+//! directive arguments, add or delete directives) addressed with
+//! selectors. Applying the same overlays in the same order to the same
+//! base always yields the same document.
+//!
+//! ```
+//! use caddyfile_rs::overlay::Overlay;
+//! use caddyfile_rs::{Caddyfile, SiteBlock, format};
+//!
+//! let base = Caddyfile::new().site(
+//!     SiteBlock::new("example.com")
+//!         .reverse_proxy("app:3000")
+//!         .directive(caddyfile_rs::Directive::new("debug")),
+//! );
+//!
+//! let prod = Overlay::new()
+//!     .set_args("site[host=example.com] reverse_proxy", &["prod-app:3000"])
+//!     .delete("debug")
+//!     .add_site(SiteBlock::new("metrics.example.com").reverse_proxy("prom:9090"));
+//!
+//! let cf = prod.apply(&base).unwrap();
+//! let out = format(&cf);
+//! assert!(out.contains("reverse_proxy prod-app:3000"));
+//! assert!(!out.contains("debug"));
+//! assert!(out.contains("metrics.example.com {"));
+//! ```
+
+use crate::ast::{Argument, Caddyfile, Directive, SiteBlock};
+use crate::selector::{Node, NodePath, Selector, SelectorError};
+
+/// Error produced while applying an overlay.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum OverlayError {
+    /// A selector in the overlay could not be parsed.
+    #[error("invalid selector '{selector}': {source}")]
+    Selector {
+        selector: String,
+        source: SelectorError,
+    },
+    /// A selector matched no node of the expected kind.
+    #[error("selector '{0}' matched nothing")]
+    NoMatch(String),
+}
+
+/// A single overlay edit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OverlayOp {
+    /// Add a site, replacing any base site with the same addresses.
+    AddSite(SiteBlock),
+    /// Replace the arguments of every matched directive.
+    SetArgs {
+        selector: String,
+        args: Vec<Argument>,
+    },
+    /// Append a directive to every matched block or directive sub-block.
+    AddDirective {
+        selector: String,
+        directive: Directive,
+    },
+    /// Delete every matched node.
+    Delete { selector: String },
+}
+
+/// Ordered set of edits for one environment.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Overlay {
+    pub ops: Vec<OverlayOp>,
+}
+
+impl Overlay {
+    /// Create an empty overlay.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { ops: Vec::new() }
+    }
+
+    /// Add a site, replacing a base site with identical addresses.
+    #[must_use]
+    pub fn add_site(mut self, site: SiteBlock) -> Self {
+        self.ops.push(OverlayOp::AddSite(site));
+        self
+    }
+
+    /// Override the arguments of matched directives with unquoted values.
+    #[must_use]
+    pub fn set_args(mut self, selector: &str, args: &[&str]) -> Self {
+        self.ops.push(OverlayOp::SetArgs {
+            selector: selector.to_string(),
+            args: args.iter().map(|a| Argument::from(*a)).collect(),
+        });
+        self
+    }
+
+    /// Append a directive to matched blocks.
+    #[must_use]
+    pub fn add_directive(mut self, selector: &str, directive: Directive) -> Self {
+        self.ops.push(OverlayOp::AddDirective {
+            selector: selector.to_string(),
+            directive,
+        });
+        self
+    }
+
+    /// Delete matched nodes.
+    #[must_use]
+    pub fn delete(mut self, selector: &str) -> Self {
+        self.ops.push(OverlayOp::Delete {
+            selector: selector.to_string(),
+        });
+        self
+    }
+
+    /// Apply this overlay to a copy of `base`.
+    ///
+    /// Every selector must match at least one node, so typos in an
+    /// environment overlay fail loudly instead of being ignored.
+    pub fn apply(&self, base: &Caddyfile) -> Result<Caddyfile, OverlayError> {
+        let mut cf = base.clone();
+        for op in &self.ops {
+            apply_op(&mut cf, op)?;
+        }
+        Ok(cf)
+    }
+}
+
+/// Apply several overlays in order on top of `base`.
+pub fn apply_all(base: &Caddyfile, overlays: &[Overlay]) -> Result<Caddyfile, OverlayError> {
+    overlays
+        .iter()
+        .try_fold(base.clone(), |cf, overlay| overlay.apply(&cf))
+}
+
+/// Addresses as printed, so raw and parsed forms compare equal.
+fn address_key(site: &SiteBlock) -> Vec<String> {
+    site.addresses.iter().map(ToString::to_string).collect()
+}
+
+fn apply_op(cf: &mut Caddyfile, op: &OverlayOp) -> Result<(), OverlayError> {
+    match op {
+        OverlayOp::AddSite(site) => {
+            let key = address_key(site);
+            if let Some(existing) = cf.sites.iter_mut().find(|s| address_key(s) == key) {
+                existing.clone_from(site);
+            } else {
+                cf.sites.push(site.clone());
+            }
+        }
+        OverlayOp::SetArgs { selector, args } => {
+            let paths = select(cf, selector)?;
+            let mut matched = false;
+            for path in &paths {
+                if let Some(d) = cf.directive_at_mut(path) {
+                    d.arguments.clone_from(args);
+                    matched = true;
+                }
+            }
+            if !matched {
+                return Err(OverlayError::NoMatch(selector.clone()));
+            }
+        }
+        OverlayOp::AddDirective {
+            selector,
+            directive,
+        } => {
+            let paths = select(cf, selector)?;
+            let mut matched = false;
+            for path in &paths {
+                let is_directive = matches!(cf.node(path), Some(Node::Directive(_)));
+                if is_directive {
+                    if let Some(d) = cf.directive_at_mut(path) {
+                        d.block.get_or_insert_with(Vec::new).push(directive.clone());
+                        matched = true;
+                    }
+                } else if let Some(list) = cf.directives_at_mut(path) {
+                    list.push(directive.clone());
+                    matched = true;
+                }
+            }
+            if !matched {
+                return Err(OverlayError::NoMatch(selector.clone()));
+            }
+        }
+        OverlayOp::Delete { selector } => {
+            let paths = select(cf, selector)?;
+            for path in paths.iter().rev() {
+                cf.remove_node(path);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Select nodes, failing when the selector is invalid or matches nothing.
+fn select(cf: &Caddyfile, selector: &str) -> Result<Vec<NodePath>, OverlayError> {
+    let parsed = Selector::parse(selector).map_err(|source| OverlayError::Selector {
+        selector: selector.to_string(),
+        source,
+    })?;
+    let paths = parsed.select(cf);
+    if paths.is_empty() {
+        return Err(OverlayError::NoMatch(selector.to_string()));
+    }
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{format, parse_str};
+
+    fn base() -> Caddyfile {
+        parse_str(
+            "example.com {\n\
+             \treverse_proxy app:3000\n\
+             \tdebug\n\
+             }\n\
+             \n\
+             admin.example.com {\n\
+             \treverse_proxy admin:3001\n\
+             }\n",
+        )
+        .expect("parse failed")
+    }
+
+    #[test]
+    fn overlays_apply_in_order() {
+        let staging =
+            Overlay::new().set_args("site[host=example.com] reverse_proxy", &["stg:3000"]);
+        let prod = Overlay::new()
+            .set_args("site[host=example.com] reverse_proxy", &["prod:3000"])
+            .delete("debug");
+
+        let cf = apply_all(&base(), &[staging, prod]).expect("apply");
+        assert_eq!(
+            format(&cf),
+            "example.com {\n\treverse_proxy prod:3000\n}\n\n\
+             admin.example.com {\n\treverse_proxy admin:3001\n}\n"
+        );
+    }
+
+    #[test]
+    fn add_site_replaces_same_addresses() {
+        let overlay = Overlay::new()
+            .add_site(SiteBlock::new("admin.example.com").respond(None, 403))
+            .add_site(SiteBlock::new("new.example.com").log());
+        let cf = overlay.apply(&base()).expect("apply");
+        assert_eq!(cf.sites.len(), 3);
+        assert_eq!(cf.sites[1].directives[0].name, "respond");
+        assert_eq!(cf.sites[2].addresses[0].host, "new.example.com");
+    }
+
+    #[test]
+    fn add_directive_to_site_and_sub_block() {
+        let overlay = Overlay::new()
+            .add_directive("site[host=admin.*]", Directive::new("log"))
+            .add_directive(
+                "site[host=admin.*] reverse_proxy",
+                Directive::new("lb_policy").arg("first"),
+            );
+        let cf = overlay.apply(&base()).expect("apply");
+        let admin = &cf.sites[1];
+        assert_eq!(admin.directives[1].name, "log");
+        let rp_block = admin.directives[0].block.as_ref().expect("block");
+        assert_eq!(rp_block[0].name, "lb_policy");
+    }
+
+    #[test]
+    fn unmatched_selector_is_an_error() {
+        let overlay = Overlay::new().delete("site[host=missing.com]");
+        assert_eq!(
+            overlay.apply(&base()),
+            Err(OverlayError::NoMatch("site[host=missing.com]".to_string()))
+        );
+
+        let overlay = Overlay::new().delete("site >");
+        assert!(matches!(
+            overlay.apply(&base()),
+            Err(OverlayError::Selector { .. })
+        ));
+    }
+}
@@ -5,7 +5,8 @@
 use std::fmt;
 
 use crate::ast::{
-    self, Argument, Caddyfile, Directive, GlobalOptions, Matcher, NamedRoute, SiteBlock, Snippet,
+    self, Address, Argument, Caddyfile, Comments, Directive, GlobalOptions, Matcher, NamedRoute,
+    SiteBlock, Snippet,
 };
 use crate::token::{Span, Token, TokenKind};
 
@@ -16,6 +17,8 @@ pub enum ParseErrorKind {
     ExpectedOpenBrace { found: Option<String> },
     /// Expected `}`, found something else or EOF.
     ExpectedCloseBrace { found: Option<String> },
+    /// A block is nested deeper than `ParseOptions::max_depth`.
+    TooDeep { max: usize },
 }
 
 impl fmt::Display for ParseErrorKind {
@@ -33,18 +36,79 @@ impl fmt::Display for ParseErrorKind {
             Self::ExpectedCloseBrace { found: Some(t) } => {
                 write!(f, "expected '}}', got '{t}'")
             }
+            Self::TooDeep { max } => {
+                write!(f, "blocks nested deeper than {max} levels")
+            }
         }
     }
 }
 
 /// Error produced during parsing.
 #[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
-#[error("{kind} at line {}, column {}", span.line, span.column)]
+#[error("{kind} at {span}")]
 pub struct ParseError {
     pub kind: ParseErrorKind,
     pub span: Span,
 }
 
+/// Default for `ParseOptions::max_depth`.
+pub const DEFAULT_MAX_DEPTH: usize = 128;
+
+/// Options controlling how tokens are turned into an AST.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Keep site addresses as raw strings (`Address::raw`) instead of
+    /// splitting them into components, so they round-trip byte for byte.
+    pub raw_addresses: bool,
+    /// Deepest block nesting accepted; the directives of a site, snippet
+    /// or global options block are depth 1. Deeper input fails with
+    /// `ParseErrorKind::TooDeep` instead of exhausting the stack.
+    pub max_depth: usize,
+    /// Record blank lines between directives in
+    /// `Directive::blank_lines_before`, for
+    /// `FormatOptions::max_blank_lines`.
+    pub blank_lines: bool,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ParseOptions {
+    /// Default options.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            raw_addresses: false,
+            max_depth: DEFAULT_MAX_DEPTH,
+            blank_lines: false,
+        }
+    }
+
+    /// Limit block nesting depth.
+    #[must_use]
+    pub const fn max_depth(mut self, max: usize) -> Self {
+        self.max_depth = max;
+        self
+    }
+
+    /// Record blank lines between directives.
+    #[must_use]
+    pub const fn blank_lines(mut self, keep: bool) -> Self {
+        self.blank_lines = keep;
+        self
+    }
+
+    /// Keep site addresses verbatim.
+    #[must_use]
+    pub const fn raw_addresses(mut self, raw: bool) -> Self {
+        self.raw_addresses = raw;
+        self
+    }
+}
+
 /// Parse a token stream into a `Caddyfile` AST.
 ///
 /// # Errors
@@ -52,25 +116,83 @@ pub struct ParseError {
 /// Returns `ParseError` on syntax errors such as unclosed
 /// braces, unexpected tokens, or invalid structure.
 pub fn parse(tokens: &[Token]) -> Result<Caddyfile, ParseError> {
-    Parser::new(tokens).parse()
+    parse_with_options(tokens, &ParseOptions::default())
+}
+
+/// Parse a token stream with explicit options.
+pub fn parse_with_options(
+    tokens: &[Token],
+    options: &ParseOptions,
+) -> Result<Caddyfile, ParseError> {
+    Parser::new(tokens, options).parse()
+}
+
+/// Parse a token stream, recovering from errors.
+///
+/// When a top-level block fails to parse it is dropped, the error is
+/// recorded and parsing resumes at the next line that starts at or left
+/// of the failed block's column. To locate a missing `}`, a failed
+/// block is re-read taking any line at or left of its own column that
+/// opens a brace as the start of the next block, so one unclosed site
+/// does not swallow the rest of the file. Returns the blocks that
+/// parsed along with every error.
+#[must_use]
+pub fn parse_with_recovery(
+    tokens: &[Token],
+    options: &ParseOptions,
+) -> (Caddyfile, Vec<ParseError>) {
+    let mut parser = Parser::new(tokens, options);
+    parser.errors = Some(Vec::new());
+    let caddyfile = parser
+        .parse_blocks()
+        .expect("errors are collected when recovering");
+    (caddyfile, parser.errors.unwrap_or_default())
 }
 
 struct Parser<'a> {
     tokens: &'a [Token],
     pos: usize,
+    options: &'a ParseOptions,
+    /// Comments seen since the last node, waiting to be attached.
+    pending: Vec<String>,
+    /// Errors collected so far when parsing with recovery.
+    errors: Option<Vec<ParseError>>,
+    /// Column of the top-level block being parsed.
+    block_column: usize,
+    /// Treat unindented lines opening a brace as a new top-level block.
+    split_blocks: bool,
+    /// Nesting depth of the directives being parsed.
+    depth: usize,
+    /// Blank lines skipped since the last directive.
+    blank_lines: usize,
 }
 
 impl<'a> Parser<'a> {
-    const fn new(tokens: &'a [Token]) -> Self {
-        Self { tokens, pos: 0 }
+    const fn new(tokens: &'a [Token], options: &'a ParseOptions) -> Self {
+        Self {
+            tokens,
+            pos: 0,
+            options,
+            pending: Vec::new(),
+            errors: None,
+            block_column: 1,
+            split_blocks: false,
+            blank_lines: 0,
+            depth: 1,
+        }
     }
 
     fn parse(mut self) -> Result<Caddyfile, ParseError> {
+        self.parse_blocks()
+    }
+
+    fn parse_blocks(&mut self) -> Result<Caddyfile, ParseError> {
         let mut caddyfile = Caddyfile {
             global_options: None,
             snippets: Vec::new(),
             named_routes: Vec::new(),
             sites: Vec::new(),
+            trailing_comments: Vec::new(),
         };
 
         self.skip_newlines_and_comments();
@@ -78,7 +200,11 @@ impl<'a> Parser<'a> {
         // Check for global options block: { at start
         // (no addresses before it)
         if self.is_global_options_block() {
-            caddyfile.global_options = Some(self.parse_global_options()?);
+            self.parse_recovering(&mut caddyfile, |p, cf| {
+                cf.global_options = Some(p.parse_global_options()?);
+                Ok(())
+            })?;
+            self.pending.clear();
             self.skip_newlines_and_comments();
         }
 
@@ -88,27 +214,98 @@ impl<'a> Parser<'a> {
             if self.pos >= self.tokens.len() {
                 break;
             }
+            self.parse_recovering(&mut caddyfile, Self::parse_block)?;
+        }
+        caddyfile.trailing_comments = std::mem::take(&mut self.pending);
 
-            let token = &self.tokens[self.pos];
+        Ok(caddyfile)
+    }
 
-            // Snippet: (name) { ... }
-            if token.text.starts_with('(') && token.text.ends_with(')') && token.text.len() > 2 {
-                caddyfile.snippets.push(self.parse_snippet()?);
-            }
-            // Named route: &(name) { ... }
-            else if token.text.starts_with("&(")
-                && token.text.ends_with(')')
-                && token.text.len() > 3
+    /// Parse one top-level snippet, named route or site block.
+    fn parse_block(&mut self, caddyfile: &mut Caddyfile) -> Result<(), ParseError> {
+        let token = &self.tokens[self.pos];
+
+        // Snippet: (name) { ... }
+        if token.text.starts_with('(') && token.text.ends_with(')') && token.text.len() > 2 {
+            caddyfile.snippets.push(self.parse_snippet()?);
+        }
+        // Named route: &(name) { ... }
+        else if token.text.starts_with("&(") && token.text.ends_with(')') && token.text.len() > 3
+        {
+            caddyfile.named_routes.push(self.parse_named_route()?);
+        }
+        // Site block
+        else {
+            caddyfile.sites.push(self.parse_site_block()?);
+        }
+        Ok(())
+    }
+
+    /// Run `parse` on the top-level block at the current position.
+    ///
+    /// When recovering, a failed block is retried with unindented
+    /// brace-opening lines taken as the next block, so the error points
+    /// at the first place a `}` is missing. The error is then recorded
+    /// and parsing skips to the next top-level block.
+    fn parse_recovering(
+        &mut self,
+        caddyfile: &mut Caddyfile,
+        parse: fn(&mut Self, &mut Caddyfile) -> Result<(), ParseError>,
+    ) -> Result<(), ParseError> {
+        let start = self.pos;
+        self.block_column = self.tokens[start].span.column;
+        let Err(error) = parse(self, caddyfile) else {
+            return Ok(());
+        };
+        if self.errors.is_none() {
+            return Err(error);
+        }
+
+        self.depth = 1;
+        self.pos = start;
+        self.split_blocks = true;
+        let retry = parse(self, caddyfile);
+        self.split_blocks = false;
+        self.depth = 1;
+        let retried = retry.is_ok();
+        let error = retry.err().unwrap_or(error);
+        self.errors.get_or_insert_default().push(error);
+        self.pending.clear();
+        if retried {
+            return Ok(());
+        }
+
+        self.pos = self.pos.max(start + 1);
+        while self.pos < self.tokens.len() {
+            let tok = &self.tokens[self.pos];
+            if self.at_line_start()
+                && tok.span.column <= self.block_column
+                && tok.kind != TokenKind::CloseBrace
             {
-                caddyfile.named_routes.push(self.parse_named_route()?);
-            }
-            // Site block
-            else {
-                caddyfile.sites.push(self.parse_site_block()?);
+                break;
             }
+            self.pos += 1;
         }
+        Ok(())
+    }
 
-        Ok(caddyfile)
+    fn at_line_start(&self) -> bool {
+        self.pos == 0 || self.tokens[self.pos - 1].kind == TokenKind::Newline
+    }
+
+    /// Whether the current line opens a block at or left of the
+    /// enclosing top-level block, which means a `}` is missing.
+    fn starts_new_top_level_block(&self) -> bool {
+        if !self.split_blocks
+            || !self.at_line_start()
+            || self.tokens[self.pos].span.column > self.block_column
+        {
+            return false;
+        }
+        self.tokens[self.pos..]
+            .iter()
+            .take_while(|t| t.kind != TokenKind::Newline)
+            .any(|t| t.kind == TokenKind::OpenBrace)
     }
 
     fn is_global_options_block(&self) -> bool {
@@ -117,42 +314,59 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_global_options(&mut self) -> Result<GlobalOptions, ParseError> {
+        let mut comments = self.take_leading_comments();
         self.expect_open_brace()?;
-        let directives = self.parse_directives()?;
-        self.expect_close_brace()?;
-        Ok(GlobalOptions { directives })
+        let directives = self.parse_block_body(&mut comments)?;
+        Ok(GlobalOptions {
+            directives,
+            comments,
+        })
     }
 
     fn parse_snippet(&mut self) -> Result<Snippet, ParseError> {
         let token = &self.tokens[self.pos];
         let name = token.text[1..token.text.len() - 1].to_string();
+        let mut comments = self.take_leading_comments();
         self.pos += 1;
         self.skip_whitespace_tokens();
         self.expect_open_brace()?;
-        let directives = self.parse_directives()?;
-        self.expect_close_brace()?;
-        Ok(Snippet { name, directives })
+        let directives = self.parse_block_body(&mut comments)?;
+        Ok(Snippet {
+            name,
+            directives,
+            comments,
+        })
     }
 
     fn parse_named_route(&mut self) -> Result<NamedRoute, ParseError> {
         let token = &self.tokens[self.pos];
         let name = token.text[2..token.text.len() - 1].to_string();
+        let mut comments = self.take_leading_comments();
         self.pos += 1;
         self.skip_whitespace_tokens();
         self.expect_open_brace()?;
-        let directives = self.parse_directives()?;
-        self.expect_close_brace()?;
-        Ok(NamedRoute { name, directives })
+        let directives = self.parse_block_body(&mut comments)?;
+        Ok(NamedRoute {
+            name,
+            directives,
+            comments,
+        })
     }
 
     fn parse_site_block(&mut self) -> Result<SiteBlock, ParseError> {
         let mut addresses = Vec::new();
+        let mut comments = self.take_leading_comments();
 
-        // Collect addresses until we hit {
+        // Collect addresses until we hit {. A trailing comma continues
+        // the list on the next line.
+        let mut continued = false;
         while self.pos < self.tokens.len() {
             let token = &self.tokens[self.pos];
             match &token.kind {
                 TokenKind::OpenBrace => break,
+                TokenKind::Newline if continued => {
+                    self.pos += 1;
+                }
                 TokenKind::Newline => {
                     self.pos += 1;
                     // If next non-whitespace is { on same
@@ -161,12 +375,20 @@ impl<'a> Parser<'a> {
                     break;
                 }
                 TokenKind::Comment => {
+                    comments.trailing = Some(token.text.clone());
+                    self.pos += 1;
+                }
+                TokenKind::Comma => {
+                    continued = true;
                     self.pos += 1;
                 }
                 _ => {
-                    // Handle comma-separated addresses
-                    let text = token.text.trim_end_matches(',');
-                    addresses.push(ast::parse_address(text));
+                    continued = false;
+                    addresses.push(if self.options.raw_addresses {
+                        Address::raw(&token.text)
+                    } else {
+                        ast::parse_address(&token.text)
+                    });
                     self.pos += 1;
                 }
             }
@@ -174,26 +396,53 @@ impl<'a> Parser<'a> {
 
         self.skip_newlines_and_comments();
 
-        // Site block may be a single-line (no braces)
+        // Without braces, as in a single-site Caddyfile, the site's
+        // directives run to the end of the file
         if self.pos >= self.tokens.len() || self.tokens[self.pos].kind != TokenKind::OpenBrace {
+            let directives = self.parse_directives()?;
+            comments.closing = std::mem::take(&mut self.pending);
             return Ok(SiteBlock {
                 addresses,
-                directives: Vec::new(),
+                directives,
+                comments,
+                braceless: true,
             });
         }
 
         self.expect_open_brace()?;
-        let directives = self.parse_directives()?;
-        self.expect_close_brace()?;
+        let directives = self.parse_block_body(&mut comments)?;
 
         Ok(SiteBlock {
             addresses,
             directives,
+            comments,
+            braceless: false,
         })
     }
 
+    /// Comments collected above the node starting at the current token.
+    fn take_leading_comments(&mut self) -> Comments {
+        Comments {
+            leading: std::mem::take(&mut self.pending),
+            ..Comments::default()
+        }
+    }
+
+    /// Parse a top-level block's directives after its `{`, up to and
+    /// including the closing `}`.
+    fn parse_block_body(&mut self, comments: &mut Comments) -> Result<Vec<Directive>, ParseError> {
+        if let Some(comment) = self.same_line_comment() {
+            comments.trailing.get_or_insert(comment);
+        }
+        let directives = self.parse_directives()?;
+        comments.closing = std::mem::take(&mut self.pending);
+        self.expect_close_brace()?;
+        Ok(directives)
+    }
+
     fn parse_directives(&mut self) -> Result<Vec<Directive>, ParseError> {
         let mut directives = Vec::new();
+        self.blank_lines = 0;
 
         loop {
             self.skip_newlines_and_comments();
@@ -203,11 +452,18 @@ impl<'a> Parser<'a> {
             }
 
             // End of block
-            if self.tokens[self.pos].kind == TokenKind::CloseBrace {
+            if self.tokens[self.pos].kind == TokenKind::CloseBrace
+                || self.starts_new_top_level_block()
+            {
                 break;
             }
 
-            directives.push(self.parse_directive()?);
+            let blank_lines = std::mem::take(&mut self.blank_lines);
+            let mut directive = self.parse_directive()?;
+            if self.options.blank_lines {
+                directive.blank_lines_before = blank_lines;
+            }
+            directives.push(directive);
         }
 
         Ok(directives)
@@ -216,12 +472,22 @@ impl<'a> Parser<'a> {
     fn parse_directive(&mut self) -> Result<Directive, ParseError> {
         let name = self.tokens[self.pos].text.clone();
         self.pos += 1;
+        let mut comments = Comments {
+            leading: std::mem::take(&mut self.pending),
+            ..Comments::default()
+        };
 
         // Check for matcher
-        let matcher = self.try_parse_matcher();
+        let matcher = if name.starts_with('@') {
+            self.try_parse_negation()
+        } else {
+            None
+        }
+        .or_else(|| self.try_parse_matcher());
 
         // Collect arguments until newline or {
         let mut arguments = Vec::new();
+        let mut continuations = Vec::new();
         while self.pos < self.tokens.len() {
             let tok = &self.tokens[self.pos];
             match &tok.kind {
@@ -231,10 +497,14 @@ impl<'a> Parser<'a> {
                 }
                 TokenKind::OpenBrace | TokenKind::CloseBrace => break,
                 TokenKind::Comment => {
+                    comments.trailing = Some(tok.text.clone());
                     self.pos += 1;
                 }
                 _ => {
-                    arguments.push(Self::token_to_argument(tok));
+                    if tok.continued {
+                        continuations.push(arguments.len());
+                    }
+                    self.push_argument(&mut arguments);
                     self.pos += 1;
                 }
             }
@@ -243,8 +513,22 @@ impl<'a> Parser<'a> {
         // Check for sub-block
         let block =
             if self.pos < self.tokens.len() && self.tokens[self.pos].kind == TokenKind::OpenBrace {
+                if self.depth >= self.options.max_depth {
+                    return Err(ParseError {
+                        kind: ParseErrorKind::TooDeep {
+                            max: self.options.max_depth,
+                        },
+                        span: self.tokens[self.pos].span.clone(),
+                    });
+                }
                 self.pos += 1; // skip {
+                if let Some(comment) = self.same_line_comment() {
+                    comments.trailing.get_or_insert(comment);
+                }
+                self.depth += 1;
                 let sub = self.parse_directives()?;
+                self.depth -= 1;
+                comments.closing = std::mem::take(&mut self.pending);
                 self.expect_close_brace()?;
                 Some(sub)
             } else {
@@ -256,6 +540,9 @@ impl<'a> Parser<'a> {
             matcher,
             arguments,
             block,
+            comments,
+            continuations,
+            blank_lines_before: 0,
         })
     }
 
@@ -281,7 +568,16 @@ impl<'a> Parser<'a> {
                 } else if tok.text.starts_with('/') {
                     let path = tok.text.clone();
                     self.pos += 1;
-                    Some(Matcher::Path(path))
+                    let more = self.block_paths();
+                    if more == 0 {
+                        return Some(Matcher::Path(path));
+                    }
+                    let mut paths = vec![path];
+                    for tok in &self.tokens[self.pos..self.pos + more] {
+                        paths.push(tok.text.clone());
+                    }
+                    self.pos += more;
+                    Some(Matcher::Paths(paths))
                 } else {
                     None
                 }
@@ -289,6 +585,60 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Number of further path tokens when the rest of the line is
+    /// nothing but paths followed by `{`.
+    fn block_paths(&self) -> usize {
+        let rest = &self.tokens[self.pos..];
+        let count = rest
+            .iter()
+            .take_while(|t| t.kind == TokenKind::Word && t.text.starts_with('/'))
+            .count();
+        match rest.get(count) {
+            Some(t) if t.kind == TokenKind::OpenBrace => count,
+            _ => 0,
+        }
+    }
+
+    /// `not <module> <args...>` in a single-line matcher definition.
+    fn try_parse_negation(&mut self) -> Option<Matcher> {
+        let [not, module, ..] = self.tokens.get(self.pos..)? else {
+            return None;
+        };
+        if not.kind != TokenKind::Word || not.text != "not" || module.kind != TokenKind::Word {
+            return None;
+        }
+        let module = module.text.clone();
+        self.pos += 2;
+        let mut args = Vec::new();
+        while let Some(tok) = self.tokens.get(self.pos) {
+            if matches!(
+                tok.kind,
+                TokenKind::Newline
+                    | TokenKind::OpenBrace
+                    | TokenKind::CloseBrace
+                    | TokenKind::Comment
+            ) {
+                break;
+            }
+            self.push_argument(&mut args);
+            self.pos += 1;
+        }
+        Some(Matcher::Not { module, args })
+    }
+
+    /// Append the current token to `args`, joining a comma back onto
+    /// the word it was split from.
+    fn push_argument(&self, args: &mut Vec<Argument>) {
+        let tok = &self.tokens[self.pos];
+        let glued = matches!(tok.kind, TokenKind::Word | TokenKind::Comma)
+            && self.pos > 0
+            && adjacent(&self.tokens[self.pos - 1], tok);
+        match args.last_mut() {
+            Some(Argument::Unquoted(last)) if glued => last.push_str(&tok.text),
+            _ => args.push(Self::token_to_argument(tok)),
+        }
+    }
+
     fn token_to_argument(token: &Token) -> Argument {
         match &token.kind {
             TokenKind::QuotedString => Argument::Quoted(token.text.clone()),
@@ -297,14 +647,26 @@ impl<'a> Parser<'a> {
                 marker: marker.clone(),
                 content: token.text.clone(),
             },
+            TokenKind::EnvVar { name, default } => Argument::EnvVar {
+                name: name.clone(),
+                default: default.clone(),
+            },
             _ => Argument::Unquoted(token.text.clone()),
         }
     }
 
+    /// Skip blank lines, collecting comments for the next node.
     fn skip_newlines_and_comments(&mut self) {
         while self.pos < self.tokens.len() {
             match self.tokens[self.pos].kind {
-                TokenKind::Newline | TokenKind::Comment => {
+                TokenKind::Newline => {
+                    if self.pos > 0 && self.tokens[self.pos - 1].kind == TokenKind::Newline {
+                        self.blank_lines += 1;
+                    }
+                    self.pos += 1;
+                }
+                TokenKind::Comment => {
+                    self.pending.push(self.tokens[self.pos].text.clone());
                     self.pos += 1;
                 }
                 _ => break,
@@ -312,6 +674,18 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Take a comment directly after the previous token on its line.
+    fn same_line_comment(&mut self) -> Option<String> {
+        let prev = &self.tokens[self.pos.checked_sub(1)?];
+        let tok = self.tokens.get(self.pos)?;
+        if tok.kind == TokenKind::Comment && tok.span.line == prev.span.line {
+            self.pos += 1;
+            Some(tok.text.clone())
+        } else {
+            None
+        }
+    }
+
     fn skip_whitespace_tokens(&mut self) {
         while self.pos < self.tokens.len() {
             if self.tokens[self.pos].kind == TokenKind::Newline {
@@ -365,10 +739,19 @@ impl<'a> Parser<'a> {
     fn eof_span(&self) -> Span {
         self.tokens
             .last()
-            .map_or(Span { line: 1, column: 1 }, |last| last.span.clone())
+            .map_or(Span::new(1, 1), |last| last.span.clone())
     }
 }
 
+/// Whether `b` directly follows `a` with a comma between or on
+/// either side, as in `GET,` `POST`.
+fn adjacent(a: &Token, b: &Token) -> bool {
+    (a.kind == TokenKind::Comma || b.kind == TokenKind::Comma)
+        && matches!(a.kind, TokenKind::Word | TokenKind::Comma)
+        && a.span.line == b.span.line
+        && a.span.column + a.text.len() == b.span.column
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -390,6 +773,31 @@ mod tests {
         assert_eq!(cf.sites[0].directives[0].name, "reverse_proxy");
     }
 
+    #[test]
+    fn comma_separated_addresses() {
+        for input in [
+            "a.com, b.com {\n}\n",
+            "a.com ,b.com {\n}\n",
+            "a.com , b.com {\n}\n",
+            "a.com,\n\tb.com {\n}\n",
+        ] {
+            let cf = parse_input(input).unwrap();
+            let hosts: Vec<_> = cf.sites[0].addresses.iter().map(|a| &a.host).collect();
+            assert_eq!(hosts, ["a.com", "b.com"], "{input:?}");
+        }
+    }
+
+    #[test]
+    fn commas_in_arguments_are_kept() {
+        let cf = parse_input("a.com {\n\theader Allow GET,POST, Content-Type ,x\n}\n").unwrap();
+        let args: Vec<_> = cf.sites[0].directives[0]
+            .arguments
+            .iter()
+            .map(Argument::value)
+            .collect();
+        assert_eq!(args, ["Allow", "GET,POST,", "Content-Type", ",x"]);
+    }
+
     #[test]
     fn global_options() {
         let cf = parse_input(
@@ -488,6 +896,46 @@ mod tests {
         assert_eq!(a.path, None);
     }
 
+    #[test]
+    fn raw_addresses_option() {
+        let tokens = tokenize("example.com:0443, HTTP://Example.com {\n\tlog\n}\n").unwrap();
+        let options = ParseOptions::new().raw_addresses(true);
+        let cf = parse_with_options(&tokens, &options).expect("parse failed");
+        let addrs = &cf.sites[0].addresses;
+        assert_eq!(addrs[0].raw.as_deref(), Some("example.com:0443"));
+        assert_eq!(addrs[0].to_string(), "example.com:0443");
+        assert_eq!(addrs[0].parsed().port, Some(443));
+        assert_eq!(addrs[1].to_string(), "HTTP://Example.com");
+
+        let cf = parse(&tokens).expect("parse failed");
+        assert_eq!(cf.sites[0].addresses[0].to_string(), "example.com:443");
+    }
+
+    #[test]
+    fn nesting_depth_is_limited() {
+        let nested = |levels: usize| {
+            format!(
+                "a.com {{\n{}{}}}\n",
+                "x {\n".repeat(levels),
+                "}\n".repeat(levels)
+            )
+        };
+        let tokens = tokenize(&nested(2)).unwrap();
+        assert!(parse_with_options(&tokens, &ParseOptions::new().max_depth(3)).is_ok());
+        let err = parse_with_options(&tokens, &ParseOptions::new().max_depth(2)).unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::TooDeep { max: 2 });
+        assert_eq!(err.span, Span::new(3, 3));
+
+        let tokens = tokenize(&nested(100_000)).unwrap();
+        let err = parse(&tokens).unwrap_err();
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::TooDeep {
+                max: DEFAULT_MAX_DEPTH
+            }
+        );
+    }
+
     #[test]
     fn unclosed_brace() {
         let result = parse_input("example.com {\n    log\n");
@@ -499,4 +947,125 @@ mod tests {
         let cf = parse_input("a.com {\n    log\n}\n\nb.com {\n    log\n}\n").expect("parse failed");
         assert_eq!(cf.sites.len(), 2);
     }
+
+    #[test]
+    fn comments_attach_to_nodes() {
+        let cf =
+            parse_input("# site\na.com { # t\n\t# lead\n\treverse_proxy app # tr\n\t# end\n}\n")
+                .expect("parse failed");
+        let site = &cf.sites[0];
+        assert_eq!(site.comments.leading, ["# site"]);
+        assert_eq!(site.comments.trailing.as_deref(), Some("# t"));
+        assert_eq!(site.comments.closing, ["# end"]);
+        let d = &site.directives[0];
+        assert_eq!(d.comments.leading, ["# lead"]);
+        assert_eq!(d.comments.trailing.as_deref(), Some("# tr"));
+        assert_eq!(d.arguments.len(), 1);
+    }
+
+    #[test]
+    fn comments_attach_to_top_level_blocks() {
+        let cf = parse_input("# g\n{ # t\n\tdebug\n}\n# s\n(s) {\n\t# end\n}\n&(r) {\n}\n# eof\n")
+            .expect("parse failed");
+        let global = cf.global_options.unwrap();
+        assert_eq!(global.comments.leading, ["# g"]);
+        assert_eq!(global.comments.trailing.as_deref(), Some("# t"));
+        assert!(global.directives[0].comments.is_empty());
+        assert_eq!(cf.snippets[0].comments.leading, ["# s"]);
+        assert_eq!(cf.snippets[0].comments.closing, ["# end"]);
+        assert!(cf.named_routes[0].comments.is_empty());
+        assert_eq!(cf.trailing_comments, ["# eof"]);
+    }
+
+    #[test]
+    fn multi_token_matchers() {
+        let cf = parse_input(
+            "a.com {\n\
+             \t@notacme not path /.well-known/acme-challenge/*\n\
+             \thandle /a/* /b/* {\n\t\trespond ok\n\t}\n\
+             \tredir /old /new 301\n\
+             }\n",
+        )
+        .expect("parse failed");
+        let d = &cf.sites[0].directives;
+        assert_eq!(
+            d[0].matcher,
+            Some(Matcher::Not {
+                module: "path".to_string(),
+                args: vec![Argument::Unquoted(
+                    "/.well-known/acme-challenge/*".to_string()
+                )],
+            })
+        );
+        assert!(d[0].arguments.is_empty());
+        assert_eq!(
+            d[1].matcher,
+            Some(Matcher::Paths(vec!["/a/*".to_string(), "/b/*".to_string()]))
+        );
+        assert_eq!(d[2].matcher, Some(Matcher::Path("/old".to_string())));
+        assert_eq!(d[2].arguments.len(), 2);
+    }
+
+    #[test]
+    fn recovery_skips_broken_blocks() {
+        let tokens = tokenize(
+            "a.com {\n\
+             \tlog\n\
+             \n\
+             b.com {\n\
+             \trespond ok\n\
+             }\n\
+             (snip) oops\n\
+             c.com {\n\
+             \tlog\n\
+             }\n",
+        )
+        .unwrap();
+        let (cf, errors) = parse_with_recovery(&tokens, &ParseOptions::new());
+        let hosts: Vec<_> = cf
+            .sites
+            .iter()
+            .map(|s| s.addresses[0].host.as_str())
+            .collect();
+        assert_eq!(hosts, ["b.com", "c.com"]);
+        assert_eq!(
+            errors,
+            [
+                ParseError {
+                    kind: ParseErrorKind::ExpectedCloseBrace {
+                        found: Some("b.com".to_string())
+                    },
+                    span: Span::new(4, 1),
+                },
+                ParseError {
+                    kind: ParseErrorKind::ExpectedOpenBrace {
+                        found: Some("oops".to_string())
+                    },
+                    span: Span::new(7, 8),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn recovery_keeps_unindented_directives() {
+        let input = "a.com {\nlog\nfile_server {\n\tbrowse\n}\n}\n";
+        let tokens = tokenize(input).unwrap();
+        let (cf, errors) = parse_with_recovery(&tokens, &ParseOptions::new());
+        assert!(errors.is_empty());
+        assert_eq!(cf, parse_input(input).unwrap());
+    }
+
+    #[test]
+    fn braceless_single_site() {
+        let cf = parse_input("localhost\n\nreverse_proxy :3000\nlog\n").expect("parse failed");
+        assert_eq!(cf.sites.len(), 1);
+        let names: Vec<_> = cf.sites[0]
+            .directives
+            .iter()
+            .map(|d| d.name.as_str())
+            .collect();
+        assert_eq!(names, ["reverse_proxy", "log"]);
+        assert!(cf.sites[0].braceless);
+    }
 }
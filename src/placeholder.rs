@@ -0,0 +1,364 @@
+//! Runtime placeholders such as `{http.request.host}` or `{path}`.
+//!
+//! Placeholders are evaluated by Caddy per request, unlike `{$VAR}`
+//! environment references which are substituted when the config loads.
+
+use std::collections::BTreeMap;
+
+use crate::ast::{Argument, Caddyfile, Directive, Matcher};
+use crate::token::{Span, Token, TokenKind};
+
+/// Shorthand placeholders recognised by the Caddyfile adapter and the
+/// names they expand to.
+pub const SHORTHANDS: &[(&str, &str)] = &[
+    ("host", "http.request.host"),
+    ("hostport", "http.request.hostport"),
+    ("port", "http.request.port"),
+    ("orig_method", "http.request.orig_method"),
+    ("orig_uri", "http.request.orig_uri"),
+    ("orig_path", "http.request.orig_uri.path"),
+    ("orig_dir", "http.request.orig_uri.path.dir"),
+    ("orig_file", "http.request.orig_uri.path.file"),
+    ("orig_query", "http.request.orig_uri.query"),
+    ("orig_?query", "http.request.orig_uri.prefixed_query"),
+    ("method", "http.request.method"),
+    ("uri", "http.request.uri"),
+    ("path", "http.request.uri.path"),
+    ("dir", "http.request.uri.path.dir"),
+    ("file", "http.request.uri.path.file"),
+    ("query", "http.request.uri.query"),
+    ("?query", "http.request.uri.prefixed_query"),
+    ("remote", "http.request.remote"),
+    ("remote_host", "http.request.remote.host"),
+    ("remote_port", "http.request.remote.port"),
+    ("scheme", "http.request.scheme"),
+    ("uuid", "http.request.uuid"),
+    ("tls_cipher", "http.request.tls.cipher_suite"),
+    ("tls_version", "http.request.tls.version"),
+    (
+        "tls_client_fingerprint",
+        "http.request.tls.client.fingerprint",
+    ),
+    ("tls_client_issuer", "http.request.tls.client.issuer"),
+    ("tls_client_serial", "http.request.tls.client.serial"),
+    ("tls_client_subject", "http.request.tls.client.subject"),
+    (
+        "tls_client_certificate_pem",
+        "http.request.tls.client.certificate_pem",
+    ),
+    (
+        "tls_client_certificate_der_base64",
+        "http.request.tls.client.certificate_der_base64",
+    ),
+    ("upstream_hostport", "http.reverse_proxy.upstream.hostport"),
+    ("client_ip", "http.vars.client_ip"),
+];
+
+/// Shorthand prefixes such as `{header.X-Foo}`, with the prefix of
+/// their long form.
+pub const SHORTHAND_PREFIXES: &[(&str, &str)] = &[
+    ("header.", "http.request.header."),
+    ("cookie.", "http.request.cookie."),
+    ("labels.", "http.request.host.labels."),
+    ("path.", "http.request.uri.path."),
+    ("query.", "http.request.uri.query."),
+    ("re.", "http.regexp."),
+    ("vars.", "http.vars."),
+    ("rp.", "http.reverse_proxy."),
+    ("resp.", "http.intercept."),
+    ("err.", "http.error."),
+    ("file_match.", "http.matchers.file."),
+];
+
+/// A placeholder occurrence in the source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlaceholderRef {
+    /// Placeholder name without braces, e.g. `http.request.host`.
+    pub name: String,
+    /// Location of the opening brace.
+    pub span: Span,
+}
+
+impl PlaceholderRef {
+    /// Leading segment of the name (`http` for `http.request.host`,
+    /// `args` for `args[0]`), or `None` for undotted shorthands like
+    /// `path`.
+    #[must_use]
+    pub fn namespace(&self) -> Option<&str> {
+        self.name
+            .find(['.', '['])
+            .map(|pos| &self.name[..pos])
+            .filter(|ns| !ns.is_empty())
+    }
+}
+
+/// Find every runtime placeholder in a token stream.
+///
+/// Words, quoted strings, backtick strings and heredocs are scanned;
+/// environment references and escaped braces are skipped.
+#[must_use]
+pub fn scan(tokens: &[Token]) -> Vec<PlaceholderRef> {
+    let mut found = Vec::new();
+    for token in tokens {
+        let (line_offset, col_offset) = match &token.kind {
+            TokenKind::Word => (0, 0),
+            TokenKind::QuotedString | TokenKind::BacktickString => (0, 1),
+            TokenKind::Heredoc { .. } => (1, 0),
+            _ => continue,
+        };
+        for (offset, name) in find_in(&token.text) {
+            let before = &token.text[..offset];
+            let newlines = before.matches('\n').count();
+            let span = if newlines == 0 && line_offset == 0 {
+                Span {
+                    line: token.span.line,
+                    column: token.span.column + col_offset + before.chars().count(),
+                    file: token.span.file.clone(),
+                }
+            } else {
+                let line_start = before.rfind('\n').map_or(0, |p| p + 1);
+                Span {
+                    line: token.span.line + line_offset + newlines,
+                    column: before[line_start..].chars().count() + 1,
+                    file: token.span.file.clone(),
+                }
+            };
+            found.push(PlaceholderRef { name, span });
+        }
+    }
+    found
+}
+
+/// Group placeholders by namespace; shorthands are keyed by `None`.
+#[must_use]
+pub fn group_by_namespace(refs: &[PlaceholderRef]) -> BTreeMap<Option<&str>, Vec<&PlaceholderRef>> {
+    let mut groups: BTreeMap<Option<&str>, Vec<&PlaceholderRef>> = BTreeMap::new();
+    for r in refs {
+        groups.entry(r.namespace()).or_default().push(r);
+    }
+    groups
+}
+
+/// Long form of a shorthand placeholder name, e.g. `http.request.host`
+/// for `host`.
+#[must_use]
+pub fn expand_name(name: &str) -> Option<String> {
+    if let Some((_, long)) = SHORTHANDS.iter().find(|(short, _)| *short == name) {
+        return Some((*long).to_string());
+    }
+    SHORTHAND_PREFIXES.iter().find_map(|(short, long)| {
+        name.strip_prefix(short)
+            .filter(|rest| !rest.is_empty())
+            .map(|rest| format!("{long}{rest}"))
+    })
+}
+
+/// Shorthand for a long placeholder name, the inverse of `expand_name`.
+#[must_use]
+pub fn shorten_name(name: &str) -> Option<String> {
+    if let Some((short, _)) = SHORTHANDS.iter().find(|(_, long)| *long == name) {
+        return Some((*short).to_string());
+    }
+    SHORTHAND_PREFIXES.iter().find_map(|(short, long)| {
+        name.strip_prefix(long)
+            .filter(|rest| !rest.is_empty())
+            .map(|rest| format!("{short}{rest}"))
+    })
+}
+
+/// Replace shorthand placeholders with their long form throughout
+/// `caddyfile`.
+///
+/// Matchers and argument values are rewritten; addresses and directive
+/// names are left alone.
+pub fn expand_shorthands(caddyfile: &mut Caddyfile) {
+    rewrite_caddyfile(caddyfile, expand_name);
+}
+
+/// Replace long placeholder names with their shorthand throughout
+/// `caddyfile`, the inverse of `expand_shorthands`.
+pub fn collapse_shorthands(caddyfile: &mut Caddyfile) {
+    rewrite_caddyfile(caddyfile, shorten_name);
+}
+
+fn rewrite_caddyfile(caddyfile: &mut Caddyfile, f: fn(&str) -> Option<String>) {
+    if let Some(global) = &mut caddyfile.global_options {
+        rewrite_directives(&mut global.directives, f);
+    }
+    for snippet in &mut caddyfile.snippets {
+        rewrite_directives(&mut snippet.directives, f);
+    }
+    for route in &mut caddyfile.named_routes {
+        rewrite_directives(&mut route.directives, f);
+    }
+    for site in &mut caddyfile.sites {
+        rewrite_directives(&mut site.directives, f);
+    }
+}
+
+fn rewrite_directives(directives: &mut [Directive], f: fn(&str) -> Option<String>) {
+    for d in directives {
+        match &mut d.matcher {
+            Some(Matcher::Path(p)) => *p = rewrite(p, f),
+            Some(Matcher::Paths(paths)) => {
+                for p in paths {
+                    *p = rewrite(p, f);
+                }
+            }
+            Some(Matcher::Not { args, .. }) => {
+                for arg in args {
+                    rewrite_argument(arg, f);
+                }
+            }
+            Some(Matcher::All | Matcher::Named(_)) | None => {}
+        }
+        for arg in &mut d.arguments {
+            rewrite_argument(arg, f);
+        }
+        if let Some(block) = &mut d.block {
+            rewrite_directives(block, f);
+        }
+    }
+}
+
+fn rewrite_argument(arg: &mut Argument, f: fn(&str) -> Option<String>) {
+    match arg {
+        Argument::Unquoted(s) | Argument::Quoted(s) | Argument::Backtick(s) => *s = rewrite(s, f),
+        Argument::Heredoc { content, .. } => *content = rewrite(content, f),
+        Argument::EnvVar { .. } => {}
+    }
+}
+
+/// Rename every placeholder in `text` for which `f` returns a new name.
+fn rewrite(text: &str, f: fn(&str) -> Option<String>) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut last = 0;
+    for (offset, name) in find_in(text) {
+        if let Some(renamed) = f(&name) {
+            out.push_str(&text[last..=offset]);
+            out.push_str(&renamed);
+            last = offset + 1 + name.len();
+        }
+    }
+    out.push_str(&text[last..]);
+    out
+}
+
+/// Byte offsets and names of `{...}` placeholders in `text`.
+pub(crate) fn find_in(text: &str) -> Vec<(usize, String)> {
+    let bytes = text.as_bytes();
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'{' => {
+                let inner = &text[i + 1..];
+                let end = inner.find(|c: char| c == '}' || c == '{' || c.is_whitespace());
+                match end {
+                    Some(len) if inner.as_bytes()[len] == b'}' && len > 0 => {
+                        let name = &inner[..len];
+                        if !name.starts_with('$') {
+                            out.push((i, name.to_string()));
+                        }
+                        i += len + 2;
+                    }
+                    _ => i += 1,
+                }
+            }
+            _ => i += 1,
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{format, parse_str, tokenize};
+
+    fn names(input: &str) -> Vec<String> {
+        scan(&tokenize(input).expect("tokenize failed"))
+            .into_iter()
+            .map(|p| p.name)
+            .collect()
+    }
+
+    #[test]
+    fn finds_placeholders_in_words_and_strings() {
+        assert_eq!(
+            names("rewrite * /index.php?{query}&p={path}\nheader X-Host \"{http.request.host}\""),
+            ["query", "path", "http.request.host"]
+        );
+    }
+
+    #[test]
+    fn skips_env_vars_and_escapes() {
+        assert!(names("respond \\{literal\\} {$TOKEN} \"{$X:y}\"").is_empty());
+    }
+
+    #[test]
+    fn namespaces() {
+        let refs = scan(&tokenize("a /{http.request.uri}/{args[0]}/{path}").unwrap());
+        let ns: Vec<_> = refs.iter().map(PlaceholderRef::namespace).collect();
+        assert_eq!(ns, [Some("http"), Some("args"), None]);
+    }
+
+    #[test]
+    fn spans_point_at_opening_brace() {
+        let refs = scan(&tokenize("a\n  try_files /x/{path} \"q{query}\"").unwrap());
+        assert_eq!(refs[0].span, Span::new(2, 16));
+        assert_eq!(refs[1].span, Span::new(2, 25));
+
+        let refs = scan(&tokenize("respond <<EOF\nhi\n  {host}\nEOF\n").unwrap());
+        assert_eq!(refs[0].span, Span::new(3, 3));
+    }
+
+    #[test]
+    fn shorthand_names() {
+        assert_eq!(expand_name("host").as_deref(), Some("http.request.host"));
+        assert_eq!(
+            expand_name("header.X-Real-IP").as_deref(),
+            Some("http.request.header.X-Real-IP")
+        );
+        assert_eq!(expand_name("header."), None);
+        assert_eq!(expand_name("http.request.host"), None);
+        assert_eq!(
+            shorten_name("http.request.uri.path.dir").as_deref(),
+            Some("dir")
+        );
+        assert_eq!(shorten_name("http.vars.x").as_deref(), Some("vars.x"));
+        for (short, long) in SHORTHANDS {
+            assert_eq!(
+                shorten_name(&expand_name(short).unwrap()).as_deref(),
+                Some(*short)
+            );
+            assert_eq!(
+                expand_name(&shorten_name(long).unwrap()).as_deref(),
+                Some(*long)
+            );
+        }
+    }
+
+    #[test]
+    fn expand_and_collapse_caddyfile() {
+        let short = "a.com {\n\
+                     \trewrite * /index.php?{query}&p={path}\n\
+                     \theader X-Host \"{host} {$ENV}\"\n\
+                     \treverse_proxy /{labels.1}/* app\n\
+                     }\n";
+        let mut cf = parse_str(short).unwrap();
+        expand_shorthands(&mut cf);
+        let long = format(&cf);
+        assert_eq!(
+            long,
+            "a.com {\n\
+             \trewrite * /index.php?{http.request.uri.query}&p={http.request.uri.path}\n\
+             \theader X-Host \"{http.request.host} {$ENV}\"\n\
+             \treverse_proxy /{http.request.host.labels.1}/* app\n\
+             }\n"
+        );
+        collapse_shorthands(&mut cf);
+        assert_eq!(format(&cf), short);
+    }
+}
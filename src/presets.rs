@@ -0,0 +1,315 @@
+//! Ready-made site blocks for common deployments.
+//!
+//! Each preset returns a `SiteBlock` that can be extended further with
+//! the builder methods before it is added to a `Caddyfile`.
+//!
+//! ```
+//! use caddyfile_rs::{Caddyfile, format, presets};
+//!
+//! let cf = Caddyfile::new().site(presets::spa("app.example.com", "/srv/app").log());
+//! assert_eq!(
+//!     format(&cf),
+//!     "app.example.com {\n\
+//!      \troot * /srv/app\n\
+//!      \tencode zstd gzip\n\
+//!      \ttry_files \"{path}\" /index.html\n\
+//!      \tfile_server\n\
+//!      \tlog\n\
+//!      }\n"
+//! );
+//! ```
+
+use crate::ast::{Directive, Matcher, SiteBlock};
+use crate::builder::{Body, Encoding, MatcherDef, Respond, ReverseProxyBuilder};
+use crate::canonical::SITE_OPTIONS;
+use crate::directives::{DnsProvider, LbPolicy};
+
+/// A single-page application: files under `root`, with unknown paths
+/// served `index.html` so the client-side router can handle them.
+#[must_use]
+pub fn spa(address: &str, root: &str) -> SiteBlock {
+    SiteBlock::new(address)
+        .root(root)
+        .encode([Encoding::Zstd, Encoding::Gzip])
+        .try_files(["{path}", "/index.html"])
+        .file_server()
+}
+
+/// Static files under `root`.
+#[must_use]
+pub fn static_site(address: &str, root: &str) -> SiteBlock {
+    SiteBlock::new(address)
+        .root(root)
+        .encode([Encoding::Zstd, Encoding::Gzip])
+        .file_server()
+}
+
+/// A PHP application under `root` served through PHP-FPM listening on
+/// `fpm_socket`, either `host:port` or `unix//path/to/socket`.
+#[must_use]
+pub fn php_site(address: &str, root: &str, fpm_socket: &str) -> SiteBlock {
+    SiteBlock::new(address)
+        .root(root)
+        .encode([Encoding::Zstd, Encoding::Gzip])
+        .directive(Directive::new("php_fastcgi").arg(fpm_socket))
+        .file_server()
+}
+
+/// A gateway proxying every request to `upstreams`, balanced round
+/// robin when there are several.
+#[must_use]
+pub fn reverse_proxy_gateway<S: AsRef<str>>(
+    address: &str,
+    upstreams: impl IntoIterator<Item = S>,
+) -> SiteBlock {
+    let mut proxy = ReverseProxyBuilder::new();
+    let mut count = 0;
+    for upstream in upstreams {
+        proxy = proxy.to(upstream.as_ref());
+        count += 1;
+    }
+    if count > 1 {
+        proxy = proxy.lb_policy(LbPolicy::RoundRobin);
+    }
+    SiteBlock::new(address)
+        .encode([Encoding::Zstd, Encoding::Gzip])
+        .reverse_proxy_with(proxy)
+}
+
+/// A wildcard site for `*.<domain>`, with one certificate obtained
+/// through the DNS challenge with `provider`, proxying each subdomain
+/// in `hosts` to its upstream and aborting requests for any other.
+///
+/// ```
+/// use caddyfile_rs::directives::DnsProvider;
+/// use caddyfile_rs::{Caddyfile, format, presets};
+///
+/// let dns = DnsProvider::env_token("cloudflare", "CF_API_TOKEN");
+/// let site = presets::wildcard_site("example.com", &dns, [("app", "app:3000")]);
+/// assert_eq!(
+///     format(&Caddyfile::new().site(site)),
+///     "*.example.com {\n\
+///      \ttls {\n\
+///      \t\tdns cloudflare {$CF_API_TOKEN}\n\
+///      \t}\n\
+///      \n\
+///      \t@app host app.example.com\n\
+///      \n\
+///      \thandle @app {\n\
+///      \t\treverse_proxy app:3000\n\
+///      \t}\n\
+///      \n\
+///      \thandle {\n\
+///      \t\tabort\n\
+///      \t}\n\
+///      }\n"
+/// );
+/// ```
+#[must_use]
+pub fn wildcard_site<S: AsRef<str>>(
+    domain: &str,
+    provider: &DnsProvider,
+    hosts: impl IntoIterator<Item = (S, S)>,
+) -> SiteBlock {
+    let mut site = SiteBlock::new(&format!("*.{domain}")).tls_dns(provider);
+    for (sub, upstream) in hosts {
+        let sub = sub.as_ref();
+        let matcher = MatcherDef::new(sub).host(&format!("{sub}.{domain}"));
+        let handle = Directive::new("handle")
+            .matcher(matcher.matcher())
+            .block(vec![Directive::new("reverse_proxy").arg(upstream.as_ref())]);
+        site = site.directive(matcher.build()).directive(handle);
+    }
+    site.directive(Directive::new("handle").block(vec![Directive::new("abort")]))
+}
+
+/// Name of the matcher `enable_maintenance` adds.
+const MAINTENANCE: &str = "maintenance";
+
+/// A `503 Service Unavailable` response showing the HTML `page`.
+#[must_use]
+pub fn maintenance_page(page: &str) -> Respond {
+    Respond::new().status(503).body(Body::heredoc("HTML", page))
+}
+
+/// Put `site` into maintenance, answering with `maintenance_page(page)`.
+///
+/// Requests matching the conditions `allow` adds are served as usual;
+/// pass `|m| m` to allow none. Caddy runs only one `handle` per block
+/// and tries path-matched ones first, so the site's handlers move into
+/// a fallback `handle` next to the maintenance one. Site options such as
+/// `tls` and `log` stay at site level. `disable_maintenance` restores
+/// the site.
+///
+/// ```
+/// use caddyfile_rs::{Caddyfile, SiteBlock, format, presets};
+///
+/// let site = SiteBlock::new("example.com").reverse_proxy("app:3000");
+/// let down = presets::enable_maintenance(site.clone(), "<h1>Back soon</h1>", |m| {
+///     m.remote_ip("10.0.0.0/8")
+/// });
+/// assert_eq!(
+///     format(&Caddyfile::new().site(down.clone())),
+///     "example.com {\n\
+///      \t@maintenance not remote_ip 10.0.0.0/8\n\
+///      \n\
+///      \thandle @maintenance {\n\
+///      \t\trespond 503 {\n\
+///      \t\t\tbody <<HTML\n\
+///      \t\t\t<h1>Back soon</h1>\n\
+///      \t\t\tHTML\n\
+///      \t\t}\n\
+///      \t}\n\
+///      \n\
+///      \thandle {\n\
+///      \t\treverse_proxy app:3000\n\
+///      \t}\n\
+///      }\n"
+/// );
+/// assert_eq!(presets::disable_maintenance(down), site);
+/// ```
+#[must_use]
+pub fn enable_maintenance<F>(site: SiteBlock, page: &str, allow: F) -> SiteBlock
+where
+    F: FnOnce(MatcherDef) -> MatcherDef,
+{
+    let site = disable_maintenance(site);
+    let allowed = allow(MatcherDef::new(MAINTENANCE));
+    let matcher = if allowed.is_empty() {
+        MatcherDef::new(MAINTENANCE).path("*")
+    } else {
+        MatcherDef::new(MAINTENANCE).not(|_| allowed)
+    };
+    let handle = Directive::new("handle")
+        .matcher(matcher.matcher())
+        .block(vec![maintenance_page(page).build()]);
+
+    let (mut directives, handlers): (Vec<_>, Vec<_>) = site
+        .directives
+        .into_iter()
+        .partition(|d| SITE_OPTIONS.contains(&d.name.as_str()));
+    directives.push(matcher.build());
+    directives.push(handle);
+    if !handlers.is_empty() {
+        directives.push(Directive::new("handle").block(handlers));
+    }
+    SiteBlock { directives, ..site }
+}
+
+/// Take `site` out of maintenance by removing what
+/// `enable_maintenance` added and unwrapping the fallback `handle`.
+#[must_use]
+pub fn disable_maintenance(mut site: SiteBlock) -> SiteBlock {
+    let name = format!("@{MAINTENANCE}");
+    let matcher = Some(Matcher::Named(MAINTENANCE.to_string()));
+    let Some(i) = site
+        .directives
+        .iter()
+        .position(|d| d.name == "handle" && d.matcher == matcher)
+    else {
+        return site;
+    };
+    site.directives.remove(i);
+    let fallback = site
+        .directives
+        .get(i)
+        .is_some_and(|d| d.name == "handle" && d.matcher.is_none());
+    if fallback {
+        let handlers = site.directives.remove(i).block.unwrap_or_default();
+        site.directives.extend(handlers);
+    }
+    site.directives.retain(|d| d.name != name);
+    site
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routing::{Request, route};
+    use crate::{Caddyfile, format, parse_str};
+
+    fn text(site: SiteBlock) -> String {
+        let cf = Caddyfile::new().site(site);
+        let output = format(&cf);
+        assert_eq!(parse_str(&output).unwrap(), cf);
+        output
+    }
+
+    #[test]
+    fn maintenance_toggle() {
+        let site = spa("example.com", "/srv");
+        let down = enable_maintenance(site.clone(), "<h1>Down</h1>", |m| m);
+        let output = text(down.clone());
+        assert!(output.starts_with(
+            "example.com {\n\
+             \t@maintenance path *\n\
+             \n\
+             \thandle @maintenance {\n"
+        ));
+        // enabling twice keeps a single maintenance handler
+        let again = enable_maintenance(down, "<h1>Still down</h1>", |m| m);
+        assert_eq!(again.directives.len(), 3);
+        assert_eq!(disable_maintenance(again), site);
+    }
+
+    #[test]
+    fn maintenance_covers_path_handles() {
+        let site = SiteBlock::new("example.com")
+            .tls(&["internal"])
+            .handle(Matcher::path("/api/*"), |h| h.reverse_proxy("api:8080"))
+            .reverse_proxy("app:3000");
+        let down = enable_maintenance(site.clone(), "<h1>Down</h1>", |m| m.header("X-Bypass", "1"));
+        assert_eq!(down.directives[0].name, "tls");
+        let cf = Caddyfile::new().site(down.clone());
+        let names = |request: Request| route(&cf, &request).unwrap().names().join(" ");
+        let api = Request::new("GET", "example.com", "/api/items");
+        assert_eq!(names(api.clone()), "tls handle respond");
+        assert_eq!(
+            names(Request::new("GET", "example.com", "/")),
+            "tls handle respond"
+        );
+        assert_eq!(
+            names(api.header("X-Bypass", "1")),
+            "tls handle handle reverse_proxy reverse_proxy"
+        );
+        assert_eq!(disable_maintenance(down), site);
+    }
+
+    #[test]
+    fn presets() {
+        assert_eq!(
+            text(static_site("example.com", "/srv/www")),
+            "example.com {\n\troot * /srv/www\n\tencode zstd gzip\n\tfile_server\n}\n"
+        );
+        assert_eq!(
+            text(php_site(
+                "blog.example.com",
+                "/srv/wp",
+                "unix//run/php/fpm.sock"
+            )),
+            "blog.example.com {\n\
+             \troot * /srv/wp\n\
+             \tencode zstd gzip\n\
+             \tphp_fastcgi unix//run/php/fpm.sock\n\
+             \tfile_server\n\
+             }\n"
+        );
+        assert_eq!(
+            text(reverse_proxy_gateway("api.example.com", ["app:3000"])),
+            "api.example.com {\n\tencode zstd gzip\n\treverse_proxy app:3000\n}\n"
+        );
+        assert_eq!(
+            text(reverse_proxy_gateway(
+                "api.example.com",
+                ["a:3000", "b:3000"]
+            )),
+            "api.example.com {\n\
+             \tencode zstd gzip\n\
+             \n\
+             \treverse_proxy a:3000 b:3000 {\n\
+             \t\tlb_policy round_robin\n\
+             \t}\n\
+             }\n"
+        );
+    }
+}
@@ -0,0 +1,225 @@
+//! Round-trip verification of source text.
+//!
+//! `verify_roundtrip` parses a config, formats it, parses the result again
+//! and compares the two ASTs. A config that passes can be rewritten by
+//! this crate without changing its meaning.
+
+use crate::ast::{Caddyfile, Directive};
+use crate::selector::{NodePath, Root};
+use crate::{Error, format, parse_str};
+
+/// Why a config failed to round-trip.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum RoundtripReport {
+    /// The input itself does not parse.
+    #[error("input does not parse: {0}")]
+    Invalid(Error),
+    /// The formatted output does not parse.
+    #[error("formatted output does not parse: {error}")]
+    Reparse { formatted: String, error: Error },
+    /// The re-parsed AST differs from the original at `path`.
+    #[error("{path}: {detail}")]
+    Diverged {
+        path: NodePath,
+        detail: String,
+        formatted: String,
+    },
+}
+
+/// Parse, format, re-parse and compare `input`.
+///
+/// Returns the first node (in document order) whose re-parsed form
+/// differs from the original.
+pub fn verify_roundtrip(input: &str) -> Result<(), RoundtripReport> {
+    let original = parse_str(input).map_err(RoundtripReport::Invalid)?;
+    let formatted = format(&original);
+    let reparsed = match parse_str(&formatted) {
+        Ok(cf) => cf,
+        Err(error) => return Err(RoundtripReport::Reparse { formatted, error }),
+    };
+    match first_divergence(&original, &reparsed) {
+        Some((path, detail)) => Err(RoundtripReport::Diverged {
+            path,
+            detail,
+            formatted,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Locate the first difference between two ASTs.
+#[must_use]
+pub fn first_divergence(a: &Caddyfile, b: &Caddyfile) -> Option<(NodePath, String)> {
+    match (&a.global_options, &b.global_options) {
+        (Some(ga), Some(gb)) => {
+            if let Some(d) = compare_directives(Root::Global, &ga.directives, &gb.directives) {
+                return Some(d);
+            }
+        }
+        (None, None) => {}
+        (Some(_), None) => return Some((root(Root::Global), "global options lost".into())),
+        (None, Some(_)) => return Some((root(Root::Global), "global options added".into())),
+    }
+
+    let snippets = a.snippets.iter().zip(&b.snippets).enumerate();
+    for (i, (sa, sb)) in snippets {
+        if sa.name != sb.name {
+            return Some(renamed(Root::Snippet(i), &sa.name, &sb.name));
+        }
+        if let Some(d) = compare_directives(Root::Snippet(i), &sa.directives, &sb.directives) {
+            return Some(d);
+        }
+    }
+    if let Some(d) = compare_counts("snippet", a.snippets.len(), b.snippets.len(), Root::Snippet) {
+        return Some(d);
+    }
+
+    let routes = a.named_routes.iter().zip(&b.named_routes).enumerate();
+    for (i, (ra, rb)) in routes {
+        if ra.name != rb.name {
+            return Some(renamed(Root::NamedRoute(i), &ra.name, &rb.name));
+        }
+        if let Some(d) = compare_directives(Root::NamedRoute(i), &ra.directives, &rb.directives) {
+            return Some(d);
+        }
+    }
+    if let Some(d) = compare_counts(
+        "named route",
+        a.named_routes.len(),
+        b.named_routes.len(),
+        Root::NamedRoute,
+    ) {
+        return Some(d);
+    }
+
+    for (i, (sa, sb)) in a.sites.iter().zip(&b.sites).enumerate() {
+        if sa.addresses != sb.addresses {
+            let list = |s: &crate::ast::SiteBlock| {
+                s.addresses
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            return Some((
+                root(Root::Site(i)),
+                format!("addresses '{}' became '{}'", list(sa), list(sb)),
+            ));
+        }
+        if let Some(d) = compare_directives(Root::Site(i), &sa.directives, &sb.directives) {
+            return Some(d);
+        }
+    }
+    compare_counts("site", a.sites.len(), b.sites.len(), Root::Site)
+}
+
+const fn root(root: Root) -> NodePath {
+    NodePath {
+        root,
+        directives: Vec::new(),
+    }
+}
+
+fn renamed(at: Root, a: &str, b: &str) -> (NodePath, String) {
+    (root(at), format!("name '{a}' became '{b}'"))
+}
+
+fn compare_counts(
+    what: &str,
+    a: usize,
+    b: usize,
+    at: fn(usize) -> Root,
+) -> Option<(NodePath, String)> {
+    (a != b).then(|| (root(at(a.min(b))), format!("{a} {what}(s) became {b}")))
+}
+
+fn compare_directives(at: Root, a: &[Directive], b: &[Directive]) -> Option<(NodePath, String)> {
+    let mut path = root(at);
+    walk(&mut path, a, b)
+}
+
+fn walk(path: &mut NodePath, a: &[Directive], b: &[Directive]) -> Option<(NodePath, String)> {
+    for (i, (da, db)) in a.iter().zip(b).enumerate() {
+        path.directives.push(i);
+        let detail = if da.name != db.name {
+            Some(format!("directive '{}' became '{}'", da.name, db.name))
+        } else if da.matcher != db.matcher {
+            Some(format!(
+                "matcher of '{}' changed: {:?} became {:?}",
+                da.name, da.matcher, db.matcher
+            ))
+        } else if da.arguments != db.arguments {
+            Some(format!(
+                "arguments of '{}' changed: {:?} became {:?}",
+                da.name, da.arguments, db.arguments
+            ))
+        } else {
+            None
+        };
+        if let Some(detail) = detail {
+            return Some((path.clone(), detail));
+        }
+        match (&da.block, &db.block) {
+            (Some(ba), Some(bb)) => {
+                if let Some(d) = walk(path, ba, bb) {
+                    return Some(d);
+                }
+            }
+            (None, None) => {}
+            (Some(_), None) => {
+                return Some((path.clone(), format!("block of '{}' lost", da.name)));
+            }
+            (None, Some(_)) => {
+                return Some((path.clone(), format!("block of '{}' added", da.name)));
+            }
+        }
+        path.directives.pop();
+    }
+    (a.len() != b.len()).then(|| {
+        (
+            path.clone(),
+            format!("{} directive(s) became {}", a.len(), b.len()),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Argument;
+
+    #[test]
+    fn accepts_supported_config() {
+        verify_roundtrip(
+            "{\n\temail a@b.c\n}\n\n(common) {\n\tencode gzip\n}\n\n\
+             example.com {\n\timport common\n\treverse_proxy /api/* app:3000\n}\n",
+        )
+        .expect("round-trip");
+    }
+
+    #[test]
+    fn invalid_input() {
+        assert!(matches!(
+            verify_roundtrip("example.com {\n"),
+            Err(RoundtripReport::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn reports_diverging_node() {
+        let a = parse_str("a.com {\n\thandle {\n\t\trespond 1\n\t}\n}\n").unwrap();
+        let mut b = a.clone();
+        b.sites[0].directives[0].block.as_mut().unwrap()[0].arguments[0] =
+            Argument::Unquoted("2".into());
+
+        let (path, detail) = first_divergence(&a, &b).expect("divergence");
+        assert_eq!(path.to_string(), "site[0] > 0 > 0");
+        assert!(detail.starts_with("arguments of 'respond' changed"));
+
+        b.sites.push(b.sites[0].clone());
+        b.sites[0] = a.sites[0].clone();
+        let (path, detail) = first_divergence(&a, &b).expect("divergence");
+        assert_eq!(path.to_string(), "site[1]");
+        assert_eq!(detail, "1 site(s) became 2");
+    }
+}
@@ -0,0 +1,392 @@
+//! Request routing simulation.
+//!
+//! [`route`] works out which site block a request lands on and which
+//! directives would handle it, without running Caddy.
+//!
+//! Sites are chosen the way Caddy does: an exact host beats a wildcard
+//! such as `*.example.com`, which beats an address without a host, and
+//! among those a longer site path wins. Inside the site, `handle` blocks
+//! are mutually exclusive: the most specific path matcher is tried first,
+//! then other matchers in source order, then a bare `handle`. `route`
+//! blocks apply their contents in order, `handle_path` strips its prefix
+//! before routing its contents, and `handle_errors` is skipped.
+//!
+//! Named matchers are evaluated for the `path`, `method`, `host`,
+//! `header` and `not` modules. Modules that depend on more than the
+//! request line and headers, such as `remote_ip` or `expression`, never
+//! match. Imports are not expanded, and directives are reported in
+//! source order rather than Caddy's directive order.
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+use crate::ast::{Address, Caddyfile, Directive, Matcher, SiteBlock};
+use crate::directives::values;
+use crate::selector::glob;
+
+/// A request to route.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Request {
+    pub method: String,
+    /// Host name without port.
+    pub host: String,
+    /// Path without query string.
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+}
+
+/// Where a request ends up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Route<'a> {
+    pub site: &'a SiteBlock,
+    /// The site address that matched.
+    pub address: &'a Address,
+    /// Directives that apply, in source order. A `handle` or `route`
+    /// block is listed before the directives it applies.
+    pub directives: Vec<&'a Directive>,
+}
+
+impl Request {
+    /// A request without headers.
+    #[must_use]
+    pub fn new(method: &str, host: &str, path: &str) -> Self {
+        Self {
+            method: method.to_string(),
+            host: host.to_string(),
+            path: path.to_string(),
+            headers: Vec::new(),
+        }
+    }
+
+    /// Add a request header.
+    #[must_use]
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+impl Route<'_> {
+    /// Names of the applied directives, in order.
+    #[must_use]
+    pub fn names(&self) -> Vec<&str> {
+        self.directives.iter().map(|d| d.name.as_str()).collect()
+    }
+}
+
+/// Route `request` through `cf`, or `None` when no site serves it.
+#[must_use]
+pub fn route<'a>(cf: &'a Caddyfile, request: &Request) -> Option<Route<'a>> {
+    let (site, address) = cf
+        .sites
+        .iter()
+        .flat_map(|site| site.addresses.iter().map(move |a| (site, a)))
+        .filter_map(|(site, a)| site_rank(a, request).map(|rank| (rank, site, a)))
+        .max_by_key(|(rank, ..)| *rank)
+        .map(|(_, site, a)| (site, a))?;
+
+    let mut matchers = HashMap::new();
+    collect_matchers(&site.directives, &mut matchers);
+    let mut directives = Vec::new();
+    Router { matchers }.walk(&site.directives, request, &mut directives);
+    Some(Route {
+        site,
+        address,
+        directives,
+    })
+}
+
+/// How specifically `address` serves `request`, if at all: host rank
+/// first, then site path length.
+fn site_rank(address: &Address, request: &Request) -> Option<(u8, usize)> {
+    let address = address.parsed();
+    let host_rank = if address.host.is_empty() {
+        1
+    } else if address.host.eq_ignore_ascii_case(&request.host) {
+        3
+    } else if address.host.contains('*') && host_matches(&address.host, &request.host) {
+        2
+    } else {
+        return None;
+    };
+    match &address.path {
+        Some(path) if !path_matches(path, &request.path) => None,
+        path => Some((host_rank, path.as_ref().map_or(0, String::len))),
+    }
+}
+
+/// Collect named matcher definitions from `directives` and their blocks.
+fn collect_matchers<'a>(directives: &'a [Directive], out: &mut HashMap<&'a str, &'a Directive>) {
+    for d in directives {
+        if let Some(name) = d.name.strip_prefix('@') {
+            out.insert(name, d);
+        } else if let Some(block) = &d.block {
+            collect_matchers(block, out);
+        }
+    }
+}
+
+struct Router<'a> {
+    matchers: HashMap<&'a str, &'a Directive>,
+}
+
+impl<'a> Router<'a> {
+    fn walk(&self, directives: &'a [Directive], request: &Request, out: &mut Vec<&'a Directive>) {
+        let mut handles: Vec<&Directive> = directives
+            .iter()
+            .filter(|d| matches!(d.name.as_str(), "handle" | "handle_path"))
+            .collect();
+        handles.sort_by_key(|d| handle_order(d));
+        let handle = handles
+            .into_iter()
+            .find(|d| self.matches(d.matcher.as_ref(), request));
+
+        for d in directives {
+            match d.name.as_str() {
+                "handle" | "handle_path" if handle.is_some_and(|h| std::ptr::eq(h, d)) => {
+                    out.push(d);
+                    let inner = strip_handle_path(d, request);
+                    self.walk(d.block.as_deref().unwrap_or_default(), &inner, out);
+                }
+                "handle" | "handle_path" | "handle_errors" => {}
+                name if name.starts_with('@') => {}
+                _ if self.matches(d.matcher.as_ref(), request) => {
+                    out.push(d);
+                    if d.name == "route" {
+                        self.walk(d.block.as_deref().unwrap_or_default(), request, out);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn matches(&self, matcher: Option<&Matcher>, request: &Request) -> bool {
+        match matcher {
+            None | Some(Matcher::All) => true,
+            Some(Matcher::Path(path)) => path_matches(path, &request.path),
+            Some(Matcher::Paths(paths)) => paths.iter().any(|p| path_matches(p, &request.path)),
+            Some(Matcher::Named(name)) => self
+                .matchers
+                .get(name.as_str())
+                .is_some_and(|d| definition_matches(d, request)),
+            Some(Matcher::Not { module, args }) => {
+                let args: Vec<String> = args.iter().map(|a| a.value().into_owned()).collect();
+                !condition(module, &args, request)
+            }
+        }
+    }
+}
+
+/// Evaluate a named matcher definition, `@name module args` or a
+/// block of conditions which must all hold.
+fn definition_matches(d: &Directive, request: &Request) -> bool {
+    if let Some(Matcher::Not { module, args }) = &d.matcher {
+        let args: Vec<String> = args.iter().map(|a| a.value().into_owned()).collect();
+        return !condition(module, &args, request);
+    }
+    if let Some(block) = &d.block {
+        return block_matches(block, request);
+    }
+    values(d)
+        .split_first()
+        .is_some_and(|(module, args)| condition(module, args, request))
+}
+
+/// All conditions of a matcher block. Repeated `path`, `method` and
+/// `host` lines are merged, so any of their values may match.
+fn block_matches(block: &[Directive], request: &Request) -> bool {
+    let mut merged: HashMap<&str, Vec<String>> = HashMap::new();
+    for sub in block {
+        match sub.name.as_str() {
+            "not" => {
+                let negated = sub.block.as_ref().map_or_else(
+                    || {
+                        values(sub)
+                            .split_first()
+                            .is_some_and(|(module, args)| condition(module, args, request))
+                    },
+                    |block| block_matches(block, request),
+                );
+                if negated {
+                    return false;
+                }
+            }
+            module @ ("path" | "method" | "host") => {
+                merged.entry(module).or_default().extend(values(sub));
+            }
+            module => {
+                if !condition(module, &values(sub), request) {
+                    return false;
+                }
+            }
+        }
+    }
+    merged
+        .iter()
+        .all(|(module, args)| condition(module, args, request))
+}
+
+/// Evaluate one matcher module.
+fn condition(module: &str, args: &[String], request: &Request) -> bool {
+    match module {
+        "path" => args.iter().any(|p| path_matches(p, &request.path)),
+        "method" => args.iter().any(|m| m.eq_ignore_ascii_case(&request.method)),
+        "host" => args.iter().any(|h| host_matches(h, &request.host)),
+        "header" => args.split_first().is_some_and(|(field, expected)| {
+            request
+                .headers
+                .iter()
+                .filter(|(name, _)| name.eq_ignore_ascii_case(field))
+                .any(|(_, value)| expected.is_empty() || expected.iter().any(|e| glob(e, value)))
+        }),
+        "not" => !args
+            .split_first()
+            .is_some_and(|(module, args)| condition(module, args, request)),
+        _ => false,
+    }
+}
+
+/// Sort key trying `handle` blocks the way Caddy orders them.
+fn handle_order(d: &Directive) -> (u8, Reverse<usize>) {
+    match &d.matcher {
+        Some(Matcher::Path(path)) => (0, Reverse(path.len())),
+        Some(Matcher::Paths(paths)) => {
+            (0, Reverse(paths.iter().map(String::len).max().unwrap_or(0)))
+        }
+        Some(_) => (1, Reverse(0)),
+        None => (2, Reverse(0)),
+    }
+}
+
+/// The request seen inside `d`: `handle_path` strips its path prefix.
+fn strip_handle_path(d: &Directive, request: &Request) -> Request {
+    let mut inner = request.clone();
+    if d.name == "handle_path" {
+        if let Some(Matcher::Path(path)) = &d.matcher {
+            let prefix = path.trim_end_matches('*').trim_end_matches('/');
+            if let Some(rest) = request.path.strip_prefix(prefix) {
+                inner.path = if rest.starts_with('/') {
+                    rest.to_string()
+                } else {
+                    format!("/{rest}")
+                };
+            }
+        }
+    }
+    inner
+}
+
+/// Case-insensitive path match where `*` matches any run of characters.
+fn path_matches(pattern: &str, path: &str) -> bool {
+    glob(&pattern.to_ascii_lowercase(), &path.to_ascii_lowercase())
+}
+
+/// Host match where a `*` label matches exactly one label.
+fn host_matches(pattern: &str, host: &str) -> bool {
+    let pattern: Vec<&str> = pattern.split('.').collect();
+    let host: Vec<&str> = host.split('.').collect();
+    pattern.len() == host.len()
+        && pattern
+            .iter()
+            .zip(&host)
+            .all(|(p, h)| *p == "*" || p.eq_ignore_ascii_case(h))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+
+    const INPUT: &str = "\
+example.com {
+\t@post {
+\t\tmethod POST
+\t\tpath /api/*
+\t}
+\t@internal header X-Internal 1
+\tencode gzip
+\theader /api/* Cache-Control no-store
+\thandle @post {
+\t\treverse_proxy writer:8080
+\t}
+\thandle /api/* {
+\t\treverse_proxy reader:8080
+\t}
+\thandle_path /static/* {
+\t\troute /css/* {
+\t\t\theader Content-Type text/css
+\t\t}
+\t\tfile_server
+\t}
+\thandle @internal {
+\t\trespond \"internal\"
+\t}
+\thandle {
+\t\trespond \"home\"
+\t}
+\thandle_errors {
+\t\trespond \"oops\"
+\t}
+}
+
+*.example.com {
+\trespond \"wildcard\"
+}
+
+:8080 {
+\trespond \"fallback\"
+}
+";
+
+    fn names(request: &Request) -> Vec<String> {
+        let cf = parse_str(INPUT).unwrap();
+        let route = route(&cf, request).unwrap();
+        route.names().into_iter().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn selects_site() {
+        let cf = parse_str(INPUT).unwrap();
+        let host =
+            |h: &str| route(&cf, &Request::new("GET", h, "/")).map(|r| r.address.to_string());
+        assert_eq!(host("EXAMPLE.com").as_deref(), Some("example.com"));
+        assert_eq!(host("www.example.com").as_deref(), Some("*.example.com"));
+        assert_eq!(host("a.b.example.com").as_deref(), Some(":8080"));
+
+        let cf = parse_str("example.com {\n\trespond 1\n}\n").unwrap();
+        assert!(route(&cf, &Request::new("GET", "other.com", "/")).is_none());
+    }
+
+    #[test]
+    fn handles_are_exclusive() {
+        assert_eq!(
+            names(&Request::new("POST", "example.com", "/api/items")),
+            ["encode", "header", "handle", "reverse_proxy"]
+        );
+        let cf = parse_str(INPUT).unwrap();
+        let r = route(&cf, &Request::new("GET", "example.com", "/api/items")).unwrap();
+        assert_eq!(r.directives[3].arguments[0].value(), "reader:8080");
+
+        assert_eq!(
+            names(&Request::new("GET", "example.com", "/")),
+            ["encode", "handle", "respond"]
+        );
+        let internal = Request::new("GET", "example.com", "/").header("x-internal", "1");
+        let cf = parse_str(INPUT).unwrap();
+        let r = route(&cf, &internal).unwrap();
+        assert_eq!(r.directives[2].arguments[0].value(), "internal");
+    }
+
+    #[test]
+    fn handle_path_strips_prefix() {
+        assert_eq!(
+            names(&Request::new("GET", "example.com", "/static/css/site.css")),
+            ["encode", "handle_path", "route", "header", "file_server"]
+        );
+        assert_eq!(
+            names(&Request::new("GET", "example.com", "/static/app.js")),
+            ["encode", "handle_path", "file_server"]
+        );
+    }
+}
@@ -0,0 +1,586 @@
+//! Node selectors for locating blocks and directives in a Caddyfile AST.
+//!
+//! A selector is a list of steps separated by whitespace (any descendant)
+//! or `>` (direct child). The first step may name a top-level block kind:
+//! `global`, `snippet`, `named_route` or `site`; every other step matches
+//! directives by name (`*` matches any name). Steps take optional
+//! `[key=value]` filters where `*` in the value is a wildcard:
+//!
+//! ```text
+//! site[host=*.example.com] reverse_proxy
+//! snippet[name=security] > header
+//! handle[matcher=/api/*] > reverse_proxy[arg0=app:3000]
+//! ```
+//!
+//! Supported filter keys are `host` and `address` for sites, `name` for
+//! snippets and named routes, and `name`, `matcher`, `arg` (any argument)
+//! and `argN` (argument at index `N`) for directives. A filter without
+//! `=` only checks that the key is present.
+
+use std::fmt;
+
+use crate::ast::{Caddyfile, Directive, GlobalOptions, NamedRoute, SiteBlock, Snippet};
+
+/// Error produced when a selector string is malformed.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum SelectorError {
+    /// The selector contains no steps.
+    #[error("empty selector")]
+    Empty,
+    /// A `[` filter is never closed.
+    #[error("unclosed filter in step '{0}'")]
+    UnclosedFilter(String),
+    /// A `>` combinator is not followed by a step.
+    #[error("dangling '>' combinator")]
+    DanglingCombinator,
+    /// A filter key that does not apply to the step.
+    #[error("unknown filter key '{key}' for '{step}'")]
+    UnknownFilter { step: String, key: String },
+}
+
+/// Top-level block a node lives in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Root {
+    Global,
+    Snippet(usize),
+    NamedRoute(usize),
+    Site(usize),
+}
+
+/// Location of a node: a top-level block plus directive indices
+/// descending through nested sub-blocks. An empty index list refers to
+/// the block itself.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodePath {
+    pub root: Root,
+    pub directives: Vec<usize>,
+}
+
+/// A node reached through a `NodePath`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Node<'a> {
+    Global(&'a GlobalOptions),
+    Snippet(&'a Snippet),
+    NamedRoute(&'a NamedRoute),
+    Site(&'a SiteBlock),
+    Directive(&'a Directive),
+}
+
+/// A parsed selector.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selector {
+    steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Step {
+    /// Combinator linking this step to the previous one.
+    child: bool,
+    kind: StepKind,
+    filters: Vec<Filter>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum StepKind {
+    Global,
+    Snippet,
+    NamedRoute,
+    Site,
+    Directive(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Filter {
+    key: String,
+    value: Option<String>,
+}
+
+impl Selector {
+    /// Parse a selector string.
+    pub fn parse(input: &str) -> Result<Self, SelectorError> {
+        let mut steps = Vec::new();
+        let mut chars = input.chars().peekable();
+        let mut child = false;
+
+        loop {
+            while chars.peek().is_some_and(|c| c.is_whitespace()) {
+                chars.next();
+            }
+            match chars.peek() {
+                None => break,
+                Some('>') => {
+                    chars.next();
+                    child = true;
+                    continue;
+                }
+                Some(_) => {}
+            }
+
+            let mut text = String::new();
+            let mut depth = 0usize;
+            while let Some(&c) = chars.peek() {
+                if depth == 0 && (c.is_whitespace() || c == '>') {
+                    break;
+                }
+                match c {
+                    '[' => depth += 1,
+                    ']' => depth = depth.saturating_sub(1),
+                    _ => {}
+                }
+                text.push(c);
+                chars.next();
+            }
+            if depth > 0 {
+                return Err(SelectorError::UnclosedFilter(text));
+            }
+
+            steps.push(Step::parse(&text, child, steps.is_empty())?);
+            child = false;
+        }
+
+        if child {
+            return Err(SelectorError::DanglingCombinator);
+        }
+        if steps.is_empty() {
+            return Err(SelectorError::Empty);
+        }
+        Ok(Self { steps })
+    }
+
+    /// Paths of all nodes matching this selector, in document order.
+    #[must_use]
+    pub fn select(&self, cf: &Caddyfile) -> Vec<NodePath> {
+        let (first, rest) = self.steps.split_first().expect("selector has steps");
+
+        let mut current: Vec<NodePath> = match &first.kind {
+            StepKind::Directive(_) => roots(cf)
+                .into_iter()
+                .flat_map(|root| {
+                    let base = NodePath {
+                        root,
+                        directives: Vec::new(),
+                    };
+                    descendants(cf, &base, false)
+                })
+                .filter(|p| first.matches(cf, p))
+                .collect(),
+            _ => roots(cf)
+                .into_iter()
+                .map(|root| NodePath {
+                    root,
+                    directives: Vec::new(),
+                })
+                .filter(|p| first.matches(cf, p))
+                .collect(),
+        };
+
+        for step in rest {
+            let mut next: Vec<NodePath> = current
+                .iter()
+                .flat_map(|p| descendants(cf, p, step.child))
+                .filter(|p| step.matches(cf, p))
+                .collect();
+            next.sort();
+            next.dedup();
+            current = next;
+        }
+
+        current
+    }
+}
+
+impl std::str::FromStr for Selector {
+    type Err = SelectorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, step) in self.steps.iter().enumerate() {
+            if i > 0 {
+                f.write_str(if step.child { " > " } else { " " })?;
+            }
+            match &step.kind {
+                StepKind::Global => f.write_str("global")?,
+                StepKind::Snippet => f.write_str("snippet")?,
+                StepKind::NamedRoute => f.write_str("named_route")?,
+                StepKind::Site => f.write_str("site")?,
+                StepKind::Directive(name) => f.write_str(name)?,
+            }
+            for filter in &step.filters {
+                match &filter.value {
+                    Some(v) => write!(f, "[{}={v}]", filter.key)?,
+                    None => write!(f, "[{}]", filter.key)?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Written as the root block followed by directive indices, e.g.
+/// `site[1] > 0 > 2`.
+impl fmt::Display for NodePath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.root {
+            Root::Global => f.write_str("global")?,
+            Root::Snippet(i) => write!(f, "snippet[{i}]")?,
+            Root::NamedRoute(i) => write!(f, "named_route[{i}]")?,
+            Root::Site(i) => write!(f, "site[{i}]")?,
+        }
+        for i in &self.directives {
+            write!(f, " > {i}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Step {
+    fn parse(text: &str, child: bool, first: bool) -> Result<Self, SelectorError> {
+        let (name, mut rest) = text
+            .find('[')
+            .map_or((text, ""), |pos| (&text[..pos], &text[pos..]));
+
+        let kind = match name {
+            "global" if first => StepKind::Global,
+            "snippet" if first => StepKind::Snippet,
+            "named_route" if first => StepKind::NamedRoute,
+            "site" if first => StepKind::Site,
+            _ => StepKind::Directive(name.to_string()),
+        };
+
+        let mut filters = Vec::new();
+        while let Some(inner) = rest.strip_prefix('[') {
+            let end = inner
+                .find(']')
+                .ok_or_else(|| SelectorError::UnclosedFilter(text.to_string()))?;
+            let body = &inner[..end];
+            let (key, value) = body.split_once('=').map_or((body, None), |(k, v)| {
+                (k, Some(v.trim_matches('"').to_string()))
+            });
+            if !kind.accepts(key) {
+                return Err(SelectorError::UnknownFilter {
+                    step: name.to_string(),
+                    key: key.to_string(),
+                });
+            }
+            filters.push(Filter {
+                key: key.to_string(),
+                value,
+            });
+            rest = &inner[end + 1..];
+        }
+
+        Ok(Self {
+            child,
+            kind,
+            filters,
+        })
+    }
+
+    fn matches(&self, cf: &Caddyfile, path: &NodePath) -> bool {
+        let Some(node) = cf.node(path) else {
+            return false;
+        };
+        let kind_ok = match (&self.kind, node) {
+            (StepKind::Global, Node::Global(_))
+            | (StepKind::Snippet, Node::Snippet(_))
+            | (StepKind::NamedRoute, Node::NamedRoute(_))
+            | (StepKind::Site, Node::Site(_)) => true,
+            (StepKind::Directive(name), Node::Directive(d)) => glob(name, &d.name),
+            _ => false,
+        };
+        kind_ok && self.filters.iter().all(|f| f.matches(node))
+    }
+}
+
+impl StepKind {
+    fn accepts(&self, key: &str) -> bool {
+        match self {
+            Self::Global => false,
+            Self::Site => matches!(key, "host" | "address"),
+            Self::Snippet | Self::NamedRoute => key == "name",
+            Self::Directive(_) => {
+                matches!(key, "name" | "matcher" | "arg")
+                    || key
+                        .strip_prefix("arg")
+                        .is_some_and(|n| n.parse::<usize>().is_ok())
+            }
+        }
+    }
+}
+
+impl Filter {
+    fn matches(&self, node: Node<'_>) -> bool {
+        let candidates: Vec<String> = match (node, self.key.as_str()) {
+            (Node::Site(site), "host") => site
+                .addresses
+                .iter()
+                .map(|a| a.parsed().host.clone())
+                .collect(),
+            (Node::Site(site), "address") => {
+                site.addresses.iter().map(ToString::to_string).collect()
+            }
+            (Node::Snippet(s), "name") => vec![s.name.clone()],
+            (Node::NamedRoute(r), "name") => vec![r.name.clone()],
+            (Node::Directive(d), "name") => vec![d.name.clone()],
+            (Node::Directive(d), "matcher") => d.matcher.iter().map(ToString::to_string).collect(),
+            (Node::Directive(d), "arg") => {
+                d.arguments.iter().map(|a| a.value().to_string()).collect()
+            }
+            (Node::Directive(d), key) => key
+                .strip_prefix("arg")
+                .and_then(|n| n.parse::<usize>().ok())
+                .and_then(|i| d.arguments.get(i))
+                .map(|a| vec![a.value().to_string()])
+                .unwrap_or_default(),
+            _ => Vec::new(),
+        };
+        self.value
+            .as_ref()
+            .map_or(!candidates.is_empty(), |pattern| {
+                candidates.iter().any(|c| glob(pattern, c))
+            })
+    }
+}
+
+impl Caddyfile {
+    /// Resolve a node path to the node it refers to.
+    #[must_use]
+    pub fn node(&self, path: &NodePath) -> Option<Node<'_>> {
+        let Some((first, rest)) = path.directives.split_first() else {
+            return Some(match path.root {
+                Root::Global => Node::Global(self.global_options.as_ref()?),
+                Root::Snippet(i) => Node::Snippet(self.snippets.get(i)?),
+                Root::NamedRoute(i) => Node::NamedRoute(self.named_routes.get(i)?),
+                Root::Site(i) => Node::Site(self.sites.get(i)?),
+            });
+        };
+        let mut d = self.root_directives(path.root)?.get(*first)?;
+        for &i in rest {
+            d = d.block.as_ref()?.get(i)?;
+        }
+        Some(Node::Directive(d))
+    }
+
+    /// Mutable access to the directive at `path`, if it is one.
+    pub fn directive_at_mut(&mut self, path: &NodePath) -> Option<&mut Directive> {
+        let (first, rest) = path.directives.split_first()?;
+        let mut d = self.root_directives_mut(path.root)?.get_mut(*first)?;
+        for &i in rest {
+            d = d.block.as_mut()?.get_mut(i)?;
+        }
+        Some(d)
+    }
+
+    /// Mutable access to the directive list a node path points into:
+    /// the block's own list for a root path, or a directive's sub-block.
+    pub fn directives_at_mut(&mut self, path: &NodePath) -> Option<&mut Vec<Directive>> {
+        if path.directives.is_empty() {
+            self.root_directives_mut(path.root)
+        } else {
+            self.directive_at_mut(path)?.block.as_mut()
+        }
+    }
+
+    /// Remove the node at `path`, returning whether anything was removed.
+    ///
+    /// When removing several nodes, remove them in reverse document
+    /// order so earlier paths stay valid.
+    pub fn remove_node(&mut self, path: &NodePath) -> bool {
+        let Some((last, parent)) = path.directives.split_last() else {
+            return match path.root {
+                Root::Global => self.global_options.take().is_some(),
+                Root::Snippet(i) => remove_at(&mut self.snippets, i),
+                Root::NamedRoute(i) => remove_at(&mut self.named_routes, i),
+                Root::Site(i) => remove_at(&mut self.sites, i),
+            };
+        };
+        let parent = NodePath {
+            root: path.root,
+            directives: parent.to_vec(),
+        };
+        self.directives_at_mut(&parent)
+            .is_some_and(|list| remove_at(list, *last))
+    }
+
+    fn root_directives(&self, root: Root) -> Option<&Vec<Directive>> {
+        Some(match root {
+            Root::Global => &self.global_options.as_ref()?.directives,
+            Root::Snippet(i) => &self.snippets.get(i)?.directives,
+            Root::NamedRoute(i) => &self.named_routes.get(i)?.directives,
+            Root::Site(i) => &self.sites.get(i)?.directives,
+        })
+    }
+
+    fn root_directives_mut(&mut self, root: Root) -> Option<&mut Vec<Directive>> {
+        Some(match root {
+            Root::Global => &mut self.global_options.as_mut()?.directives,
+            Root::Snippet(i) => &mut self.snippets.get_mut(i)?.directives,
+            Root::NamedRoute(i) => &mut self.named_routes.get_mut(i)?.directives,
+            Root::Site(i) => &mut self.sites.get_mut(i)?.directives,
+        })
+    }
+}
+
+fn remove_at<T>(list: &mut Vec<T>, i: usize) -> bool {
+    (i < list.len()).then(|| list.remove(i)).is_some()
+}
+
+fn roots(cf: &Caddyfile) -> Vec<Root> {
+    cf.global_options
+        .iter()
+        .map(|_| Root::Global)
+        .chain((0..cf.snippets.len()).map(Root::Snippet))
+        .chain((0..cf.named_routes.len()).map(Root::NamedRoute))
+        .chain((0..cf.sites.len()).map(Root::Site))
+        .collect()
+}
+
+/// Directive paths below `path`: direct children only, or all
+/// descendants in pre-order.
+fn descendants(cf: &Caddyfile, path: &NodePath, child_only: bool) -> Vec<NodePath> {
+    let list = match cf.node(path) {
+        Some(Node::Directive(d)) => d.block.as_deref().unwrap_or_default(),
+        Some(_) => cf.root_directives(path.root).map_or(&[][..], Vec::as_slice),
+        None => &[],
+    };
+
+    let mut out = Vec::new();
+    for (i, d) in list.iter().enumerate() {
+        let mut p = path.clone();
+        p.directives.push(i);
+        let has_block = d.block.is_some();
+        out.push(p.clone());
+        if !child_only && has_block {
+            out.extend(descendants(cf, &p, false));
+        }
+    }
+    out
+}
+
+/// Match `text` against a pattern where `*` matches any run of characters.
+pub(crate) fn glob(pattern: &str, text: &str) -> bool {
+    let Some((head, rest)) = pattern.split_once('*') else {
+        return pattern == text;
+    };
+    let Some(mut remaining) = text.strip_prefix(head) else {
+        return false;
+    };
+    let mut parts: Vec<&str> = rest.split('*').collect();
+    let tail = parts.pop().unwrap_or_default();
+    for part in parts {
+        match remaining.find(part) {
+            Some(pos) => remaining = &remaining[pos + part.len()..],
+            None => return false,
+        }
+    }
+    remaining.len() >= tail.len() && remaining.ends_with(tail)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+
+    const INPUT: &str = "\
+(security) {
+\theader {
+\t\tX-Frame-Options DENY
+\t}
+}
+
+app.example.com {
+\thandle /api/* {
+\t\treverse_proxy api:8080
+\t}
+
+\treverse_proxy web:3000
+}
+
+other.org {
+\treverse_proxy other:3000
+}
+";
+
+    fn select(selector: &str) -> Vec<NodePath> {
+        let cf = parse_str(INPUT).expect("parse failed");
+        Selector::parse(selector)
+            .expect("valid selector")
+            .select(&cf)
+    }
+
+    #[test]
+    fn glob_matching() {
+        assert!(glob("*.example.com", "app.example.com"));
+        assert!(!glob("*.example.com", "example.org"));
+        assert!(glob("a*c*e", "abcde"));
+        assert!(glob("*", ""));
+        assert!(!glob("ab*ba", "aba"));
+    }
+
+    #[test]
+    fn descendant_and_child_combinators() {
+        assert_eq!(select("site[host=*.example.com] reverse_proxy").len(), 2);
+        assert_eq!(select("site[host=*.example.com] > reverse_proxy").len(), 1);
+        assert_eq!(select("reverse_proxy").len(), 3);
+    }
+
+    #[test]
+    fn directive_filters() {
+        let paths = select("handle[matcher=/api/*] > reverse_proxy[arg0=api:*]");
+        assert_eq!(
+            paths,
+            vec![NodePath {
+                root: Root::Site(0),
+                directives: vec![0, 0],
+            }]
+        );
+        assert_eq!(select("snippet[name=security] X-Frame-Options").len(), 1);
+    }
+
+    #[test]
+    fn remove_selected_nodes() {
+        let mut cf = parse_str(INPUT).expect("parse failed");
+        let paths = Selector::parse("reverse_proxy").unwrap().select(&cf);
+        for path in paths.iter().rev() {
+            assert!(cf.remove_node(path));
+        }
+        assert!(
+            Selector::parse("reverse_proxy")
+                .unwrap()
+                .select(&cf)
+                .is_empty()
+        );
+        assert_eq!(cf.sites[0].directives.len(), 1);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(Selector::parse("  "), Err(SelectorError::Empty));
+        assert_eq!(
+            Selector::parse("site >"),
+            Err(SelectorError::DanglingCombinator)
+        );
+        assert!(matches!(
+            Selector::parse("site[host=a"),
+            Err(SelectorError::UnclosedFilter(_))
+        ));
+        assert!(matches!(
+            Selector::parse("site[name=a]"),
+            Err(SelectorError::UnknownFilter { .. })
+        ));
+    }
+
+    #[test]
+    fn display_roundtrips() {
+        let s = Selector::parse("site[host=*.example.com]  >  handle reverse_proxy[arg]").unwrap();
+        assert_eq!(
+            s.to_string(),
+            "site[host=*.example.com] > handle reverse_proxy[arg]"
+        );
+    }
+}
@@ -0,0 +1,135 @@
+//! Assertions for tests of code that generates Caddyfiles.
+//!
+//! Enabled by the `testing` feature, usually as a dev-dependency:
+//!
+//! ```toml
+//! [dev-dependencies]
+//! caddyfile-rs = { version = "0.1", features = ["testing"] }
+//! ```
+//!
+//! `assert_golden` compares output with a file checked into the
+//! repository. Run the tests with `CADDYFILE_UPDATE_GOLDEN=1` to write
+//! the current output to the files instead.
+
+use std::path::Path;
+
+use crate::ast::Caddyfile;
+use crate::roundtrip::first_divergence;
+use crate::{format, parse_str};
+
+/// Environment variable that makes `assert_golden` rewrite its files.
+pub const UPDATE_GOLDEN_VAR: &str = "CADDYFILE_UPDATE_GOLDEN";
+
+/// Assert that `input` parses and formats back to exactly `input`.
+///
+/// # Panics
+///
+/// If `input` does not parse or formats differently.
+///
+/// ```
+/// caddyfile_rs::testing::assert_roundtrip("example.com {\n\tlog\n}\n");
+/// ```
+#[track_caller]
+pub fn assert_roundtrip(input: &str) {
+    let cf = parse_str(input).unwrap_or_else(|e| panic!("input does not parse: {e}"));
+    let output = format(&cf);
+    assert_eq!(
+        output, input,
+        "round-trip mismatch:\n--- expected ---\n{input}\n--- got ---\n{output}"
+    );
+}
+
+/// Assert that `original` formats to text that parses back to the same
+/// AST.
+///
+/// # Panics
+///
+/// If the formatted text does not parse, or at the first node that
+/// differs after re-parsing.
+#[track_caller]
+pub fn assert_ast_roundtrip(original: &Caddyfile) {
+    let formatted = format(original);
+    let parsed = parse_str(&formatted).unwrap_or_else(|e| {
+        panic!(
+            "failed to re-parse formatted output: {e}\n\
+             --- formatted ---\n{formatted}"
+        )
+    });
+    if let Some((path, detail)) = first_divergence(original, &parsed) {
+        panic!("{path}: {detail}\n--- formatted ---\n{formatted}");
+    }
+}
+
+/// Assert that `actual` equals the contents of the golden file at
+/// `path`.
+///
+/// With `CADDYFILE_UPDATE_GOLDEN` set, the file (and its parent
+/// directories) is written with `actual` instead and the assertion
+/// passes.
+///
+/// # Panics
+///
+/// If the file cannot be read or written, or its contents differ.
+#[track_caller]
+pub fn assert_golden(path: impl AsRef<Path>, actual: &str) {
+    let path = path.as_ref();
+    if std::env::var_os(UPDATE_GOLDEN_VAR).is_some() {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .unwrap_or_else(|e| panic!("cannot create {}: {e}", dir.display()));
+        }
+        std::fs::write(path, actual)
+            .unwrap_or_else(|e| panic!("cannot write {}: {e}", path.display()));
+        return;
+    }
+    let expected = std::fs::read_to_string(path).unwrap_or_else(|e| {
+        panic!(
+            "cannot read golden file {}: {e}\n\
+             run with {UPDATE_GOLDEN_VAR}=1 to create it",
+            path.display()
+        )
+    });
+    assert_eq!(
+        actual,
+        expected,
+        "output differs from {}; run with {UPDATE_GOLDEN_VAR}=1 to update it\n\
+         --- expected ---\n{expected}\n--- got ---\n{actual}",
+        path.display()
+    );
+}
+
+/// Format `caddyfile` and compare it with the golden file at `path`;
+/// see [`assert_golden`].
+///
+/// # Panics
+///
+/// As [`assert_golden`].
+#[track_caller]
+pub fn assert_golden_caddyfile(path: impl AsRef<Path>, caddyfile: &Caddyfile) {
+    assert_golden(path, &format(caddyfile));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Directive, SiteBlock};
+
+    #[test]
+    fn golden_file_matches() {
+        let dir = std::env::temp_dir().join(format!("caddyfile-rs-golden-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let golden = dir.join("site.caddy");
+        std::fs::write(&golden, "example.com {\n\tlog\n}\n").unwrap();
+
+        let cf =
+            Caddyfile::new().site(SiteBlock::new("example.com").directive(Directive::new("log")));
+        assert_golden_caddyfile(&golden, &cf);
+        assert_ast_roundtrip(&cf);
+
+        let other = Caddyfile::new().site(SiteBlock::new("example.org"));
+        let result = std::panic::catch_unwind(|| assert_golden_caddyfile(&golden, &other));
+        assert!(result.is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -2,11 +2,68 @@
 //!
 //! Used as the intermediate representation between lexing and parsing.
 
+use std::fmt;
+use std::sync::Arc;
+
 /// Source location for error reporting.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Span {
     pub line: usize,
     pub column: usize,
+    /// Name of the source file, set by `tokenize_named`.
+    pub file: Option<Arc<str>>,
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)?;
+        if let Some(file) = &self.file {
+            write!(f, " in {file}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Span {
+    /// A location without a file name.
+    #[must_use]
+    pub const fn new(line: usize, column: usize) -> Self {
+        Self {
+            line,
+            column,
+            file: None,
+        }
+    }
+
+    /// Column as an editor shows it, expanding tabs to stops every
+    /// `tab_width` columns. `column` itself counts a tab as one.
+    ///
+    /// `source` must be the text the span was produced from.
+    #[must_use]
+    pub fn visual_column(&self, source: &str, tab_width: usize) -> usize {
+        let line = source
+            .split('\n')
+            .nth(self.line.saturating_sub(1))
+            .unwrap_or_default();
+        let line = if self.line == 1 {
+            line.strip_prefix('\u{feff}').unwrap_or(line)
+        } else {
+            line
+        };
+        let tab_width = tab_width.max(1);
+        let mut visual = 0;
+        for (i, ch) in line.char_indices() {
+            if i + 1 >= self.column {
+                break;
+            }
+            visual = if ch == '\t' {
+                (visual / tab_width + 1) * tab_width
+            } else {
+                visual + 1
+            };
+        }
+        visual + 1
+    }
 }
 
 /// Token kinds produced by the lexer.
@@ -26,6 +83,9 @@ pub enum TokenKind {
     OpenBrace,
     /// Closing brace `}`.
     CloseBrace,
+    /// Comma at the start or end of a word, as in `a.com, b.com`.
+    /// Commas inside a word stay part of it.
+    Comma,
     /// Newline (line separator).
     Newline,
     /// Environment variable `{$VAR}` or `{$VAR:default}`.
@@ -33,6 +93,9 @@ pub enum TokenKind {
         name: String,
         default: Option<String>,
     },
+    /// Run of spaces, tabs and line continuations, only produced by
+    /// `tokenize_with_trivia`.
+    Whitespace,
 }
 
 /// A single token with its kind, text, and source location.
@@ -41,4 +104,37 @@ pub struct Token {
     pub kind: TokenKind,
     pub text: String,
     pub span: Span,
+    /// Whether a `\` line continuation directly precedes this token.
+    pub continued: bool,
+}
+
+impl Token {
+    /// Whether this token only carries layout (`TokenKind::Whitespace`).
+    #[must_use]
+    pub const fn is_trivia(&self) -> bool {
+        matches!(self.kind, TokenKind::Whitespace)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn visual_column_tab_stops() {
+        let span = Span::new(2, 4);
+        assert_eq!(span.visual_column("x\na\tb c", 4), 6);
+        assert_eq!(span.visual_column("x\n\u{e9}\tb", 8), 9);
+        assert_eq!(span.visual_column("x\nabc", 4), 4);
+    }
+
+    #[test]
+    fn display_includes_file() {
+        assert_eq!(Span::new(3, 2).to_string(), "line 3, column 2");
+        let span = Span {
+            file: Some("sites/a.caddy".into()),
+            ..Span::new(3, 2)
+        };
+        assert_eq!(span.to_string(), "line 3, column 2 in sites/a.caddy");
+    }
 }
@@ -0,0 +1,376 @@
+//! Checks that a Caddyfile built in code prints as valid syntax.
+//!
+//! The AST accepts any strings, so a generator can build a directive
+//! named `two words` or an address with a space in it, and `format`
+//! prints it as is. `Caddyfile::validate` catches such nodes before the
+//! text is written out.
+//!
+//! ```
+//! use caddyfile_rs::{Caddyfile, Directive, SiteBlock, ValidationErrorKind};
+//!
+//! let cf = Caddyfile::new().site(
+//!     SiteBlock::new("example.com").directive(Directive::new("respond").arg("hello world")),
+//! );
+//! let err = cf.validate().unwrap_err();
+//! assert_eq!(err.path.to_string(), "site[0] > 0");
+//! assert!(matches!(err.kind, ValidationErrorKind::UnquotedArgument { .. }));
+//! ```
+
+use crate::ast::{Address, Argument, Caddyfile, Directive, Matcher};
+use crate::directives::Import;
+use crate::formatter::needs_quotes;
+use crate::selector::{Node, NodePath, Root, Selector};
+
+/// A node that would not print as valid Caddyfile syntax.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("{path}: {kind}")]
+pub struct ValidationError {
+    /// Block or directive holding the problem.
+    pub path: NodePath,
+    pub kind: ValidationErrorKind,
+}
+
+/// What is wrong with the node at a `ValidationError`'s path.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ValidationErrorKind {
+    /// A directive name that is empty or not a single bare word.
+    #[error("invalid directive name '{0}'")]
+    DirectiveName(String),
+    /// An unquoted argument that would not lex back as one argument.
+    #[error("argument '{value}' of '{directive}' must be quoted")]
+    UnquotedArgument { directive: String, value: String },
+    /// A backtick argument containing a backtick.
+    #[error("backtick argument of '{directive}' contains a backtick")]
+    Backtick { directive: String },
+    /// A matcher that is empty or not a single bare word.
+    #[error("invalid matcher '{matcher}' on '{directive}'")]
+    Matcher { directive: String, matcher: String },
+    /// A snippet name that is empty or breaks `(name)`.
+    #[error("invalid snippet name '{0}'")]
+    SnippetName(String),
+    /// A named route name that is empty or breaks `&(name)`.
+    #[error("invalid named route name '{0}'")]
+    NamedRouteName(String),
+    /// A site block without addresses.
+    #[error("site has no address")]
+    NoAddress,
+    /// An address without host or port, or one that is not a single
+    /// bare word.
+    #[error("invalid address '{0}'")]
+    Address(String),
+    /// An `import` of a snippet the file does not define.
+    #[error("import of undefined snippet '{0}'")]
+    UndefinedSnippet(String),
+}
+
+impl Caddyfile {
+    /// Check that every name, address and argument prints as valid
+    /// syntax, returning the first offending node in document order.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if let Some(global) = &self.global_options {
+            check_directives(Root::Global, &global.directives)?;
+        }
+        for (i, snippet) in self.snippets.iter().enumerate() {
+            if snippet.name.is_empty() || needs_quotes(&format!("({})", snippet.name)) {
+                let kind = ValidationErrorKind::SnippetName(snippet.name.clone());
+                return Err(error(Root::Snippet(i), kind));
+            }
+            check_directives(Root::Snippet(i), &snippet.directives)?;
+        }
+        for (i, route) in self.named_routes.iter().enumerate() {
+            if route.name.is_empty() || needs_quotes(&format!("&({})", route.name)) {
+                let kind = ValidationErrorKind::NamedRouteName(route.name.clone());
+                return Err(error(Root::NamedRoute(i), kind));
+            }
+            check_directives(Root::NamedRoute(i), &route.directives)?;
+        }
+        for (i, site) in self.sites.iter().enumerate() {
+            if site.addresses.is_empty() {
+                return Err(error(Root::Site(i), ValidationErrorKind::NoAddress));
+            }
+            if let Some(address) = site.addresses.iter().find(|a| !valid_address(a)) {
+                let kind = ValidationErrorKind::Address(address.to_string());
+                return Err(error(Root::Site(i), kind));
+            }
+            check_directives(Root::Site(i), &site.directives)?;
+        }
+        Ok(())
+    }
+
+    /// `validate`, and also fail on the first import `import_warnings`
+    /// reports.
+    pub fn validate_strict(&self) -> Result<(), ValidationError> {
+        self.validate()?;
+        self.import_warnings()
+            .into_iter()
+            .next()
+            .map_or(Ok(()), Err)
+    }
+
+    /// Imports naming a snippet this file does not define.
+    ///
+    /// A target containing `/`, `.`, `*` or `{` is taken for a file
+    /// import and never reported. Caddy would read any other undefined
+    /// name as a file path too, which is rarely what a generator meant.
+    ///
+    /// ```
+    /// use caddyfile_rs::{Caddyfile, SiteBlock};
+    ///
+    /// let cf = Caddyfile::new().site(
+    ///     SiteBlock::new("example.com")
+    ///         .import("security")
+    ///         .import("/etc/caddy/common.caddy"),
+    /// );
+    /// let warnings = cf.import_warnings();
+    /// assert_eq!(warnings.len(), 1);
+    /// assert_eq!(
+    ///     warnings[0].to_string(),
+    ///     "site[0] > 0: import of undefined snippet 'security'"
+    /// );
+    /// assert!(cf.validate().is_ok());
+    /// assert!(cf.validate_strict().is_err());
+    /// ```
+    #[must_use]
+    pub fn import_warnings(&self) -> Vec<ValidationError> {
+        let imports = Selector::parse("import")
+            .map(|s| s.select(self))
+            .unwrap_or_default();
+        imports
+            .into_iter()
+            .filter_map(|path| {
+                let Some(Node::Directive(d)) = self.node(&path) else {
+                    return None;
+                };
+                let target = Import::try_from(d).ok()?.target;
+                let is_file = target.contains(['/', '.', '*', '{']);
+                if is_file || self.snippets.iter().any(|s| s.name == target) {
+                    return None;
+                }
+                Some(ValidationError {
+                    path,
+                    kind: ValidationErrorKind::UndefinedSnippet(target),
+                })
+            })
+            .collect()
+    }
+}
+
+const fn error(root: Root, kind: ValidationErrorKind) -> ValidationError {
+    ValidationError {
+        path: NodePath {
+            root,
+            directives: Vec::new(),
+        },
+        kind,
+    }
+}
+
+fn valid_address(address: &Address) -> bool {
+    if address.raw.is_none() && address.host.is_empty() && address.port.is_none() {
+        return false;
+    }
+    let text = address.to_string();
+    !(text.starts_with('(') || text.starts_with("&(") || needs_quotes(&text))
+}
+
+fn check_directives(root: Root, directives: &[Directive]) -> Result<(), ValidationError> {
+    let mut path = NodePath {
+        root,
+        directives: Vec::new(),
+    };
+    walk(&mut path, directives).map_err(|kind| ValidationError { path, kind })
+}
+
+/// Check `directives`, leaving `path` at the offending directive.
+fn walk(path: &mut NodePath, directives: &[Directive]) -> Result<(), ValidationErrorKind> {
+    for (i, directive) in directives.iter().enumerate() {
+        path.directives.push(i);
+        check_directive(directive)?;
+        if let Some(block) = &directive.block {
+            walk(path, block)?;
+        }
+        path.directives.pop();
+    }
+    Ok(())
+}
+
+fn check_directive(directive: &Directive) -> Result<(), ValidationErrorKind> {
+    let name = &directive.name;
+    if needs_quotes(name) || name.starts_with('#') {
+        return Err(ValidationErrorKind::DirectiveName(name.clone()));
+    }
+    let matcher_ok = match &directive.matcher {
+        Some(Matcher::Named(n)) => !n.is_empty() && !needs_quotes(&format!("@{n}")),
+        Some(Matcher::Path(p)) => !needs_quotes(p),
+        Some(Matcher::Paths(paths)) => !paths.is_empty() && !paths.iter().any(|p| needs_quotes(p)),
+        Some(Matcher::All | Matcher::Not { .. }) | None => true,
+    };
+    if !matcher_ok {
+        return Err(ValidationErrorKind::Matcher {
+            directive: name.clone(),
+            matcher: directive
+                .matcher
+                .as_ref()
+                .map(ToString::to_string)
+                .unwrap_or_default(),
+        });
+    }
+    for arg in &directive.arguments {
+        match arg {
+            Argument::Unquoted(value) if needs_quotes(value) => {
+                return Err(ValidationErrorKind::UnquotedArgument {
+                    directive: name.clone(),
+                    value: value.clone(),
+                });
+            }
+            Argument::Backtick(value) if value.contains('`') => {
+                return Err(ValidationErrorKind::Backtick {
+                    directive: name.clone(),
+                });
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{Comments, NamedRoute, SiteBlock, Snippet};
+    use crate::parse_str;
+
+    fn kind(cf: &Caddyfile) -> ValidationErrorKind {
+        cf.validate().unwrap_err().kind
+    }
+
+    #[test]
+    fn parsed_files_are_valid() {
+        let input = "(common) {\n\tencode gzip\n}\n\n\
+                     &(api) {\n\trespond \"hello world\"\n}\n\n\
+                     {$DOMAIN}, :8080 {\n\t@api path /api/*\n\thandle @api {\n\t\tinvoke api\n\t}\n}\n";
+        assert_eq!(parse_str(input).unwrap().validate(), Ok(()));
+    }
+
+    #[test]
+    fn reports_first_invalid_node() {
+        let site = |d: Directive| Caddyfile::new().site(SiteBlock::new("example.com").directive(d));
+
+        let nested = site(Directive::new("handle").block(vec![
+            Directive::new("log"),
+            Directive::new("respond").arg("{ oops"),
+        ]));
+        let err = nested.validate().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "site[0] > 0 > 1: argument '{ oops' of 'respond' must be quoted"
+        );
+
+        assert_eq!(
+            kind(&site(Directive::new("reverse proxy"))),
+            ValidationErrorKind::DirectiveName("reverse proxy".into())
+        );
+        assert_eq!(
+            kind(&site(Directive::new(""))),
+            ValidationErrorKind::DirectiveName(String::new())
+        );
+        assert!(matches!(
+            kind(&site(Directive::new("respond").backtick_arg("a`b"))),
+            ValidationErrorKind::Backtick { .. }
+        ));
+        assert!(matches!(
+            kind(&site(
+                Directive::new("handle").matcher(Matcher::Named(String::new()))
+            )),
+            ValidationErrorKind::Matcher { .. }
+        ));
+        assert_eq!(
+            site(Directive::new("respond").quoted_arg("{ ok }")).validate(),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn reports_invalid_names_and_addresses() {
+        let snippet = Caddyfile {
+            snippets: vec![Snippet {
+                name: String::new(),
+                directives: vec![],
+                comments: Comments::default(),
+            }],
+            ..Caddyfile::new()
+        };
+        assert_eq!(
+            kind(&snippet),
+            ValidationErrorKind::SnippetName(String::new())
+        );
+
+        let route = Caddyfile {
+            named_routes: vec![NamedRoute {
+                name: "my route".into(),
+                directives: vec![],
+                comments: Comments::default(),
+            }],
+            ..Caddyfile::new()
+        };
+        assert_eq!(
+            kind(&route),
+            ValidationErrorKind::NamedRouteName("my route".into())
+        );
+
+        let mut site = SiteBlock::new("example.com");
+        site.addresses.clear();
+        assert_eq!(
+            kind(&Caddyfile::new().site(site)),
+            ValidationErrorKind::NoAddress
+        );
+        assert_eq!(
+            kind(&Caddyfile::new().site(SiteBlock::new("example .com"))),
+            ValidationErrorKind::Address("example .com".into())
+        );
+        assert_eq!(
+            kind(&Caddyfile::new().site(SiteBlock::new("https://"))),
+            ValidationErrorKind::Address("https://".into())
+        );
+    }
+
+    #[test]
+    fn undefined_snippet_imports() {
+        let cf = Caddyfile::new()
+            .snippet(Snippet {
+                name: "cors".into(),
+                directives: vec![
+                    Directive::new("header")
+                        .arg("Access-Control-Allow-Origin")
+                        .arg("{args[0]}"),
+                ],
+                comments: Comments::default(),
+            })
+            .site(
+                SiteBlock::new("example.com")
+                    .import_with_args("cors", ["https://app.example.com"])
+                    .import("sites/*.caddy")
+                    .handle(Matcher::path("/admin/*"), |b| b.import("auth")),
+            );
+        assert_eq!(
+            crate::format(&cf),
+            "(cors) {\n\theader Access-Control-Allow-Origin {args[0]}\n}\n\n\
+             example.com {\n\
+             \timport cors https://app.example.com\n\
+             \timport sites/*.caddy\n\
+             \n\
+             \thandle /admin/* {\n\
+             \t\timport auth\n\
+             \t}\n\
+             }\n"
+        );
+        let warnings = cf.import_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].path.directives, [2, 0]);
+        assert_eq!(
+            warnings[0].kind,
+            ValidationErrorKind::UndefinedSnippet("auth".into())
+        );
+        assert_eq!(cf.validate_strict(), Err(warnings[0].clone()));
+    }
+}
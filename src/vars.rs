@@ -0,0 +1,348 @@
+//! The `vars` directive and `{vars.*}` placeholder resolution.
+//!
+//! `vars` sets request-scoped variables which later handlers read back
+//! through `{vars.name}` (or `{http.vars.name}`). Because Caddy sorts
+//! `vars` ahead of other handlers, a definition applies to its whole
+//! directive list and every nested block, but not to sibling `handle`
+//! or `route` blocks. `resolve_vars` links each usage to the definitions
+//! in scope and reports those with none.
+
+use crate::ast::{Argument, Caddyfile, Comments, Directive, Matcher};
+use crate::placeholder;
+use crate::selector::{NodePath, Root};
+
+/// A typed `vars` directive.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Vars {
+    pub matcher: Option<Matcher>,
+    /// Variables in source order; a key without value sets it to empty.
+    pub entries: Vec<(String, Option<Argument>)>,
+}
+
+impl Vars {
+    /// Create an empty `vars` directive.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            matcher: None,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Add a variable.
+    #[must_use]
+    pub fn var(mut self, name: &str, value: impl Into<Argument>) -> Self {
+        self.entries.push((name.to_string(), Some(value.into())));
+        self
+    }
+
+    /// Read a `vars` directive, either `vars key value` or the block
+    /// form. Returns `None` for any other directive.
+    #[must_use]
+    pub fn from_directive(d: &Directive) -> Option<Self> {
+        if d.name != "vars" {
+            return None;
+        }
+        let mut entries = Vec::new();
+        let mut args = d.arguments.iter();
+        if let Some(key) = args.next() {
+            entries.push((key.value().to_string(), args.next().cloned()));
+        }
+        for sub in d.block.iter().flatten() {
+            // A value starting with `/`, `*` or `@` is lexed as a matcher.
+            let value = sub
+                .matcher
+                .as_ref()
+                .map(|m| Argument::Unquoted(m.to_string()))
+                .or_else(|| sub.arguments.first().cloned());
+            entries.push((sub.name.clone(), value));
+        }
+        Some(Self {
+            matcher: d.matcher.clone(),
+            entries,
+        })
+    }
+
+    /// Look a variable's value up.
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&Argument> {
+        self.entries
+            .iter()
+            .find(|(k, _)| k == name)
+            .and_then(|(_, v)| v.as_ref())
+    }
+}
+
+impl From<&Vars> for Directive {
+    fn from(vars: &Vars) -> Self {
+        let entry = |(k, v): &(String, Option<Argument>)| {
+            let mut args = vec![Argument::Unquoted(k.clone())];
+            args.extend(v.clone());
+            args
+        };
+        if let [single] = vars.entries.as_slice() {
+            return Self {
+                name: "vars".to_string(),
+                matcher: vars.matcher.clone(),
+                arguments: entry(single),
+                block: None,
+                comments: Comments::default(),
+                continuations: Vec::new(),
+                blank_lines_before: 0,
+            };
+        }
+        Self {
+            name: "vars".to_string(),
+            matcher: vars.matcher.clone(),
+            arguments: Vec::new(),
+            block: Some(
+                vars.entries
+                    .iter()
+                    .map(|e| {
+                        let mut args = entry(e);
+                        let name = args.remove(0).value().to_string();
+                        Self {
+                            name,
+                            matcher: None,
+                            arguments: args,
+                            block: None,
+                            comments: Comments::default(),
+                            continuations: Vec::new(),
+                            blank_lines_before: 0,
+                        }
+                    })
+                    .collect(),
+            ),
+            comments: Comments::default(),
+            continuations: Vec::new(),
+            blank_lines_before: 0,
+        }
+    }
+}
+
+/// A variable set by a `vars` directive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VarDefinition {
+    pub name: String,
+    pub value: Option<Argument>,
+    /// Location of the `vars` directive.
+    pub path: NodePath,
+}
+
+/// A `{vars.name}` reference.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VarUsage {
+    pub name: String,
+    /// Location of the directive containing the placeholder.
+    pub path: NodePath,
+    /// Indices into `VarResolution::definitions` that are in scope.
+    pub definitions: Vec<usize>,
+}
+
+/// Result of `resolve_vars`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VarResolution {
+    pub definitions: Vec<VarDefinition>,
+    pub usages: Vec<VarUsage>,
+}
+
+impl VarResolution {
+    /// Usages with no definition in scope.
+    ///
+    /// Usages inside snippets are skipped, since their definitions
+    /// depend on where the snippet is imported.
+    pub fn undefined(&self) -> impl Iterator<Item = &VarUsage> {
+        self.usages
+            .iter()
+            .filter(|u| u.definitions.is_empty() && !matches!(u.path.root, Root::Snippet(_)))
+    }
+
+    /// Definitions in scope for `usage`.
+    pub fn definitions_for<'a>(
+        &'a self,
+        usage: &'a VarUsage,
+    ) -> impl Iterator<Item = &'a VarDefinition> {
+        usage.definitions.iter().map(|&i| &self.definitions[i])
+    }
+
+    /// Every definition of variable `name`, in any scope.
+    pub fn definitions_named<'a>(
+        &'a self,
+        name: &'a str,
+    ) -> impl Iterator<Item = &'a VarDefinition> {
+        self.definitions.iter().filter(move |d| d.name == name)
+    }
+
+    /// Definitions no usage refers to.
+    pub fn unused(&self) -> impl Iterator<Item = &VarDefinition> {
+        self.definitions
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !self.usages.iter().any(|u| u.definitions.contains(i)))
+            .map(|(_, d)| d)
+    }
+}
+
+/// Collect `vars` definitions and `{vars.*}` usages and link them.
+#[must_use]
+pub fn resolve_vars(cf: &Caddyfile) -> VarResolution {
+    let mut res = VarResolution::default();
+    let roots = cf
+        .global_options
+        .iter()
+        .map(|g| (Root::Global, &g.directives))
+        .chain(
+            cf.snippets
+                .iter()
+                .enumerate()
+                .map(|(i, s)| (Root::Snippet(i), &s.directives)),
+        )
+        .chain(
+            cf.named_routes
+                .iter()
+                .enumerate()
+                .map(|(i, r)| (Root::NamedRoute(i), &r.directives)),
+        )
+        .chain(
+            cf.sites
+                .iter()
+                .enumerate()
+                .map(|(i, s)| (Root::Site(i), &s.directives)),
+        );
+    for (root, directives) in roots {
+        let mut path = NodePath {
+            root,
+            directives: Vec::new(),
+        };
+        collect(&mut res, &mut path, directives, false);
+    }
+
+    for usage in &mut res.usages {
+        usage.definitions = res
+            .definitions
+            .iter()
+            .enumerate()
+            .filter(|(_, def)| def.name == usage.name && in_scope(&def.path, &usage.path))
+            .map(|(i, _)| i)
+            .collect();
+    }
+    res
+}
+
+/// A definition is visible from its own directive list downwards.
+fn in_scope(def: &NodePath, usage: &NodePath) -> bool {
+    let parent = &def.directives[..def.directives.len() - 1];
+    def.root == usage.root && usage.directives.starts_with(parent)
+}
+
+fn collect(res: &mut VarResolution, path: &mut NodePath, list: &[Directive], in_matcher: bool) {
+    for (i, d) in list.iter().enumerate() {
+        path.directives.push(i);
+        // Inside a named matcher, `vars` is a matcher condition.
+        let in_matcher = in_matcher || d.name.starts_with('@');
+        if !in_matcher && let Some(vars) = Vars::from_directive(d) {
+            for (name, value) in vars.entries {
+                res.definitions.push(VarDefinition {
+                    name,
+                    value,
+                    path: path.clone(),
+                });
+            }
+        }
+        let texts = d
+            .matcher
+            .iter()
+            .map(ToString::to_string)
+            .chain(d.arguments.iter().map(|a| a.value().to_string()));
+        for text in texts {
+            for (_, name) in placeholder::find_in(&text) {
+                if let Some(var) = var_name(&name) {
+                    res.usages.push(VarUsage {
+                        name: var.to_string(),
+                        path: path.clone(),
+                        definitions: Vec::new(),
+                    });
+                }
+            }
+        }
+        if let Some(block) = &d.block {
+            collect(res, path, block, in_matcher);
+        }
+        path.directives.pop();
+    }
+}
+
+fn var_name(placeholder: &str) -> Option<&str> {
+    placeholder
+        .strip_prefix("vars.")
+        .or_else(|| placeholder.strip_prefix("http.vars."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formatter::format_node;
+    use crate::parse_str;
+    use crate::selector::Node;
+
+    #[test]
+    fn typed_forms() {
+        let cf =
+            parse_str("a.com {\n\tvars env prod\n\tvars {\n\t\tbase /srv\n\t\tdebug\n\t}\n}\n")
+                .unwrap();
+        let inline = Vars::from_directive(&cf.sites[0].directives[0]).unwrap();
+        assert_eq!(inline.get("env").map(Argument::value), Some("prod".into()));
+        let block = Vars::from_directive(&cf.sites[0].directives[1]).unwrap();
+        assert_eq!(block.get("base").map(Argument::value), Some("/srv".into()));
+        assert_eq!(block.entries[1], ("debug".to_string(), None));
+        assert_eq!(
+            format_node(Node::Directive(&Directive::from(&block))),
+            "vars {\n\tbase /srv\n\tdebug\n}\n"
+        );
+        assert_eq!(Directive::from(&inline), cf.sites[0].directives[0]);
+
+        let unused: Vec<_> = resolve_vars(&cf).unused().map(|d| d.name.clone()).collect();
+        assert_eq!(unused, ["env", "base", "debug"]);
+    }
+
+    #[test]
+    fn scopes_follow_blocks() {
+        let cf = parse_str(
+            "a.com {\n\
+             \tvars site_wide 1\n\
+             \thandle /a/* {\n\t\tvars local 1\n\t\trespond \"{vars.local} {vars.site_wide}\"\n\t}\n\
+             \thandle {\n\t\trespond \"{http.vars.local}\"\n\t}\n\
+             }\n",
+        )
+        .unwrap();
+        let res = resolve_vars(&cf);
+        assert_eq!(res.definitions.len(), 2);
+        assert_eq!(res.usages.len(), 3);
+        assert_eq!(res.usages[0].definitions, [1]);
+        assert_eq!(res.usages[1].definitions, [0]);
+
+        let undefined: Vec<_> = res.undefined().collect();
+        assert_eq!(undefined.len(), 1);
+        assert_eq!(undefined[0].name, "local");
+        assert_eq!(undefined[0].path.to_string(), "site[0] > 2 > 0");
+
+        let local: Vec<_> = res.definitions_for(&res.usages[0]).collect();
+        assert_eq!(local[0].path.to_string(), "site[0] > 1 > 0");
+        assert_eq!(res.definitions_named("site_wide").count(), 1);
+        assert_eq!(res.unused().count(), 0);
+    }
+
+    #[test]
+    fn matcher_conditions_are_not_definitions() {
+        let cf = parse_str(
+            "a.com {\n\t@dbg {\n\t\tvars \"{vars.debug}\" on\n\t}\n\trespond @dbg 200\n}\n\n\
+             (s) {\n\trespond \"{vars.x}\"\n}\n",
+        )
+        .unwrap();
+        let res = resolve_vars(&cf);
+        assert!(res.definitions.is_empty());
+        assert_eq!(res.unused().count(), 0);
+        let undefined: Vec<_> = res.undefined().map(|u| u.name.as_str()).collect();
+        assert_eq!(undefined, ["debug"]);
+    }
+}
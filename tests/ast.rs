@@ -4,8 +4,8 @@
 mod common;
 
 use caddyfile_rs::{
-    Address, Argument, Caddyfile, Directive, GlobalOptions, Matcher, NamedRoute, Scheme, SiteBlock,
-    Snippet,
+    Address, Argument, Caddyfile, Comments, Directive, GlobalOptions, Matcher, NamedRoute, Scheme,
+    SiteBlock, Snippet,
 };
 use common::assert_ast_roundtrip;
 
@@ -20,6 +20,7 @@ fn display_address_full() {
         host: "example.com".to_string(),
         port: Some(8443),
         path: Some("/api".to_string()),
+        raw: None,
     };
     assert_eq!(addr.to_string(), "https://example.com:8443/api");
 }
@@ -31,6 +32,7 @@ fn display_address_minimal() {
         host: "localhost".to_string(),
         port: None,
         path: None,
+        raw: None,
     };
     assert_eq!(addr.to_string(), "localhost");
 }
@@ -40,6 +42,18 @@ fn display_matcher_variants() {
     assert_eq!(Matcher::All.to_string(), "*");
     assert_eq!(Matcher::Path("/health".to_string()).to_string(), "/health");
     assert_eq!(Matcher::Named("api".to_string()).to_string(), "@api");
+    assert_eq!(
+        Matcher::Paths(vec!["/a/*".to_string(), "/b/*".to_string()]).to_string(),
+        "/a/* /b/*"
+    );
+    assert_eq!(
+        Matcher::Not {
+            module: "path".to_string(),
+            args: vec![caddyfile_rs::Argument::Unquoted("/x/*".to_string())],
+        }
+        .to_string(),
+        "not path /x/*"
+    );
 }
 
 #[test]
@@ -197,6 +211,7 @@ fn ast_fidelity_global_options() {
                 Directive::new("email").arg("admin@example.com"),
                 Directive::new("admin").arg("off"),
             ],
+            comments: Comments::default(),
         })
         .site(SiteBlock::new("example.com").log());
     assert_ast_roundtrip(&cf);
@@ -211,6 +226,7 @@ fn ast_fidelity_global_with_nested_block() {
                 Directive::new("servers")
                     .block(vec![Directive::new("protocols").arg("h1").arg("h2")]),
             ],
+            comments: Comments::default(),
         })
         .site(SiteBlock::new("example.com").log());
     assert_ast_roundtrip(&cf);
@@ -225,6 +241,7 @@ fn ast_fidelity_snippet() {
                 Directive::new("output").arg("stderr"),
                 Directive::new("format").arg("console"),
             ])],
+            comments: Comments::default(),
         })
         .site(
             SiteBlock::new("example.com")
@@ -243,6 +260,7 @@ fn ast_fidelity_named_route() {
                 Directive::new("basic_auth")
                     .block(vec![Directive::new("admin").arg("$2a$14$hash")]),
             ],
+            comments: Comments::default(),
         })
         .site(
             SiteBlock::new("admin.example.com")
@@ -258,6 +276,7 @@ fn ast_fidelity_multiple_snippets_and_named_routes() {
         .snippet(Snippet {
             name: "log".to_string(),
             directives: vec![Directive::new("log")],
+            comments: Comments::default(),
         })
         .snippet(Snippet {
             name: "sec".to_string(),
@@ -265,12 +284,14 @@ fn ast_fidelity_multiple_snippets_and_named_routes() {
                 Directive::new("header")
                     .block(vec![Directive::new("X-Frame-Options").quoted_arg("DENY")]),
             ],
+            comments: Comments::default(),
         })
         .named_route(NamedRoute {
             name: "auth".to_string(),
             directives: vec![
                 Directive::new("basic_auth").block(vec![Directive::new("admin").arg("pass")]),
             ],
+            comments: Comments::default(),
         })
         .site(SiteBlock::new("example.com").log());
     assert_ast_roundtrip(&cf);
@@ -410,6 +431,7 @@ fn ast_fidelity_full_production_config() {
                 Directive::new("servers")
                     .block(vec![Directive::new("protocols").arg("h1").arg("h2")]),
             ],
+            comments: Comments::default(),
         })
         .snippet(Snippet {
             name: "security".to_string(),
@@ -417,6 +439,7 @@ fn ast_fidelity_full_production_config() {
                 Directive::new("X-Content-Type-Options").quoted_arg("nosniff"),
                 Directive::new("X-Frame-Options").quoted_arg("DENY"),
             ])],
+            comments: Comments::default(),
         })
         .named_route(NamedRoute {
             name: "auth".to_string(),
@@ -424,6 +447,7 @@ fn ast_fidelity_full_production_config() {
                 Directive::new("basic_auth")
                     .block(vec![Directive::new("admin").arg("$2a$14$hash")]),
             ],
+            comments: Comments::default(),
         })
         .site(
             SiteBlock::new("example.com")
@@ -463,6 +487,7 @@ fn ast_fidelity_empty_caddyfile() {
 fn ast_fidelity_global_only() {
     let cf = Caddyfile::new().global(GlobalOptions {
         directives: vec![Directive::new("admin").arg("off")],
+        comments: Comments::default(),
     });
     assert_ast_roundtrip(&cf);
 }
@@ -0,0 +1,217 @@
+//! Conformance tests for `caddy_fmt`, taken from the test cases of
+//! Caddy's own formatter (`caddyconfig/caddyfile/formatter_test.go`).
+
+use caddyfile_rs::caddy_fmt;
+
+/// Check one case; like caddy's test, a trailing newline is implied.
+fn check(description: &str, input: &str, expected: &str) {
+    let mut expected = expected.to_string();
+    if !expected.ends_with('\n') {
+        expected.push('\n');
+    }
+    assert_eq!(caddy_fmt(input), expected, "{description}");
+}
+
+#[test]
+fn very_simple() {
+    check(
+        "very simple",
+        "abc   def\n\tg hi jkl\nmn",
+        "abc def\ng hi jkl\nmn",
+    );
+}
+
+#[test]
+fn basic_indentation_line_breaks_and_nesting() {
+    check(
+        "basic indentation, line breaks, and nesting",
+        "  a\nb\n\n\tc {\n\t\td\n}\n\ne { f\n}\n\n\n\ng {\nh {\ni\n}\n}\n\nj { k {\nl\n}\n}\n\nm {\n\tn { o\n\t}\n\tp { q r\ns }\n}\n\n\t{\n{ t\n\tu\n\n\tv\nw\n}\n}",
+        "a\nb\n\nc {\n\td\n}\n\ne {\n\tf\n}\n\ng {\n\th {\n\t\ti\n\t}\n}\n\nj {\n\tk {\n\t\tl\n\t}\n}\n\nm {\n\tn {\n\t\to\n\t}\n\tp {\n\t\tq r\n\t\ts\n\t}\n}\n\n{\n\t{\n\t\tt\n\t\tu\n\n\t\tv\n\t\tw\n\t}\n}",
+    );
+}
+
+#[test]
+fn block_spacing() {
+    check(
+        "block spacing",
+        "a{\n\tb\n}\n\nc{ d\n}",
+        "a {\n\tb\n}\n\nc {\n\td\n}",
+    );
+}
+
+#[test]
+fn advanced_spacing() {
+    check(
+        "advanced spacing",
+        "abc {\n\tdef\n}ghi{\n\tjkl mno\npqr}",
+        "abc {\n\tdef\n}\n\nghi {\n\tjkl mno\n\tpqr\n}",
+    );
+}
+
+#[test]
+fn env_var_placeholders() {
+    check(
+        "env var placeholders",
+        "{$A}\n\nb {\n{$C}\n}\n\nd { {$E}\n}\n\n{ {$F}\n}\n",
+        "{$A}\n\nb {\n\t{$C}\n}\n\nd {\n\t{$E}\n}\n\n{\n\t{$F}\n}",
+    );
+}
+
+#[test]
+fn env_var_placeholders_with_port() {
+    check("env var placeholders with port", ":{$PORT}", ":{$PORT}");
+}
+
+#[test]
+fn comments() {
+    check(
+        "comments",
+        "#a \"\\n\"\n\n #b {\n\tc\n}\n\nd {\ne#f\n# g\n}\n\nh { # i\n}",
+        "#a \"\\n\"\n\n#b {\nc\n}\n\nd {\n\te#f\n\t# g\n}\n\nh {\n\t# i\n}",
+    );
+}
+
+#[test]
+fn quotes_and_escaping() {
+    check(
+        "quotes and escaping",
+        "\"a \\\"b\\\" \"#c\n\td\n\ne {\n\"f\"\n}\n\ng { \"h\"\n}\n\ni {\n\t\"foo\nbar\"\n}\n\nj {\n\"\\\"k\\\" l m\"\n}",
+        "\"a \\\"b\\\" \"#c\nd\n\ne {\n\t\"f\"\n}\n\ng {\n\t\"h\"\n}\n\ni {\n\t\"foo\nbar\"\n}\n\nj {\n\t\"\\\"k\\\" l m\"\n}",
+    );
+}
+
+#[test]
+fn bad_nesting_too_many_open() {
+    check(
+        "bad nesting (too many open)",
+        "a\n{\n\t{\n}\n",
+        "a {\n\t{\n\t}\n",
+    );
+}
+
+#[test]
+fn bad_nesting_too_many_close() {
+    check(
+        "bad nesting (too many close)",
+        "a\n{\n\t{\n}}}\n",
+        "a {\n\t{\n\t}\n}\n}\n",
+    );
+}
+
+#[test]
+fn json() {
+    check(
+        "json",
+        "foo\nbar \"{\\\"key\\\":34}\"\n",
+        "foo\nbar \"{\\\"key\\\":34}\"",
+    );
+}
+
+#[test]
+fn escaping_after_spaces() {
+    check(
+        "escaping after spaces",
+        "foo \\\"literal\\\"",
+        "foo \\\"literal\\\"",
+    );
+}
+
+#[test]
+fn simple_placeholders_as_standalone_tokens() {
+    check(
+        "simple placeholders as standalone tokens",
+        "foo {bar}",
+        "foo {bar}",
+    );
+}
+
+#[test]
+fn simple_placeholders_within_tokens() {
+    check(
+        "simple placeholders within tokens",
+        "foo{bar} foo{bar}baz",
+        "foo{bar} foo{bar}baz",
+    );
+}
+
+#[test]
+fn placeholders_and_malformed_braces() {
+    check(
+        "placeholders and malformed braces",
+        "foo{bar} foo{ bar}baz",
+        "foo{bar} foo {\n\tbar\n}\n\nbaz",
+    );
+}
+
+#[test]
+fn hash_within_string_is_not_a_comment() {
+    check(
+        "hash within string is not a comment",
+        "redir / /some/#/path",
+        "redir / /some/#/path",
+    );
+}
+
+#[test]
+fn brace_does_not_fold_into_comment_above() {
+    check(
+        "brace does not fold into comment above",
+        "# comment\n{\n\tfoo\n}",
+        "# comment\n{\n\tfoo\n}",
+    );
+}
+
+#[test]
+fn vscode_extension_bad_formatting() {
+    check(
+        "matthewpi/vscode-caddyfile-support#13 - bad formatting",
+        "{\n\temail {$ACMEEMAIL}\n\t#debug\n}\n\nblock {\n}\n",
+        "{\n\temail {$ACMEEMAIL}\n\t#debug\n}\n\nblock {\n}\n",
+    );
+}
+
+#[test]
+fn keep_heredoc_as_is() {
+    check(
+        "keep heredoc as-is",
+        "block {\n\theredoc <<HEREDOC\n\tHere's more than one space       Here's more than one space\n\tHEREDOC\n}\n",
+        "block {\n\theredoc <<HEREDOC\n\tHere's more than one space       Here's more than one space\n\tHEREDOC\n}\n",
+    );
+}
+
+#[test]
+fn mixing_heredoc_with_regular_part() {
+    check(
+        "Mixing heredoc with regular part",
+        "block {\n\theredoc <<HEREDOC\n\tHere's more than one space       Here's more than one space\n\tHEREDOC\n\trespond \"More than one space will be eaten\"     200\n}\n\nblock2 {\n\theredoc <<HEREDOC\n\tHere's more than one space       Here's more than one space\n\tHEREDOC\n\trespond \"More than one space will be eaten\" 200\n}\n",
+        "block {\n\theredoc <<HEREDOC\n\tHere's more than one space       Here's more than one space\n\tHEREDOC\n\trespond \"More than one space will be eaten\" 200\n}\n\nblock2 {\n\theredoc <<HEREDOC\n\tHere's more than one space       Here's more than one space\n\tHEREDOC\n\trespond \"More than one space will be eaten\" 200\n}\n",
+    );
+}
+
+#[test]
+fn heredoc_as_regular_token() {
+    check(
+        "Heredoc as regular token",
+        "block {\n\theredoc <<HEREDOC                                       \"More than one space will be eaten\"\n}\n",
+        "block {\n\theredoc <<HEREDOC \"More than one space will be eaten\"\n}\n",
+    );
+}
+
+#[test]
+fn escape_heredoc() {
+    check(
+        "Escape heredoc",
+        "block {\n\theredoc \\<<HEREDOC\n\trespond \"More than one space will be eaten\"                   200\n}\n",
+        "block {\n\theredoc \\<<HEREDOC\n\trespond \"More than one space will be eaten\" 200\n}\n",
+    );
+}
+
+#[test]
+fn accepts_unparseable_input() {
+    // an unterminated string is copied to the end of the input
+    check(
+        "unterminated string",
+        "a {\n\tb \"c  d\n}",
+        "a {\n\tb \"c  d\n}",
+    );
+}
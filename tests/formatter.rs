@@ -64,3 +64,429 @@ fn format_matcher_all() {
     let output = format(&cf);
     assert!(output.contains("respond * 200"));
 }
+
+#[test]
+fn format_braceless_single_site() {
+    use caddyfile_rs::{BraceStyle, FormatOptions, format_with_options, parse_str};
+
+    let input = "localhost\nreverse_proxy :3000\n\nfile_server {\n\tbrowse\n}\n";
+    let cf = parse_str(input).unwrap();
+    assert_eq!(cf.sites.len(), 1);
+    assert_eq!(cf.sites[0].directives.len(), 2);
+
+    let options = FormatOptions::new().braceless_single_site(true);
+    assert_eq!(format_with_options(&cf, &options), input);
+    assert_eq!(
+        format(&cf),
+        "localhost {\n\treverse_proxy :3000\n\n\tfile_server {\n\t\tbrowse\n\t}\n}\n"
+    );
+
+    // With more than one site the option has no effect.
+    let two = Caddyfile::new()
+        .site(SiteBlock::new("a.com").log())
+        .site(SiteBlock::new("b.com").log());
+    assert_eq!(format_with_options(&two, &options), format(&two));
+
+    // Auto keeps the shape the site was written in
+    let auto = FormatOptions::new().brace_style(BraceStyle::Auto);
+    assert_eq!(format_with_options(&cf, &auto), input);
+    let braced = parse_str(&format(&cf)).unwrap();
+    assert_eq!(format_with_options(&braced, &auto), format(&cf));
+}
+
+#[test]
+fn format_keeps_line_continuations() {
+    use caddyfile_rs::{FormatOptions, format_with_options, parse_str};
+
+    let input = "example.com {\n\treverse_proxy app1:80 \\\n\t\tapp2:80 \\\n\t\tapp3:80\n}\n";
+    let cf = parse_str(input).unwrap();
+    assert_eq!(cf.sites[0].directives[0].continuations, [1, 2]);
+
+    let options = FormatOptions::new().continuations(true);
+    assert_eq!(format_with_options(&cf, &options), input);
+    assert_eq!(
+        format(&cf),
+        "example.com {\n\treverse_proxy app1:80 app2:80 app3:80\n}\n"
+    );
+}
+
+#[test]
+fn format_blank_line_policy() {
+    use caddyfile_rs::{
+        FormatOptions, ParseOptions, format_with_options, parse_with_options, tokenize,
+    };
+
+    let input = "a.com {\n\tencode gzip\n\n\n\n\tlog\n\thandle {\n\t\tfile_server\n\t}\n}\n\nb.com {\n\tlog\n}\n";
+    let tokens = tokenize(input).unwrap();
+    let cf = parse_with_options(&tokens, &ParseOptions::new().blank_lines(true)).unwrap();
+    assert_eq!(cf.sites[0].directives[1].blank_lines_before, 3);
+
+    assert_eq!(
+        format(&cf),
+        "a.com {\n\tencode gzip\n\tlog\n\n\thandle {\n\t\tfile_server\n\t}\n}\n\nb.com {\n\tlog\n}\n"
+    );
+    let options = FormatOptions::new()
+        .max_blank_lines(2)
+        .blank_lines_around_blocks(false)
+        .blank_line_between_blocks(false);
+    assert_eq!(
+        format_with_options(&cf, &options),
+        "a.com {\n\tencode gzip\n\n\n\tlog\n\thandle {\n\t\tfile_server\n\t}\n}\nb.com {\n\tlog\n}\n"
+    );
+}
+
+#[test]
+fn format_wraps_long_lines() {
+    use caddyfile_rs::{FormatOptions, format_with_options, parse_str};
+
+    let cf = Caddyfile::new().site(SiteBlock::new("example.com").directive(
+        Directive::new("tls").block(vec![
+                Directive::new("ciphers")
+                    .arg("TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384")
+                    .arg("TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384")
+                    .arg("TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256"),
+                Directive::new("protocols").arg("tls1.2").arg("tls1.3"),
+            ]),
+    ));
+    let output = format_with_options(&cf, &FormatOptions::new().max_line_width(80));
+    assert_eq!(
+        output,
+        "example.com {\n\
+         \ttls {\n\
+         \t\tciphers TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384 \\\n\
+         \t\t\tTLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384 \\\n\
+         \t\t\tTLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256\n\
+         \t\tprotocols tls1.2 tls1.3\n\
+         \t}\n\
+         }\n"
+    );
+    // the wrapped arguments belong to the same directive
+    assert_eq!(format(&parse_str(&output).unwrap()), format(&cf));
+}
+
+#[test]
+fn format_aligns_header_and_map_columns() {
+    use caddyfile_rs::{FormatOptions, format_with_options, parse_str};
+
+    let input = "example.com {\n\
+                 \theader {\n\
+                 \t\tStrict-Transport-Security \"max-age=31536000\"\n\
+                 \t\tX-Frame-Options DENY\n\
+                 \t\t-Server\n\
+                 \t}\n\
+                 \n\
+                 \tmap {host} {backend} {\n\
+                 \t\ta.example.com app1:80 # first\n\
+                 \t\tdefault app2:80\n\
+                 \t}\n\
+                 }\n";
+    let cf = parse_str(input).unwrap();
+    assert_eq!(format(&cf), input);
+    assert_eq!(
+        format_with_options(&cf, &FormatOptions::new().align_columns(true)),
+        "example.com {\n\
+         \theader {\n\
+         \t\tStrict-Transport-Security \"max-age=31536000\"\n\
+         \t\tX-Frame-Options           DENY\n\
+         \t\t-Server\n\
+         \t}\n\
+         \n\
+         \tmap {host} {backend} {\n\
+         \t\ta.example.com app1:80 # first\n\
+         \t\tdefault       app2:80\n\
+         \t}\n\
+         }\n"
+    );
+}
+
+#[test]
+fn format_line_endings() {
+    use caddyfile_rs::{FormatOptions, LineEnding, format_with_options, parse_str};
+
+    let cf = Caddyfile::new().site(SiteBlock::new("example.com").log());
+    let crlf = FormatOptions::new().line_ending(LineEnding::Crlf);
+    let output = format_with_options(&cf, &crlf);
+    assert_eq!(output, "example.com {\r\n\tlog\r\n}\r\n");
+    assert_eq!(parse_str(&output).unwrap(), cf);
+
+    let bare = crlf.final_newline(false);
+    assert_eq!(
+        format_with_options(&cf, &bare),
+        "example.com {\r\n\tlog\r\n}"
+    );
+    assert_eq!(
+        format_with_options(&cf, &FormatOptions::new().final_newline(false)),
+        "example.com {\n\tlog\n}"
+    );
+}
+
+#[test]
+fn compute_edits_touch_only_changed_text() {
+    use caddyfile_rs::{TextEdit, compute_edits, parse_str};
+
+    fn apply(source: &str, edits: &[TextEdit]) -> String {
+        let mut out = source.to_string();
+        for edit in edits.iter().rev() {
+            out.replace_range(edit.range.clone(), &edit.new_text);
+        }
+        out
+    }
+
+    let original =
+        "a.com {\n\tlog\n    encode   gzip\n\trespond \"ok\"\n}\nb.com {\n\tfile_server\n}";
+    let cf = parse_str(original).unwrap();
+    let edits = compute_edits(original, &cf);
+    assert_eq!(apply(original, &edits), format(&cf));
+    assert_eq!(
+        edits,
+        [
+            TextEdit {
+                range: 13..25,
+                new_text: "\tencode".to_string(),
+            },
+            TextEdit {
+                range: 47..47,
+                new_text: "\n".to_string(),
+            },
+            TextEdit {
+                range: 69..69,
+                new_text: "\n".to_string(),
+            },
+        ]
+    );
+
+    let formatted = format(&cf);
+    assert!(compute_edits(&formatted, &cf).is_empty());
+}
+
+#[test]
+fn compute_edits_keep_source_style() {
+    use caddyfile_rs::{FormatOptions, compute_edits, compute_edits_with_options, parse_str};
+
+    let original =
+        "a.com {\r\n  log\r\n  respond \"ok\"\r\n}\r\n\r\nb.com {\r\n  file_server\r\n}\r\n";
+    let cf = parse_str(original).unwrap();
+    assert_eq!(compute_edits(original, &cf).len(), 1);
+    let options = FormatOptions::from_source(original);
+    assert!(compute_edits_with_options(original, &cf, &options).is_empty());
+}
+
+#[test]
+fn display_fragments() {
+    let directive = Directive::new("reverse_proxy")
+        .arg("app:3000")
+        .block(vec![Directive::new("lb_policy").arg("first")]);
+    assert_eq!(
+        directive.to_string(),
+        "reverse_proxy app:3000 {\n\tlb_policy first\n}\n"
+    );
+
+    let site = SiteBlock::new("example.com").directive(directive);
+    assert_eq!(
+        site.to_string(),
+        "example.com {\n\treverse_proxy app:3000 {\n\t\tlb_policy first\n\t}\n}\n"
+    );
+    let cf = Caddyfile::new().site(site);
+    assert_eq!(cf.to_string(), format(&cf));
+}
+
+#[test]
+fn format_quoting_policy() {
+    use caddyfile_rs::{
+        FormatError, FormatOptions, Quoting, format_with_options, parse_str,
+        try_format_with_options,
+    };
+
+    let cf = Caddyfile::new().site(
+        SiteBlock::new("example.com")
+            .directive(Directive::new("respond").arg("hello world").arg("200"))
+            .directive(Directive::new("header").block(vec![
+                Directive::new("X-Note").arg("#1 \"quoted\""),
+                Directive::new("X-Host").arg("{host}"),
+            ])),
+    );
+    assert!(format(&cf).contains("respond hello world 200"));
+
+    let auto = FormatOptions::new().quoting(Quoting::Auto);
+    let output = format_with_options(&cf, &auto);
+    assert!(output.contains("respond \"hello world\" 200"));
+    assert!(output.contains("X-Note \"#1 \\\"quoted\\\"\""));
+    assert!(output.contains("X-Host {host}"));
+    let reparsed = parse_str(&output).unwrap();
+    assert_eq!(
+        reparsed.sites[0].directives[0].arguments[0].value(),
+        "hello world"
+    );
+
+    let strict = FormatOptions::new().quoting(Quoting::Strict);
+    assert_eq!(
+        try_format_with_options(&cf, &strict),
+        Err(FormatError::NeedsQuotes {
+            directive: "respond".to_string(),
+            value: "hello world".to_string(),
+        })
+    );
+    let safe = Caddyfile::new().site(SiteBlock::new("example.com").log());
+    assert_eq!(try_format_with_options(&safe, &strict), Ok(format(&safe)));
+}
+
+#[test]
+fn format_sorted_by_directive_order() {
+    use caddyfile_rs::{DirectiveSort, FormatOptions, format_with_options, parse_str};
+
+    let cf = parse_str(
+        "{\n\torder respond before file_server\n}\n\n\
+         a.com {\n\tfile_server\n\tencode gzip\n\trespond /ping pong\n}\n",
+    )
+    .unwrap();
+    let options = FormatOptions::new().directive_sort(DirectiveSort::Caddy);
+    assert_eq!(
+        format_with_options(&cf, &options),
+        "{\n\torder respond before file_server\n}\n\n\
+         a.com {\n\tencode gzip\n\trespond /ping pong\n\tfile_server\n}\n"
+    );
+}
+
+#[test]
+fn format_to_writers() {
+    use caddyfile_rs::{
+        FormatOptions, LineEnding, format_into, format_to, format_to_with_options,
+        format_with_options, parse_str,
+    };
+
+    let cf = parse_str(
+        "{\n\tdebug\n}\n\n(common) {\n\tencode gzip\n}\n\na.com {\n\tlog\n}\n\nb.com {\n\tlog\n}\n# end\n",
+    )
+    .unwrap();
+    let mut bytes = Vec::new();
+    format_to(&cf, &mut bytes).unwrap();
+    assert_eq!(String::from_utf8(bytes).unwrap(), format(&cf));
+
+    let mut text = String::new();
+    format_into(&cf, &mut text).unwrap();
+    assert_eq!(text, format(&cf));
+
+    let options = FormatOptions::new()
+        .line_ending(LineEnding::Crlf)
+        .final_newline(false)
+        .blank_line_between_blocks(false);
+    let mut bytes = Vec::new();
+    format_to_with_options(&cf, &options, &mut bytes).unwrap();
+    assert_eq!(
+        String::from_utf8(bytes).unwrap(),
+        format_with_options(&cf, &options)
+    );
+    assert!(format_with_options(&cf, &options).ends_with("}\r\n# end"));
+}
+
+#[test]
+fn format_keeps_detected_style() {
+    use caddyfile_rs::{
+        FormatOptions, Indent, LineEnding, SourceStyle, detect_style, format_with_options,
+        parse_str,
+    };
+
+    let spaces =
+        "example.com {\r\n  encode gzip\r\n\r\n  handle {\r\n    file_server\r\n  }\r\n}\r\n";
+    assert_eq!(
+        detect_style(spaces),
+        SourceStyle {
+            indent: Indent::Spaces(2),
+            line_ending: LineEnding::Crlf,
+        }
+    );
+    let cf = parse_str(spaces).unwrap();
+    let options = FormatOptions::from_source(spaces);
+    assert_eq!(format_with_options(&cf, &options), spaces);
+
+    let tabs = format(&cf);
+    assert_eq!(detect_style(&tabs).indent, Indent::Tabs);
+    assert_eq!(detect_style("a.com\n").indent, Indent::Tabs);
+    assert_eq!(
+        format_with_options(&cf, &FormatOptions::new().indent(Indent::Spaces(4))),
+        "example.com {\n    encode gzip\n\n    handle {\n        file_server\n    }\n}\n"
+    );
+}
+
+#[test]
+fn format_indents_heredocs() {
+    use caddyfile_rs::{Argument, FormatOptions, Indent, format_with_options, parse_str};
+
+    let flat = "example.com {\n\thandle {\n\t\trespond <<HTML\n<p>hi</p>\n\n  <p>there</p>\nHTML 200\n\t}\n}\n";
+    let cf = parse_str(flat).unwrap();
+    let out = format(&cf);
+    assert_eq!(
+        out,
+        "example.com {\n\thandle {\n\t\trespond <<HTML\n\t\t<p>hi</p>\n\n\t\t  <p>there</p>\n\t\tHTML 200\n\t}\n}\n"
+    );
+
+    // the closing marker's indentation is stripped again on parse
+    let content =
+        |cf: &caddyfile_rs::Caddyfile| match &cf.sites[0].directives[0].block.as_ref().unwrap()[0]
+            .arguments[0]
+        {
+            Argument::Heredoc { content, .. } => content.clone(),
+            other => panic!("expected heredoc, got {other:?}"),
+        };
+    let reparsed = parse_str(&out).unwrap();
+    assert_eq!(content(&reparsed), content(&cf));
+    assert_eq!(content(&cf), "<p>hi</p>\n\n  <p>there</p>");
+
+    let spaces = format_with_options(&cf, &FormatOptions::new().indent(Indent::Spaces(2)));
+    assert!(spaces.contains("    respond <<HTML\n    <p>hi</p>\n"));
+    assert_eq!(content(&parse_str(&spaces).unwrap()), content(&cf));
+}
+
+#[test]
+fn format_crlf_heredocs_idempotent() {
+    use caddyfile_rs::{FormatOptions, format_with_options, parse_str};
+
+    let input = "example.com {\r\n\trespond <<HTML\r\n\t<h1>hi</h1>\r\n\t<p>there</p>\r\n\tHTML 200\r\n}\r\n";
+    let options = FormatOptions::from_source(input);
+    let once = format_with_options(&parse_str(input).unwrap(), &options);
+    assert_eq!(once, input);
+    let twice = format_with_options(&parse_str(&once).unwrap(), &options);
+    assert_eq!(twice, once);
+}
+
+#[test]
+fn format_empty_blocks() {
+    use caddyfile_rs::{EmptyBlocks, FormatOptions, format_with_options, parse_str};
+
+    let cf = Caddyfile::new().site(
+        SiteBlock::new("example.com")
+            .directive(Directive::new("encode").arg("gzip"))
+            .directive(
+                Directive::new("php_fastcgi")
+                    .arg("localhost:9000")
+                    .block(vec![]),
+            )
+            .directive(Directive::new("file_server")),
+    );
+    let with = |empty| format_with_options(&cf, &FormatOptions::new().empty_blocks(empty));
+
+    assert_eq!(
+        with(EmptyBlocks::Expanded),
+        "example.com {\n\tencode gzip\n\n\tphp_fastcgi localhost:9000 {\n\t}\n\n\tfile_server\n}\n"
+    );
+    let collapsed = with(EmptyBlocks::Collapsed);
+    assert_eq!(
+        collapsed,
+        "example.com {\n\tencode gzip\n\tphp_fastcgi localhost:9000 {}\n\tfile_server\n}\n"
+    );
+    assert_eq!(parse_str(&collapsed).unwrap(), cf);
+    assert_eq!(
+        with(EmptyBlocks::Omitted),
+        "example.com {\n\tencode gzip\n\tphp_fastcgi localhost:9000\n\tfile_server\n}\n"
+    );
+
+    // a block holding only comments stays expanded
+    let commented = parse_str("example.com {\n\ttls {\n\t\t# TODO\n\t}\n}\n").unwrap();
+    assert_eq!(
+        format_with_options(
+            &commented,
+            &FormatOptions::new().empty_blocks(EmptyBlocks::Collapsed)
+        ),
+        "example.com {\n\ttls {\n\t\t# TODO\n\t}\n}\n"
+    );
+}
@@ -311,3 +311,19 @@ fn lex_error_span_multiline() {
     let err = tokenize("a\nb\n\"unclosed").unwrap_err();
     assert!(err.span.line >= 3);
 }
+
+#[test]
+fn lex_error_visual_column_expands_tabs() {
+    let input = "example.com {\n\t\t\"unclosed";
+    let err = tokenize(input).unwrap_err();
+    assert_eq!(err.span.column, 3);
+    assert_eq!(err.span.visual_column(input, 4), 9);
+    assert_eq!(err.span.visual_column(input, 8), 17);
+
+    let err = caddyfile_rs::parse_str(input).unwrap_err();
+    assert_eq!(
+        err.describe(input, 4),
+        "unterminated quoted string at line 2, column 3 (visual column 9)"
+    );
+    assert_eq!(err.describe(input, 1), err.to_string());
+}
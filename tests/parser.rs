@@ -1,6 +1,6 @@
 //! Parser edge cases and error tests.
 
-use caddyfile_rs::{ParseErrorKind, parse, parse_str, tokenize};
+use caddyfile_rs::{ParseErrorKind, parse, parse_str, parse_str_named, tokenize};
 
 // -----------------------------------------------------------
 // Parser errors.
@@ -18,6 +18,13 @@ fn parse_error_unclosed_brace() {
     ));
 }
 
+#[test]
+fn parse_error_names_file() {
+    let err = parse_str_named("example.com {\n\tlog\n", "sites/example.caddy").unwrap_err();
+    assert_eq!(err.span().file.as_deref(), Some("sites/example.caddy"));
+    assert!(err.to_string().ends_with("in sites/example.caddy"));
+}
+
 #[test]
 fn parse_error_nested_unclosed() {
     let tokens = tokenize("example.com {\n\theader {\n\t\tX-Test value\n}\n").expect("tokenize");
@@ -158,6 +165,53 @@ fn parser_treats_slash_prefix_as_path_matcher() {
     assert_eq!(directive.arguments[0].value(), "200");
 }
 
+// -----------------------------------------------------------
+// Environment references keep their name and default.
+// -----------------------------------------------------------
+
+#[test]
+fn parser_keeps_env_var_arguments() {
+    use caddyfile_rs::{Argument, format};
+
+    let input = "example.com {\n\treverse_proxy {$UPSTREAM} {$BACKUP:app:3000}\n}\n";
+    let cf = parse_str(input).unwrap();
+    let args = &cf.sites[0].directives[0].arguments;
+    assert_eq!(
+        args[..],
+        [
+            Argument::EnvVar {
+                name: "UPSTREAM".to_string(),
+                default: None,
+            },
+            Argument::EnvVar {
+                name: "BACKUP".to_string(),
+                default: Some("app:3000".to_string()),
+            },
+        ]
+    );
+    assert_eq!(args[1].value(), "{$BACKUP:app:3000}");
+    assert_eq!(format(&cf), input);
+}
+
+// -----------------------------------------------------------
+// Collecting every error in one pass.
+// -----------------------------------------------------------
+
+#[test]
+fn parse_all_errors_reports_lex_and_parse_errors() {
+    let input = "a.com {\n\trespond \"oops\n}\n\n(snip) x\n\nb.com {\n\tlog\n}\n";
+    let (cf, errors) = caddyfile_rs::parse_all_errors(input);
+    assert!(cf.is_none());
+    let lines: Vec<_> = errors.iter().map(|e| e.span().line).collect();
+    assert_eq!(lines, [2, 5]);
+    assert!(matches!(errors[0], caddyfile_rs::Error::Lex(_)));
+    assert!(matches!(errors[1], caddyfile_rs::Error::Parse(_)));
+
+    let (cf, errors) = caddyfile_rs::parse_all_errors("a.com {\n\tlog\n}\n");
+    assert!(errors.is_empty());
+    assert_eq!(cf.unwrap().sites.len(), 1);
+}
+
 // -----------------------------------------------------------
 // Error display.
 // -----------------------------------------------------------
@@ -10,8 +10,8 @@
 //! correctness.
 
 use caddyfile_rs::{
-    Argument, Caddyfile, Directive, GlobalOptions, Matcher, NamedRoute, SiteBlock, Snippet, format,
-    parse_str, tokenize,
+    Argument, Caddyfile, Comments, Directive, GlobalOptions, Matcher, NamedRoute, SiteBlock,
+    Snippet, format, parse_str, tokenize,
 };
 use proptest::prelude::*;
 
@@ -65,6 +65,9 @@ fn directive(depth: u32) -> impl Strategy<Value = Directive> {
             matcher,
             arguments,
             block: None,
+            comments: Comments::default(),
+            continuations: Vec::new(),
+            blank_lines_before: 0,
         }
     });
 
@@ -82,6 +85,9 @@ fn directive(depth: u32) -> impl Strategy<Value = Directive> {
                 matcher: None,
                 arguments,
                 block: Some(sub),
+                comments: Comments::default(),
+                continuations: Vec::new(),
+                blank_lines_before: 0,
             });
 
         prop_oneof![
@@ -110,12 +116,20 @@ fn address() -> impl Strategy<Value = String> {
 
 /// Snippet
 fn snippet() -> impl Strategy<Value = Snippet> {
-    ("[a-z]{2,10}", directives()).prop_map(|(name, directives)| Snippet { name, directives })
+    ("[a-z]{2,10}", directives()).prop_map(|(name, directives)| Snippet {
+        name,
+        directives,
+        comments: Comments::default(),
+    })
 }
 
 /// Named route
 fn named_route() -> impl Strategy<Value = NamedRoute> {
-    ("[a-z]{2,10}", directives()).prop_map(|(name, directives)| NamedRoute { name, directives })
+    ("[a-z]{2,10}", directives()).prop_map(|(name, directives)| NamedRoute {
+        name,
+        directives,
+        comments: Comments::default(),
+    })
 }
 
 /// Site block
@@ -134,7 +148,7 @@ fn site_block() -> impl Strategy<Value = SiteBlock> {
 fn global_options() -> impl Strategy<Value = Option<GlobalOptions>> {
     prop_oneof![
         3 => Just(None),
-        1 => directives().prop_map(|d| Some(GlobalOptions { directives: d })),
+        1 => directives().prop_map(|d| Some(GlobalOptions { directives: d, comments: Comments::default() })),
     ]
 }
 
@@ -152,6 +166,7 @@ fn caddyfile() -> impl Strategy<Value = Caddyfile> {
                 snippets,
                 named_routes,
                 sites,
+                trailing_comments: Vec::new(),
             },
         )
 }
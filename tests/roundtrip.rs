@@ -33,6 +33,23 @@ fn roundtrip_matcher() {
     roundtrip("example.com {\n\trespond /health 200\n}\n");
 }
 
+#[test]
+fn roundtrip_non_ascii() {
+    roundtrip("café.example.com {\n\trespond \"Grüße 🌍\" `naïve`\n}\n");
+}
+
+#[test]
+fn roundtrip_bare_placeholders() {
+    roundtrip(
+        "example.com {\n\trespond {remote_host}\n\n\treverse_proxy app:80 {\n\t\theader_up Host {upstream_hostport}\n\t}\n}\n",
+    );
+}
+
+#[test]
+fn roundtrip_heredoc_trailing_arguments() {
+    roundtrip("example.com {\n\trespond <<HTML\n\t<p>hi</p>\n\tHTML 200\n\tlog\n}\n");
+}
+
 #[test]
 fn roundtrip_multiple_sites() {
     roundtrip("a.com {\n\tlog\n}\n\nb.com {\n\tlog\n}\n");
@@ -88,6 +105,13 @@ fn roundtrip_multiple_addresses_comma_separated() {
     );
 }
 
+#[test]
+fn roundtrip_commas_in_arguments() {
+    roundtrip(
+        "example.com {\n\theader Access-Control-Allow-Headers Content-Type, Authorization\n\theader Allow GET,POST\n}\n",
+    );
+}
+
 #[test]
 fn roundtrip_scheme_host_port_path_address() {
     roundtrip(
@@ -244,8 +268,8 @@ fn roundtrip_respond_with_heredoc() {
     roundtrip(
         "example.com {\n\
          \trespond /health <<EOF\n\
-         {\"status\":\"ok\"}\n\
-         EOF\n\
+         \t{\"status\":\"ok\"}\n\
+         \tEOF\n\
          }\n",
     );
 }
@@ -478,6 +502,18 @@ fn roundtrip_abort_and_error() {
     );
 }
 
+#[test]
+fn roundtrip_multi_token_matchers() {
+    roundtrip(
+        "example.com {\n\
+         \t@external not remote_ip private_ranges\n\n\
+         \thandle /a/* /b/* {\n\
+         \t\trespond @external 403\n\
+         \t}\n\
+         }\n",
+    );
+}
+
 #[test]
 fn roundtrip_with_comments() {
     let input = "\
@@ -494,6 +530,48 @@ example.com {
     assert_eq!(cf.sites[0].directives.len(), 2);
     assert_eq!(cf.sites[0].directives[0].name, "log");
     assert_eq!(cf.sites[0].directives[1].name, "reverse_proxy");
+    roundtrip(input);
+}
+
+#[test]
+fn roundtrip_trailing_and_closing_comments() {
+    roundtrip(
+        "example.com { # primary\n\
+         \treverse_proxy app:3000 # backend\n\n\
+         \thandle /api/* {\n\
+         \t\trespond ok\n\
+         \t\t# more routes later\n\
+         \t}\n\
+         \t# end of site\n\
+         }\n",
+    );
+}
+
+#[test]
+fn roundtrip_comments_on_every_block() {
+    roundtrip(
+        "# settings\n\
+         { # global\n\
+         \tdebug\n\
+         \t# end of options\n\
+         }\n\
+         \n\
+         # shared headers\n\
+         (common) {\n\
+         \theader -Server # hide\n\
+         }\n\
+         \n\
+         &(api) {\n\
+         \treverse_proxy app:8080\n\
+         \t# more upstreams later\n\
+         }\n\
+         \n\
+         example.com {\n\
+         \timport common\n\
+         }\n\
+         \n\
+         # vim: ft=caddyfile\n",
+    );
 }
 
 #[test]
@@ -589,9 +667,9 @@ fn roundtrip_heredoc_html_page() {
     roundtrip(
         "example.com {\n\
          \trespond <<HTML\n\
-         <!DOCTYPE html>\n\
-         <html><body><h1>Hello</h1></body></html>\n\
-         HTML\n\
+         \t<!DOCTYPE html>\n\
+         \t<html><body><h1>Hello</h1></body></html>\n\
+         \tHTML\n\
          }\n",
     );
 }
@@ -787,7 +865,8 @@ fn roundtrip_ipv6_and_ipv4_multi_address() {
 #[test]
 fn idempotent_format_three_rounds() {
     use caddyfile_rs::{
-        Caddyfile, Directive, GlobalOptions, Matcher, SiteBlock, Snippet, format, parse_str,
+        Caddyfile, Comments, Directive, GlobalOptions, Matcher, SiteBlock, Snippet, format,
+        parse_str,
     };
 
     let cf = Caddyfile::new()
@@ -797,6 +876,7 @@ fn idempotent_format_three_rounds() {
                 Directive::new("servers")
                     .block(vec![Directive::new("protocols").arg("h1").arg("h2")]),
             ],
+            comments: Comments::default(),
         })
         .snippet(Snippet {
             name: "sec".to_string(),
@@ -804,6 +884,7 @@ fn idempotent_format_three_rounds() {
                 Directive::new("header")
                     .block(vec![Directive::new("X-Frame-Options").quoted_arg("DENY")]),
             ],
+            comments: Comments::default(),
         })
         .site(
             SiteBlock::new("example.com")
@@ -825,3 +906,11 @@ fn idempotent_format_three_rounds() {
     assert_eq!(r1, r2);
     assert_eq!(r2, r3);
 }
+
+#[test]
+fn roundtrip_raw_addresses() {
+    let input = "example.com:0443, http://[::1]:8080/api/ {\n\tlog\n}\n";
+    let options = caddyfile_rs::ParseOptions::new().raw_addresses(true);
+    let cf = caddyfile_rs::parse_str_with_options(input, &options).expect("parse failed");
+    assert_eq!(caddyfile_rs::format(&cf), input);
+}